@@ -0,0 +1,66 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use serde::Deserialize;
+use core::admin::{Admin, Role};
+use core::audit::AdminAction;
+use core::BasicProcessor;
+use crate::{CliError, ErrorType};
+use crate::audit::HashChain;
+use crate::write::write_sensitive;
+
+/// Admin operation row (json line), e.g. `{"principal":"alice","op":"unlock","client":1}`
+#[derive(Debug, Deserialize)]
+struct AdminOp {
+    principal: String,
+
+    #[serde(flatten)]
+    action: AdminAction,
+}
+
+/// `--admin-ops`: unlock, release of review and adjustment operations applied before processing. Only principals listed
+/// with `--admin` are authorized, denied and failed operations are reported and skipped.
+/// Every operation is recorded in audit log, written to `audit_log` as json lines.
+/// With `chain` records are hash-chained (see `HashChain`), chain is returned for root hash in run manifest.
+pub fn apply<'a, I>(file_path: &str, admins: I, processor: &mut BasicProcessor, audit_log: Option<&str>, chain: bool, overwrite: bool) -> Result<Option<HashChain>, CliError>
+    where I: IntoIterator<Item=&'a str>
+{
+    let admins: HashSet<String> = admins.into_iter().map(|it| it.to_string()).collect();
+    let mut admin = Admin::new(move |principal: &str| match admins.contains(principal) {
+        true => Role::Admin,
+        false => Role::Submitter,
+    });
+
+    let reader = BufReader::new(File::open(file_path)?);
+    for (line, op) in (1..).zip(serde_json::Deserializer::from_reader(reader).into_iter::<AdminOp>()) {
+        let op = op.map_err(|e| CliError::new(ErrorType::CsvRead, format!("Invalid admin operation at line {}: {}", line, e)))?;
+        let result = match op.action {
+            AdminAction::Unlock { client } => admin.unlock(&op.principal, processor, client),
+            AdminAction::ReleaseReview { client } => admin.release_review(&op.principal, processor, client),
+            AdminAction::Adjust { client, amount } => admin.adjust(&op.principal, processor, client, amount),
+            AdminAction::Snapshot => return Err(CliError::new(ErrorType::CsvRead, format!("Invalid admin operation at line {}: snapshot is written with --dump-path", line))),
+            AdminAction::ConfigChange { .. } => return Err(CliError::new(ErrorType::CsvRead, format!("Invalid admin operation at line {}: config is changed with --config", line))),
+        };
+        if let Err(e) = result {
+            eprintln!("Admin operation at line {} skipped: {}", line, e);
+        }
+    }
+
+    let Some(file_path) = audit_log else {
+        return Ok(None);
+    };
+    let mut hash_chain = HashChain::new();
+    write_sensitive(file_path, overwrite, |file| {
+        for event in admin.audit_log().events() {
+            let result = match chain {
+                true => serde_json::to_writer(&mut *file, &hash_chain.link(event)?),
+                false => serde_json::to_writer(&mut *file, event),
+            };
+            result.map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+            file.write_all(b"\n")?;
+        }
+        Ok(())
+    })?;
+
+    Ok(chain.then_some(hash_chain))
+}
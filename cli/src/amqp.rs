@@ -0,0 +1,205 @@
+use std::time::Duration;
+use clap::ArgMatches;
+use futures::StreamExt;
+use lapin::options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions, BasicQosOptions, BasicRejectOptions, ConfirmSelectOptions};
+use lapin::publisher_confirm::Confirmation;
+use lapin::types::{AMQPValue, FieldTable};
+use lapin::{BasicProperties, Channel, Connection, ConnectionProperties};
+use core::{BasicProcessor, TransactionProcessor};
+use core::dlq::DeadLetter;
+use core::pipeline::CancellationToken;
+use crate::{dlq, signal, CliError, ErrorType};
+use crate::config::{Config, LogLevel};
+use crate::health::Health;
+use crate::journal::Journal;
+use crate::stats::{ConsumeReport, StatsDump};
+use crate::telemetry::Telemetry;
+use crate::output::{write_output, OutputFormat};
+
+// how often cancellation is checked while waiting for messages
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// message header marking historical transaction, see `BasicProcessor::set_backfill`
+const BACKFILL_HEADER: &str = "backfill";
+
+/// `consume-amqp` subcommand: consume JSON transactions from RabbitMQ queue and apply them.
+/// Every transaction is synced to `--journal` before it is applied (see `Journal`), so message is settled
+/// only after its transaction is persisted. Applied and rejected transactions are acked, transactions failed with retryable error are nacked
+/// and requeued. Rejected transactions are published as dead letters to DLQ exchange (routing key
+/// is error code) and acked only after broker confirmed the dead letter. Without DLQ exchange, and for
+/// malformed messages, message is rejected without requeue (queue's own dead lettering applies).
+/// Message with `backfill` header set to true is historical transaction, it doesn't lock account.
+/// Accounts are kept in memory and written to stdout on shutdown (Ctrl-C).
+/// With `--otlp-endpoint` (`otel` feature) every message is traced, see `Telemetry`.
+/// With `--stats-json` stats of the run are dumped periodically, see `StatsDump`.
+/// With `--health-addr` (`health` feature) probes and drain are served over HTTP, see `Health`.
+/// With `--config` tunable policies are reloaded without restart, see `Config`.
+pub fn consume(matches: &ArgMatches) -> Result<(), CliError> {
+    let arg = |name: &str| matches.value_of(name).ok_or_else(|| {
+        // this should not happen at this stage, clap requires arg or sets default
+        CliError::new(ErrorType::CliParseError, format!("Arg {} not found", name))
+    });
+    let uri = arg("uri")?;
+    let queue = arg("queue")?;
+    let dlq_exchange = matches.value_of("dlq_exchange");
+    let prefetch = arg("prefetch")?.parse::<u16>()
+        .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg prefetch must be a number"))?;
+    let format = arg("format")?.parse::<OutputFormat>()?;
+
+    let mut stats = StatsDump::from_matches(matches, "consume-amqp")?;
+    let mut processor = BasicProcessor::new().with_recorder(stats.recorder());
+    let mut config = Config::from_matches(matches)?;
+    config.apply(&mut processor);
+    if let Some(snapshot) = matches.value_of("snapshot") {
+        processor.restore(dlq::read_snapshot(snapshot)?)
+            .map_err(|e| CliError::from_source(ErrorType::Process, e))?;
+    }
+    let (mut journal, replayed) = Journal::open(arg("journal")?, &mut processor)?;
+    if config.logs(LogLevel::Info) && replayed > 0 {
+        eprintln!("Replayed {} journaled transactions", replayed);
+    }
+
+    let telemetry = Telemetry::from_matches(matches)?;
+    let cancel = CancellationToken::new();
+    signal::register_shutdown(&cancel)?;
+    let health = Health::from_matches(matches, &cancel)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let report = runtime.block_on(async {
+        let connection = Connection::connect(uri, ConnectionProperties::default()).await.map_err(amqp_error)?;
+        let channel = connection.create_channel().await.map_err(amqp_error)?;
+        channel.basic_qos(prefetch, BasicQosOptions::default()).await.map_err(amqp_error)?;
+        if dlq_exchange.is_some() {
+            channel.confirm_select(ConfirmSelectOptions::default()).await.map_err(amqp_error)?;
+        }
+        let mut consumer = channel.basic_consume(queue, "", BasicConsumeOptions::default(), FieldTable::default()).await
+            .map_err(amqp_error)?;
+        health.ready();
+
+        let mut report = ConsumeReport::default();
+        while !cancel.is_cancelled() {
+            stats.tick(&report);
+            config.reload(&mut processor);
+            let delivery = match tokio::time::timeout(POLL_INTERVAL, consumer.next()).await {
+                Ok(Some(delivery)) => delivery.map_err(amqp_error)?,
+                Ok(None) => break,
+                // no message, check cancellation again
+                Err(_) => continue,
+            };
+            report.read += 1;
+
+            let headers = delivery.properties.headers().iter()
+                .flat_map(|it| it.inner())
+                .filter_map(|(name, value)| Some((name.as_str(), String::from_utf8(value.as_long_string()?.as_bytes().to_vec()).ok()?)));
+            let trace = telemetry.start(headers);
+
+            let transaction = match trace.decode(&delivery.data) {
+                Ok(transaction) => transaction,
+                Err(e) => {
+                    if config.logs(LogLevel::Warn) {
+                        eprintln!("Malformed transaction (delivery {}): {}", delivery.delivery_tag, e);
+                    }
+                    report.rejected += 1;
+                    trace.end(Some(&e));
+                    delivery.reject(BasicRejectOptions { requeue: false }).await.map_err(amqp_error)?;
+                    continue;
+                }
+            };
+
+            let backfill = delivery.properties.headers().as_ref().is_some_and(is_backfill);
+            journal.append(&transaction, backfill)?;
+            processor.set_backfill(backfill);
+
+            // processor takes transaction, copy is kept for dead letter
+            let result = trace.apply(|| processor.process(transaction.clone()));
+            trace.end(result.as_ref().err().map(|it| it as _));
+            match result {
+                Ok(()) => {
+                    report.processed += 1;
+                    delivery.ack(BasicAckOptions::default()).await.map_err(amqp_error)?;
+                }
+                Err(e) if e.is_retryable() => {
+                    if config.logs(LogLevel::Warn) {
+                        eprintln!("Transaction {} requeued: {} {}", transaction.tx_id(), e.code(), e);
+                    }
+                    report.retried += 1;
+                    delivery.nack(BasicNackOptions { requeue: true, ..Default::default() }).await.map_err(amqp_error)?;
+                }
+                Err(e) => {
+                    if config.logs(LogLevel::Warn) {
+                        eprintln!("Transaction {} rejected: {} {}", transaction.tx_id(), e.code(), e);
+                    }
+                    report.rejected += 1;
+                    match dlq_exchange {
+                        Some(exchange) => {
+                            publish_dead_letter(&channel, exchange, DeadLetter::new(transaction, &e)).await?;
+                            delivery.ack(BasicAckOptions::default()).await.map_err(amqp_error)?;
+                        }
+                        None => delivery.reject(BasicRejectOptions { requeue: false }).await.map_err(amqp_error)?,
+                    }
+                }
+            }
+        }
+
+        connection.close(0, "shutdown").await.map_err(amqp_error)?;
+        Ok::<_, CliError>(report)
+    })?;
+    telemetry.shutdown()?;
+    stats.finish(&report)?;
+    if config.logs(LogLevel::Info) {
+        eprintln!("Consumed {} messages, {} applied, {} rejected, {} requeued", report.read, report.processed, report.rejected, report.retried);
+    }
+
+    let stdout = std::io::stdout();
+    write_output(format.writer(stdout.lock()).as_mut(), processor.finalize())
+}
+
+// waits for publisher confirm, so rejected transaction is acked only when dead letter is stored
+async fn publish_dead_letter(channel: &Channel, exchange: &str, dead_letter: DeadLetter) -> Result<(), CliError> {
+    let payload = serde_json::to_vec(&dead_letter).map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+    let properties = BasicProperties::default().with_content_type("application/json".into());
+    let confirmation = channel.basic_publish(exchange, dead_letter.code(), BasicPublishOptions::default(), &payload, properties).await
+        .map_err(amqp_error)?
+        .await
+        .map_err(amqp_error)?;
+
+    match confirmation {
+        Confirmation::Nack(_) => Err(CliError::new(ErrorType::IO, format!("Dead letter not accepted by exchange '{}'", exchange))),
+        _ => Ok(()),
+    }
+}
+
+// historical transaction, see `BACKFILL_HEADER`
+fn is_backfill(headers: &FieldTable) -> bool {
+    headers.inner().iter()
+        .any(|(name, value)| name.as_str().eq_ignore_ascii_case(BACKFILL_HEADER) && match value {
+            AMQPValue::Boolean(it) => *it,
+            value => value.as_long_string().is_some_and(|it| it.to_string().eq_ignore_ascii_case("true")),
+        })
+}
+
+fn amqp_error(e: lapin::Error) -> CliError {
+    CliError::from_source(ErrorType::IO, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use lapin::types::{AMQPValue, FieldTable};
+    use crate::amqp::is_backfill;
+
+    #[test]
+    fn backfill_header_then_bool_or_true_string() {
+        let headers = |value: AMQPValue| {
+            let mut headers = FieldTable::default();
+            headers.insert("Backfill".into(), value);
+            headers
+        };
+        assert!(!is_backfill(&FieldTable::default()));
+        assert!(is_backfill(&headers(AMQPValue::Boolean(true))));
+        assert!(!is_backfill(&headers(AMQPValue::Boolean(false))));
+        assert!(is_backfill(&headers(AMQPValue::LongString("TRUE".into()))));
+        assert!(!is_backfill(&headers(AMQPValue::LongString("yes".into()))));
+    }
+}
@@ -0,0 +1,212 @@
+use std::io;
+use csv::{ByteRecord, Position};
+use csv_core::ReadRecordResult;
+use futures::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use core::transaction::Transaction;
+use crate::reader::Columns;
+use crate::source::{ReaderOptions, SourceError};
+
+// bytes read from async reader at once
+const CHUNK: usize = 8 * 1024;
+
+// the first line is buffered up to this size to detect delimiter
+const MAX_DELIMITER_PEEK: usize = 64 * 1024;
+
+/// Parse csv from async reader (socket, file) without blocking the runtime, for services which also serve
+/// queries. Rows are parsed with `csv-core` on the task which polls the stream, the same way as by
+/// `read_from_csv` (trimmed fields, flexible rows, header with type,client,tx,amount). Input is read only
+/// while stream is polled and at most one chunk is buffered, so reading stops when stream is not polled
+/// (back-pressure). Header and read errors end the stream, malformed rows are reported and skipped.
+pub fn read_from_async<R>(reader: R, options: &ReaderOptions) -> impl Stream<Item=Result<Transaction, SourceError>>
+    where R: AsyncRead + Unpin
+{
+    let reader = AsyncRecordReader {
+        reader,
+        options: options.clone(),
+        csv: csv_core::Reader::new(),
+        input: vec![0; CHUNK],
+        start: 0,
+        end: 0,
+        eof: false,
+        output: vec![0; CHUNK],
+        ends: vec![0; 16],
+        record: ByteRecord::new(),
+        columns: None,
+    };
+
+    futures::stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        match reader.next().await {
+            Ok(Some(Ok(transaction))) => Some((Ok(transaction), Some(reader))),
+            Ok(Some(Err(e))) => Some((Err(e), Some(reader))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+struct AsyncRecordReader<R> {
+    reader: R,
+    options: ReaderOptions,
+    // parser state, created again with delimiter resolved from the first line
+    csv: csv_core::Reader,
+
+    // not parsed bytes are input[start..end]
+    input: Vec<u8>,
+    start: usize,
+    end: usize,
+    eof: bool,
+
+    // fields of current record and their end offsets
+    output: Vec<u8>,
+    ends: Vec<usize>,
+    record: ByteRecord,
+
+    // header of input, read with the first record
+    columns: Option<Columns>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRecordReader<R> {
+    // next row, Err when header is invalid or input can't be read (stream ends)
+    async fn next(&mut self) -> Result<Option<Result<Transaction, SourceError>>, SourceError> {
+        if self.columns.is_none() {
+            let delimiter = self.delimiter().await?;
+            self.csv = csv_core::ReaderBuilder::new().delimiter(delimiter).build();
+            if !self.read_record().await? {
+                return Err(SourceError::Parse { line: 1, message: "Expected header: type,client,tx,amount".to_string() });
+            }
+            self.columns = Some(Columns::new(&self.record, &self.options)?);
+        }
+
+        match self.read_record().await? {
+            true => Ok(self.columns.as_ref().map(|it| it.transaction(&self.record))),
+            false => Ok(None),
+        }
+    }
+
+    // delimiter of `options`, the first line is buffered (not consumed) when it is detected
+    async fn delimiter(&mut self) -> Result<u8, SourceError> {
+        while !self.eof && !self.input[self.start..self.end].contains(&b'\n') && self.end - self.start < MAX_DELIMITER_PEEK {
+            self.fill().await?;
+        }
+        Ok(self.options.delimiter.resolve(&mut &self.input[self.start..self.end])?)
+    }
+
+    // more input after not parsed bytes, buffer grows only when it is full of not parsed bytes
+    async fn fill(&mut self) -> io::Result<()> {
+        if self.start > 0 {
+            self.input.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+        if self.end == self.input.len() {
+            self.input.resize(self.input.len() * 2, 0);
+        }
+
+        let read = self.reader.read(&mut self.input[self.end..]).await?;
+        self.eof = read == 0;
+        self.end += read;
+        Ok(())
+    }
+
+    // next record into `record` with trimmed fields and line where it starts, false at the end of input
+    async fn read_record(&mut self) -> Result<bool, SourceError> {
+        let line = self.csv.line();
+        let (mut output, mut ends) = (0, 0);
+        loop {
+            // parser takes empty input as the end of input
+            if self.start == self.end && !self.eof {
+                self.fill().await?;
+                continue;
+            }
+            let (result, read, written, ended) = self.csv.read_record(&self.input[self.start..self.end], &mut self.output[output..], &mut self.ends[ends..]);
+            self.start += read;
+            output += written;
+            ends += ended;
+
+            match result {
+                // parser is called with empty input at the end of input, it ends the last record
+                ReadRecordResult::InputEmpty => {}
+                ReadRecordResult::OutputFull => self.output.resize(self.output.len() * 2, 0),
+                ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                ReadRecordResult::Record => break,
+                ReadRecordResult::End => return Ok(false),
+            }
+        }
+
+        self.record.clear();
+        let mut from = 0;
+        for end in &self.ends[..ends] {
+            self.record.push_field(self.output[from..*end].trim_ascii());
+            from = *end;
+        }
+        let mut position = Position::new();
+        position.set_line(line);
+        self.record.set_position(Some(position));
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use futures::StreamExt;
+    use tokio::io::AsyncWriteExt;
+    use crate::async_reader::read_from_async;
+    use crate::source::{Delimiter, ReaderOptions, SourceError};
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    #[test]
+    fn rows_then_same_as_sync_reader() {
+        let input = "type ; client ; tx ; amount\ndeposit;1;1;1.5\n\nwithdrawal;1;2;\"0\n.5\"\ndispute;1;1;\nresolve;1;x;\nchargeback;1;1";
+        let options = ReaderOptions { delimiter: Delimiter::Auto, ..ReaderOptions::default() };
+
+        // tiny chunks, records and the first line are split across reads
+        let (mut writer, reader) = tokio::io::duplex(3);
+        let transactions: Vec<_> = runtime().block_on(async {
+            tokio::spawn(async move { writer.write_all(input.as_bytes()).await });
+            read_from_async(reader, &options).collect().await
+        });
+
+        let expected: Vec<_> = crate::reader::read_from_csv(input.as_bytes(), &options).unwrap().collect();
+        assert_eq!(transactions.len(), 5);
+        assert_eq!(format!("{:?}", transactions), format!("{:?}", expected));
+        assert!(matches!(&transactions[3], Err(SourceError::Parse { line: 7, .. })));
+    }
+
+    #[test]
+    fn invalid_header_then_error_and_end() {
+        let transactions: Vec<_> = runtime().block_on(read_from_async("client,amount\n1,1.0\n".as_bytes(), &ReaderOptions::default()).collect());
+        assert!(matches!(&transactions[..], [Err(SourceError::Parse { line: 1, .. })]));
+    }
+
+    #[test]
+    fn stream_not_polled_then_writer_waits() {
+        let row = "deposit,1,1,1.0\n";
+        let rows = 10_000;
+
+        runtime().block_on(async {
+            let (mut writer, reader) = tokio::io::duplex(1024);
+            let writer = tokio::spawn(async move {
+                writer.write_all(b"type,client,tx,amount\n").await?;
+                for _ in 0..rows {
+                    writer.write_all(row.as_bytes()).await?;
+                }
+                writer.shutdown().await
+            });
+
+            // only one chunk is read ahead while stream is not polled
+            let mut transactions = Box::pin(read_from_async(reader, &ReaderOptions::default()));
+            assert!(transactions.next().await.is_some_and(|it| it.is_ok()));
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(!writer.is_finished());
+
+            assert_eq!(transactions.count().await, rows - 1);
+            assert!(writer.await.unwrap().is_ok());
+        });
+    }
+}
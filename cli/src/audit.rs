@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use clap::ArgMatches;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use crate::{manifest, CliError, ErrorType};
+use crate::checksum::to_hex;
+
+// previous hash of the first record
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Event record with hash of previous record and own hash, so any altered, removed or reordered
+/// record breaks the chain
+#[derive(Debug, Serialize)]
+pub struct ChainedEvent<'a, T> {
+    #[serde(flatten)]
+    event: &'a T,
+    prev_hash: String,
+    hash: String,
+}
+
+/// Hash chain of event log: hash of record is SHA-256 of previous hash and record fields (json with sorted keys),
+/// hash of the last record is root hash of the log
+#[derive(Debug, Clone)]
+pub struct HashChain {
+    last: String,
+    len: u64,
+}
+
+impl Default for HashChain {
+    fn default() -> Self {
+        HashChain { last: GENESIS.to_string(), len: 0 }
+    }
+}
+
+impl HashChain {
+    pub fn new() -> Self {
+        HashChain::default()
+    }
+
+    /// Append event to chain
+    pub fn link<'a, T: Serialize>(&mut self, event: &'a T) -> Result<ChainedEvent<'a, T>, CliError> {
+        let fields = match serde_json::to_value(event).map_err(|e| CliError::from_source(ErrorType::IO, e))? {
+            Value::Object(fields) => fields,
+            _ => return Err(CliError::new(ErrorType::IO, "Chained event must be json object")),
+        };
+        let prev_hash = self.last.clone();
+        let hash = self.next(&fields);
+        Ok(ChainedEvent { event, prev_hash, hash })
+    }
+
+    /// Hash of the last record, hash of empty log is all zeros
+    pub fn root(&self) -> &str {
+        &self.last
+    }
+
+    pub fn events(&self) -> u64 {
+        self.len
+    }
+
+    fn next(&mut self, fields: &Map<String, Value>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.last.as_bytes());
+        // serde_json map keeps keys sorted, so writer and verifier hash the same bytes
+        hasher.update(Value::Object(fields.clone()).to_string().as_bytes());
+        self.last = to_hex(&hasher.finalize());
+        self.len += 1;
+        self.last.clone()
+    }
+}
+
+/// `verify-audit` subcommand: recompute hash chain of audit log written with `--audit-chain` and compare
+/// root hash with `--root-hash` or root hash in run manifest
+pub fn verify(matches: &ArgMatches) -> Result<(), CliError> {
+    let file_path = matches.value_of("file_path")
+        .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg file path not found"))?;
+    let expected = match (matches.value_of("root_hash"), matches.value_of("manifest")) {
+        (Some(root_hash), _) => Some(root_hash.trim().to_string()),
+        (None, Some(manifest)) => Some(manifest::read_audit_root(manifest)?),
+        (None, None) => None,
+    };
+
+    let chain = verify_chain(file_path)?;
+    if let Some(expected) = expected {
+        if !chain.root().eq_ignore_ascii_case(&expected) {
+            return Err(CliError::new(ErrorType::Integrity, format!("Audit log {} root hash mismatch, expected {}, actual {}", file_path, expected, chain.root())));
+        }
+    }
+
+    println!("{} events, root hash {}", chain.events(), chain.root());
+    Ok(())
+}
+
+fn verify_chain(file_path: &str) -> Result<HashChain, CliError> {
+    let mut chain = HashChain::new();
+    for (line, record) in (1..).zip(BufReader::new(File::open(file_path)?).lines()) {
+        let record = record?;
+        let error = |message: &str| CliError::new(ErrorType::Integrity, format!("Audit log {} broken at line {}: {}", file_path, line, message));
+
+        let mut fields = match serde_json::from_str(&record) {
+            Ok(Value::Object(fields)) => fields,
+            _ => return Err(error("not a json object")),
+        };
+        let (Some(Value::String(prev_hash)), Some(Value::String(hash))) = (fields.remove("prev_hash"), fields.remove("hash")) else {
+            return Err(error("missing hash, log was written without --audit-chain"));
+        };
+
+        if prev_hash != chain.root() {
+            return Err(error("previous hash mismatch, record removed or reordered"));
+        }
+        if hash != chain.next(&fields) {
+            return Err(error("hash mismatch, record altered"));
+        }
+    }
+    Ok(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use core::audit::{AdminAction, AuditLog};
+    use core::error::ProcessError;
+    use crate::audit::HashChain;
+
+    #[test]
+    fn known_events_then_fixed_hashes() {
+        let mut log = AuditLog::new();
+        log.record("alice", AdminAction::Adjust { client: 2, amount: Decimal::new(15, 1) }, &Ok::<_, ProcessError>(()));
+        log.record("mallory", AdminAction::Unlock { client: 1 }, &Err::<(), _>(ProcessError::Unauthorized));
+
+        // keys are hashed sorted at every level (serde_json without preserve_order), not in field order
+        let mut chain = HashChain::new();
+        let first = serde_json::to_value(chain.link(&log.events()[0]).unwrap()).unwrap();
+        assert_eq!(first["prev_hash"], "0".repeat(64));
+        assert_eq!(first["hash"], "15ae38b019b382c8c8c22751a9038a1a6efb50c815cb0ed2ad26381c372f1b4a");
+        let second = serde_json::to_value(chain.link(&log.events()[1]).unwrap()).unwrap();
+        assert_eq!(second["prev_hash"], first["hash"]);
+        assert_eq!(second["hash"], "bbf8607d3e2a5578ae27ccfed182fa0190b6123ce6c2821fa1549eda46b289c5");
+        assert_eq!(chain.root(), "bbf8607d3e2a5578ae27ccfed182fa0190b6123ce6c2821fa1549eda46b289c5");
+        assert_eq!(chain.events(), 2);
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+use std::fs::File;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use core::account::view::AccountView;
+use core::client::Client;
+use core::history::TransactionArchive;
+use crate::{CliError, ErrorType};
+use crate::source::{self, ReaderOptions};
+
+/// Opening balance row, total is computed from available and held.
+/// Held, locked and closed columns are optional, so closing snapshot (accounts output) is valid input too.
+#[derive(Debug, Deserialize)]
+struct OpeningBalance {
+    client: Client,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    held: Option<Decimal>,
+
+    #[serde(default)]
+    locked: bool,
+
+    #[serde(default)]
+    closed: bool,
+}
+
+/// `--opening-balances`: accounts (client, available, held, locked) loaded before processing,
+/// e.g. closing snapshot of previous day. Every client may be listed only once.
+pub fn read_opening_balances(file_path: &str) -> Result<Vec<AccountView>, CliError> {
+    let mut clients = HashSet::new();
+    let mut accounts = Vec::new();
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(File::open(file_path)?);
+    for (line, record) in (2..).zip(reader.deserialize::<OpeningBalance>()) {
+        let balance = record?;
+        let error = |message: String| CliError::new(ErrorType::CsvRead, format!("Invalid opening balance at line {}: {}", line, message));
+
+        if !clients.insert(balance.client) {
+            return Err(error(format!("duplicate client {}", balance.client)));
+        }
+
+        let account = AccountView::new(balance.client, balance.available, balance.held.unwrap_or_default(), balance.locked, balance.closed)
+            .map_err(|e| error(e.to_string()))?;
+        accounts.push(account);
+    }
+
+    Ok(accounts)
+}
+
+/// `--history`: input files of previous runs, deposits and withdrawals are kept so transactions
+/// disputed after accounts were restored from `--opening-balances` can still be found
+pub fn read_history<'a, I>(file_paths: I, options: &ReaderOptions) -> Result<TransactionArchive, CliError>
+    where I: IntoIterator<Item=&'a str>
+{
+    let mut archive = TransactionArchive::new();
+    for file_path in file_paths {
+        for transaction in source::open(file_path, options)? {
+            archive.insert(transaction?);
+        }
+    }
+
+    Ok(archive)
+}
@@ -0,0 +1,123 @@
+use std::convert::Infallible;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use clap::ArgMatches;
+use serde::Serialize;
+use core::account::view::AccountView;
+use core::{BasicProcessor, TransactionProcessor, WrapProcessor};
+use core::transaction::Transaction;
+use core::pipeline::{run, RunOptions};
+use crate::{source, CliError, ErrorType};
+use crate::source::ReaderOptions;
+
+// names accepted by --processors
+#[cfg(not(feature = "fastmath"))]
+const PROCESSORS: [&str; 2] = ["basic", "wrap"];
+#[cfg(feature = "fastmath")]
+const PROCESSORS: [&str; 3] = ["basic", "wrap", "fixed"];
+
+/// `bench` subcommand: run every processor over the same transactions (read into memory once, so
+/// input parsing is not measured), verify all processors produce the same accounts and print
+/// throughput per processor. Peak memory is measured only with `profiling` feature.
+pub fn bench(matches: &ArgMatches) -> Result<(), CliError> {
+    let input = matches.value_of("input").ok_or_else(|| {
+        // this should not happen at this stage
+        CliError::new(ErrorType::CliParseError, "Arg input not found")
+    })?;
+    let processors = matches.value_of("processors").unwrap_or("basic,wrap").split(',')
+        .map(str::trim)
+        .map(|it| PROCESSORS.into_iter().find(|name| *name == it)
+            .ok_or_else(|| CliError::new(ErrorType::CliParseError, format!("Unknown processor '{}', available: {}", it, PROCESSORS.join(",")))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let transactions = source::open(input, &ReaderOptions::default())?.collect::<Result<Vec<_>, _>>()?;
+
+    let mut results: Vec<BenchResult> = Vec::with_capacity(processors.len());
+    let mut expected: Option<Vec<AccountView>> = None;
+    for name in processors {
+        let (result, accounts) = match name {
+            "wrap" => measure(name, &transactions, WrapProcessor::new())?,
+            #[cfg(feature = "fastmath")]
+            "fixed" => measure(name, &transactions, core::FixedProcessor::new())?,
+            _ => measure(name, &transactions, BasicProcessor::new())?,
+        };
+
+        match &expected {
+            Some(expected) if *expected != accounts => {
+                return Err(CliError::new(ErrorType::Integrity, format!("Output of processor '{}' doesn't match output of '{}'", name, results[0].processor)));
+            }
+            Some(_) => {}
+            None => expected = Some(accounts),
+        }
+        results.push(result);
+    }
+
+    let stdout = std::io::stdout();
+    match matches.value_of("format").unwrap_or("text") {
+        "json" => serde_json::to_writer_pretty(stdout.lock(), &results)
+            .map_err(|e| CliError::from_source(ErrorType::IO, e)),
+        _ => write_text(&results, &mut stdout.lock()),
+    }
+}
+
+/// Measurement of single processor
+#[derive(Serialize)]
+struct BenchResult {
+    processor: &'static str,
+    transactions: u64,
+    rejected: u64,
+    accounts: u64,
+    elapsed_ms: u128,
+    transactions_per_second: u64,
+
+    // live heap bytes above the level before run, only with `profiling` feature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peak_memory_bytes: Option<u64>,
+}
+
+fn measure<P>(name: &'static str, transactions: &[Transaction], processor: P) -> Result<(BenchResult, Vec<AccountView>), CliError>
+    where P: TransactionProcessor,
+          P::Output: IntoIterator<Item=AccountView>
+{
+    #[cfg(feature = "profiling")]
+    let baseline = crate::profiling::reset_peak();
+
+    let mut accounts = Vec::new();
+    let started = Instant::now();
+    let report = run(transactions.iter().cloned().map(Ok::<_, Infallible>), processor, &mut accounts, &RunOptions::default())?;
+    let elapsed = started.elapsed();
+
+    #[cfg(feature = "profiling")]
+    let peak_memory_bytes = Some(crate::profiling::peak_bytes().saturating_sub(baseline));
+    #[cfg(not(feature = "profiling"))]
+    let peak_memory_bytes = None;
+
+    // processors may write accounts in any order
+    accounts.sort_unstable_by_key(|it| *it.client());
+
+    let result = BenchResult {
+        processor: name,
+        transactions: report.read,
+        rejected: report.rejected,
+        accounts: accounts.len() as u64,
+        elapsed_ms: elapsed.as_millis(),
+        transactions_per_second: throughput(report.read, elapsed),
+        peak_memory_bytes,
+    };
+    Ok((result, accounts))
+}
+
+fn throughput(transactions: u64, elapsed: Duration) -> u64 {
+    (transactions as f64 / elapsed.as_secs_f64().max(f64::EPSILON)) as u64
+}
+
+fn write_text<W: Write>(results: &[BenchResult], writer: &mut W) -> Result<(), CliError> {
+    writeln!(writer, "{:<10} {:>12} {:>10} {:>10} {:>12} {:>14} {:>14}", "processor", "transactions", "rejected", "accounts", "elapsed ms", "tx/s", "peak memory")?;
+    for result in results {
+        let memory = result.peak_memory_bytes.map_or_else(|| "-".to_owned(), |it| it.to_string());
+        writeln!(writer, "{:<10} {:>12} {:>10} {:>10} {:>12} {:>14} {:>14}",
+                 result.processor, result.transactions, result.rejected, result.accounts, result.elapsed_ms, result.transactions_per_second, memory)?;
+    }
+    writeln!(writer, "\nOutputs of all processors match")?;
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use sha2::{Digest, Sha256};
+use crate::{CliError, ErrorType};
+use crate::source::{STDIN, TCP_PREFIX};
+
+/// SHA-256 of data streamed through `reader`/`writer` wrappers, shared with the wrapper,
+/// so checksum can be taken after the stream is consumed by pipeline (also on other thread, e.g. prefetch)
+#[derive(Clone, Default)]
+pub struct StreamDigest {
+    hasher: Arc<Mutex<Sha256>>,
+}
+
+impl StreamDigest {
+    pub fn new() -> Self {
+        StreamDigest::default()
+    }
+
+    pub fn reader<R: Read>(&self, inner: R) -> DigestReader<R> {
+        DigestReader {
+            inner,
+            digest: self.clone(),
+        }
+    }
+
+    pub fn writer<W: Write>(&self, inner: W) -> DigestWriter<W> {
+        DigestWriter {
+            inner,
+            digest: self.clone(),
+        }
+    }
+
+    /// Hex encoded checksum of data seen so far
+    pub fn hex(&self) -> String {
+        to_hex(&self.hasher.lock().unwrap_or_else(PoisonError::into_inner).clone().finalize())
+    }
+
+    fn update(&self, data: &[u8]) {
+        self.hasher.lock().unwrap_or_else(PoisonError::into_inner).update(data);
+    }
+}
+
+pub struct DigestReader<R: Read> {
+    inner: R,
+    digest: StreamDigest,
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.digest.update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+pub struct DigestWriter<W: Write> {
+    inner: W,
+    digest: StreamDigest,
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.digest.update(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Refuse input file which SHA-256 doesn't match expected one (hex, case insensitive).
+/// Stdin and tcp streams can't be verified before processing.
+pub fn verify_input(path: &str, expected: &str) -> Result<(), CliError> {
+    if path == STDIN || path.starts_with(TCP_PREFIX) {
+        return Err(CliError::new(ErrorType::Integrity, "Checksum verification requires input file"));
+    }
+
+    let actual = file_sha256(path)?;
+    match actual.eq_ignore_ascii_case(expected.trim()) {
+        true => Ok(()),
+        false => Err(CliError::new(ErrorType::Integrity, format!("Input {} SHA-256 mismatch, expected {}, actual {}", path, expected.trim(), actual))),
+    }
+}
+
+/// Hex encoded SHA-256 of file content
+pub fn file_sha256<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+
+    #[cfg(feature = "object-store")]
+    if crate::remote::is_remote(&path) {
+        io::copy(&mut crate::remote::open(path)?, &mut hasher)?;
+        return Ok(to_hex(&hasher.finalize()));
+    }
+
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|it| format!("{:02x}", it)).collect()
+}
@@ -1,18 +1,937 @@
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgGroup, ArgMatches, Command};
+use crate::output::{DATABASE_FORMATS, OUTPUT_FORMATS};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
 const NAME: &str = env!("CARGO_PKG_NAME");
 
 pub fn build() -> ArgMatches {
-    Command::new(NAME)
+    let command = Command::new(NAME)
         .about("Simple CSV reader for transaction analyze")
         .version(VERSION)
         .arg_required_else_help(true)
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
         .author(AUTHOR)
         .arg(Arg::new("file_path")
-            .help("File path where csv file is located")
+            .help("File path where csv file is located, '-' for stdin, *.gz for gzip file or tcp://host:port")
             .required(true)
             .index(1)
-        ).get_matches()
+        )
+        .arg(Arg::new("format")
+            .help("Output format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(OUTPUT_FORMATS.iter().chain(DATABASE_FORMATS).copied())
+            .default_value("csv")
+        )
+        .arg(Arg::new("out_shards")
+            .help("Split accounts output into N files partitioned by client id")
+            .long("out-shards")
+            .takes_value(true)
+            .value_name("N")
+            .requires("out_dir")
+        )
+        .arg(Arg::new("split_locked")
+            .help("Split accounts output into accounts_locked and accounts_unlocked files")
+            .long("split-locked")
+            .requires("out_dir")
+        )
+        .arg(Arg::new("by_tenant")
+            .help("Keep accounts per tenant (tenant column), accounts of each tenant are written into <tenant> file")
+            .long("by-tenant")
+            .requires("out_dir")
+        )
+        .arg(Arg::new("tenant")
+            .help("Tenant of rows without tenant column value, e.g. when every tenant sends own file")
+            .long("tenant")
+            .takes_value(true)
+            .value_name("name")
+            .requires("by_tenant")
+        )
+        .arg(Arg::new("tenant_quota")
+            .help("Max transactions of every tenant, further transactions of tenant are rejected (E212)")
+            .long("tenant-quota")
+            .takes_value(true)
+            .value_name("N")
+            .requires("by_tenant")
+        )
+        .arg(Arg::new("tenant_stats")
+            .help("Json file with processed, rejected and throttled transactions per tenant")
+            .long("tenant-stats")
+            .takes_value(true)
+            .value_name("file_path")
+            .requires("by_tenant")
+        )
+        .group(ArgGroup::new("partition")
+            .args(&["out_shards", "split_locked", "by_tenant"])
+        )
+        .arg(Arg::new("out_dir")
+            .help("Directory where split output files are written")
+            .long("out-dir")
+            .takes_value(true)
+            .value_name("dir_path")
+            .requires("partition")
+        )
+        .arg(Arg::new("checkpoint")
+            .help("File where run checkpoint (consumed transactions) is written when interrupted")
+            .long("checkpoint")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("dump_path")
+            .help("File where current accounts state is written on SIGHUP")
+            .long("dump-path")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("fx_table")
+            .help("FX table csv (currency,rate) used to convert multi-currency balances into reporting currency")
+            .long("fx-table")
+            .takes_value(true)
+            .value_name("file_path")
+            .requires("reporting_currency")
+            .conflicts_with("partition")
+        )
+        .arg(Arg::new("reporting_currency")
+            .help("Reporting currency, also currency of rows without currency column")
+            .long("reporting-currency")
+            .takes_value(true)
+            .value_name("code")
+            .requires("fx_table")
+        )
+        .arg(Arg::new("opening_balances")
+            .help("Accounts csv (client, available, held, locked) loaded before processing, e.g. closing snapshot of previous day")
+            .long("opening-balances")
+            .takes_value(true)
+            .value_name("file_path")
+            .conflicts_with("fx_table")
+        )
+        .arg(Arg::new("history")
+            .help("Input csv of previous runs, deposits and withdrawals are looked up by late disputes of restored accounts")
+            .long("history")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("file_path")
+            .requires("opening_balances")
+        )
+        .arg(Arg::new("admin_ops")
+            .help("Json lines file with admin operations (unlock, adjust) applied before processing, e.g. {\"principal\":\"alice\",\"op\":\"unlock\",\"client\":1}")
+            .long("admin-ops")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("admin")
+            .help("Principal authorized for admin operations, other principals are denied")
+            .long("admin")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("principal")
+            .requires("admin_ops")
+        )
+        .arg(Arg::new("audit_log")
+            .help("Json lines file with audit events of admin operations, also denied ones")
+            .long("audit-log")
+            .takes_value(true)
+            .value_name("file_path")
+            .requires("admin_ops")
+        )
+        .arg(Arg::new("erase")
+            .help("Erase client (account and retained transactions) before processing, for deletion requests, only anonymized tombstone is kept")
+            .long("erase")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("client")
+        )
+        .arg(Arg::new("tombstones")
+            .help("Json file with tombstones of erased clients (sequence, account, number of transactions, no client id)")
+            .long("tombstones")
+            .takes_value(true)
+            .value_name("file_path")
+            .requires("erase")
+        )
+        .arg(Arg::new("audit_chain")
+            .help("Hash-chain audit log records (previous record hash and own hash), root hash is added to run manifest")
+            .long("audit-chain")
+            .requires("audit_log")
+        )
+        .arg(Arg::new("compact")
+            .help("Evict settled transactions of every client except the last N (and disputed ones) when retained transactions double, bounds memory of long-running sources")
+            .long("compact")
+            .takes_value(true)
+            .value_name("keep_last_n")
+        )
+        .arg(Arg::new("compact_archive")
+            .help("Csv file with transactions evicted by --compact, valid --history input")
+            .long("compact-archive")
+            .takes_value(true)
+            .value_name("file_path")
+            .requires("compact")
+        )
+        .arg(Arg::new("posting")
+            .help("Periodic posting applied at wall-clock boundaries (UTC), e.g. daily:fee:0.5 or monthly:interest:0.01")
+            .long("posting")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("period:kind:amount")
+        )
+        .arg(Arg::new("dormant_after")
+            .help("Add dormant column, account is dormant without activity in last N transactions")
+            .long("dormant-after")
+            .takes_value(true)
+            .value_name("N")
+        )
+        .arg(Arg::new("dispute_window")
+            .help("Transactions can be disputed only within next N transactions, older ones are evicted from memory")
+            .long("dispute-window")
+            .takes_value(true)
+            .value_name("N")
+        )
+        .arg(Arg::new("dispute_expiry")
+            .help("Disputes not resolved or charged back within N transactions (or by the end of run) expire")
+            .long("dispute-expiry")
+            .takes_value(true)
+            .value_name("N|end")
+        )
+        .arg(Arg::new("dispute_expiry_action")
+            .help("Expired dispute is resolved (held funds released) or only flagged with event")
+            .long("dispute-expiry-action")
+            .takes_value(true)
+            .possible_values(["resolve", "flag"])
+            .default_value("resolve")
+        )
+        .arg(Arg::new("dispute_events")
+            .help("Json lines file with events of expired disputes")
+            .long("dispute-events")
+            .takes_value(true)
+            .value_name("file_path")
+            .requires("dispute_expiry")
+        )
+        .arg(Arg::new("status_events")
+            .help("Json lines file with account status changes (closed, locked by chargeback, unlocked by admin)")
+            .long("status-events")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("held_accrual")
+            .help("Add accrued column, interest (or penalty with negative rate) on disputed funds per N transactions held, e.g. 0.001:1000")
+            .long("held-accrual")
+            .takes_value(true)
+            .value_name("rate:N")
+        )
+        .arg(Arg::new("suspense")
+            .help("Json suspense ledger: disputes, resolves and chargebacks referencing missing or other client transactions with amounts in question")
+            .long("suspense")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("backfill")
+            .help("Input is historical data reloaded into existing state: chargebacks don't lock accounts, risk rules don't put accounts under review, no status, dispute or suspense events")
+            .long("backfill")
+        )
+        .arg(Arg::new("ordered")
+            .help("Write accounts in ascending client order, output is bit-for-bit reproducible across runs and platforms")
+            .long("ordered")
+        )
+        .arg(Arg::new("prefetch")
+            .help("Read input ahead on separate thread, at most N transactions are kept in memory and overflow is spilled to temporary file (bursty stdin or tcp sources)")
+            .long("prefetch")
+            .takes_value(true)
+            .value_name("N")
+        )
+        .arg(Arg::new("batch_size")
+            .help("Process transactions in batches of N, consecutive deposits of one client are applied at once (same results, faster for deposit-heavy files)")
+            .long("batch-size")
+            .takes_value(true)
+            .value_name("N")
+        )
+        .arg(Arg::new("latency")
+            .help("Measure processing latency per transaction type, p50/p99 are printed after run and written to manifest")
+            .long("latency")
+        )
+        .arg(Arg::new("dlq")
+            .help("File where rejected transactions are written (one json object with error code per line)")
+            .long("dlq")
+            .takes_value(true)
+            .value_name("file_path")
+            .conflicts_with("fx_table")
+        )
+        .arg(Arg::new("dlq_route")
+            .help("Route rejected transactions with given error codes (or code prefixes) to separate file, e.g. E205=duplicates.ndjson or E1=malformed.ndjson")
+            .long("dlq-route")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("codes=file_path")
+            .conflicts_with("fx_table")
+        )
+        .arg(Arg::new("results_out")
+            .help("File where result of every transaction (applied or rejected with error code) is written as csv in input order")
+            .long("results-out")
+            .takes_value(true)
+            .value_name("file_path")
+            .conflicts_with("fx_table")
+        )
+        .arg(Arg::new("manifest")
+            .help("File where run manifest (input and output checksums, row and error counts, duration) is written as json")
+            .long("manifest")
+            .takes_value(true)
+            .value_name("file_path")
+            .conflicts_with("fx_table")
+        )
+        .arg(Arg::new("output_sidecar")
+            .help("File where row count and SHA-256 of accounts output are written as json")
+            .long("output-sidecar")
+            .takes_value(true)
+            .value_name("file_path")
+            .conflicts_with("fx_table")
+        )
+        .arg(Arg::new("delimiter")
+            .help("Input field delimiter: comma, semicolon, tab, pipe, any single character or auto (detected from the first line)")
+            .long("delimiter")
+            .takes_value(true)
+            .default_value("comma")
+        )
+        .arg(Arg::new("unknown_types")
+            .help("Rows with unknown transaction type: fail the run or skip and report them with line numbers")
+            .long("unknown-types")
+            .takes_value(true)
+            .possible_values(["fail", "skip"])
+            .default_value("fail")
+        )
+        .arg(Arg::new("type_map")
+            .help("Translate vendor specific type name to transaction type, e.g. CREDIT=deposit")
+            .long("type-map")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("name=type")
+        )
+        .arg(Arg::new("filter")
+            .help("Write only accounts matching expression, e.g. \"total > 0 && !locked\" (fields: client, available, held, total, locked, closed)")
+            .long("filter")
+            .takes_value(true)
+            .value_name("expression")
+            .conflicts_with("fx_table")
+        )
+        .arg(Arg::new("force")
+            .help("Overwrite existing output files (shards, dlq, manifest, sidecar)")
+            .long("force")
+        )
+        .arg(Arg::new("expect_sha256")
+            .help("Refuse to process input file which SHA-256 doesn't match")
+            .long("expect-sha256")
+            .takes_value(true)
+            .value_name("hash")
+            .conflicts_with("verify_manifest")
+        )
+        .arg(Arg::new("verify_manifest")
+            .help("Refuse to process input file which SHA-256 doesn't match input checksum in manifest")
+            .long("verify-manifest")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("ledger")
+            .help("Ingestion ledger of processed input files (path, SHA-256, rows), already ingested input is refused")
+            .long("ledger")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("reprocess")
+            .help("Process input already recorded in ingestion ledger")
+            .long("reprocess")
+            .requires("ledger")
+        )
+        .subcommand(Command::new("replay-dlq")
+            .about("Re-submit rejected transactions from DLQ file against accounts snapshot and write updated accounts")
+            .arg(Arg::new("file_path")
+                .help("DLQ file written with --dlq")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::new("snapshot")
+                .help("Accounts snapshot (csv output of previous run or dump)")
+                .long("snapshot")
+                .takes_value(true)
+                .required(true)
+                .value_name("file_path")
+            )
+            .arg(Arg::new("format")
+                .help("Output format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(OUTPUT_FORMATS)
+                .default_value("csv")
+            )
+            .arg(Arg::new("dlq")
+                .help("File where transactions rejected again are written")
+                .long("dlq")
+                .takes_value(true)
+                .value_name("file_path")
+            )
+            .arg(Arg::new("dlq_route")
+                .help("Route rejected transactions with given error codes (or code prefixes) to separate file, e.g. E205=duplicates.ndjson or E1=malformed.ndjson")
+                .long("dlq-route")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .value_name("codes=file_path")
+            )
+            .arg(Arg::new("force")
+                .help("Overwrite existing dlq files")
+                .long("force")
+            )
+        )
+        .subcommand(Command::new("report")
+            .about("Process transactions and write aggregates: total funds, top accounts, locked accounts and balance distribution")
+            .arg(Arg::new("file_path")
+                .help("File path where csv file is located, '-' for stdin, *.gz for gzip file or tcp://host:port")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::new("top")
+                .help("Number of accounts with the highest total")
+                .long("top")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("10")
+            )
+            .arg(Arg::new("buckets")
+                .help("Bounds of balance distribution buckets, e.g. 0,100,1000")
+                .long("buckets")
+                .takes_value(true)
+                .value_name("bounds")
+            )
+            .arg(Arg::new("hierarchy")
+                .help("Sub-accounts csv (client, parent), consolidated accounts of top-level parents are added to report")
+                .long("hierarchy")
+                .takes_value(true)
+                .value_name("file_path")
+            )
+            .arg(Arg::new("minimize_pii")
+                .help("Report without per-client balances: aggregates and anomalies (locked, held, negative accounts) by pseudonym, pseudonym to client lookup is written to this separate file")
+                .long("minimize-pii")
+                .takes_value(true)
+                .value_name("lookup_file_path")
+                .requires("pii_key")
+                .conflicts_with_all(&["top", "hierarchy"])
+            )
+            .arg(Arg::new("pii_key")
+                .help("Secret of pseudonyms (HMAC-SHA256 of client id), read from file or from environment variable with env:<NAME>")
+                .long("pii-key")
+                .takes_value(true)
+                .value_name("file_path|env:NAME")
+                .requires("minimize_pii")
+            )
+            .arg(Arg::new("force")
+                .help("Overwrite existing lookup file")
+                .long("force")
+            )
+            .arg(Arg::new("format")
+                .help("Report format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(["text", "json"])
+                .default_value("text")
+            )
+        )
+        .subcommand(Command::new("reconcile")
+            .about("Match balances of external statement with accounts output per client and report mismatches with transactions of client")
+            .arg(Arg::new("statement")
+                .help("External balances csv (client, available, optional held and total)")
+                .long("statement")
+                .takes_value(true)
+                .value_name("file_path")
+                .required(true)
+            )
+            .arg(Arg::new("accounts")
+                .help("Accounts csv written by csv-cli-analyzer")
+                .long("accounts")
+                .takes_value(true)
+                .value_name("file_path")
+                .required(true)
+            )
+            .arg(Arg::new("transactions")
+                .help("Input csv of accounts output, transactions of mismatched clients are listed")
+                .long("transactions")
+                .takes_value(true)
+                .value_name("file_path")
+            )
+            .arg(Arg::new("format")
+                .help("Report format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(["text", "json"])
+                .default_value("text")
+            )
+        )
+        .subcommand(Command::new("delta")
+            .about("Per-client changes between previous and current accounts snapshot (available, held, total, newly locked), unchanged accounts are skipped")
+            .arg(Arg::new("previous")
+                .help("Previous accounts snapshot (csv output)")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::new("current")
+                .help("Current accounts snapshot (csv output)")
+                .required(true)
+                .index(2)
+            )
+            .arg(Arg::new("format")
+                .help("Output format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(OUTPUT_FORMATS)
+                .default_value("csv")
+            )
+        )
+        .subcommand(Command::new("snapshot")
+            .about("Accounts snapshots tooling: diff of two snapshots, merge of snapshots of sharded runs")
+            .subcommand_required(true)
+            .subcommand(Command::new("diff")
+                .about("Changed accounts between two snapshots (same rows as delta), fails when snapshots differ")
+                .arg(Arg::new("previous")
+                    .help("First accounts snapshot (csv output)")
+                    .required(true)
+                    .index(1)
+                    .value_name("a")
+                )
+                .arg(Arg::new("current")
+                    .help("Second accounts snapshot (csv output)")
+                    .required(true)
+                    .index(2)
+                    .value_name("b")
+                )
+                .arg(Arg::new("format")
+                    .help("Output format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(OUTPUT_FORMATS)
+                    .default_value("csv")
+                )
+            )
+            .subcommand(Command::new("merge")
+                .about("Accounts of snapshots with disjoint client sets (runs over input sharded by client) in ascending client order")
+                .arg(Arg::new("snapshots")
+                    .help("Accounts snapshots (csv output), client can be in one snapshot only")
+                    .required(true)
+                    .multiple_values(true)
+                    .min_values(1)
+                    .index(1)
+                )
+                .arg(Arg::new("format")
+                    .help("Output format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(OUTPUT_FORMATS)
+                    .default_value("csv")
+                )
+            )
+        )
+        .subcommand(Command::new("distribute")
+            .about("Map-reduce style run: split input by client into shards, process every shard with separate worker process and merge accounts of workers")
+            .arg(Arg::new("file_path")
+                .help("File path where csv file is located, '-' for stdin, *.gz for gzip file or tcp://host:port")
+                .required_unless_present("worker_results")
+                .index(1)
+            )
+            .arg(Arg::new("workers")
+                .help("Number of shards and worker processes")
+                .long("workers")
+                .takes_value(true)
+                .value_name("N")
+                .required_unless_present("worker_results")
+            )
+            .arg(Arg::new("work_dir")
+                .help("Existing directory of shard files (shard_<i>.csv) and accounts of workers (shard_<i>.accounts.csv)")
+                .long("work-dir")
+                .takes_value(true)
+                .value_name("dir")
+                .required_unless_present("worker_results")
+            )
+            .arg(Arg::new("delimiter")
+                .help("Input field delimiter: comma, semicolon, tab, pipe, any single character or auto (detected from the first line), shards are comma delimited")
+                .long("delimiter")
+                .takes_value(true)
+                .default_value("comma")
+            )
+            .arg(Arg::new("split_only")
+                .help("Only split input and print paths of shard files, e.g. to process shards on other machines")
+                .long("split-only")
+            )
+            .arg(Arg::new("worker_results")
+                .help("Merge accounts outputs (csv) of workers run elsewhere instead of splitting input")
+                .long("worker-results")
+                .takes_value(true)
+                .multiple_values(true)
+                .min_values(1)
+                .value_name("file_path")
+                .conflicts_with_all(&["file_path", "workers", "work_dir", "split_only", "worker_args"])
+            )
+            .arg(Arg::new("worker_args")
+                .help("Arguments of worker processes, e.g. -- --dispute-window 1000 (output format of workers must stay csv)")
+                .multiple_values(true)
+                .index(2)
+                .last(true)
+            )
+            .arg(Arg::new("force")
+                .help("Overwrite existing shard files and accounts of workers")
+                .long("force")
+            )
+            .arg(Arg::new("format")
+                .help("Output format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(OUTPUT_FORMATS)
+                .default_value("csv")
+            )
+        )
+        .subcommand(Command::new("verify-audit")
+            .about("Verify hash chain of audit log written with --audit-chain and print its root hash")
+            .arg(Arg::new("file_path")
+                .help("Audit log written with --audit-log")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::new("root_hash")
+                .help("Expected root hash")
+                .long("root-hash")
+                .takes_value(true)
+                .value_name("hash")
+            )
+            .arg(Arg::new("manifest")
+                .help("Run manifest with expected root hash")
+                .long("manifest")
+                .takes_value(true)
+                .value_name("file_path")
+                .conflicts_with("root_hash")
+            )
+        )
+        .subcommand(Command::new("bench")
+            .about("Run processors over the same transactions, verify outputs match and print throughput per processor")
+            .arg(Arg::new("input")
+                .help("File path where csv file is located, '-' for stdin or *.gz for gzip file")
+                .long("input")
+                .takes_value(true)
+                .required(true)
+                .value_name("file_path")
+            )
+            .arg(Arg::new("processors")
+                .help("Comma separated processors to compare")
+                .long("processors")
+                .takes_value(true)
+                .value_name("names")
+                .default_value("basic,wrap")
+            )
+            .arg(Arg::new("format")
+                .help("Results format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(["text", "json"])
+                .default_value("text")
+            )
+        );
+
+    #[cfg(feature = "metadata")]
+    let command = command.arg(Arg::new("metadata_columns")
+        .help("Extra input columns kept with transactions and written to DLQ, e.g. branch,channel")
+        .long("metadata-columns")
+        .takes_value(true)
+        .value_name("columns")
+    );
+
+    #[cfg(feature = "scripting")]
+    let command = command.arg(Arg::new("script")
+        .help("Rhai script evaluated for every transaction with tx and account variables, returns false or reason string to reject transaction")
+        .long("script")
+        .takes_value(true)
+        .value_name("file_path")
+    );
+
+    #[cfg(feature = "plugins")]
+    let command = command
+        .arg(Arg::new("plugin")
+            .help("WebAssembly processor plugin, name is file stem unless given as name=file_path")
+            .long("plugin")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("[name=]file_path")
+        )
+        .arg(Arg::new("processor")
+            .help("Processor used for transactions, 'basic' (built-in, default) or name of loaded plugin")
+            .long("processor")
+            .takes_value(true)
+            .value_name("name")
+            .conflicts_with_all(&["partition", "fx_table"])
+        );
+
+    #[cfg(feature = "sqlite")]
+    let command = command
+        .arg(Arg::new("sqlite_source_table")
+            .help("Table with transactions (type, client, tx, amount) read with --format sqlite")
+            .long("sqlite-source-table")
+            .takes_value(true)
+            .default_value("transactions")
+        )
+        .arg(Arg::new("sqlite_results_table")
+            .help("Table where accounts are written with --format sqlite")
+            .long("sqlite-results-table")
+            .takes_value(true)
+            .default_value("accounts")
+        )
+        .arg(Arg::new("sqlite_outbox_table")
+            .help("Outbox table where accounts changed by the run are written with --format sqlite, in the same database transaction (published with relay-outbox)")
+            .long("sqlite-outbox-table")
+            .takes_value(true)
+            .value_name("table")
+        )
+        .arg(Arg::new("account_store")
+            .help("SQLite file used as persistent account store: accounts are loaded when first seen and every change is written through")
+            .long("account-store")
+            .takes_value(true)
+            .value_name("file_path")
+            .conflicts_with_all(&["by_tenant", "fx_table"])
+        )
+        .arg(Arg::new("warm_up")
+            .help("Number of most recently active accounts loaded from --account-store before processing")
+            .long("warm-up")
+            .takes_value(true)
+            .value_name("N")
+            .requires("account_store")
+        )
+        .arg(Arg::new("store_flush_every")
+            .help("Changed accounts are buffered and written to --account-store in one batch every N saves (write-ahead logged to <file>.wal)")
+            .long("store-flush-every")
+            .takes_value(true)
+            .value_name("N")
+            .requires("account_store")
+        )
+        .arg(Arg::new("store_flush_ms")
+            .help("Changed accounts are buffered and written to --account-store in one batch every T milliseconds (write-ahead logged to <file>.wal)")
+            .long("store-flush-ms")
+            .takes_value(true)
+            .value_name("T")
+            .requires("account_store")
+        )
+        .arg(Arg::new("store_log_level")
+            .help("Verbosity of write-behind messages (recovery, failed flush, conflicts) on stderr [default: info]")
+            .long("store-log-level")
+            .takes_value(true)
+            .value_name("level")
+            .possible_values(["error", "warn", "info"])
+            .requires("account_store")
+        )
+        .subcommand(Command::new("relay-outbox")
+            .about("Publish unpublished outbox events as JSON lines to stdout and mark them published")
+            .arg(Arg::new("database")
+                .help("SQLite file with outbox table")
+                .takes_value(true)
+                .required(true)
+                .value_name("file_path")
+            )
+            .arg(Arg::new("outbox_table")
+                .help("Outbox table")
+                .long("outbox-table")
+                .takes_value(true)
+                .default_value("outbox")
+            )
+            .arg(Arg::new("batch_size")
+                .help("Number of events published before they are marked published")
+                .long("batch-size")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("100")
+            )
+            .arg(Arg::new("follow")
+                .help("Keep polling outbox for new events until Ctrl-C")
+                .long("follow")
+            )
+        );
+
+    #[cfg(feature = "nats")]
+    let nats = Command::new("consume-nats")
+        .about("Consume JSON transactions from NATS JetStream and publish updated accounts, accounts are written to stdout on shutdown")
+        .arg(Arg::new("server")
+            .help("NATS server url")
+            .long("server")
+            .takes_value(true)
+            .value_name("url")
+            .default_value("nats://localhost:4222")
+        )
+        .arg(Arg::new("stream")
+            .help("JetStream stream with transactions")
+            .long("stream")
+            .takes_value(true)
+            .required(true)
+        )
+        .arg(Arg::new("consumer")
+            .help("Durable consumer name, created when missing")
+            .long("consumer")
+            .takes_value(true)
+            .required(true)
+        )
+        .arg(Arg::new("subject")
+            .help("Consume only transactions published to subject (filter of created consumer)")
+            .long("subject")
+            .takes_value(true)
+        )
+        .arg(Arg::new("events_subject")
+            .help("Subject where updated account is published after each applied transaction")
+            .long("events-subject")
+            .takes_value(true)
+            .required(true)
+            .value_name("subject")
+        )
+        .arg(Arg::new("snapshot")
+            .help("Accounts snapshot restored before consuming (csv output of previous run)")
+            .long("snapshot")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("format")
+            .help("Output format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(OUTPUT_FORMATS)
+            .default_value("csv")
+        );
+    #[cfg(all(feature = "nats", feature = "otel"))]
+    let nats = nats.arg(otlp_endpoint());
+    #[cfg(feature = "nats")]
+    let nats = nats.args(consumer_args());
+    #[cfg(all(feature = "nats", feature = "health"))]
+    let nats = nats.arg(health_addr());
+    #[cfg(feature = "nats")]
+    let command = command.subcommand(nats);
+
+    #[cfg(feature = "amqp")]
+    let amqp = Command::new("consume-amqp")
+        .about("Consume JSON transactions from RabbitMQ queue, accounts are written to stdout on shutdown")
+        .arg(Arg::new("uri")
+            .help("AMQP broker uri")
+            .long("uri")
+            .takes_value(true)
+            .default_value("amqp://127.0.0.1:5672/%2f")
+        )
+        .arg(Arg::new("queue")
+            .help("Queue with transactions")
+            .long("queue")
+            .takes_value(true)
+            .required(true)
+        )
+        .arg(Arg::new("dlq_exchange")
+            .help("Exchange where rejected transactions are published (routing key is error code), without it rejected messages are dead lettered by broker")
+            .long("dlq-exchange")
+            .takes_value(true)
+            .value_name("exchange")
+        )
+        .arg(Arg::new("prefetch")
+            .help("Number of unacked messages delivered at once")
+            .long("prefetch")
+            .takes_value(true)
+            .value_name("N")
+            .default_value("100")
+        )
+        .arg(Arg::new("snapshot")
+            .help("Accounts snapshot restored before consuming (csv output of previous run)")
+            .long("snapshot")
+            .takes_value(true)
+            .value_name("file_path")
+        )
+        .arg(Arg::new("format")
+            .help("Output format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(OUTPUT_FORMATS)
+            .default_value("csv")
+        );
+    #[cfg(all(feature = "amqp", feature = "otel"))]
+    let amqp = amqp.arg(otlp_endpoint());
+    #[cfg(feature = "amqp")]
+    let amqp = amqp.args(consumer_args());
+    #[cfg(all(feature = "amqp", feature = "health"))]
+    let amqp = amqp.arg(health_addr());
+    #[cfg(feature = "amqp")]
+    let command = command.subcommand(amqp);
+
+    #[cfg(feature = "encryption")]
+    let command = command
+        .arg(Arg::new("encryption_key")
+            .help("AES-256-GCM key (64 hex characters) of snapshot, DLQ and audit log files, read from file or from environment variable with env:<NAME>")
+            .long("encryption-key")
+            .takes_value(true)
+            .value_name("file_path|env:NAME")
+            .global(true)
+        )
+        .subcommand(Command::new("decrypt")
+            .about("Write decrypted snapshot, DLQ or audit log file to stdout")
+            .arg(Arg::new("file_path")
+                .help("File written with --encryption-key")
+                .required(true)
+                .index(1)
+            )
+        );
+
+    command.get_matches()
+}
+
+// journal, config reload and periodic stats document of consumed transactions
+#[cfg(any(feature = "nats", feature = "amqp"))]
+fn consumer_args() -> Vec<Arg<'static>> {
+    let args = vec![
+        Arg::new("journal")
+            .help("Json lines file where every consumed transaction is synced before its message is acked, replayed after --snapshot on start (use it with the same snapshot)")
+            .long("journal")
+            .takes_value(true)
+            .required(true)
+            .value_name("file_path"),
+        Arg::new("config")
+            .help("Json file with tunable policies (dispute_window, amount_limit, log_level: error|warn|info), reloaded without restart when changed")
+            .long("config")
+            .takes_value(true)
+            .value_name("file_path"),
+        Arg::new("audit_log")
+            .help("Json lines file where config change audit events are appended, stderr without it")
+            .long("audit-log")
+            .takes_value(true)
+            .value_name("file_path")
+            .requires("config"),
+        Arg::new("stats_json")
+            .help("File where JSON stats of the run (messages, transactions by type and error code, accounts) are written periodically and on shutdown")
+            .long("stats-json")
+            .takes_value(true)
+            .value_name("file_path"),
+        Arg::new("stats_interval")
+            .help("Seconds between stats dumps")
+            .long("stats-interval")
+            .takes_value(true)
+            .value_name("seconds")
+            .default_value("10"),
+    ];
+    #[cfg(feature = "stats-push")]
+    let args = [args, vec![
+        Arg::new("stats_url")
+            .help("HTTP endpoint where JSON stats are posted periodically and on shutdown")
+            .long("stats-url")
+            .takes_value(true)
+            .value_name("url"),
+    ]].concat();
+    args
+}
+
+// orchestration endpoints of consumer
+#[cfg(all(feature = "health", any(feature = "nats", feature = "amqp")))]
+fn health_addr() -> Arg<'static> {
+    Arg::new("health_addr")
+        .help("Address of HTTP server with /healthz, /readyz (ready after snapshot restore and broker connection) and POST /admin/drain (stop consuming, finish message in flight)")
+        .long("health-addr")
+        .takes_value(true)
+        .value_name("host:port")
+}
+
+// trace export of consumed transactions
+#[cfg(all(feature = "otel", any(feature = "nats", feature = "amqp")))]
+fn otlp_endpoint() -> Arg<'static> {
+    Arg::new("otlp_endpoint")
+        .help("OTLP/HTTP traces endpoint, e.g. http://localhost:4318/v1/traces, spans are exported per transaction (ingest, validate, apply)")
+        .long("otlp-endpoint")
+        .takes_value(true)
+        .value_name("url")
 }
@@ -0,0 +1,88 @@
+use std::io::BufWriter;
+use core::BasicProcessor;
+use core::transaction::Transaction;
+use crate::{CliError, ErrorType};
+use crate::write::TempFile;
+
+// retained transactions which trigger the first compaction
+const MIN_HIGH_WATER: usize = 100_000;
+
+/// Compaction of settled transactions (`--compact <keep_last_n>`), bounds memory of long-running stdin/tcp
+/// sources. Processor is compacted when number of retained transactions doubles since last compaction,
+/// evicted transactions are written to `--compact-archive` csv file (valid `--history` input).
+pub struct Compaction {
+    keep_last_n: usize,
+
+    // retained transactions which trigger next compaction
+    high_water: usize,
+
+    // archive file is written atomically, it appears at file path on `finish`
+    archive: Option<csv::Writer<BufWriter<TempFile>>>,
+
+    // first error, compaction is not repeated after error
+    error: Option<CliError>,
+
+    evicted: usize,
+}
+
+impl Compaction {
+    pub fn new(keep_last_n: usize, archive: Option<&str>, overwrite: bool) -> Result<Self, CliError> {
+        let archive = match archive {
+            Some(file_path) => {
+                let mut writer = csv::Writer::from_writer(BufWriter::new(TempFile::create(file_path, overwrite)?));
+                writer.write_record(["type", "client", "tx", "amount"])?;
+                Some(writer)
+            }
+            None => None,
+        };
+
+        Ok(Compaction { keep_last_n, high_water: MIN_HIGH_WATER, archive, error: None, evicted: 0 })
+    }
+
+    /// Compact processor when retained transactions reach high water mark
+    pub fn run(&mut self, processor: &mut BasicProcessor) {
+        if self.error.is_some() || processor.retained_transactions() < self.high_water {
+            return;
+        }
+
+        let mut archive_error = None;
+        let archive = &mut self.archive;
+        let result = processor.compact_with(self.keep_last_n, |transaction| {
+            if let (Some(writer), None) = (archive.as_mut(), &archive_error) {
+                archive_error = write_transaction(writer, &transaction).err();
+            }
+        });
+
+        match (result, archive_error) {
+            (Ok(evicted), None) => {
+                self.evicted += evicted;
+                self.high_water = MIN_HIGH_WATER.max(processor.retained_transactions() * 2);
+            }
+            (Err(e), _) => self.error = Some(CliError::from_source(ErrorType::Process, e)),
+            (_, Some(e)) => self.error = Some(e),
+        }
+    }
+
+    /// Persist archive file, returns number of evicted transactions
+    pub fn finish(self) -> Result<usize, CliError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        if let Some(writer) = self.archive {
+            writer.into_inner()
+                .map_err(|e| CliError::new(ErrorType::IO, e.to_string()))?
+                .into_inner()
+                .map_err(|e| e.into_error())?
+                .persist()?;
+        }
+        Ok(self.evicted)
+    }
+}
+
+// input csv row: type, client, tx, amount
+fn write_transaction<W: std::io::Write>(writer: &mut csv::Writer<W>, transaction: &Transaction) -> Result<(), CliError> {
+    let amount = transaction.amount().map(|it| it.to_string()).unwrap_or_default();
+    writer.write_record([transaction.r#type().name(), &transaction.client().to_string(), &transaction.tx_id().to_string(), &amount])?;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use clap::ArgMatches;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use core::audit::{AdminAction, AuditLog};
+use core::error::ProcessError;
+use core::BasicProcessor;
+use crate::{CliError, ErrorType};
+
+// how often config file is checked for changes
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// principal of config change audit events
+const CONFIG_PRINCIPAL: &str = "config";
+
+/// Verbosity of consumer messages on stderr
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    // failures only
+    Error,
+
+    // also rejected and redelivered messages
+    Warn,
+
+    // also run summary and config reloads
+    #[default]
+    Info,
+}
+
+/// Tunable policies, settings missing in file are not set
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Policies {
+    #[serde(default)]
+    dispute_window: Option<u64>,
+
+    #[serde(default)]
+    amount_limit: Option<Decimal>,
+
+    #[serde(default)]
+    log_level: LogLevel,
+}
+
+impl Policies {
+    fn read(path: &PathBuf) -> Result<Self, CliError> {
+        let content = fs::read(path)?;
+        serde_json::from_slice(&content)
+            .map_err(|e| CliError::new(ErrorType::CliParseError, format!("Invalid config {}: {}", path.display(), e)))
+    }
+
+    fn apply(&self, processor: &mut BasicProcessor) {
+        processor.set_dispute_window(self.dispute_window);
+        processor.set_amount_limit(self.amount_limit);
+    }
+
+    // settings which differ, name with old and new value
+    fn changes(&self, other: &Policies) -> Vec<(&'static str, Option<String>, Option<String>)> {
+        let settings = |it: &Policies| [
+            ("dispute_window", it.dispute_window.map(|it| it.to_string())),
+            ("amount_limit", it.amount_limit.map(|it| it.to_string())),
+            ("log_level", Some(format!("{:?}", it.log_level).to_lowercase())),
+        ];
+        settings(self).into_iter()
+            .zip(settings(other))
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((setting, old), (_, new))| (setting, old, new))
+            .collect()
+    }
+}
+
+/// `--config`: tunable policies of consumer (dispute window, amount limit, log level) in JSON file, e.g.
+/// `{"dispute_window":10000,"amount_limit":"5000","log_level":"warn"}`. File is checked for changes every second
+/// while consuming and changed policies are applied between messages without restart. Every changed setting is
+/// recorded as `ConfigChange` audit event, json line appended to `--audit-log` (stderr without it). Invalid file
+/// is reported and previous policies are kept. Without `--config` nothing is watched.
+#[derive(Default)]
+pub struct Config {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    checked: Option<Instant>,
+    policies: Policies,
+    audit_log: AuditLog,
+    audit_file: Option<File>,
+}
+
+impl Config {
+    /// Policies are read from file, invalid file at start is an error
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, CliError> {
+        let Some(path) = matches.value_of("config").map(PathBuf::from) else {
+            return Ok(Config::default());
+        };
+        let audit_file = matches.value_of("audit_log")
+            .map(|it| OpenOptions::new().create(true).append(true).open(it))
+            .transpose()?;
+
+        Ok(Config {
+            modified: fs::metadata(&path)?.modified().ok(),
+            checked: Some(Instant::now()),
+            policies: Policies::read(&path)?,
+            path: Some(path),
+            audit_log: AuditLog::new(),
+            audit_file,
+        })
+    }
+
+    /// Apply current policies to processor
+    pub fn apply(&self, processor: &mut BasicProcessor) {
+        self.policies.apply(processor);
+    }
+
+    /// Message of `level` is written
+    pub fn logs(&self, level: LogLevel) -> bool {
+        level <= self.policies.log_level
+    }
+
+    /// Apply changed policies when config file was modified since the last check
+    pub fn reload(&mut self, processor: &mut BasicProcessor) {
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        if self.checked.is_some_and(|it| it.elapsed() < CHECK_INTERVAL) {
+            return;
+        }
+        self.checked = Some(Instant::now());
+
+        let modified = fs::metadata(&path).and_then(|it| it.modified()).ok();
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        let policies = match Policies::read(&path) {
+            Ok(policies) => policies,
+            Err(e) => {
+                eprintln!("Config not reloaded, previous policies are kept: {}", e);
+                return;
+            }
+        };
+        let changes = self.policies.changes(&policies);
+        policies.apply(processor);
+        self.policies = policies;
+
+        for (setting, old, new) in changes {
+            let action = AdminAction::ConfigChange { setting: setting.to_string(), old, new };
+            let event = self.audit_log.record(CONFIG_PRINCIPAL, action, &Ok::<_, ProcessError>(()));
+            let result = match &mut self.audit_file {
+                Some(file) => serde_json::to_writer(&mut *file, event)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| file.write_all(b"\n")),
+                None => serde_json::to_string(event).map(|it| eprintln!("Config changed: {}", it)).map_err(std::io::Error::from),
+            };
+            if let Err(e) = result {
+                eprintln!("Can't write config change audit event: {}", e);
+            }
+        }
+        if self.logs(LogLevel::Info) {
+            eprintln!("Config reloaded from {}", path.display());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, BTreeSet};
+use clap::ArgMatches;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use core::account::view::AccountView;
+use core::client::Client;
+use crate::{dlq, CliError, ErrorType};
+use crate::output::{write_output, OutputFormat};
+
+/// Kind of change of client account between snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Change {
+    New,
+    Changed,
+    Removed,
+}
+
+/// Change of client account, missing account counts as zero balances
+#[derive(Debug, Serialize)]
+pub struct AccountDelta {
+    client: Client,
+    change: Change,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    total: Decimal,
+
+    // locked in current snapshot, unlocked (or missing) in previous one
+    newly_locked: bool,
+}
+
+/// `delta` subcommand: per-client changes (available, held, total and newly locked accounts) between previous
+/// and current accounts snapshot in ascending client order, unchanged accounts are skipped. Daily consumers
+/// get change feed instead of re-diffing full dumps.
+pub fn delta(matches: &ArgMatches) -> Result<(), CliError> {
+    let format = matches.value_of("format").unwrap_or("csv").parse::<OutputFormat>()?;
+    let deltas = read_deltas(matches)?;
+
+    let stdout = std::io::stdout();
+    write_output(format.writer(stdout.lock()).as_mut(), deltas.into_iter())
+}
+
+/// Changed accounts between snapshots of `previous` and `current` args in ascending client order
+pub fn read_deltas(matches: &ArgMatches) -> Result<Vec<AccountDelta>, CliError> {
+    let snapshot = |name: &str| -> Result<BTreeMap<Client, AccountView>, CliError> {
+        let file_path = matches.value_of(name)
+            .ok_or_else(|| CliError::new(ErrorType::CliParseError, format!("Arg {} not found", name)))?;
+        Ok(dlq::read_accounts(file_path)?.into_iter().map(|it| (*it.client(), it)).collect())
+    };
+    let previous = snapshot("previous")?;
+    let current = snapshot("current")?;
+
+    let clients: BTreeSet<_> = previous.keys().chain(current.keys()).copied().collect();
+    Ok(clients.into_iter().filter_map(|client| account_delta(client, previous.get(&client), current.get(&client))).collect())
+}
+
+// None when account didn't change
+fn account_delta(client: Client, previous: Option<&AccountView>, current: Option<&AccountView>) -> Option<AccountDelta> {
+    let change = match (previous, current) {
+        (None, Some(_)) => Change::New,
+        (Some(_), None) => Change::Removed,
+        (Some(previous), Some(current)) if previous == current => return None,
+        _ => Change::Changed,
+    };
+
+    let balance = |account: Option<&AccountView>, field: fn(&AccountView) -> &Decimal| account.map_or(Decimal::ZERO, |it| *field(it));
+    let delta = |field: fn(&AccountView) -> &Decimal| balance(current, field).saturating_sub(balance(previous, field));
+
+    Some(AccountDelta {
+        client,
+        change,
+        available: delta(AccountView::available),
+        held: delta(AccountView::held),
+        total: delta(AccountView::total),
+        newly_locked: current.is_some_and(AccountView::locked) && !previous.is_some_and(AccountView::locked),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use core::account::view::AccountView;
+    use crate::delta::{account_delta, Change};
+
+    fn account(available: i64, held: i64, locked: bool) -> AccountView {
+        AccountView::new(1, Decimal::from(available), Decimal::from(held), locked, false).unwrap()
+    }
+
+    #[test]
+    fn same_account_then_no_delta() {
+        assert!(account_delta(1, Some(&account(5, 1, false)), Some(&account(5, 1, false))).is_none());
+    }
+
+    #[test]
+    fn changed_account_then_differences_and_newly_locked() {
+        let delta = account_delta(1, Some(&account(5, 1, false)), Some(&account(2, 0, true))).unwrap();
+        assert_eq!(delta.change, Change::Changed);
+        assert_eq!((delta.available, delta.held, delta.total), (Decimal::from(-3), Decimal::from(-1), Decimal::from(-4)));
+        assert!(delta.newly_locked);
+
+        // still locked
+        let delta = account_delta(1, Some(&account(2, 0, true)), Some(&account(3, 0, true))).unwrap();
+        assert!(!delta.newly_locked);
+    }
+
+    #[test]
+    fn missing_account_then_zero_balances() {
+        let delta = account_delta(1, None, Some(&account(5, 1, true))).unwrap();
+        assert_eq!(delta.change, Change::New);
+        assert_eq!((delta.available, delta.held, delta.total), (Decimal::from(5), Decimal::from(1), Decimal::from(6)));
+        assert!(delta.newly_locked);
+
+        let delta = account_delta(1, Some(&account(5, 1, false)), None).unwrap();
+        assert_eq!(delta.change, Change::Removed);
+        assert_eq!(delta.total, Decimal::from(-6));
+        assert!(!delta.newly_locked);
+    }
+}
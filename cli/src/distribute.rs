@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use clap::ArgMatches;
+use core::client::Client;
+use core::shard::ShardRouter;
+use crate::{snapshot, source, CliError, ErrorType};
+use crate::output::{write_output, OutputFormat};
+use crate::source::Delimiter;
+use crate::write::{check_overwrite, TempFile};
+
+/// `distribute` subcommand: map-reduce style run. Input is split by client (`ShardRouter`, same as `--out-shards`)
+/// into N shard files in work dir, every shard is processed by separate worker process and accounts of workers
+/// are merged in ascending client order. Shards can be processed on other machines instead (`--split-only`)
+/// and their accounts outputs merged with `--worker-results`.
+pub fn distribute(matches: &ArgMatches) -> Result<(), CliError> {
+    let format = matches.value_of("format").unwrap_or("csv").parse::<OutputFormat>()?;
+
+    if let Some(results) = matches.values_of("worker_results") {
+        return write_merged(format, results);
+    }
+
+    let file_path = matches.value_of("file_path").ok_or_else(|| {
+        // this should not happen at this stage, clap requires file path without worker results
+        CliError::new(ErrorType::CliParseError, "Arg file path not found")
+    })?;
+    let work_dir = Path::new(matches.value_of("work_dir").ok_or_else(|| {
+        // this should not happen at this stage, clap requires work dir
+        CliError::new(ErrorType::CliParseError, "Arg work dir not found")
+    })?);
+    let router = matches.value_of("workers").unwrap_or_default().parse::<usize>()
+        .ok()
+        .and_then(ShardRouter::new)
+        .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Workers must be positive number"))?;
+    let delimiter = matches.value_of("delimiter").unwrap_or("comma").parse::<Delimiter>()?;
+    let force = matches.is_present("force");
+
+    let shards = split(file_path, delimiter, work_dir, router, force)?;
+    if matches.is_present("split_only") {
+        for shard in shards {
+            println!("{}", shard.display());
+        }
+        return Ok(());
+    }
+
+    let worker_args: Vec<&str> = matches.values_of("worker_args").into_iter().flatten().collect();
+    let results = run_workers(&shards, &worker_args, force)?;
+    write_merged(format, results.iter().filter_map(|it| it.to_str()))
+}
+
+// split input into shard csv files (comma delimited), rows keep input order within shard
+fn split(file_path: &str, delimiter: Delimiter, work_dir: &Path, router: ShardRouter, force: bool) -> Result<Vec<PathBuf>, CliError> {
+    let mut input = BufReader::new(source::open_stream(file_path)?);
+    let delimiter = delimiter.resolve(&mut input)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .trim(csv::Trim::All)
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(input);
+
+    let headers = reader.byte_headers()?.clone();
+    let client_column = headers.iter().position(|it| it == b"client")
+        .ok_or_else(|| CliError::new(ErrorType::CsvRead, "line 1: Expected header: type,client,tx,amount"))?;
+
+    let paths: Vec<PathBuf> = (0..router.shards()).map(|shard| work_dir.join(format!("shard_{}.csv", shard))).collect();
+    let mut writers = paths.iter()
+        .map(|path| {
+            let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(BufWriter::new(TempFile::create(path, force)?));
+            writer.write_byte_record(&headers)?;
+            Ok(writer)
+        })
+        .collect::<Result<Vec<_>, CliError>>()?;
+
+    for record in reader.byte_records() {
+        let record = record?;
+        // malformed client id goes to the first shard, so its worker rejects the row as single run would
+        let shard = std::str::from_utf8(record.get(client_column).unwrap_or_default())
+            .ok()
+            .and_then(|it| it.parse::<Client>().ok())
+            .map_or(0, |it| router.shard(it));
+        writers[shard].write_byte_record(&record)?;
+    }
+
+    for writer in writers {
+        let writer = writer.into_inner().map_err(|e| CliError::from_source(ErrorType::IO, e.into_error()))?;
+        writer.into_inner().map_err(|e| CliError::from_source(ErrorType::IO, e.into_error()))?.persist()?;
+    }
+    Ok(paths)
+}
+
+// process every shard with worker process (this binary), accounts of shard are written to `<shard>.accounts.csv`
+fn run_workers(shards: &[PathBuf], worker_args: &[&str], force: bool) -> Result<Vec<PathBuf>, CliError> {
+    let exe = std::env::current_exe()?;
+
+    let results: Vec<PathBuf> = shards.iter().map(|it| it.with_extension("accounts.csv")).collect();
+    for result in &results {
+        check_overwrite(result, force)?;
+    }
+
+    // workers run concurrently, all of them are awaited even when some fail
+    let children = shards.iter().zip(&results)
+        .map(|(shard, result)| -> Result<Child, CliError> {
+            Ok(Command::new(&exe)
+                .arg(shard)
+                .args(worker_args)
+                .stdout(File::create(result)?)
+                .spawn()?)
+        })
+        .collect::<Vec<_>>();
+
+    let mut failed = Vec::new();
+    for (child, shard) in children.into_iter().zip(shards) {
+        match child.and_then(|mut it| Ok(it.wait()?)) {
+            Ok(status) if status.success() => {}
+            Ok(status) => failed.push(format!("{} ({})", shard.display(), status)),
+            Err(e) => failed.push(format!("{} ({})", shard.display(), e)),
+        }
+    }
+
+    match failed.is_empty() {
+        true => Ok(results),
+        false => Err(CliError::new(ErrorType::Process, format!("Workers failed: {}", failed.join(", ")))),
+    }
+}
+
+fn write_merged<'a, I>(format: OutputFormat, results: I) -> Result<(), CliError>
+    where I: IntoIterator<Item=&'a str>
+{
+    let accounts = snapshot::merge_snapshots(results)?;
+
+    let stdout = std::io::stdout();
+    write_output(format.writer(stdout.lock()).as_mut(), accounts.into_iter())
+}
@@ -0,0 +1,159 @@
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use clap::ArgMatches;
+use core::account::view::AccountView;
+use core::BasicProcessor;
+use core::dlq::{DeadLetter, DlqRouter};
+use core::pipeline::{run_with_dlq, RunOptions, Sink};
+use core::snapshot::Snapshot;
+use crate::{CliError, ErrorType};
+use crate::output::OutputFormat;
+use crate::write::{TempFile, SNAPSHOT_VERSION_PREFIX};
+
+/// Dead letter queue file, one json object (transaction, sequence, error code and message) per line.
+/// File is written atomically (see `TempFile`), it appears at file path on `finish`.
+/// File is encrypted with `--encryption-key` (see `TempFile::create_sensitive`).
+pub struct NdjsonDlqWriter {
+    // None after finish
+    writer: Option<BufWriter<TempFile>>,
+}
+
+impl NdjsonDlqWriter {
+    pub fn create(file_path: &str, overwrite: bool) -> Result<Self, CliError> {
+        Ok(NdjsonDlqWriter {
+            writer: Some(BufWriter::new(TempFile::create_sensitive(file_path, overwrite)?)),
+        })
+    }
+}
+
+impl Sink<DeadLetter> for NdjsonDlqWriter {
+    type Error = CliError;
+
+    fn write(&mut self, record: DeadLetter) -> Result<(), Self::Error> {
+        let writer = self.writer.as_mut()
+            .ok_or_else(|| CliError::new(ErrorType::IO, "DLQ file already finished"))?;
+        serde_json::to_writer(&mut *writer, &record)
+            .map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        if let Some(writer) = self.writer.take() {
+            writer.into_inner().map_err(|e| e.into_error())?.persist()?;
+        }
+        Ok(())
+    }
+}
+
+/// Build DLQ router from `--dlq-route <codes>=<file_path>` options (codes or code prefixes separated by comma)
+/// and default `--dlq <file_path>`. Routes with the same file share one writer. None if DLQ is not configured.
+/// Existing files are overwritten only with `overwrite` (`--force`).
+pub fn open_router<'a, I>(default: Option<&str>, routes: I, overwrite: bool) -> Result<Option<DlqRouter<'static, CliError>>, CliError>
+    where I: IntoIterator<Item=&'a str>
+{
+    // file path with code patterns, in order of first occurrence
+    let mut files: Vec<(&str, Vec<&str>)> = Vec::new();
+    for route in routes {
+        let (patterns, file_path) = route.split_once('=')
+            .filter(|(patterns, file_path)| !patterns.is_empty() && !file_path.is_empty())
+            .ok_or_else(|| CliError::new(ErrorType::CliParseError, format!("Invalid dlq route '{}', expected <codes>=<file_path>", route)))?;
+
+        match files.iter_mut().find(|(it, _)| *it == file_path) {
+            Some((_, it)) => it.extend(patterns.split(',')),
+            None => files.push((file_path, patterns.split(',').collect())),
+        }
+    }
+
+    if files.is_empty() && default.is_none() {
+        return Ok(None);
+    }
+
+    let mut router = DlqRouter::new();
+    for (file_path, patterns) in files {
+        router = router.route(&patterns, NdjsonDlqWriter::create(file_path, overwrite)?);
+    }
+    if let Some(default) = default {
+        router = router.default_route(NdjsonDlqWriter::create(default, overwrite)?);
+    }
+
+    Ok(Some(router))
+}
+
+/// Read dead letters written by `NdjsonDlqWriter`
+pub fn read_dlq(file_path: &str) -> Result<impl Iterator<Item=Result<DeadLetter, CliError>>, CliError> {
+    let reader = BufReader::new(open_sensitive(file_path)?);
+    Ok(serde_json::Deserializer::from_reader(reader)
+        .into_iter::<DeadLetter>()
+        .map(|it| it.map_err(|e| CliError::from_source(ErrorType::CsvRead, e))))
+}
+
+/// Load accounts snapshot (csv output of previous run or dump)
+pub fn read_snapshot(file_path: &str) -> Result<Snapshot, CliError> {
+    let mut reader = BufReader::new(open_sensitive(file_path)?);
+
+    // snapshot written by older release has no version line, version is detected by columns
+    let mut first = String::new();
+    reader.read_line(&mut first)?;
+    let version = first.trim().strip_prefix(SNAPSHOT_VERSION_PREFIX)
+        .map(|it| it.parse::<u32>()
+            .map_err(|_| CliError::new(ErrorType::CliParseError, format!("Invalid snapshot version {} in {}", it, file_path))))
+        .transpose()?;
+    if version.is_some() {
+        first.clear();
+    }
+
+    let mut csv = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(first.as_bytes().chain(reader));
+    let version = match version {
+        Some(version) => version,
+        None => Snapshot::detect_version(csv.headers()?.iter()),
+    };
+    let accounts = csv.into_deserialize::<AccountView>()
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Snapshot::with_version(version, accounts))
+}
+
+/// Accounts of snapshot migrated to current snapshot version
+pub fn read_accounts(file_path: &str) -> Result<Vec<AccountView>, CliError> {
+    read_snapshot(file_path)?.into_accounts()
+        .map_err(|e| CliError::from_source(ErrorType::Process, e))
+}
+
+/// `replay-dlq` subcommand: re-submit rejected transactions against accounts snapshot
+/// and write updated accounts. Transactions rejected again are written to new DLQ file.
+pub fn replay(matches: &ArgMatches) -> Result<(), CliError> {
+    let file_path = matches.value_of("file_path").ok_or_else(|| {
+        // this should not happen at this stage
+        CliError::new(ErrorType::CliParseError, "Arg file path not found")
+    })?;
+    let snapshot = matches.value_of("snapshot").ok_or_else(|| {
+        // this should not happen at this stage, clap requires snapshot
+        CliError::new(ErrorType::CliParseError, "Arg snapshot not found")
+    })?;
+    let format = matches.value_of("format").unwrap_or("csv").parse::<OutputFormat>()?;
+
+    let mut processor = BasicProcessor::new();
+    processor.restore(read_snapshot(snapshot)?)
+        .map_err(|e| CliError::from_source(ErrorType::Process, e))?;
+
+    let source = read_dlq(file_path)?.map(|it| it.map(DeadLetter::into_transaction));
+    let mut dlq = open_router(matches.value_of("dlq"), matches.values_of("dlq_route").into_iter().flatten(), matches.is_present("force"))?;
+
+    let stdout = std::io::stdout();
+    let report = run_with_dlq(source, processor, &mut format.writer(stdout.lock()), dlq.as_mut(), &RunOptions::default(), |_| {})?;
+    eprintln!("Replayed {} transactions, {} accepted, {} rejected", report.read, report.processed, report.rejected);
+
+    Ok(())
+}
+
+// DLQ and snapshot written with --encryption-key are decrypted (`encryption` feature)
+#[cfg(feature = "encryption")]
+fn open_sensitive(file_path: &str) -> Result<Box<dyn std::io::Read>, CliError> {
+    crate::encryption::read_sensitive(file_path)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open_sensitive(file_path: &str) -> Result<std::fs::File, CliError> {
+    Ok(std::fs::File::open(file_path)?)
+}
@@ -0,0 +1,272 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::sync::OnceLock;
+use clap::ArgMatches;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use crate::{CliError, ErrorType};
+
+// file header, followed by random nonce prefix and chunks
+const MAGIC: &[u8; 8] = b"CSVAENC1";
+
+// nonce is prefix, chunk counter and last chunk flag
+const PREFIX_LEN: usize = NONCE_LEN - 5;
+
+// plaintext size of every chunk except the last one
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const TAG_LEN: usize = 16;
+
+// key of sensitive outputs (snapshots, DLQ, audit log), set once from --encryption-key
+static KEY: OnceLock<EncryptionKey> = OnceLock::new();
+
+/// AES-256-GCM key, 64 hex characters
+pub struct EncryptionKey {
+    key: LessSafeKey,
+}
+
+impl EncryptionKey {
+    /// Key from `env:<NAME>` environment variable or from file
+    pub fn load(source: &str) -> Result<Self, CliError> {
+        let hex = match source.strip_prefix("env:") {
+            Some(name) => env::var(name)
+                .map_err(|_| CliError::new(ErrorType::CliParseError, format!("Encryption key variable {} not set", name)))?,
+            None => fs::read_to_string(source)?,
+        };
+
+        Self::from_hex(&hex)
+    }
+
+    fn from_hex(hex: &str) -> Result<Self, CliError> {
+        let hex = hex.trim();
+        let bytes = (0..hex.len()).step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|it| u8::from_str_radix(it, 16).ok()))
+            .collect::<Option<Vec<_>>>()
+            .filter(|it| it.len() == AES_256_GCM.key_len())
+            .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Encryption key must be 64 hex characters (256 bits)"))?;
+        let key = UnboundKey::new(&AES_256_GCM, &bytes)
+            .map_err(|_| CliError::new(ErrorType::CliParseError, "Invalid encryption key"))?;
+
+        Ok(EncryptionKey { key: LessSafeKey::new(key) })
+    }
+}
+
+/// Encrypt sensitive outputs written from now on with key from `--encryption-key`
+pub fn configure(matches: &ArgMatches) -> Result<(), CliError> {
+    if let Some(source) = matches.value_of("encryption_key") {
+        let _ = KEY.set(EncryptionKey::load(source)?);
+    }
+    Ok(())
+}
+
+/// Configured key, None when sensitive outputs are written as plain text
+pub fn key() -> Option<&'static EncryptionKey> {
+    KEY.get()
+}
+
+/// Streaming encryption: data is split into chunks sealed with AES-256-GCM. Nonce of chunk is random
+/// file prefix, chunk counter and last chunk flag, so reordered, removed or truncated chunks are detected.
+pub struct Encryptor {
+    key: &'static EncryptionKey,
+    prefix: [u8; PREFIX_LEN],
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+impl Encryptor {
+    /// Write header to `out`
+    pub fn new<W: Write>(key: &'static EncryptionKey, out: &mut W) -> io::Result<Self> {
+        let mut prefix = [0; PREFIX_LEN];
+        SystemRandom::new().fill(&mut prefix)
+            .map_err(|_| io::Error::other("can't generate nonce"))?;
+        out.write_all(MAGIC)?;
+        out.write_all(&prefix)?;
+
+        Ok(Encryptor { key, prefix, counter: 0, buffer: Vec::with_capacity(CHUNK_SIZE + TAG_LEN) })
+    }
+
+    pub fn write<W: Write>(&mut self, out: &mut W, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let len = data.len().min(CHUNK_SIZE - self.buffer.len());
+            self.buffer.extend_from_slice(&data[..len]);
+            data = &data[len..];
+            if self.buffer.len() == CHUNK_SIZE {
+                self.seal(out, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the last chunk (possibly empty), more data can't be written
+    pub fn finish<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        self.seal(out, true)
+    }
+
+    fn seal<W: Write>(&mut self, out: &mut W, last: bool) -> io::Result<()> {
+        let nonce = nonce(&self.prefix, self.counter, last);
+        self.counter = self.counter.checked_add(1)
+            .ok_or_else(|| io::Error::other("encrypted file too large"))?;
+        self.key.key.seal_in_place_append_tag(nonce, Aad::empty(), &mut self.buffer)
+            .map_err(|_| io::Error::other("can't encrypt chunk"))?;
+
+        out.write_all(&(self.buffer.len() as u32).to_be_bytes())?;
+        out.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+/// Decrypt file written by `Encryptor`
+pub fn decrypt<R: BufRead, W: Write>(key: &EncryptionKey, mut reader: R, mut writer: W) -> Result<(), CliError> {
+    let error = |message: &str| CliError::new(ErrorType::Integrity, format!("Can't decrypt: {}", message));
+
+    let mut header = [0; MAGIC.len() + PREFIX_LEN];
+    reader.read_exact(&mut header).map_err(|_| error("not an encrypted file"))?;
+    if !header.starts_with(MAGIC) {
+        return Err(error("not an encrypted file"));
+    }
+    let mut prefix = [0; PREFIX_LEN];
+    prefix.copy_from_slice(&header[MAGIC.len()..]);
+
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE + TAG_LEN);
+    for counter in 0_u32.. {
+        let mut len = [0; 4];
+        reader.read_exact(&mut len).map_err(|_| error("file truncated"))?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > CHUNK_SIZE + TAG_LEN {
+            return Err(error("invalid chunk"));
+        }
+
+        chunk.resize(len, 0);
+        reader.read_exact(&mut chunk).map_err(|_| error("file truncated"))?;
+
+        // chunk at the end of file must be sealed as the last one, so truncation is detected
+        let last = reader.fill_buf()?.is_empty();
+        let data = key.key.open_in_place(nonce(&prefix, counter, last), Aad::empty(), &mut chunk)
+            .map_err(|_| error("wrong key, file altered or truncated"))?;
+        writer.write_all(data)?;
+
+        if last {
+            return Ok(());
+        }
+    }
+    Err(error("file too large"))
+}
+
+/// Content of sensitive input (snapshot, DLQ), decrypted when it is encrypted and key is configured
+pub fn read_sensitive(file_path: &str) -> Result<Box<dyn Read>, CliError> {
+    let data = fs::read(file_path)?;
+    match (data.starts_with(MAGIC), key()) {
+        (true, Some(key)) => {
+            let mut plain = Vec::with_capacity(data.len());
+            decrypt(key, data.as_slice(), &mut plain)?;
+            Ok(Box::new(Cursor::new(plain)))
+        }
+        (true, None) => Err(CliError::new(ErrorType::Integrity, format!("File {} is encrypted, use --encryption-key", file_path))),
+        (false, _) => Ok(Box::new(Cursor::new(data))),
+    }
+}
+
+/// `decrypt` subcommand: write decrypted file to stdout
+pub fn decrypt_command(matches: &ArgMatches) -> Result<(), CliError> {
+    let file_path = matches.value_of("file_path")
+        .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg file path not found"))?;
+    let key = key().ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg encryption-key not found"))?;
+
+    let stdout = io::stdout();
+    decrypt(key, BufReader::new(fs::File::open(file_path)?), stdout.lock())
+}
+
+fn nonce(prefix: &[u8; PREFIX_LEN], counter: u32, last: bool) -> Nonce {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..NONCE_LEN - 1].copy_from_slice(&counter.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last as u8;
+    Nonce::assume_unique_for_key(nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encryption::{decrypt, EncryptionKey, Encryptor, CHUNK_SIZE, MAGIC, PREFIX_LEN, TAG_LEN};
+
+    const HEADER_LEN: usize = MAGIC.len() + PREFIX_LEN;
+
+    // length prefix and sealed full chunk
+    const FULL_CHUNK_LEN: usize = 4 + CHUNK_SIZE + TAG_LEN;
+
+    fn key(byte: &str) -> &'static EncryptionKey {
+        Box::leak(Box::new(EncryptionKey::from_hex(&byte.repeat(32)).unwrap()))
+    }
+
+    fn encrypt(key: &'static EncryptionKey, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encryptor = Encryptor::new(key, &mut out).unwrap();
+        // uneven writes, chunks are filled across them
+        for part in data.chunks(1000) {
+            encryptor.write(&mut out, part).unwrap();
+        }
+        encryptor.finish(&mut out).unwrap();
+        out
+    }
+
+    fn decrypted(key: &EncryptionKey, encrypted: &[u8]) -> Option<Vec<u8>> {
+        let mut plain = Vec::new();
+        decrypt(key, encrypted, &mut plain).ok().map(|_| plain)
+    }
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|it| (it % 251) as u8).collect()
+    }
+
+    #[test]
+    fn encrypt_then_decrypt_same_data() {
+        let key = key("2a");
+        for len in [0, 1, CHUNK_SIZE, 2 * CHUNK_SIZE + 10] {
+            let data = data(len);
+            let encrypted = encrypt(key, &data);
+            assert_eq!(encrypted.len(), HEADER_LEN + len / CHUNK_SIZE * FULL_CHUNK_LEN + 4 + len % CHUNK_SIZE + TAG_LEN);
+            assert_eq!(decrypted(key, &encrypted), Some(data));
+        }
+    }
+
+    #[test]
+    fn wrong_key_or_altered_byte_then_error() {
+        let encrypted = encrypt(key("2a"), &data(100));
+        assert_eq!(decrypted(key("2b"), &encrypted), None);
+
+        let mut altered = encrypted.clone();
+        altered[HEADER_LEN + 10] ^= 1;
+        assert_eq!(decrypted(key("2a"), &altered), None);
+    }
+
+    #[test]
+    fn truncated_then_error() {
+        let key = key("2a");
+        let encrypted = encrypt(key, &data(2 * CHUNK_SIZE + 10));
+
+        // the last chunk removed, the chunk before it is not sealed as the last one
+        assert_eq!(decrypted(key, &encrypted[..HEADER_LEN + 2 * FULL_CHUNK_LEN]), None);
+        // cut inside chunk or length
+        assert_eq!(decrypted(key, &encrypted[..encrypted.len() - 1]), None);
+        assert_eq!(decrypted(key, &encrypted[..HEADER_LEN + FULL_CHUNK_LEN + 2]), None);
+        assert_eq!(decrypted(key, &encrypted[..HEADER_LEN]), None);
+    }
+
+    #[test]
+    fn reordered_chunks_then_error() {
+        let key = key("2a");
+        let encrypted = encrypt(key, &data(2 * CHUNK_SIZE + 10));
+        let (header, chunks) = encrypted.split_at(HEADER_LEN);
+        let (first, rest) = chunks.split_at(FULL_CHUNK_LEN);
+        let (second, last) = rest.split_at(FULL_CHUNK_LEN);
+
+        let reordered = [header, second, first, last].concat();
+        assert_eq!(decrypted(key, &reordered), None);
+
+        // chunk of other file with the same key has other nonce prefix
+        let other = encrypt(key, &data(2 * CHUNK_SIZE + 10));
+        let spliced = [header, first, &other[HEADER_LEN + FULL_CHUNK_LEN..]].concat();
+        assert_eq!(decrypted(key, &spliced), None);
+    }
+}
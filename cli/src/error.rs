@@ -1,6 +1,8 @@
+use std::convert::Infallible;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use csv::ErrorKind;
+use core::pipeline::PipelineError;
 use crate::error::ErrorType::{CsvRead, IO};
 use crate::ErrorType::{CsvOther, CsvWrite};
 
@@ -11,11 +13,16 @@ pub enum ErrorType {
     CsvRead,
     CsvWrite,
     CsvOther,
+    Process,
+    Integrity,
 }
 
 pub struct CliError {
     message: String,
     error_type: ErrorType,
+
+    // underlying error (csv, io, process, ...), message is usually its Display
+    source: Option<Box<dyn Error + Send + Sync>>,
 }
 
 impl CliError {
@@ -25,9 +32,24 @@ impl CliError {
         CliError {
             error_type,
             message: message.to_string(),
+            source: None,
         }
     }
 
+    /// Error with message taken from source, source is kept in error chain
+    pub fn from_source<E>(error_type: ErrorType, source: E) -> Self
+        where E: Error + Send + Sync + 'static
+    {
+        CliError::new(error_type, &source).caused_by(source)
+    }
+
+    pub fn caused_by<E>(mut self, source: E) -> Self
+        where E: Error + Send + Sync + 'static
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
     #[allow(dead_code)]
     pub fn message(&self) -> &str {
         &self.message
@@ -41,31 +63,64 @@ impl CliError {
 
 impl Debug for CliError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "App error type: {:?}, Message: {}", self.error_type, self.message)
+        write!(f, "{}", self)?;
+
+        // message already contains direct source, print only deeper causes
+        let mut cause = self.source().and_then(|it| it.source());
+        while let Some(e) = cause {
+            write!(f, "\nCaused by: {}", e)?;
+            cause = e.source();
+        }
+        Ok(())
     }
 }
 
 impl Display for CliError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "App error type: {:?}, Message: {}", self.error_type, self.message)
     }
 }
 
-impl Error for CliError {}
+impl Error for CliError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|it| it as &(dyn Error + 'static))
+    }
+}
 
 impl From<csv::Error> for CliError {
     fn from(e: csv::Error) -> Self {
-        match e.kind() {
-            ErrorKind::Io(e) => CliError::new(IO, e.to_string()),
-            ErrorKind::Serialize(e) => CliError::new(CsvWrite, e),
-            ErrorKind::Deserialize { .. } => CliError::new(CsvRead, e.to_string()),
-            _ => CliError::new(CsvOther, e.to_string())
-        }
+        let error_type = match e.kind() {
+            ErrorKind::Io(_) => IO,
+            ErrorKind::Serialize(_) => CsvWrite,
+            ErrorKind::Deserialize { .. } => CsvRead,
+            _ => CsvOther
+        };
+        CliError::from_source(error_type, e)
     }
 }
 
 impl From<std::io::Error> for CliError {
     fn from(e: std::io::Error) -> Self {
-        CliError::new(IO, e.to_string())
+        CliError::from_source(IO, e)
+    }
+}
+
+impl From<Infallible> for CliError {
+    fn from(e: Infallible) -> Self {
+        match e {}
     }
-}
\ No newline at end of file
+}
+
+impl<S, W, D, R> From<PipelineError<S, W, D, R>> for CliError
+    where CliError: From<S> + From<W> + From<D> + From<R>
+{
+    fn from(e: PipelineError<S, W, D, R>) -> Self {
+        match e {
+            PipelineError::Source(e) => e.into(),
+            PipelineError::Process(e) => CliError::new(ErrorType::Process, format!("{} {}", e.code(), e)).caused_by(e),
+            PipelineError::Sink(e) => e.into(),
+            PipelineError::Dlq(e) => e.into(),
+            PipelineError::Results(e) => e.into(),
+        }
+    }
+}
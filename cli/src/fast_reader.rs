@@ -0,0 +1,201 @@
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::str::FromStr;
+use rust_decimal::Decimal;
+use core::transaction::Transaction;
+use core::transaction_type::TypeAliases;
+use crate::source::{Delimiter, ReaderOptions, SourceError, TransactionSource};
+
+const HEADER: [&[u8]; 4] = [b"type", b"client", b"tx", b"amount"];
+
+// parse_line error reported as SourceError::UnknownType
+const UNKNOWN_TYPE: &str = "unknown transaction type";
+
+/// Alternative reader for the fixed 4-column schema: type, client, tx, amount.
+/// Lines are parsed directly from bytes (without serde and csv crate), line buffer is reused.
+/// Quoted fields are not supported. Header is the first non-empty line, input with other header
+/// (e.g. reference, currency or tenant columns) or with metadata columns is read by regular csv reader.
+pub fn read_from_csv<R>(reader: R, options: &ReaderOptions) -> Result<Box<dyn TransactionSource>, SourceError>
+    where R: io::Read + 'static
+{
+    let mut reader = BufReader::with_capacity(64 * 1024, reader);
+
+    // lines up to header are kept for regular reader
+    let mut consumed = Vec::new();
+    let mut line_number = 0;
+    let header = loop {
+        let start = consumed.len();
+        if reader.read_until(b'\n', &mut consumed)? == 0 {
+            break None;
+        }
+        line_number += 1;
+
+        let line = trim(&consumed[start..]);
+        if !line.is_empty() {
+            let delimiter = options.delimiter.resolve(&mut &*line)?;
+            break Some((delimiter, options.metadata_columns.is_empty() && is_header(line, delimiter)));
+        }
+    };
+
+    match header {
+        Some((delimiter, true)) => Ok(Box::new(FastReader {
+            reader,
+            line: Vec::with_capacity(64),
+            line_number,
+            done: false,
+            type_aliases: options.type_aliases.clone(),
+            delimiter,
+        })),
+        header => {
+            // delimiter detected from header, not from blank lines before it
+            let delimiter = header.map_or(options.delimiter, |(it, _)| Delimiter::Byte(it));
+            let options = ReaderOptions { delimiter, ..options.clone() };
+            Ok(Box::new(crate::reader::read_from_csv(io::Cursor::new(consumed).chain(reader), &options)?))
+        }
+    }
+}
+
+struct FastReader<R> {
+    reader: BufReader<R>,
+    line: Vec<u8>,
+    line_number: u64,
+    done: bool,
+    type_aliases: TypeAliases,
+    delimiter: u8,
+}
+
+impl<R: io::Read> Iterator for FastReader<R> {
+    type Item = Result<Transaction, SourceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line_number += 1;
+                    let line = trim(&self.line);
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    return Some(parse_line(line, self.delimiter, &self.type_aliases).map_err(|message| match message {
+                        UNKNOWN_TYPE => SourceError::UnknownType {
+                            line: self.line_number,
+                            value: String::from_utf8_lossy(trim(line.split(|it| *it == self.delimiter).next().unwrap_or_default())).into_owned(),
+                        },
+                        message => SourceError::Parse { line: self.line_number, message: message.to_string() },
+                    }));
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn is_header(line: &[u8], delimiter: u8) -> bool {
+    line.split(|it| *it == delimiter).map(trim).eq(HEADER)
+}
+
+fn parse_line(line: &[u8], delimiter: u8, type_aliases: &TypeAliases) -> Result<Transaction, &'static str> {
+    let mut fields = line.split(|it| *it == delimiter).map(trim);
+
+    let r#type = fields.next()
+        .and_then(|it| type_aliases.resolve(it))
+        .ok_or(UNKNOWN_TYPE)?;
+
+    let client = fields.next().and_then(parse_int).ok_or("invalid client")?;
+    let client = u16::try_from(client).map_err(|_| "invalid client")?;
+
+    let tx = fields.next().and_then(parse_int).ok_or("invalid tx")?;
+    let tx = u32::try_from(tx).map_err(|_| "invalid tx")?;
+
+    let amount = match fields.next() {
+        None | Some(b"") => None,
+        Some(amount) => {
+            let amount = std::str::from_utf8(amount).map_err(|_| "invalid amount")?;
+            Some(Decimal::from_str(amount).map_err(|_| "invalid amount")?)
+        }
+    };
+
+    if fields.next().is_some() {
+        return Err("too many fields");
+    }
+
+    Ok(Transaction::new(r#type, client, tx, amount))
+}
+
+fn parse_int(bytes: &[u8]) -> Option<u64> {
+    if bytes.is_empty() || bytes.len() > 10 {
+        return None;
+    }
+
+    bytes.iter().try_fold(0_u64, |acc, it| {
+        match it {
+            b'0'..=b'9' => Some(acc * 10 + (it - b'0') as u64),
+            _ => None,
+        }
+    })
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|it| !it.is_ascii_whitespace()).unwrap_or(bytes.len());
+    let end = bytes.iter().rposition(|it| !it.is_ascii_whitespace()).map_or(start, |it| it + 1);
+    &bytes[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use core::transaction_type::TransactionType::{Deposit, Withdrawal};
+    use crate::fast_reader::read_from_csv;
+    use crate::source::{Delimiter, ReaderOptions, SourceError};
+
+    fn read(input: &'static str, options: &ReaderOptions) -> Vec<Result<core::transaction::Transaction, SourceError>> {
+        read_from_csv(input.as_bytes(), options).unwrap().collect()
+    }
+
+    #[test]
+    fn fixed_schema_then_parsed() {
+        let transactions = read("type, client, tx, amount\ndeposit, 1, 1, 1.5\n\nwithdrawal,2,2,0.5", &ReaderOptions::default());
+
+        let transactions: Vec<_> = transactions.into_iter().map(Result::unwrap).collect();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!((transactions[0].r#type(), transactions[0].client(), transactions[0].amount()), (&Deposit, 1, Some(Decimal::new(15, 1))));
+        assert_eq!((transactions[1].r#type(), transactions[1].tx_id()), (&Withdrawal, 2));
+    }
+
+    #[test]
+    fn blank_lines_before_header_then_header_checked() {
+        let transactions = read("\n  \ntype;client;tx;amount\ndeposit;1;1;1\ndeposit;x;2;1\n", &ReaderOptions { delimiter: Delimiter::Auto, ..Default::default() });
+
+        assert_eq!(transactions[0].as_ref().unwrap().tx_id(), 1);
+        assert!(matches!(&transactions[1], Err(SourceError::Parse { line: 5, message }) if message == "invalid client"));
+    }
+
+    #[test]
+    fn malformed_rows_then_errors_with_line() {
+        let transactions = read("type,client,tx,amount\ndeposit,1,1,1,extra\nrefund,1,2,1\n", &ReaderOptions::default());
+
+        assert!(matches!(&transactions[0], Err(SourceError::Parse { line: 2, message }) if message == "too many fields"));
+        assert!(matches!(&transactions[1], Err(SourceError::UnknownType { line: 3, value }) if value == "refund"));
+    }
+
+    #[test]
+    fn extended_schema_then_regular_reader() {
+        let transactions = read("\ntype,client,tx,amount,reference,tenant\ndeposit,1,1,1.0,memo 1,acme\ndeposit,1,2,x,,\n", &ReaderOptions::default());
+
+        assert_eq!(transactions[0].as_ref().unwrap().reference(), Some("memo 1"));
+        assert!(matches!(&transactions[1], Err(SourceError::Parse { line: 4, .. })));
+    }
+
+    #[test]
+    fn unknown_header_then_error() {
+        assert!(matches!(read_from_csv("kind,who\ndeposit,1\n".as_bytes(), &ReaderOptions::default()), Err(SourceError::Parse { line: 1, .. })));
+    }
+}
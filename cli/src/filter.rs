@@ -0,0 +1,369 @@
+use std::str::FromStr;
+use rust_decimal::Decimal;
+use core::account::view::AccountView;
+use core::pipeline::Sink;
+use crate::{CliError, ErrorType};
+
+/// Account filter expression (`--filter`), e.g. `total > 0 && !locked` or `(held >= 100 || closed) && client != 7`.
+/// Fields: client, available, held, total (numbers) and locked, closed (bools).
+/// Operators: `==`, `!=`, `<`, `<=`, `>`, `>=`, `!`, `&&`, `||` and parentheses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare(Operand, Op, Operand),
+    // bool field or literal
+    Value(Operand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Client,
+    Available,
+    Held,
+    Total,
+    Locked,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Field(Field),
+    Number(Decimal),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Number(Decimal),
+    Bool(bool),
+}
+
+impl Filter {
+    pub fn matches(&self, account: &AccountView) -> bool {
+        match self {
+            Filter::Or(left, right) => left.matches(account) || right.matches(account),
+            Filter::And(left, right) => left.matches(account) && right.matches(account),
+            Filter::Not(filter) => !filter.matches(account),
+            Filter::Compare(left, op, right) => op.compare(left.value(account), right.value(account)),
+            Filter::Value(operand) => operand.value(account) == Value::Bool(true),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens: &tokens, position: 0 };
+        let filter = parser.or()?;
+
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(filter_error(format!("unexpected '{}'", token))),
+        }
+    }
+}
+
+impl Field {
+    fn is_bool(&self) -> bool {
+        matches!(self, Field::Locked | Field::Closed)
+    }
+}
+
+impl FromStr for Field {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(Field::Client),
+            "available" => Ok(Field::Available),
+            "held" => Ok(Field::Held),
+            "total" => Ok(Field::Total),
+            "locked" => Ok(Field::Locked),
+            "closed" => Ok(Field::Closed),
+            _ => Err(filter_error(format!("unknown field '{}'", s))),
+        }
+    }
+}
+
+impl Op {
+    fn compare(&self, left: Value, right: Value) -> bool {
+        let ordering = match (left, right) {
+            (Value::Number(left), Value::Number(right)) => left.cmp(&right),
+            (Value::Bool(left), Value::Bool(right)) => left.cmp(&right),
+            // rejected by parser
+            _ => return false,
+        };
+
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+impl Operand {
+    fn value(&self, account: &AccountView) -> Value {
+        match self {
+            Operand::Field(Field::Client) => Value::Number(Decimal::from(*account.client())),
+            Operand::Field(Field::Available) => Value::Number(*account.available()),
+            Operand::Field(Field::Held) => Value::Number(*account.held()),
+            Operand::Field(Field::Total) => Value::Number(*account.total()),
+            Operand::Field(Field::Locked) => Value::Bool(account.locked()),
+            Operand::Field(Field::Closed) => Value::Bool(account.closed()),
+            Operand::Number(number) => Value::Number(*number),
+            Operand::Bool(bool) => Value::Bool(*bool),
+        }
+    }
+
+    fn is_bool(&self) -> bool {
+        match self {
+            Operand::Field(field) => field.is_bool(),
+            Operand::Number(_) => false,
+            Operand::Bool(_) => true,
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<&'a str, CliError> {
+        let token = self.peek().ok_or_else(|| filter_error("unexpected end of expression"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> Result<Filter, CliError> {
+        let mut filter = self.and()?;
+        while self.peek() == Some("||") {
+            self.position += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, CliError> {
+        let mut filter = self.unary()?;
+        while self.peek() == Some("&&") {
+            self.position += 1;
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<Filter, CliError> {
+        match self.peek() {
+            Some("!") => {
+                self.position += 1;
+                Ok(Filter::Not(Box::new(self.unary()?)))
+            }
+            Some("(") => {
+                self.position += 1;
+                let filter = self.or()?;
+                match self.next()? {
+                    ")" => Ok(filter),
+                    token => Err(filter_error(format!("expected ')', found '{}'", token))),
+                }
+            }
+            _ => self.comparison(),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Filter, CliError> {
+        let left = self.operand()?;
+        let op = match self.peek() {
+            Some("==") => Op::Eq,
+            Some("!=") => Op::Ne,
+            Some("<") => Op::Lt,
+            Some("<=") => Op::Le,
+            Some(">") => Op::Gt,
+            Some(">=") => Op::Ge,
+            _ if left.is_bool() => return Ok(Filter::Value(left)),
+            _ => return Err(filter_error(format!("expected comparison after '{}'", self.tokens[self.position - 1]))),
+        };
+        self.position += 1;
+
+        let right = self.operand()?;
+        match (left.is_bool(), right.is_bool(), op) {
+            (false, false, _) | (true, true, Op::Eq | Op::Ne) => Ok(Filter::Compare(left, op, right)),
+            (true, true, _) => Err(filter_error("bools can be compared only with '==' or '!='")),
+            _ => Err(filter_error("can't compare number with bool")),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, CliError> {
+        match self.next()? {
+            "true" => Ok(Operand::Bool(true)),
+            "false" => Ok(Operand::Bool(false)),
+            token if token.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => Decimal::from_str(token)
+                .map(Operand::Number)
+                .map_err(|_| filter_error(format!("invalid number '{}'", token))),
+            token => Ok(Operand::Field(token.parse()?)),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<String>, CliError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' | ')' => tokens.push(c.to_string()),
+            '&' | '|' | '=' => match chars.next_if_eq(&c) {
+                Some(_) => tokens.push(format!("{}{}", c, c)),
+                None => return Err(filter_error(format!("unexpected '{}', expected '{}{}'", c, c, c))),
+            },
+            '!' | '<' | '>' => match chars.next_if_eq(&'=') {
+                Some(_) => tokens.push(format!("{}=", c)),
+                None => tokens.push(c.to_string()),
+            },
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let mut token = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '.') {
+                    token.push(c);
+                }
+                tokens.push(token);
+            }
+            c => return Err(filter_error(format!("unexpected '{}'", c))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn filter_error<M: AsRef<str>>(message: M) -> CliError {
+    CliError::new(ErrorType::CliParseError, format!("Invalid filter: {}", message.as_ref()))
+}
+
+/// Sink writing only accounts matching filter (all accounts without filter)
+pub struct FilterSink<'f, S> {
+    filter: Option<&'f Filter>,
+    sink: S,
+
+    // accounts passed to inner sink
+    written: u64,
+}
+
+impl<'f, S> FilterSink<'f, S> {
+    pub fn new(filter: Option<&'f Filter>, sink: S) -> Self {
+        FilterSink {
+            filter,
+            sink,
+            written: 0,
+        }
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl<'f, S: Sink<AccountView>> Sink<AccountView> for FilterSink<'f, S> {
+    type Error = S::Error;
+
+    fn write(&mut self, record: AccountView) -> Result<(), Self::Error> {
+        if self.filter.is_some_and(|it| !it.matches(&record)) {
+            return Ok(());
+        }
+
+        self.written += 1;
+        self.sink.write(record)
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use core::account::view::AccountView;
+    use core::pipeline::Sink;
+    use crate::filter::{Field, Filter, FilterSink, Op, Operand};
+
+    fn account(client: u16, available: i64, held: i64, locked: bool, closed: bool) -> AccountView {
+        AccountView::new(client, Decimal::from(available), Decimal::from(held), locked, closed).unwrap()
+    }
+
+    fn matches(filter: &str, account: &AccountView) -> bool {
+        filter.parse::<Filter>().unwrap().matches(account)
+    }
+
+    #[test]
+    fn and_before_or_then_precedence() {
+        let filter: Filter = "closed || total > 0 && !locked".parse().unwrap();
+        assert_eq!(filter, Filter::Or(
+            Box::new(Filter::Value(Operand::Field(Field::Closed))),
+            Box::new(Filter::And(
+                Box::new(Filter::Compare(Operand::Field(Field::Total), Op::Gt, Operand::Number(Decimal::ZERO))),
+                Box::new(Filter::Not(Box::new(Filter::Value(Operand::Field(Field::Locked))))),
+            )),
+        ));
+    }
+
+    #[test]
+    fn expressions_then_matching_accounts() {
+        let active = account(1, 5, 2, false, false);
+        let locked = account(7, 0, 100, true, false);
+
+        assert!(matches("total > 0 && !locked", &active));
+        assert!(!matches("total > 0 && !locked", &locked));
+        assert!(matches("(held >= 100 || closed) && client != 8", &locked));
+        assert!(!matches("(held >= 100 || closed) && client != 7", &locked));
+        assert!(matches("available == 5.0 && held < 2.5 && total <= 7", &active));
+        assert!(matches("locked == true && closed != true", &locked));
+        assert!(matches("available > -1", &locked));
+    }
+
+    #[test]
+    fn invalid_expressions_then_error() {
+        for filter in ["", "total", "total >", "total > 0 &", "total = 0", "(total > 0", "total > 0)", "balance > 0",
+            "locked > false", "total == true", "total > 1x", "total > 0 $"] {
+            assert!(filter.parse::<Filter>().is_err(), "{}", filter);
+        }
+    }
+
+    #[test]
+    fn filter_sink_then_only_matching_accounts_written() {
+        let filter: Filter = "!locked".parse().unwrap();
+        let mut accounts = Vec::new();
+        let mut sink = FilterSink::new(Some(&filter), &mut accounts);
+        sink.write(account(1, 1, 0, false, false)).unwrap();
+        sink.write(account(2, 1, 0, true, false)).unwrap();
+        sink.write(account(3, 1, 0, false, true)).unwrap();
+        assert_eq!(sink.written(), 2);
+        assert_eq!(accounts.iter().map(|it| *it.client()).collect::<Vec<_>>(), [1, 3]);
+
+        let mut sink = FilterSink::new(None, Vec::new());
+        sink.write(account(2, 1, 0, true, false)).unwrap();
+        assert_eq!(sink.written(), 1);
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs::File;
+use core::MultiCurrencyProcessor;
+use core::fx::{FxRate, FxTable};
+use core::pipeline::CancellationToken;
+use core::TransactionProcessor;
+use crate::{source, CliError, ErrorType};
+use crate::output::OutputFormat;
+use crate::reader::read_from_csv;
+use crate::source::ReaderOptions;
+
+/// Load FX table from csv with `currency,rate` header
+pub fn read_fx_table(file_path: &str, reporting: &str) -> Result<FxTable, CliError> {
+    let rates = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(File::open(file_path)?)
+        .into_deserialize::<FxRate>()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    FxTable::from_rates(reporting.to_string(), rates)
+        .map_err(|e| CliError::from_source(ErrorType::CsvRead, e))
+}
+
+/// Process multi-currency input (optional `currency` column) with separate balances per currency
+/// and write accounts with native and converted (reporting currency) figures.
+pub fn execute(file_path: &str, reader_options: &ReaderOptions, fx_table: &FxTable, format: OutputFormat, cancel: &CancellationToken) -> Result<(), CliError> {
+    let reader = read_from_csv(source::open_stream(file_path)?, reader_options)?;
+    let mut processor: MultiCurrencyProcessor = MultiCurrencyProcessor::new(fx_table.reporting().to_string());
+
+    for (sequence, record) in (1..).zip(reader.with_currency()) {
+
+        //check if record contains error
+        //if yes - abort
+        let (currency, transaction) = record?;
+        let transaction = transaction.with_sequence(sequence);
+
+        // same as in pipeline, process errors are ignored
+        let _ = match currency {
+            Some(currency) => processor.process_in(&currency, transaction),
+            None => processor.process(transaction),
+        };
+
+        if cancel.is_cancelled() {
+            eprintln!("Interrupted, partial output written");
+            break;
+        }
+    }
+
+    let stdout = std::io::stdout();
+    let mut writer = format.writer(stdout.lock());
+    for (currency, accounts) in processor.finalize() {
+        for account in accounts {
+            let account = fx_table.convert_account(&currency, &account)
+                .map_err(|e| CliError::from_source(ErrorType::Process, e))?;
+            writer.write(&account)?;
+        }
+    }
+
+    writer.finish()
+}
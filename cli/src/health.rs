@@ -0,0 +1,140 @@
+use clap::ArgMatches;
+use core::pipeline::CancellationToken;
+use crate::CliError;
+#[cfg(feature = "health")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "health")]
+use std::sync::Arc;
+#[cfg(feature = "health")]
+use std::thread::JoinHandle;
+#[cfg(feature = "health")]
+use tiny_http::{Method, Response, Server};
+#[cfg(feature = "health")]
+use crate::ErrorType;
+
+/// Orchestration endpoints of consumer (e.g. Kubernetes probes and pre-stop hook). With `health` feature and
+/// `--health-addr` HTTP server is started in background thread:
+/// - `GET /healthz` 200 while process runs (liveness)
+/// - `GET /readyz` 200 after snapshot is restored and broker is connected, 503 before and after shutdown started
+/// - `POST /admin/drain` stops consuming new messages, message in flight is finished and accounts are written
+///   as on shutdown (Ctrl-C), unacked messages are redelivered to other consumers by broker
+///
+/// Otherwise nothing is served.
+#[derive(Default)]
+pub struct Health {
+    #[cfg(feature = "health")]
+    server: Option<(Arc<Server>, Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl Health {
+    /// Drain cancels `cancel`, same as shutdown signal
+    #[cfg_attr(not(feature = "health"), allow(unused_variables))]
+    pub fn from_matches(matches: &ArgMatches, cancel: &CancellationToken) -> Result<Self, CliError> {
+        #[cfg(feature = "health")]
+        if let Some(addr) = matches.value_of("health_addr") {
+            let server = Arc::new(Server::http(addr).map_err(|e| CliError::new(ErrorType::IO, format!("Can't listen on {}: {}", addr, e)))?);
+            let ready = Arc::new(AtomicBool::new(false));
+            let thread = {
+                let (server, ready, cancel) = (server.clone(), ready.clone(), cancel.clone());
+                std::thread::spawn(move || serve(&server, &ready, &cancel))
+            };
+            return Ok(Health { server: Some((server, ready, thread)) });
+        }
+
+        Ok(Health::default())
+    }
+
+    /// Consumer is ready to process messages
+    pub fn ready(&self) {
+        #[cfg(feature = "health")]
+        if let Some((_, ready, _)) = &self.server {
+            ready.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Health {
+    fn drop(&mut self) {
+        #[cfg(feature = "health")]
+        if let Some((server, _, thread)) = self.server.take() {
+            server.unblock();
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(feature = "health")]
+fn serve(server: &Server, ready: &AtomicBool, cancel: &CancellationToken) {
+    for request in server.incoming_requests() {
+        let (status, body) = match (request.method(), request.url()) {
+            (Method::Get, "/healthz") => (200, "ok"),
+            (Method::Get, "/readyz") if ready.load(Ordering::Relaxed) && !cancel.is_cancelled() => (200, "ready"),
+            (Method::Get, "/readyz") => (503, "not ready"),
+            (Method::Post, "/admin/drain") => {
+                cancel.cancel();
+                (202, "draining")
+            }
+            _ => (404, "not found"),
+        };
+        if let Err(e) = request.respond(Response::from_string(body).with_status_code(status)) {
+            eprintln!("Can't respond to health request: {}", e);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "health"))]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use clap::{Arg, Command};
+    use core::pipeline::CancellationToken;
+    use crate::health::Health;
+
+    fn health(cancel: &CancellationToken) -> (Health, SocketAddr) {
+        let matches = Command::new("consumer")
+            .arg(Arg::new("health_addr").long("health-addr").takes_value(true))
+            .get_matches_from(["consumer", "--health-addr", "127.0.0.1:0"]);
+        let health = Health::from_matches(&matches, cancel).unwrap();
+        let addr = health.server.as_ref().and_then(|(server, _, _)| server.server_addr().to_ip()).unwrap();
+        (health, addr)
+    }
+
+    // status code of response
+    fn request(addr: SocketAddr, method: &str, path: &str) -> u16 {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n\r\n", method, path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response.split(' ').nth(1).and_then(|it| it.parse().ok()).unwrap()
+    }
+
+    #[test]
+    fn probes_then_ready_after_ready_until_drained() {
+        let cancel = CancellationToken::new();
+        let (health, addr) = health(&cancel);
+
+        assert_eq!(request(addr, "GET", "/healthz"), 200);
+        assert_eq!(request(addr, "GET", "/readyz"), 503);
+        health.ready();
+        assert_eq!(request(addr, "GET", "/readyz"), 200);
+        assert_eq!(request(addr, "GET", "/admin/drain"), 404);
+        assert!(!cancel.is_cancelled());
+
+        assert_eq!(request(addr, "POST", "/admin/drain"), 202);
+        assert!(cancel.is_cancelled());
+        assert_eq!(request(addr, "GET", "/readyz"), 503);
+        assert_eq!(request(addr, "GET", "/healthz"), 200);
+        assert_eq!(request(addr, "GET", "/metrics"), 404);
+
+        // serving thread is unblocked and joined
+        drop(health);
+    }
+
+    #[test]
+    fn no_health_addr_then_nothing_served() {
+        let matches = Command::new("consumer")
+            .arg(Arg::new("health_addr").long("health-addr").takes_value(true))
+            .get_matches_from(["consumer"]);
+        assert!(Health::from_matches(&matches, &CancellationToken::new()).unwrap().server.is_none());
+    }
+}
@@ -0,0 +1,30 @@
+use std::fs::File;
+use serde::Deserialize;
+use core::account::hierarchy::AccountHierarchy;
+use core::client::Client;
+use crate::{CliError, ErrorType};
+
+/// Sub-account registry row
+#[derive(Debug, Deserialize)]
+struct SubAccount {
+    client: Client,
+    parent: Client,
+}
+
+/// `--hierarchy`: parent of sub-accounts (client, parent). Client may have only one parent
+/// and relationships can't form a cycle.
+pub fn read_hierarchy(file_path: &str) -> Result<AccountHierarchy, CliError> {
+    let mut hierarchy = AccountHierarchy::new();
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(File::open(file_path)?);
+    for (line, record) in (2..).zip(reader.deserialize::<SubAccount>()) {
+        let sub_account = record?;
+        if !hierarchy.insert(sub_account.client, sub_account.parent) {
+            return Err(CliError::new(ErrorType::CsvRead, format!("Invalid hierarchy at line {}: client {} has parent already or creates a cycle", line, sub_account.client)));
+        }
+    }
+
+    Ok(hierarchy)
+}
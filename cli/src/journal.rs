@@ -0,0 +1,169 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use core::transaction::Transaction;
+use core::{BasicProcessor, TransactionProcessor};
+use crate::{CliError, ErrorType};
+
+// line of journal, backfill flag of transaction is replayed with it
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    transaction: T,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    backfill: bool,
+}
+
+/// Write-ahead journal of consumed transactions (json lines), accounts of consumer are kept in memory only.
+/// Every transaction is appended and synced to disk before it is applied, so its message is acked only
+/// when it is persisted. On start the journal is replayed on top of `--snapshot`, so it must be used with
+/// the same snapshot and policies. Message journaled but not acked before crash is redelivered, processor
+/// rejects it as duplicate transaction.
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// Open or create journal at `path` and replay its transactions into `processor`,
+    /// returns number of replayed transactions. Line partially written before crash is dropped.
+    pub fn open<P: AsRef<Path>>(path: P, processor: &mut BasicProcessor) -> Result<(Self, usize), CliError> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+
+        // transaction of partial line was not applied, so its message was not acked
+        let complete = content.iter().rposition(|it| *it == b'\n').map_or(0, |it| it + 1);
+        if complete < content.len() {
+            file.set_len(complete as u64)?;
+            file.sync_data()?;
+        }
+
+        let mut replayed = 0;
+        for (line, entry) in content[..complete].split(|it| *it == b'\n').enumerate().filter(|(_, it)| !it.is_empty()) {
+            let entry: Entry<Transaction> = serde_json::from_slice(entry)
+                .map_err(|e| CliError::new(ErrorType::IO, format!("Invalid journal entry at line {}: {}", line + 1, e)))?;
+            processor.set_backfill(entry.backfill);
+            // rejected transactions are rejected again, they are journaled before they are applied
+            let _ = processor.process(entry.transaction);
+            replayed += 1;
+        }
+        processor.set_backfill(false);
+
+        Ok((Journal { file }, replayed))
+    }
+
+    /// Append transaction and wait until it is on disk
+    pub fn append(&mut self, transaction: &Transaction, backfill: bool) -> Result<(), CliError> {
+        let mut line = serde_json::to_vec(&Entry { transaction, backfill })
+            .map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use rust_decimal::Decimal;
+    use core::error::ProcessError;
+    use core::transaction::Transaction;
+    use core::transaction_type::TransactionType;
+    use core::{BasicProcessor, TransactionProcessor};
+    use crate::journal::Journal;
+
+    fn journal_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("csv-cli-analyzer-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    // journal and apply transaction, the way consumer does before ack
+    fn consume(journal: &mut Journal, processor: &mut BasicProcessor, transaction: Transaction, backfill: bool) -> Result<(), ProcessError> {
+        journal.append(&transaction, backfill).unwrap();
+        processor.set_backfill(backfill);
+        processor.process(transaction)
+    }
+
+    fn accounts(processor: BasicProcessor) -> String {
+        format!("{:?}", processor.finalize().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn reopen_then_state_replayed() {
+        let path = journal_path("replay");
+        let mut processor = BasicProcessor::new();
+        let (mut journal, replayed) = Journal::open(&path, &mut processor).unwrap();
+        assert_eq!(replayed, 0);
+
+        consume(&mut journal, &mut processor, Transaction::new(TransactionType::Deposit, 1, 1, Some(Decimal::from(10))), false).unwrap();
+        consume(&mut journal, &mut processor, Transaction::new(TransactionType::Deposit, 2, 2, Some(Decimal::from(5))), false).unwrap();
+        assert!(consume(&mut journal, &mut processor, Transaction::new(TransactionType::Withdrawal, 2, 3, Some(Decimal::from(6))), false).is_err());
+        consume(&mut journal, &mut processor, Transaction::new(TransactionType::Dispute, 2, 2, None), true).unwrap();
+        consume(&mut journal, &mut processor, Transaction::new(TransactionType::Chargeback, 2, 2, None), true).unwrap();
+        drop(journal);
+
+        let mut restored = BasicProcessor::new();
+        let (_, replayed) = Journal::open(&path, &mut restored).unwrap();
+        assert_eq!(replayed, 5);
+        assert_eq!(accounts(restored), accounts(processor));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn partial_last_line_then_dropped() {
+        let path = journal_path("partial");
+        let mut processor = BasicProcessor::new();
+        let (mut journal, _) = Journal::open(&path, &mut processor).unwrap();
+        journal.append(&Transaction::new(TransactionType::Deposit, 1, 1, Some(Decimal::from(10))), false).unwrap();
+        drop(journal);
+
+        // crash while the second transaction was written
+        let mut content = fs::read(&path).unwrap();
+        let complete = content.len();
+        content.extend_from_slice(br#"{"transaction":{"type":"deposit","cli"#);
+        fs::write(&path, content).unwrap();
+
+        let mut restored = BasicProcessor::new();
+        let (mut journal, replayed) = Journal::open(&path, &mut restored).unwrap();
+        assert_eq!(replayed, 1);
+        assert_eq!(fs::metadata(&path).unwrap().len(), complete as u64);
+
+        // later appends are not joined with partial line
+        journal.append(&Transaction::new(TransactionType::Deposit, 1, 2, Some(Decimal::from(1))), false).unwrap();
+        drop(journal);
+        let (_, replayed) = Journal::open(&path, &mut BasicProcessor::new()).unwrap();
+        assert_eq!(replayed, 2);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn invalid_complete_line_then_error() {
+        let path = journal_path("invalid");
+        fs::write(&path, "{}\n").unwrap();
+        assert!(Journal::open(&path, &mut BasicProcessor::new()).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn redelivered_after_crash_then_applied_once() {
+        let path = journal_path("redelivered");
+        let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(Decimal::from(10)));
+        let (mut journal, _) = Journal::open(&path, &mut BasicProcessor::new()).unwrap();
+        journal.append(&deposit, false).unwrap();
+        // crash before ack
+        drop(journal);
+
+        let mut processor = BasicProcessor::new();
+        let (_, replayed) = Journal::open(&path, &mut processor).unwrap();
+        assert_eq!(replayed, 1);
+        assert!(matches!(processor.process(deposit), Err(ProcessError::TransactionExists)));
+        assert_eq!(processor.find_account(1).unwrap().total(), &Decimal::from(10));
+
+        fs::remove_file(path).unwrap();
+    }
+}
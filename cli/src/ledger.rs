@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::ErrorKind;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::{CliError, ErrorType};
+use crate::checksum::file_sha256;
+use crate::source::{STDIN, TCP_PREFIX};
+use crate::write::write_json;
+
+/// Input file processed by a complete run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    path: String,
+    sha256: String,
+
+    // number of transactions read
+    rows: u64,
+
+    // unix timestamp (seconds) of the end of run
+    ingested_at: u64,
+}
+
+/// Ingestion ledger (`--ledger <file_path>`): json file with input files already processed, so
+/// cron-driven batch jobs can't apply the same file twice. Files are identified by SHA-256 of content,
+/// a renamed or copied file is still recognized. Ledger is replaced atomically after each recorded run.
+pub struct IngestionLedger {
+    path: String,
+    entries: Vec<LedgerEntry>,
+}
+
+impl IngestionLedger {
+    /// Ledger file is created by the first recorded run
+    pub fn open(path: &str) -> Result<Self, CliError> {
+        let entries = match File::open(path) {
+            Ok(file) => serde_json::from_reader(file).map_err(|e| CliError::from_source(ErrorType::Integrity, e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(IngestionLedger { path: path.to_string(), entries })
+    }
+
+    /// SHA-256 of input, refused when already ingested by previous run (unless `reprocess`).
+    /// Stdin and tcp streams can't be identified before processing.
+    pub fn check(&self, input: &str, reprocess: bool) -> Result<String, CliError> {
+        if input == STDIN || input.starts_with(TCP_PREFIX) {
+            return Err(CliError::new(ErrorType::Integrity, "Ingestion ledger requires input file"));
+        }
+
+        let sha256 = file_sha256(input)?;
+        match self.entries.iter().find(|it| it.sha256 == sha256) {
+            Some(entry) if !reprocess => Err(CliError::new(ErrorType::Integrity, format!(
+                "Input {} was already ingested as {} ({} rows, at {}), use --reprocess to process it again",
+                input, entry.path, entry.rows, entry.ingested_at
+            ))),
+            _ => Ok(sha256),
+        }
+    }
+
+    /// Record processed input, previous entry of reprocessed file is replaced
+    pub fn record(mut self, input: &str, sha256: String, rows: u64) -> Result<(), CliError> {
+        let ingested_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |it| it.as_secs());
+        self.entries.retain(|it| it.sha256 != sha256);
+        self.entries.push(LedgerEntry { path: input.to_string(), sha256, rows, ingested_at });
+        write_json(&self.path, true, &self.entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::ErrorType;
+    use crate::ledger::{IngestionLedger, LedgerEntry};
+    use crate::source::{STDIN, TCP_PREFIX};
+
+    // deposit, withdrawal and a dispute resolved and another charged back, one row each
+    const INPUT: &str = "type,client,tx,amount\n\
+        deposit,1,1,10.0\n\
+        deposit,1,2,5.0\n\
+        withdrawal,1,3,1.5\n\
+        dispute,1,1,\n\
+        resolve,1,1,\n\
+        dispute,1,2,\n\
+        chargeback,1,2,\n";
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("csv-cli-analyzer-ledger-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn input(name: &str, content: &str) -> String {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn entries(path: &Path) -> Vec<LedgerEntry> {
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn recorded_input_with_every_transaction_type_then_refused() {
+        let path = temp_path("recorded");
+        let ledger_path = path.to_string_lossy().into_owned();
+        let input = input("recorded-input", INPUT);
+
+        let ledger = IngestionLedger::open(&ledger_path).unwrap();
+        assert!(ledger.entries.is_empty());
+        let sha256 = ledger.check(&input, false).unwrap();
+        ledger.record(&input, sha256.clone(), 7).unwrap();
+
+        let entries = entries(&path);
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].path.as_str(), entries[0].sha256.as_str(), entries[0].rows), (input.as_str(), sha256.as_str(), 7));
+        assert!(entries[0].ingested_at > 0);
+
+        // renamed or copied file is recognized by content
+        let copy = self::input("recorded-copy", INPUT);
+        let ledger = IngestionLedger::open(&ledger_path).unwrap();
+        let error = ledger.check(&copy, false).unwrap_err();
+        assert!(matches!(error.error_type(), ErrorType::Integrity));
+        assert!(error.message().contains("already ingested"));
+        assert_eq!(ledger.check(&copy, true).unwrap(), sha256);
+
+        for it in [path.to_string_lossy().into_owned(), input, copy] {
+            fs::remove_file(it).unwrap();
+        }
+    }
+
+    #[test]
+    fn reprocessed_and_other_inputs_then_one_entry_per_content() {
+        let path = temp_path("reprocessed");
+        let ledger_path = path.to_string_lossy().into_owned();
+        let first = input("reprocessed-first", INPUT);
+        let second = input("reprocessed-second", "type,client,tx,amount\ndeposit,2,8,1.0\n");
+
+        for (input, rows, reprocess) in [(&first, 7, false), (&second, 1, false), (&first, 7, true)] {
+            let ledger = IngestionLedger::open(&ledger_path).unwrap();
+            let sha256 = ledger.check(input, reprocess).unwrap();
+            ledger.record(input, sha256, rows).unwrap();
+        }
+
+        // rows of reprocessed file are not counted twice
+        let entries = entries(&path);
+        assert_eq!(entries.iter().map(|it| it.path.as_str()).collect::<Vec<_>>(), [second.as_str(), first.as_str()]);
+        assert_eq!(entries.iter().map(|it| it.rows).sum::<u64>(), 8);
+
+        for it in [path.to_string_lossy().into_owned(), first, second] {
+            fs::remove_file(it).unwrap();
+        }
+    }
+
+    #[test]
+    fn stream_input_then_refused() {
+        let ledger = IngestionLedger::open(&temp_path("stream").to_string_lossy()).unwrap();
+        for input in [STDIN.to_string(), format!("{}127.0.0.1:9000", TCP_PREFIX)] {
+            assert!(matches!(ledger.check(&input, true).unwrap_err().error_type(), ErrorType::Integrity));
+        }
+    }
+
+    #[test]
+    fn invalid_ledger_then_integrity_error() {
+        let path = temp_path("invalid");
+        fs::write(&path, "{\"path\":").unwrap();
+        let error = IngestionLedger::open(&path.to_string_lossy()).err().unwrap();
+        assert!(matches!(error.error_type(), ErrorType::Integrity));
+        fs::remove_file(path).unwrap();
+    }
+}
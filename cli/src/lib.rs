@@ -0,0 +1,15 @@
+//! Transaction sources of the analyzer: csv readers (sync and async), input streams (local, remote),
+//! reader options and errors, e.g. for services which embed the reader. The `csv-cli-analyzer` binary
+//! is built on top of it.
+
+pub use crate::error::{CliError, ErrorType};
+
+pub mod error;
+pub mod reader;
+pub mod source;
+#[cfg(feature = "object-store")]
+pub mod remote;
+#[cfg(feature = "fast-parse")]
+pub mod fast_reader;
+#[cfg(feature = "async-reader")]
+pub mod async_reader;
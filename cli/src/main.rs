@@ -1,10 +1,69 @@
+use csv_cli_analyzer::{error, reader, source};
 use crate::error::{CliError, ErrorType};
 
-mod error;
 mod cli;
-mod reader;
 mod write;
+mod output;
+#[cfg(feature = "parquet")]
+mod parquet_output;
+#[cfg(feature = "parallel")]
+mod parallel_output;
 mod process;
+mod signal;
+mod fx;
+mod tenant;
+mod dlq;
+mod results;
+mod checksum;
+mod manifest;
+mod ledger;
+mod compaction;
+mod filter;
+mod report;
+mod reconcile;
+mod delta;
+mod snapshot;
+mod distribute;
+mod pii;
+mod bench;
+mod balances;
+mod admin;
+mod audit;
+mod hierarchy;
+#[cfg(feature = "object-store")]
+use csv_cli_analyzer::remote;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+mod write_behind;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(feature = "amqp")]
+mod amqp;
+#[cfg(any(feature = "nats", feature = "amqp"))]
+mod telemetry;
+#[cfg(any(feature = "nats", feature = "amqp"))]
+mod stats;
+#[cfg(any(feature = "nats", feature = "amqp"))]
+mod health;
+// log level of write-behind buffer of account store
+#[cfg(any(feature = "nats", feature = "amqp", feature = "sqlite"))]
+#[cfg_attr(not(any(feature = "nats", feature = "amqp")), allow(dead_code))]
+mod config;
+#[cfg(any(feature = "nats", feature = "amqp"))]
+mod journal;
+#[cfg(feature = "scripting")]
+mod script;
+#[cfg(feature = "plugins")]
+mod plugin;
+#[cfg(feature = "profiling")]
+mod profiling;
+#[cfg(feature = "encryption")]
+mod encryption;
+
+#[cfg(feature = "profiling")]
+#[global_allocator]
+static ALLOCATOR: profiling::CountingAllocator = profiling::CountingAllocator;
 
 fn main() -> Result<(), CliError> {
     let matches = cli::build();
@@ -0,0 +1,157 @@
+use std::fs::File;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use core::pipeline::RunReport;
+use crate::{CliError, ErrorType};
+use crate::write::write_json;
+use crate::audit::HashChain;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileChecksum {
+    // file path, '-' for stdin/stdout
+    path: String,
+    sha256: String,
+
+    // number of records, only for accounts output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rows: Option<u64>,
+}
+
+impl FileChecksum {
+    pub fn new<P: ToString>(path: P, sha256: String) -> Self {
+        FileChecksum {
+            path: path.to_string(),
+            sha256,
+            rows: None,
+        }
+    }
+
+    pub fn with_rows(mut self, rows: u64) -> Self {
+        self.rows = Some(rows);
+        self
+    }
+}
+
+/// Run manifest, written after run with `--manifest <file_path>`, so batch runs can be audited and reproduced
+#[derive(Debug, Serialize)]
+pub struct Manifest<'a> {
+    // version of csv-cli-analyzer
+    version: &'static str,
+
+    // checksum of input as read (gzip file is not decompressed)
+    input: FileChecksum,
+
+    // row counts and error counts by code
+    report: &'a RunReport,
+
+    // processing duration in milliseconds
+    duration_ms: u128,
+
+    // checksums of accounts output (stdout or shard files)
+    outputs: &'a [FileChecksum],
+
+    // checksums of other files written by run: DLQ, results, ledger, audit log, event logs, ...
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    files: Vec<FileChecksum>,
+
+    // root hash of hash-chained audit log
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audit_log: Option<AuditRoot>,
+}
+
+impl<'a> Manifest<'a> {
+    pub fn new(input: FileChecksum, report: &'a RunReport, duration: Duration, outputs: &'a [FileChecksum]) -> Self {
+        Manifest {
+            version: VERSION,
+            input,
+            report,
+            duration_ms: duration.as_millis(),
+            outputs,
+            files: Vec::new(),
+            audit_log: None,
+        }
+    }
+
+    pub fn with_files(mut self, files: Vec<FileChecksum>) -> Self {
+        self.files = files;
+        self
+    }
+
+    pub fn with_audit_log(mut self, audit_log: AuditRoot) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    pub fn write(&self, file_path: &str, overwrite: bool) -> Result<(), CliError> {
+        write_json(file_path, overwrite, self)
+    }
+}
+
+/// Audit log written with `--audit-chain`, `verify-audit` subcommand checks the log against root hash
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditRoot {
+    path: String,
+    events: u64,
+    root_hash: String,
+}
+
+impl AuditRoot {
+    pub fn new<P: ToString>(path: P, chain: &HashChain) -> Self {
+        AuditRoot {
+            path: path.to_string(),
+            events: chain.events(),
+            root_hash: chain.root().to_string(),
+        }
+    }
+}
+
+/// Row count and checksums of accounts output, written to sidecar file with `--output-sidecar <file_path>`,
+/// so downstream loaders can verify they received a complete, uncorrupted result
+#[derive(Debug, Serialize)]
+pub struct OutputSidecar<'a> {
+    // accounts in all outputs
+    rows: u64,
+    outputs: &'a [FileChecksum],
+}
+
+impl<'a> OutputSidecar<'a> {
+    pub fn new(outputs: &'a [FileChecksum]) -> Self {
+        OutputSidecar {
+            rows: outputs.iter().filter_map(|it| it.rows).sum(),
+            outputs,
+        }
+    }
+
+    pub fn write(&self, file_path: &str, overwrite: bool) -> Result<(), CliError> {
+        write_json(file_path, overwrite, self)
+    }
+}
+
+/// Input part of manifest, other fields are ignored when manifest is read
+#[derive(Debug, Deserialize)]
+struct ManifestInput {
+    input: FileChecksum,
+}
+
+/// Expected input checksum from manifest written by previous run (or by producer of input file)
+pub fn read_input_sha256(file_path: &str) -> Result<String, CliError> {
+    let manifest: ManifestInput = serde_json::from_reader(File::open(file_path)?)
+        .map_err(|e| CliError::from_source(ErrorType::CsvRead, e))?;
+    Ok(manifest.input.sha256)
+}
+
+/// Audit part of manifest, other fields are ignored when manifest is read
+#[derive(Debug, Deserialize)]
+struct ManifestAudit {
+    audit_log: Option<AuditRoot>,
+}
+
+/// Root hash of audit log from run manifest
+pub fn read_audit_root(file_path: &str) -> Result<String, CliError> {
+    let manifest: ManifestAudit = serde_json::from_reader(File::open(file_path)?)
+        .map_err(|e| CliError::from_source(ErrorType::CsvRead, e))?;
+    manifest.audit_log.map(|it| it.root_hash)
+        .ok_or_else(|| CliError::new(ErrorType::Integrity, format!("Manifest {} has no audit log root hash", file_path)))
+}
@@ -0,0 +1,185 @@
+use std::time::Duration;
+use async_nats::jetstream;
+use async_nats::jetstream::consumer::pull;
+use async_nats::jetstream::AckKind;
+use async_nats::HeaderMap;
+use clap::ArgMatches;
+use futures::StreamExt;
+use core::{BasicProcessor, TransactionProcessor};
+use core::pipeline::CancellationToken;
+use crate::{dlq, signal, CliError, ErrorType};
+use crate::config::{Config, LogLevel};
+use crate::health::Health;
+use crate::journal::Journal;
+use crate::stats::{ConsumeReport, StatsDump};
+use crate::telemetry::Telemetry;
+use crate::output::{write_output, OutputFormat};
+
+// how often cancellation is checked while waiting for messages
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// message header marking historical transaction, see `BasicProcessor::set_backfill`
+const BACKFILL_HEADER: &str = "Backfill";
+
+/// `consume-nats` subcommand: consume JSON transactions from JetStream durable consumer and publish
+/// updated account (JSON) to events subject after each applied transaction.
+/// Message is acked only after its transaction is synced to `--journal` (see `Journal`) and account update
+/// is stored by JetStream, so transactions are never lost: failed with retryable error are nak'ed
+/// (redelivered), malformed or rejected are terminated.
+/// Message with `Backfill: true` header is historical transaction: it doesn't lock account and no account
+/// update is published for it. Accounts are kept in memory and written to stdout on shutdown (Ctrl-C).
+/// With `--otlp-endpoint` (`otel` feature) every message is traced, see `Telemetry`.
+/// With `--stats-json` stats of the run are dumped periodically, see `StatsDump`.
+/// With `--health-addr` (`health` feature) probes and drain are served over HTTP, see `Health`.
+/// With `--config` tunable policies are reloaded without restart, see `Config`.
+pub fn consume(matches: &ArgMatches) -> Result<(), CliError> {
+    let arg = |name: &str| matches.value_of(name).ok_or_else(|| {
+        // this should not happen at this stage, clap requires arg or sets default
+        CliError::new(ErrorType::CliParseError, format!("Arg {} not found", name))
+    });
+    let server = arg("server")?;
+    let stream = arg("stream")?;
+    let consumer = arg("consumer")?;
+    let events_subject = arg("events_subject")?.to_string();
+    let format = arg("format")?.parse::<OutputFormat>()?;
+
+    let mut stats = StatsDump::from_matches(matches, "consume-nats")?;
+    let mut processor = BasicProcessor::new().with_recorder(stats.recorder());
+    let mut config = Config::from_matches(matches)?;
+    config.apply(&mut processor);
+    if let Some(snapshot) = matches.value_of("snapshot") {
+        processor.restore(dlq::read_snapshot(snapshot)?)
+            .map_err(|e| CliError::from_source(ErrorType::Process, e))?;
+    }
+    let (mut journal, replayed) = Journal::open(arg("journal")?, &mut processor)?;
+    if config.logs(LogLevel::Info) && replayed > 0 {
+        eprintln!("Replayed {} journaled transactions", replayed);
+    }
+
+    let telemetry = Telemetry::from_matches(matches)?;
+    let cancel = CancellationToken::new();
+    signal::register_shutdown(&cancel)?;
+    let health = Health::from_matches(matches, &cancel)?;
+
+    let consumer_config = pull::Config {
+        durable_name: Some(consumer.to_string()),
+        filter_subject: matches.value_of("subject").unwrap_or_default().to_string(),
+        ..Default::default()
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let report = runtime.block_on(async {
+        let client = async_nats::connect(server).await.map_err(nats_error)?;
+        let context = jetstream::new(client);
+        let consumer = context.get_stream(stream).await.map_err(nats_error)?
+            .get_or_create_consumer(consumer, consumer_config).await.map_err(nats_error)?;
+        let mut messages = consumer.messages().await.map_err(nats_error)?;
+        health.ready();
+
+        let mut report = ConsumeReport::default();
+        while !cancel.is_cancelled() {
+            stats.tick(&report);
+            config.reload(&mut processor);
+            let message = match tokio::time::timeout(POLL_INTERVAL, messages.next()).await {
+                Ok(Some(message)) => message.map_err(nats_error)?,
+                Ok(None) => break,
+                // no message, check cancellation again
+                Err(_) => continue,
+            };
+            report.read += 1;
+
+            let headers = message.headers.iter()
+                .flat_map(|it| it.iter())
+                .filter_map(|(name, values)| Some((name.as_ref(), values.first()?.to_string())));
+            let trace = telemetry.start(headers);
+
+            let transaction = match trace.decode(&message.payload) {
+                Ok(transaction) => transaction,
+                Err(e) => {
+                    if config.logs(LogLevel::Warn) {
+                        eprintln!("Malformed transaction on '{}': {}", message.subject, e);
+                    }
+                    report.rejected += 1;
+                    trace.end(Some(&e));
+                    message.ack_with(AckKind::Term).await.map_err(nats_error)?;
+                    continue;
+                }
+            };
+
+            let client = transaction.client();
+            let backfill = is_backfill(message.headers.as_ref());
+            journal.append(&transaction, backfill)?;
+            processor.set_backfill(backfill);
+            let result = trace.apply(|| processor.process(transaction));
+            trace.end(result.as_ref().err().map(|it| it as _));
+            match result {
+                Ok(()) => {
+                    if let Some(account) = processor.find_account(client).filter(|_| !backfill) {
+                        let payload = serde_json::to_vec(&account).map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+                        // wait for JetStream ack of account update before transaction is acked
+                        context.publish(events_subject.clone(), payload.into()).await.map_err(nats_error)?
+                            .await.map_err(nats_error)?;
+                    }
+                    report.processed += 1;
+                    message.ack().await.map_err(nats_error)?;
+                }
+                Err(e) if e.is_retryable() => {
+                    if config.logs(LogLevel::Warn) {
+                        eprintln!("Transaction on '{}' will be redelivered: {} {}", message.subject, e.code(), e);
+                    }
+                    report.retried += 1;
+                    message.ack_with(AckKind::Nak(None)).await.map_err(nats_error)?;
+                }
+                Err(e) => {
+                    if config.logs(LogLevel::Warn) {
+                        eprintln!("Transaction on '{}' rejected: {} {}", message.subject, e.code(), e);
+                    }
+                    report.rejected += 1;
+                    message.ack_with(AckKind::Term).await.map_err(nats_error)?;
+                }
+            }
+        }
+
+        Ok::<_, CliError>(report)
+    })?;
+    telemetry.shutdown()?;
+    stats.finish(&report)?;
+    if config.logs(LogLevel::Info) {
+        eprintln!("Consumed {} messages, {} applied, {} rejected, {} redelivered", report.read, report.processed, report.rejected, report.retried);
+    }
+
+    let stdout = std::io::stdout();
+    write_output(format.writer(stdout.lock()).as_mut(), processor.finalize())
+}
+
+// historical transaction, see `BACKFILL_HEADER`
+fn is_backfill(headers: Option<&HeaderMap>) -> bool {
+    headers.and_then(|it| it.get(BACKFILL_HEADER))
+        .is_some_and(|it| it.as_str().eq_ignore_ascii_case("true"))
+}
+
+// ack errors are boxed, all are reported as IO errors
+fn nats_error<E>(e: E) -> CliError
+    where E: Into<Box<dyn std::error::Error + Send + Sync>>
+{
+    std::io::Error::other(e).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_nats::HeaderMap;
+    use crate::nats::is_backfill;
+
+    #[test]
+    fn backfill_header_then_case_insensitive_true() {
+        assert!(!is_backfill(None));
+        let mut headers = HeaderMap::new();
+        assert!(!is_backfill(Some(&headers)));
+        headers.insert("Backfill", "TRUE");
+        assert!(is_backfill(Some(&headers)));
+        headers.insert("Backfill", "false");
+        assert!(!is_backfill(Some(&headers)));
+    }
+}
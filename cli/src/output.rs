@@ -0,0 +1,239 @@
+use std::io::Write;
+use std::str::FromStr;
+use serde::Serialize;
+use core::pipeline::Sink;
+use crate::{CliError, ErrorType};
+
+/// Output writer abstraction. Records are streamed one by one through `write`,
+/// `finish` must be called at the end to flush buffered data (closing brackets, table, etc.).
+/// To add new output format implement this trait and register it in `OutputFormat`.
+pub trait OutputWriter<T: Serialize> {
+    fn write(&mut self, record: &T) -> Result<(), CliError>;
+
+    fn finish(&mut self) -> Result<(), CliError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+    Table,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+pub const OUTPUT_FORMATS: &[&str] = &[
+    "csv",
+    "json",
+    "table",
+    #[cfg(feature = "parquet")]
+    "parquet",
+];
+
+/// Formats where transactions are read from and accounts are written to the same database file,
+/// handled outside of `OutputFormat`
+pub const DATABASE_FORMATS: &[&str] = &[
+    #[cfg(feature = "sqlite")]
+    crate::sqlite::SQLITE_FORMAT,
+];
+
+impl FromStr for OutputFormat {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "table" => Ok(OutputFormat::Table),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(OutputFormat::Parquet),
+            _ => Err(CliError::new(ErrorType::CliParseError, format!("Unknown output format: {}", s)))
+        }
+    }
+}
+
+impl OutputFormat {
+    /// File extension used for output files
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Table => "txt",
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+
+    pub fn writer<'a, W, T>(&self, writer: W) -> Box<dyn OutputWriter<T> + 'a>
+        where W: Write + 'a,
+              T: Serialize + 'a
+    {
+        match self {
+            OutputFormat::Csv => Box::new(CsvOutput::new(writer)),
+            OutputFormat::Json => Box::new(JsonOutput::new(writer)),
+            OutputFormat::Table => Box::new(TableOutput::new(writer)),
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => Box::new(crate::parquet_output::ParquetOutput::new(writer)),
+        }
+    }
+
+    /// Same as `writer`, but with `parallel` feature csv and json records are serialized by thread pool
+    pub fn parallel_writer<'a, W, T>(&self, writer: W) -> Box<dyn OutputWriter<T> + 'a>
+        where W: Write + 'a,
+              T: Serialize + Clone + Send + Sync + 'a
+    {
+        #[cfg(feature = "parallel")]
+        if matches!(self, OutputFormat::Csv | OutputFormat::Json) {
+            return Box::new(crate::parallel_output::ParallelOutput::new(writer, *self));
+        }
+
+        self.writer(writer)
+    }
+}
+
+/// Csv with header row
+pub struct CsvOutput<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> CsvOutput<W> {
+    pub fn new(writer: W) -> Self {
+        CsvOutput {
+            writer: csv::WriterBuilder::new()
+                .has_headers(true)
+                .from_writer(writer)
+        }
+    }
+}
+
+impl<W: Write, T: Serialize> OutputWriter<T> for CsvOutput<W> {
+    fn write(&mut self, record: &T) -> Result<(), CliError> {
+        self.writer.serialize(record)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), CliError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Json array, one record per line
+pub struct JsonOutput<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> JsonOutput<W> {
+    pub fn new(writer: W) -> Self {
+        JsonOutput {
+            writer,
+            count: 0,
+        }
+    }
+}
+
+impl<W: Write, T: Serialize> OutputWriter<T> for JsonOutput<W> {
+    fn write(&mut self, record: &T) -> Result<(), CliError> {
+        let separator: &[u8] = if self.count == 0 { b"[\n" } else { b",\n" };
+        self.writer.write_all(separator)?;
+        serde_json::to_writer(&mut self.writer, record)
+            .map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), CliError> {
+        let end: &[u8] = if self.count == 0 { b"[]\n" } else { b"\n]\n" };
+        self.writer.write_all(end)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Human readable table with aligned columns.
+/// Column widths are known only after all records, so rows are buffered (as csv) until `finish`.
+pub struct TableOutput<W: Write> {
+    writer: W,
+    buffer: csv::Writer<Vec<u8>>,
+}
+
+impl<W: Write> TableOutput<W> {
+    pub fn new(writer: W) -> Self {
+        TableOutput {
+            writer,
+            buffer: csv::WriterBuilder::new()
+                .has_headers(true)
+                .from_writer(Vec::new()),
+        }
+    }
+}
+
+impl<W: Write, T: Serialize> OutputWriter<T> for TableOutput<W> {
+    fn write(&mut self, record: &T) -> Result<(), CliError> {
+        self.buffer.serialize(record)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), CliError> {
+        let buffer = std::mem::replace(&mut self.buffer, csv::Writer::from_writer(Vec::new()))
+            .into_inner()
+            .map_err(|e| CliError::from_source(ErrorType::IO, e.into_error()))?;
+
+        let rows = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(buffer.as_slice())
+            .into_records()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut widths: Vec<usize> = Vec::new();
+        for row in rows.iter() {
+            widths.resize(widths.len().max(row.len()), 0);
+            for (width, field) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(field.len());
+            }
+        }
+
+        for (i, row) in rows.iter().enumerate() {
+            let line = row.iter()
+                .zip(widths.iter())
+                .map(|(field, width)| format!("{:<width$}", field, width = width))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            writeln!(self.writer, "{}", line.trim_end())?;
+
+            // separator after header
+            if i == 0 {
+                let line = widths.iter().map(|it| "-".repeat(*it)).collect::<Vec<_>>().join("-+-");
+                writeln!(self.writer, "{}", line)?;
+            }
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Output writer used as pipeline sink
+impl<'a, T: Serialize> Sink<T> for Box<dyn OutputWriter<T> + 'a> {
+    type Error = CliError;
+
+    fn write(&mut self, record: T) -> Result<(), Self::Error> {
+        OutputWriter::write(self.as_mut(), &record)
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        OutputWriter::finish(self.as_mut())
+    }
+}
+
+/// Stream all records through output writer
+pub fn write_output<T>(writer: &mut dyn OutputWriter<T>, iter: impl Iterator<Item=T>) -> Result<(), CliError>
+    where T: Serialize
+{
+    for record in iter {
+        writer.write(&record)?;
+    }
+
+    writer.finish()
+}
@@ -0,0 +1,88 @@
+use std::io::Write;
+use rayon::prelude::*;
+use serde::Serialize;
+use crate::{CliError, ErrorType};
+use crate::output::{OutputFormat, OutputWriter};
+
+// the smallest number of records serialized by one task
+const MIN_CHUNK: usize = 1024;
+
+/// Csv or json output serialized by rayon thread pool. Records are buffered until `finish`,
+/// then chunks are serialized to per-task buffers and written in order, so output is the same
+/// as of `CsvOutput` and `JsonOutput`.
+pub struct ParallelOutput<W: Write, T> {
+    writer: W,
+    format: OutputFormat,
+    records: Vec<T>,
+}
+
+impl<W: Write, T> ParallelOutput<W, T> {
+    /// `format` is csv or json, other formats are written as csv
+    pub fn new(writer: W, format: OutputFormat) -> Self {
+        ParallelOutput {
+            writer,
+            format,
+            records: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write, T: Serialize + Clone + Send + Sync> OutputWriter<T> for ParallelOutput<W, T> {
+    fn write(&mut self, record: &T) -> Result<(), CliError> {
+        self.records.push(record.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), CliError> {
+        let records = std::mem::take(&mut self.records);
+        let chunk = (records.len() / (rayon::current_num_threads() * 4)).max(MIN_CHUNK);
+
+        let format = self.format;
+        let buffers = records.par_chunks(chunk)
+            .enumerate()
+            .map(|(index, chunk)| serialize(format, chunk, index == 0))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if format == OutputFormat::Json {
+            let start: &[u8] = if records.is_empty() { b"[]\n" } else { b"[\n" };
+            self.writer.write_all(start)?;
+        }
+        for (index, buffer) in buffers.iter().enumerate() {
+            if format == OutputFormat::Json && index > 0 {
+                self.writer.write_all(b",\n")?;
+            }
+            self.writer.write_all(buffer)?;
+        }
+        if format == OutputFormat::Json && !records.is_empty() {
+            self.writer.write_all(b"\n]\n")?;
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// csv rows (header only in the first chunk) or json records separated with ",\n"
+fn serialize<T: Serialize>(format: OutputFormat, records: &[T], first: bool) -> Result<Vec<u8>, CliError> {
+    match format {
+        OutputFormat::Json => {
+            let mut buffer = Vec::new();
+            for (index, record) in records.iter().enumerate() {
+                if index > 0 {
+                    buffer.extend_from_slice(b",\n");
+                }
+                serde_json::to_writer(&mut buffer, record).map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+            }
+            Ok(buffer)
+        }
+        _ => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(first)
+                .from_writer(Vec::new());
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.into_inner().map_err(|e| CliError::from_source(ErrorType::IO, e.into_error()))
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::Write;
+use std::sync::Arc;
+use parquet::data_type::{ByteArray, ByteArrayType};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde::Serialize;
+use crate::{CliError, ErrorType};
+use crate::output::OutputWriter;
+
+/// Parquet file with single row group, all columns stored as UTF8 strings
+/// (decimal values keep exact representation).
+/// Records are buffered (as csv) until `finish`, then written as columns.
+pub struct ParquetOutput<W: Write> {
+    writer: W,
+    buffer: csv::Writer<Vec<u8>>,
+}
+
+impl<W: Write> ParquetOutput<W> {
+    pub fn new(writer: W) -> Self {
+        ParquetOutput {
+            writer,
+            buffer: csv::WriterBuilder::new()
+                .has_headers(true)
+                .from_writer(Vec::new()),
+        }
+    }
+}
+
+impl<W: Write, T: Serialize> OutputWriter<T> for ParquetOutput<W> {
+    fn write(&mut self, record: &T) -> Result<(), CliError> {
+        self.buffer.serialize(record)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), CliError> {
+        let buffer = std::mem::replace(&mut self.buffer, csv::Writer::from_writer(Vec::new()))
+            .into_inner()
+            .map_err(|e| CliError::from_source(ErrorType::IO, e.into_error()))?;
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .from_reader(buffer.as_slice());
+
+        let headers = reader.headers()?.clone();
+        let mut columns: Vec<Vec<ByteArray>> = vec![Vec::new(); headers.len()];
+        for record in reader.records() {
+            for (column, field) in columns.iter_mut().zip(record?.iter()) {
+                column.push(ByteArray::from(field));
+            }
+        }
+
+        let fields = headers.iter()
+            .map(|it| format!("REQUIRED BYTE_ARRAY {} (UTF8);", it))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let schema = parse_message_type(&format!("message accounts {{ {} }}", fields)).map_err(parquet_error)?;
+
+        // parquet writer requires Send writer, so write into memory first
+        let mut file = SerializedFileWriter::new(Vec::new(), Arc::new(schema), Arc::new(WriterProperties::builder().build()))
+            .map_err(parquet_error)?;
+
+        let mut row_group = file.next_row_group().map_err(parquet_error)?;
+        let mut values = columns.iter();
+        while let Some(mut column) = row_group.next_column().map_err(parquet_error)? {
+            let data = values.next().map(|it| it.as_slice()).unwrap_or(&[]);
+            column.typed::<ByteArrayType>().write_batch(data, None, None).map_err(parquet_error)?;
+            column.close().map_err(parquet_error)?;
+        }
+        row_group.close().map_err(parquet_error)?;
+
+        let bytes = file.into_inner().map_err(parquet_error)?;
+        self.writer.write_all(&bytes)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn parquet_error(e: parquet::errors::ParquetError) -> CliError {
+    CliError::from_source(ErrorType::IO, e)
+}
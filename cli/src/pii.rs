@@ -0,0 +1,187 @@
+use std::convert::Infallible;
+use std::env;
+use std::fs;
+use std::io::Write;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use core::account::view::AccountView;
+use core::client::Client;
+use core::pipeline::Sink;
+use crate::{CliError, ErrorType};
+use crate::checksum::to_hex;
+use crate::write::write_sensitive;
+
+// HMAC-SHA256 block size
+const BLOCK_LEN: usize = 64;
+
+// pseudonym is truncated HMAC, 128 bits
+const PSEUDONYM_LEN: usize = 16;
+
+/// Secret of pseudonyms, the same key gives the same pseudonym of client across runs
+pub struct PseudonymKey {
+    // key padded to block size (hashed first when longer)
+    block: [u8; BLOCK_LEN],
+}
+
+impl PseudonymKey {
+    /// Key from `env:<NAME>` environment variable or from file
+    pub fn load(source: &str) -> Result<Self, CliError> {
+        let key = match source.strip_prefix("env:") {
+            Some(name) => env::var(name)
+                .map_err(|_| CliError::new(ErrorType::CliParseError, format!("Pseudonym key variable {} not set", name)))?,
+            None => fs::read_to_string(source)?,
+        };
+
+        let key = key.trim().as_bytes();
+        if key.is_empty() {
+            return Err(CliError::new(ErrorType::CliParseError, "Pseudonym key can't be empty"));
+        }
+
+        let mut block = [0; BLOCK_LEN];
+        match key.len() > BLOCK_LEN {
+            true => block[..32].copy_from_slice(&Sha256::digest(key)),
+            false => block[..key.len()].copy_from_slice(key),
+        }
+        Ok(PseudonymKey { block })
+    }
+
+    /// Hex encoded HMAC-SHA256 of client id (truncated), client id can't be recovered without lookup file
+    pub fn pseudonym(&self, client: Client) -> String {
+        let pad = |byte: u8| self.block.map(|it| it ^ byte);
+        let inner = Sha256::new().chain_update(pad(0x36)).chain_update(client.to_be_bytes()).finalize();
+        let outer = Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize();
+        to_hex(&outer[..PSEUDONYM_LEN])
+    }
+}
+
+/// Account which needs attention, identified by pseudonym only
+#[derive(Debug, Serialize)]
+pub struct Anomaly {
+    key: String,
+
+    // locked (charged back), held (open disputes), negative (available funds below zero)
+    kinds: Vec<&'static str>,
+}
+
+impl Anomaly {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+    pub fn kinds(&self) -> &[&'static str] {
+        &self.kinds
+    }
+}
+
+/// Sink which passes accounts to inner sink (aggregates) and keeps pseudonymous anomalies,
+/// clients of anomalies are kept for lookup file
+pub struct AnomalySink<'a, S> {
+    inner: S,
+    key: &'a PseudonymKey,
+    anomalies: Vec<(Client, Anomaly)>,
+}
+
+impl<'a, S> AnomalySink<'a, S> {
+    pub fn new(inner: S, key: &'a PseudonymKey) -> Self {
+        AnomalySink { inner, key, anomalies: Vec::new() }
+    }
+
+    pub fn into_parts(self) -> (S, Vec<(Client, Anomaly)>) {
+        (self.inner, self.anomalies)
+    }
+}
+
+impl<S> Sink<AccountView> for AnomalySink<'_, S>
+    where S: Sink<AccountView, Error=Infallible>
+{
+    type Error = Infallible;
+
+    fn write(&mut self, record: AccountView) -> Result<(), Self::Error> {
+        let kinds: Vec<_> = [(record.locked(), "locked"), (!record.held().is_zero(), "held"), (*record.available() < Decimal::ZERO, "negative")]
+            .into_iter()
+            .filter_map(|(is, kind)| is.then_some(kind))
+            .collect();
+        if !kinds.is_empty() {
+            self.anomalies.push((*record.client(), Anomaly { key: self.key.pseudonym(*record.client()), kinds }));
+        }
+        self.inner.write(record)
+    }
+}
+
+/// Lookup file (csv: key, client) of pseudonyms, stored separately from report in restricted environment.
+/// File is encrypted with `--encryption-key` (see `TempFile::create_sensitive`).
+pub fn write_lookup(file_path: &str, overwrite: bool, anomalies: &[(Client, Anomaly)]) -> Result<(), CliError> {
+    write_sensitive(file_path, overwrite, |file| {
+        writeln!(file, "key,client")?;
+        for (client, anomaly) in anomalies {
+            writeln!(file, "{},{}", anomaly.key, client)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use rust_decimal::Decimal;
+    use core::account::view::AccountView;
+    use core::pipeline::Sink;
+    use crate::pii::{write_lookup, AnomalySink, PseudonymKey};
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("csv-cli-analyzer-pii-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn key(name: &str, secret: &str) -> PseudonymKey {
+        let path = temp_path(name);
+        fs::write(&path, secret).unwrap();
+        let key = PseudonymKey::load(path.to_str().unwrap());
+        fs::remove_file(path).unwrap();
+        key.unwrap()
+    }
+
+    #[test]
+    fn pseudonym_then_truncated_hmac_sha256() {
+        // trailing newline of key file is ignored
+        assert_eq!(key("short", "secret\n").pseudonym(1), "2e0e81ae604743632aba56bd1979d57d");
+        // key longer than block is hashed
+        assert_eq!(key("long", &"k".repeat(100)).pseudonym(65535), "30f0476d0bfa85a13528a767e8fc865d");
+        assert_ne!(key("other", "secret2").pseudonym(1), "2e0e81ae604743632aba56bd1979d57d");
+    }
+
+    #[test]
+    fn empty_or_missing_key_then_error() {
+        let path = temp_path("empty");
+        fs::write(&path, " \n").unwrap();
+        assert!(PseudonymKey::load(path.to_str().unwrap()).is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(PseudonymKey::load(path.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn accounts_then_anomalies_by_pseudonym_and_lookup() {
+        let key = key("anomalies", "secret");
+        let account = |client, available: i64, held: i64, locked| AccountView::new(client, Decimal::from(available), Decimal::from(held), locked, false).unwrap();
+
+        let mut sink = AnomalySink::new(Vec::new(), &key);
+        sink.write(account(1, 5, 0, false)).unwrap();
+        sink.write(account(2, 5, 1, false)).unwrap();
+        sink.write(account(3, -2, 0, true)).unwrap();
+        let (accounts, anomalies) = sink.into_parts();
+
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(anomalies.iter().map(|(client, it)| (*client, it.key().to_string(), it.kinds().to_vec())).collect::<Vec<_>>(), [
+            (2, key.pseudonym(2), vec!["held"]),
+            (3, key.pseudonym(3), vec!["locked", "negative"]),
+        ]);
+        assert!(!serde_json::to_string(&anomalies[1].1).unwrap().contains("client"));
+
+        let lookup = temp_path("lookup");
+        write_lookup(lookup.to_str().unwrap(), false, &anomalies).unwrap();
+        assert_eq!(fs::read_to_string(&lookup).unwrap(), format!("key,client\n{},2\n{},3\n", key.pseudonym(2), key.pseudonym(3)));
+        fs::remove_file(lookup).unwrap();
+    }
+}
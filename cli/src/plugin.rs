@@ -0,0 +1,146 @@
+use std::path::Path;
+use wasmtime::{Engine, Instance, Memory, Module, Store, TypedFunc};
+use core::account::view::AccountView;
+use core::error::ProcessError;
+use core::pipeline::CancellationToken;
+use core::transaction::Transaction;
+use core::TransactionProcessor;
+use crate::{source, CliError, ErrorType};
+use crate::output::OutputFormat;
+use crate::source::ReaderOptions;
+
+/// Name of built-in processor, selected when `--processor` is not set
+pub const BASIC_PROCESSOR: &str = "basic";
+
+/// Transaction processor implemented as WebAssembly module (`--plugin`), e.g. Rust `cdylib` built
+/// for `wasm32-unknown-unknown`. Module can't import anything and must export:
+/// - `memory`
+/// - `alloc(len: i32) -> i32` - buffer for transaction passed to `process`
+/// - `process(ptr: i32, len: i32) -> i32` - JSON transaction (`{"type":"deposit","client":1,"tx":1,"amount":"1.0"}`),
+///   returns 0 when applied or number of rejection error code (201 for `E201`)
+/// - `finalize() -> i64` - JSON array of accounts (as in json output), returned as `ptr << 32 | len`
+pub struct WasmProcessor {
+    name: String,
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    process: TypedFunc<(i32, i32), i32>,
+    finalize: TypedFunc<(), i64>,
+
+    // trap or invalid memory access, plugin is not called after failure
+    failure: Option<String>,
+}
+
+impl WasmProcessor {
+    pub fn load<P: AsRef<Path>>(name: &str, path: P) -> Result<Self, CliError> {
+        let engine = Engine::default();
+        let module = Module::from_file(&engine, path.as_ref())
+            .map_err(|e| plugin_error(name, format!("can't load '{}': {}", path.as_ref().display(), e)))?;
+
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[]).map_err(|e| plugin_error(name, e))?;
+        let memory = instance.get_memory(&mut store, "memory")
+            .ok_or_else(|| plugin_error(name, "memory is not exported"))?;
+
+        Ok(WasmProcessor {
+            alloc: instance.get_typed_func(&mut store, "alloc").map_err(|e| plugin_error(name, e))?,
+            process: instance.get_typed_func(&mut store, "process").map_err(|e| plugin_error(name, e))?,
+            finalize: instance.get_typed_func(&mut store, "finalize").map_err(|e| plugin_error(name, e))?,
+            name: name.to_string(),
+            store,
+            memory,
+            failure: None,
+        })
+    }
+
+    fn call_process(&mut self, transaction: &Transaction) -> Result<i32, String> {
+        let payload = serde_json::to_vec(transaction).map_err(|e| e.to_string())?;
+        let len = i32::try_from(payload.len()).map_err(|e| e.to_string())?;
+
+        let ptr = self.alloc.call(&mut self.store, len).map_err(call_error)?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, &payload).map_err(|e| e.to_string())?;
+        self.process.call(&mut self.store, (ptr, len)).map_err(call_error)
+    }
+
+    fn call_finalize(&mut self) -> Result<Vec<AccountView>, String> {
+        let packed = self.finalize.call(&mut self.store, ()).map_err(call_error)?;
+        let (ptr, len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+
+        let accounts = self.memory.data(&self.store).get(ptr..ptr + len)
+            .ok_or("accounts are out of plugin memory")?;
+        serde_json::from_slice(accounts).map_err(|e| format!("invalid accounts: {}", e))
+    }
+}
+
+impl TransactionProcessor for WasmProcessor {
+    type Output = Result<Vec<AccountView>, CliError>;
+
+    fn process(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        if self.failure.is_some() {
+            return Err(ProcessError::UnknownOrUnexpectedError);
+        }
+
+        match self.call_process(&transaction) {
+            Ok(0) => Ok(()),
+            Ok(code) => Err(ProcessError::from_code(&format!("E{}", code)).unwrap_or(ProcessError::UnknownOrUnexpectedError)),
+            Err(e) => {
+                self.failure = Some(e);
+                Err(ProcessError::UnknownOrUnexpectedError)
+            }
+        }
+    }
+
+    fn finalize(mut self) -> Self::Output {
+        if let Some(failure) = self.failure.take() {
+            return Err(plugin_error(&self.name, failure));
+        }
+
+        self.call_finalize().map_err(|e| plugin_error(&self.name, e))
+    }
+}
+
+/// Path of plugin selected by name, plugins are given as `name=path` or `path` (name is file stem)
+pub fn find<'a>(plugins: impl Iterator<Item=&'a str>, name: &str) -> Result<&'a str, CliError> {
+    plugins
+        .map(|it| match it.split_once('=') {
+            Some((name, path)) => (name, path),
+            None => (Path::new(it).file_stem().and_then(|it| it.to_str()).unwrap_or(it), it),
+        })
+        .find(|(plugin, _)| *plugin == name)
+        .map(|(_, path)| path)
+        .ok_or_else(|| CliError::new(ErrorType::CliParseError, format!("Processor '{}' not found, load it with --plugin", name)))
+}
+
+/// Process transactions with plugin processor and write accounts returned by plugin
+pub fn execute(file_path: &str, reader_options: &ReaderOptions, processor: WasmProcessor, format: OutputFormat, cancel: &CancellationToken) -> Result<(), CliError> {
+    let mut processor = processor;
+
+    for (sequence, record) in (1..).zip(source::open(file_path, reader_options)?) {
+        let transaction = record?.with_sequence(sequence);
+
+        // same as in pipeline, process errors are ignored
+        let _ = processor.process(transaction);
+
+        if cancel.is_cancelled() {
+            eprintln!("Interrupted, partial output written");
+            break;
+        }
+    }
+
+    let stdout = std::io::stdout();
+    let mut writer = format.writer(stdout.lock());
+    for account in processor.finalize()? {
+        writer.write(&account)?;
+    }
+
+    writer.finish()
+}
+
+// trap message without wasm backtrace
+fn call_error(e: wasmtime::Error) -> String {
+    e.root_cause().to_string()
+}
+
+fn plugin_error<M: ToString>(name: &str, message: M) -> CliError {
+    CliError::new(ErrorType::Process, format!("Plugin '{}' failed: {}", name, message.to_string()))
+}
@@ -1,54 +1,619 @@
 use clap::ArgMatches;
 use crate::{CliError, ErrorType};
-use core::TransactionProcessor;
 use core::BasicProcessor;
-use core::transaction::Transaction;
-use crate::reader::read_from_file;
-use crate::write::write;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use core::pipeline::{run_with_results, CancellationToken, DumpTrigger, RunOptions, RunReport};
+use core::queue::{prefetch, SpillQueue};
+use core::schedule::{Posting, PostingScheduler};
+use core::expiry::{DisputeExpiry, ExpiryAction};
+use core::accrual::HeldAccrual;
+use core::suspense::SuspenseLedger;
+use core::store::CacheStats;
+use core::client::Client;
+use rust_decimal::Decimal;
+use crate::{admin, balances, bench, checksum, dlq, fx, manifest, report, signal, source, tenant};
+use crate::source::{ReaderOptions, SourceError, TransactionSource, UnknownTypes};
+use core::transaction_type::TransactionType;
+use crate::filter::{Filter, FilterSink};
+use crate::write::{check_overwrite, write_json, write_snapshot, ShardSink};
+use crate::output::OutputFormat;
+use crate::checksum::{file_sha256, StreamDigest};
+use crate::manifest::{AuditRoot, FileChecksum, Manifest, OutputSidecar};
+use crate::ledger::IngestionLedger;
+use crate::compaction::Compaction;
+use crate::results::ResultsWriter;
+
+// path of stdout in run manifest
+const STDOUT: &str = "-";
 
 pub fn execute(matches: &ArgMatches) -> Result<(), CliError> {
+    if let Some(("replay-dlq", matches)) = matches.subcommand() {
+        #[cfg(feature = "encryption")]
+        crate::encryption::configure(matches)?;
+        return dlq::replay(matches);
+    }
+    #[cfg(feature = "encryption")]
+    if let Some(("decrypt", matches)) = matches.subcommand() {
+        crate::encryption::configure(matches)?;
+        return crate::encryption::decrypt_command(matches);
+    }
+    if let Some(("report", matches)) = matches.subcommand() {
+        #[cfg(feature = "encryption")]
+        crate::encryption::configure(matches)?;
+        return report::report(matches);
+    }
+    if let Some(("reconcile", matches)) = matches.subcommand() {
+        #[cfg(feature = "encryption")]
+        crate::encryption::configure(matches)?;
+        return crate::reconcile::reconcile(matches);
+    }
+    if let Some(("delta", matches)) = matches.subcommand() {
+        #[cfg(feature = "encryption")]
+        crate::encryption::configure(matches)?;
+        return crate::delta::delta(matches);
+    }
+    if let Some(("snapshot", matches)) = matches.subcommand() {
+        #[cfg(feature = "encryption")]
+        crate::encryption::configure(matches)?;
+        return crate::snapshot::snapshot(matches);
+    }
+    if let Some(("distribute", matches)) = matches.subcommand() {
+        return crate::distribute::distribute(matches);
+    }
+    if let Some(("verify-audit", matches)) = matches.subcommand() {
+        return crate::audit::verify(matches);
+    }
+    if let Some(("bench", matches)) = matches.subcommand() {
+        return bench::bench(matches);
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(("relay-outbox", matches)) = matches.subcommand() {
+        return crate::sqlite::relay(matches);
+    }
+    #[cfg(feature = "nats")]
+    if let Some(("consume-nats", matches)) = matches.subcommand() {
+        return crate::nats::consume(matches);
+    }
+    #[cfg(feature = "amqp")]
+    if let Some(("consume-amqp", matches)) = matches.subcommand() {
+        return crate::amqp::consume(matches);
+    }
+
     let file_path = matches.value_of("file_path").ok_or_else(|| {
         // this should not happen at this stage
         CliError::new(ErrorType::CliParseError, "Arg file path not found")
     })?;
 
-    // read file, deserialize csv via serde and return iterator
-    let tx_iter = read_from_file(file_path)?;
+    // snapshots, DLQ and audit log contain customer data and often land on shared storage
+    #[cfg(feature = "encryption")]
+    crate::encryption::configure(matches)?;
 
-    // use default process for transaction
-    // we can easily create new one or use WrapProcessor instead
-    let proc_iter = process(tx_iter, BasicProcessor::new())?;
+    // refuse to process wrong input file (e.g. wrong day's file)
+    let expected_sha256 = match (matches.value_of("expect_sha256"), matches.value_of("verify_manifest")) {
+        (Some(expected), _) => Some(expected.to_string()),
+        (None, Some(manifest)) => Some(manifest::read_input_sha256(manifest)?),
+        (None, None) => None,
+    };
+    if let Some(expected) = expected_sha256 {
+        checksum::verify_input(file_path, &expected)?;
+    }
+
+    let reader_options = reader_options(matches)?;
+
+    // Ctrl-C stops reading, processed accounts are still written
+    let cancel = CancellationToken::new();
+    signal::register_shutdown(&cancel)?;
+
+    // transactions read from and accounts written to SQLite file
+    #[cfg(feature = "sqlite")]
+    if matches.value_of("format") == Some(crate::sqlite::SQLITE_FORMAT) {
+        check_run_mode(matches, "--format sqlite", &["by_tenant", "fx_table"])?;
+        let source_table = matches.value_of("sqlite_source_table").unwrap_or("transactions");
+        let results_table = matches.value_of("sqlite_results_table").unwrap_or("accounts");
+        let outbox_table = matches.value_of("sqlite_outbox_table");
+        return crate::sqlite::execute(file_path, source_table, results_table, outbox_table, &reader_options.type_aliases, matches.is_present("force"), &cancel);
+    }
 
-    // Write csv and use stdout writer.
+    // Write csv (or other selected format) and use stdout writer.
     // The output should be a list of client IDs (client), available amounts (available), held amounts
     // (held), total amounts (total), and whether the account is locked (locked).
-    write(proc_iter)?;
+    // For huge account sets output can be split into shards, each written by its own thread.
+    let format = matches.value_of("format").unwrap_or("csv").parse::<OutputFormat>()?;
+
+    // multi-currency input, balances are kept per currency and converted into reporting currency on output
+    if let Some(fx_table) = matches.value_of("fx_table") {
+        check_run_mode(matches, "--fx-table", &["by_tenant"])?;
+        let reporting = matches.value_of("reporting_currency").ok_or_else(|| {
+            // this should not happen at this stage, clap requires reporting currency together with fx table
+            CliError::new(ErrorType::CliParseError, "Arg reporting currency not found")
+        })?;
+        return fx::execute(file_path, &reader_options, &fx::read_fx_table(fx_table, reporting)?, format, &cancel);
+    }
+
+    // multi-tenant input, accounts are kept per tenant and written into file per tenant
+    if matches.is_present("by_tenant") {
+        check_run_mode(matches, "--by-tenant", &[])?;
+        let out_dir = matches.value_of("out_dir").ok_or_else(|| {
+            // this should not happen at this stage, clap requires out-dir together with by-tenant
+            CliError::new(ErrorType::CliParseError, "Arg out dir not found")
+        })?;
+        let quota = matches.value_of("tenant_quota")
+            .map(str::parse::<u64>)
+            .transpose()
+            .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg tenant-quota must be a number"))?;
+        let options = tenant::TenantOptions {
+            default_tenant: matches.value_of("tenant"),
+            quota,
+            stats: matches.value_of("tenant_stats"),
+            out_dir,
+        };
+        return tenant::execute(file_path, &reader_options, &options, format, matches.is_present("force"), &cancel);
+    }
+
+    // third-party processor loaded from WebAssembly plugin
+    #[cfg(feature = "plugins")]
+    if let Some(name) = matches.value_of("processor").filter(|it| *it != crate::plugin::BASIC_PROCESSOR) {
+        check_run_mode(matches, "--processor", &["by_tenant"])?;
+        let path = crate::plugin::find(matches.values_of("plugin").into_iter().flatten(), name)?;
+        let processor = crate::plugin::WasmProcessor::load(name, path)?;
+        return crate::plugin::execute(file_path, &reader_options, processor, format, &cancel);
+    }
+
+    // cron-driven batch jobs must not apply the same day's transactions twice
+    let ledger = matches.value_of("ledger").map(IngestionLedger::open).transpose()?;
+    let input_sha256 = ledger.as_ref().map(|it| it.check(file_path, matches.is_present("reprocess"))).transpose()?;
+
+    // output files are written atomically, existing files are overwritten only with --force
+    let force = matches.is_present("force");
+    let manifest = matches.value_of("manifest");
+    let sidecar = matches.value_of("output_sidecar");
+    for path in manifest.iter().chain(sidecar.iter()) {
+        check_overwrite(path, force)?;
+    }
+
+    // input/output checksums for run manifest are computed while data is streamed
+    let input_digest = manifest.map(|_| StreamDigest::new());
+    let output_digest = manifest.or(sidecar).map(|_| StreamDigest::new());
+    let started = Instant::now();
+    #[cfg(feature = "profiling")]
+    let allocations = crate::profiling::AllocationSnapshot::now();
+
+    // open source (file, gzip file, stdin or tcp stream) and return iterator
+    // with --prefetch source is opened and read ahead on separate thread, so producer outpacing processor
+    // doesn't stall, memory is bounded by spilling overflow to disk
+    let prefetch_capacity = matches.value_of("prefetch")
+        .map(str::parse::<usize>)
+        .transpose()
+        .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg prefetch must be a number"))?;
+    let source: Box<dyn TransactionSource> = match prefetch_capacity {
+        Some(capacity) => {
+            let (path, options, digest) = (file_path.to_string(), reader_options.clone(), input_digest.clone());
+            let open = move || open_source(&path, &options, digest.as_ref()).unwrap_or_else(|e| Box::new(std::iter::once(Err(e))));
+            Box::new(prefetch(open, SpillQueue::new(capacity)))
+        }
+        None => open_source(file_path, &reader_options, input_digest.as_ref())?,
+    };
+
+    // with --unknown-types skip rows with unknown transaction type are reported instead of failing the run
+    let unknown_types = UnknownTypes::default();
+    let source: Box<dyn TransactionSource> = match matches.value_of("unknown_types") {
+        Some("skip") => Box::new(unknown_types.skip(source)),
+        _ => source,
+    };
+
+    // use default process for transaction
+    // we can easily create new one or use WrapProcessor instead
+    // late disputes of transactions processed by previous runs are resolved from --history files
+    let mut processor = match matches.values_of("history") {
+        Some(file_paths) => BasicProcessor::new().with_history(balances::read_history(file_paths, &reader_options)?),
+        None => BasicProcessor::new(),
+    };
+
+    // daily runs start from closing balances of previous day
+    if let Some(opening_balances) = matches.value_of("opening_balances") {
+        for account in balances::read_opening_balances(opening_balances)? {
+            processor.restore_account(account);
+        }
+    }
+
+    // accounts of previous runs are loaded from persistent store when first seen, changes are written through
+    #[cfg(feature = "sqlite")]
+    if let Some(account_store) = matches.value_of("account_store") {
+        let store = crate::sqlite::SqliteAccountStore::open(account_store)?;
+        // with write-behind, changes are written in batches, crash-safe through write-ahead log
+        let flush_every = matches.value_of("store_flush_every").map(str::parse::<usize>).transpose()
+            .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg store-flush-every must be a number"))?;
+        let flush_ms = matches.value_of("store_flush_ms").map(str::parse::<u64>).transpose()
+            .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg store-flush-ms must be a number"))?;
+        processor = match (flush_every, flush_ms) {
+            (None, None) => processor.with_account_store(store),
+            (flush_every, flush_ms) => {
+                let wal_path = PathBuf::from(format!("{}.wal", account_store));
+                let max_delay = flush_ms.map(std::time::Duration::from_millis);
+                let log_level = match matches.value_of("store_log_level") {
+                    Some("error") => crate::config::LogLevel::Error,
+                    Some("warn") => crate::config::LogLevel::Warn,
+                    _ => crate::config::LogLevel::Info,
+                };
+                processor.with_account_store(crate::write_behind::WriteBehind::open(store, &wal_path, flush_every.unwrap_or(usize::MAX), max_delay, log_level)?)
+            }
+        };
+        if let Some(warm_up) = matches.value_of("warm_up") {
+            let limit = warm_up.parse::<usize>()
+                .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg warm-up must be a number"))?;
+            processor.warm_up(limit).map_err(|e| CliError::from_source(ErrorType::Process, e))?;
+        }
+    }
+
+    // corrections (unlock, adjustment) of authorized admins, recorded in audit log
+    // with --audit-chain root hash of audit log is added to run manifest
+    let audit_chain = match matches.value_of("admin_ops") {
+        Some(admin_ops) => admin::apply(admin_ops, matches.values_of("admin").into_iter().flatten(), &mut processor,
+                                        matches.value_of("audit_log"), matches.is_present("audit_chain"), force)?,
+        None => None,
+    };
+
+    // compliance-driven deletion requests, erased clients are not written to output
+    if let Some(clients) = matches.values_of("erase") {
+        for client in clients {
+            let client = client.parse::<Client>()
+                .map_err(|_| CliError::new(ErrorType::CliParseError, format!("Invalid client '{}' to erase", client)))?;
+            if let Err(e) = processor.erase_client(client) {
+                eprintln!("Client {} not erased: {}", client, e);
+            }
+        }
+        if let Some(tombstones) = matches.value_of("tombstones") {
+            write_json(tombstones, force, &processor.tombstones())?;
+        }
+    }
+
+    // compliance flag for inactive accounts
+    if let Some(dormant_after) = matches.value_of("dormant_after") {
+        let threshold = dormant_after.parse::<u64>()
+            .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg dormant-after must be a number"))?;
+        processor.set_dormant_threshold(Some(threshold));
+    }
+
+    // memory proportional to dispute window rather than to total history
+    if let Some(dispute_window) = matches.value_of("dispute_window") {
+        let window = dispute_window.parse::<u64>()
+            .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg dispute-window must be a number"))?;
+        processor.set_dispute_window(Some(window));
+    }
+
+    // held funds of forgotten disputes are released (or flagged) after expiry
+    if let Some(dispute_expiry) = matches.value_of("dispute_expiry") {
+        let expiry = match dispute_expiry {
+            "end" => DisputeExpiry::EndOfRun,
+            after => DisputeExpiry::After(after.parse::<u64>()
+                .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg dispute-expiry must be a number or end"))?),
+        };
+        let action = match matches.value_of("dispute_expiry_action") {
+            Some("flag") => ExpiryAction::Flag,
+            _ => ExpiryAction::Resolve,
+        };
+        processor.set_dispute_expiry(Some(expiry), action);
+        if let Some(dispute_events) = matches.value_of("dispute_events") {
+            processor.set_expiry_listener(Some(event_log(dispute_events, force, "dispute")?));
+        }
+    }
+
+    // account locks by chargeback, closes and unlocks by admin
+    if let Some(status_events) = matches.value_of("status_events") {
+        processor.set_status_listener(Some(event_log(status_events, force, "status")?));
+    }
+
+    // platforms compensating clients for held balances
+    if let Some(held_accrual) = matches.value_of("held_accrual") {
+        let accrual = held_accrual.split_once(':')
+            .and_then(|(rate, period)| Some((rate.parse::<Decimal>().ok()?, period.parse::<u64>().ok()?)))
+            .and_then(|(rate, period)| HeldAccrual::new(rate, period).ok())
+            .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg held-accrual must be <rate>:<N>, N a positive number"))?;
+        processor.set_held_accrual(Some(accrual));
+    }
+
+    // amounts of disputes referencing missing or other client transactions, written after run
+    let suspense = matches.value_of("suspense").map(|file_path| {
+        let ledger = Arc::new(Mutex::new(SuspenseLedger::new()));
+        let listener = ledger.clone();
+        processor.set_suspense_listener(Some(Box::new(move |entry| {
+            if let Ok(mut ledger) = listener.lock() {
+                ledger.record(entry.clone());
+            }
+        })));
+        (file_path, ledger)
+    });
+
+    // reproducible output for reconciliation, accounts are sorted by client when written
+    processor.set_ordered(matches.is_present("ordered"));
+
+    // historical data doesn't freeze accounts or alert again
+    processor.set_backfill(matches.is_present("backfill"));
+
+    // bespoke validation rules without recompiling
+    #[cfg(feature = "scripting")]
+    if let Some(script) = matches.value_of("script") {
+        processor.set_rule(Some(crate::script::compile(script)?));
+    }
+
+    // SIGHUP dumps current accounts state, useful for long-running stdin/tcp sources
+    let dump_path = matches.value_of("dump_path");
+    let dump = match dump_path {
+        Some(_) => {
+            let dump = DumpTrigger::new();
+            signal::register_dump(&dump)?;
+            Some(dump)
+        }
+        None => None,
+    };
+
+    // periodic fees/interest posted as synthetic transactions, useful for long-running stdin/tcp sources
+    let postings = matches.values_of("posting")
+        .map_or(Ok(Vec::new()), |it| it.map(|it| it.parse::<Posting>()).collect::<Result<Vec<_>, _>>())
+        .map_err(|e| CliError::from_source(ErrorType::CliParseError, e))?;
+    let mut scheduler = match postings.is_empty() {
+        true => None,
+        false => Some(PostingScheduler::new(postings, SystemTime::now())),
+    };
+
+    // rejected transactions with error codes, can be re-submitted with replay-dlq
+    // with --dlq-route rejected transactions are split into separate files by error code
+    let mut dlq = dlq::open_router(matches.value_of("dlq"), matches.values_of("dlq_route").into_iter().flatten(), force)?;
+
+    // per-row acknowledgements for upstream systems
+    let mut results = matches.value_of("results_out").map(|it| ResultsWriter::create(it, force)).transpose()?;
+
+    let batch_size = matches.value_of("batch_size")
+        .map_or(Ok(1), str::parse::<usize>)
+        .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg batch-size must be a number"))?;
+    let options = RunOptions { cancel: Some(cancel), dump, measure_latency: matches.is_present("latency"), batch_size, ..Default::default() };
+    // old settled transactions are evicted, optionally to archive file
+    let mut compaction = match matches.value_of("compact") {
+        Some(keep_last_n) => {
+            let keep_last_n = keep_last_n.parse::<usize>()
+                .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg compact must be a number"))?;
+            Some(Compaction::new(keep_last_n, matches.value_of("compact_archive"), force)?)
+        }
+        None => None,
+    };
+    // account cache of account store, reported after run
+    let mut cache_stats = CacheStats::default();
+    let hook = |processor: &mut BasicProcessor| {
+        cache_stats = processor.cache_stats();
+
+        if let Some(compaction) = compaction.as_mut() {
+            compaction.run(processor);
+        }
+
+        if let Some(scheduler) = scheduler.as_mut() {
+            scheduler.run(SystemTime::now(), processor);
+        }
+
+        if let Some(dump_path) = dump_path.filter(|_| options.dump.as_ref().is_some_and(|it| it.take())) {
+            // dump error must not stop processing
+            if let Err(e) = write_snapshot(dump_path, format, processor.into_iter()) {
+                eprintln!("Can't dump accounts state: {}", e);
+            }
+        }
+    };
+
+    // only accounts matching --filter expression are written
+    let filter = matches.value_of("filter").map(str::parse::<Filter>).transpose()?;
+
+    // accounts output split into files: by client id (--out-shards) or by locked status (--split-locked)
+    let out_dir = || matches.value_of("out_dir").ok_or_else(|| {
+        // this should not happen at this stage, clap requires out-dir together with out-shards or split-locked
+        CliError::new(ErrorType::CliParseError, "Arg out dir not found")
+    });
+    let partitioned = match matches.value_of("out_shards") {
+        Some(shards) => {
+            let shards = shards.parse::<usize>()
+                .ok()
+                .filter(|it| *it > 0)
+                .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg out-shards must be a positive number"))?;
+            Some(ShardSink::new(format, shards, out_dir()?))
+        }
+        None if matches.is_present("split_locked") => Some(ShardSink::split_locked(format, out_dir()?)),
+        None => None,
+    };
+
+    let (report, shard_outputs): (RunReport, Vec<(PathBuf, u64)>) = match partitioned {
+        Some(sink) => {
+            let mut sink = sink.with_overwrite(force);
+            for path in sink.paths() {
+                check_overwrite(path, force)?;
+            }
+            let mut filtered = FilterSink::new(filter.as_ref(), &mut sink);
+            let report = run_with_results(source, processor, &mut filtered, dlq.as_mut(), results.as_mut(), &options, hook)?;
+            let report = RunReport { written: filtered.written(), ..report };
+            (report, sink.paths().into_iter().zip(sink.rows().iter().copied()).collect())
+        }
+        None => {
+            //we can easy change writer to other types
+            let stdout = std::io::stdout();
+            let mut writer = match &output_digest {
+                Some(digest) => format.parallel_writer(digest.writer(stdout.lock())),
+                None => format.parallel_writer(stdout.lock()),
+            };
+            let mut sink = FilterSink::new(filter.as_ref(), &mut writer);
+            let report = run_with_results(source, processor, &mut sink, dlq.as_mut(), results.as_mut(), &options, hook)?;
+            (RunReport { written: sink.written(), ..report }, Vec::new())
+        }
+    };
+
+    if let Some(compaction) = compaction {
+        let evicted = compaction.finish()?;
+        if evicted > 0 {
+            eprintln!("Compacted {} settled transactions", evicted);
+        }
+    }
+
+    if let Some((file_path, ledger)) = suspense {
+        let ledger = ledger.lock().map_err(|_| CliError::new(ErrorType::Process, "Suspense ledger not available"))?;
+        write_json(file_path, force, &*ledger)?;
+        if !ledger.entries().is_empty() {
+            eprintln!("{} transactions in suspense", ledger.entries().len());
+        }
+    }
+
+    // checksums and row counts of accounts output, stdout is hashed while written, shard files after write
+    let outputs = match output_digest {
+        Some(_) if !shard_outputs.is_empty() => shard_outputs.iter()
+            .map(|(path, rows)| Ok(FileChecksum::new(path.display(), file_sha256(path)?).with_rows(*rows)))
+            .collect::<Result<Vec<_>, CliError>>()?,
+        Some(digest) => vec![FileChecksum::new(STDOUT, digest.hex()).with_rows(report.written)],
+        None => Vec::new(),
+    };
+
+    if let Some(sidecar) = sidecar {
+        OutputSidecar::new(&outputs).write(sidecar, force)?;
+    }
+
+    // interrupted run is not recorded, input must be processed again
+    if let (Some(ledger), Some(sha256), false) = (ledger, input_sha256, report.cancelled) {
+        ledger.record(file_path, sha256, report.read)?;
+    }
+
+    // guides optimization of slow paths, e.g. dispute lookup
+    for (r#type, histogram) in &report.latency {
+        if let (Some(p50), Some(p99)) = (histogram.percentile(0.5), histogram.percentile(0.99)) {
+            eprintln!("Latency {:<10} p50 {:?}, p99 {:?}, max {:?} ({} transactions)", r#type, p50, p99, histogram.max(), histogram.count());
+        }
+    }
+
+    // tracked number for memory optimizations, includes reading input and writing output
+    #[cfg(feature = "profiling")]
+    {
+        let (allocations, bytes) = allocations.per_million(report.read);
+        eprintln!("Allocations per 1M transactions: {} ({} bytes)", allocations, bytes);
+    }
+
+    if cache_stats != CacheStats::default() {
+        eprintln!("Account cache: {} warmed up, {} hits, {} misses", cache_stats.warmed(), cache_stats.hits(), cache_stats.misses());
+    }
+
+    if unknown_types.count() > 0 {
+        eprintln!("Skipped {} rows with unknown transaction type: {}", unknown_types.count(), unknown_types.summary(10));
+    }
+
+    if report.cancelled {
+        eprintln!("Interrupted after {} transactions, partial output written", report.read);
+        if let Some(checkpoint) = matches.value_of("checkpoint") {
+            write_checkpoint(checkpoint, &report)?;
+        }
+    }
+
+    // manifest is written last, so it has checksums of all other files written by run
+    if let (Some(manifest), Some(input_digest)) = (manifest, input_digest) {
+        let input = FileChecksum::new(file_path, input_digest.hex());
+        let mut run_manifest = Manifest::new(input, &report, started.elapsed(), &outputs).with_files(written_files(matches, &report)?);
+        if let (Some(audit_log), Some(chain)) = (matches.value_of("audit_log"), &audit_chain) {
+            run_manifest = run_manifest.with_audit_log(AuditRoot::new(audit_log, chain));
+        }
+        run_manifest.write(manifest, force)?;
+    }
 
     Ok(())
 }
 
-pub fn process<I, P, S>(iter: I, processor: P) -> Result<impl Iterator<Item=S>, CliError>
-    where I: Iterator<Item=Result<Transaction, csv::Error>>,
-          P: TransactionProcessor + IntoIterator<Item=S>,
-          S: serde::Serialize
+/// Checksums of files written by run besides accounts output, in order of options. Files which were
+/// not created (e.g. audit log without admin operations) are skipped.
+fn written_files(matches: &ArgMatches, report: &RunReport) -> Result<Vec<FileChecksum>, CliError> {
+    let dlq_routes = matches.values_of("dlq_route").into_iter().flatten().filter_map(|it| it.split_once('=').map(|(_, path)| path));
+    let audit_log = matches.value_of("audit_log").filter(|_| matches.is_present("admin_ops"));
+    let tombstones = matches.value_of("tombstones").filter(|_| matches.is_present("erase"));
+    let checkpoint = matches.value_of("checkpoint").filter(|_| report.cancelled);
+    let others = ["results_out", "suspense", "dispute_events", "status_events", "compact_archive", "ledger", "output_sidecar"]
+        .into_iter()
+        .filter_map(|it| matches.value_of(it));
 
-{
-    let mut processor = processor;
+    let mut paths: Vec<&str> = Vec::new();
+    for path in matches.value_of("dlq").into_iter().chain(dlq_routes).chain(audit_log).chain(tombstones).chain(others).chain(checkpoint) {
+        if !paths.contains(&path) && std::path::Path::new(path).exists() {
+            paths.push(path);
+        }
+    }
+    paths.into_iter()
+        .map(|path| Ok(FileChecksum::new(path, file_sha256(path)?)))
+        .collect()
+}
+
+// source of transactions, with digest input is hashed as read
+fn open_source(path: &str, options: &ReaderOptions, digest: Option<&StreamDigest>) -> Result<Box<dyn TransactionSource>, SourceError> {
+    match digest {
+        Some(digest) => source::from_reader(source::decode(path, digest.reader(source::open_raw(path)?)), options),
+        None => source::open(path, options),
+    }
+}
 
-    for record in iter {
+/// Checkpoint is a run report in json format. `read` is the number of consumed source transactions.
+/// Checkpoint of previous run is replaced.
+fn write_checkpoint(file_path: &str, report: &RunReport) -> Result<(), CliError> {
+    write_json(file_path, true, report)
+}
+
+// options applied only by the default run, run modes returning early (--format sqlite, --fx-table, --by-tenant,
+// plugin --processor) don't apply them. Options of features which are not compiled in are skipped.
+const RUN_OPTIONS: [&str; 31] = [
+    "out_shards", "split_locked", "checkpoint", "dump_path", "opening_balances", "history", "admin_ops", "erase",
+    "compact", "posting", "dormant_after", "dispute_window", "dispute_expiry", "status_events", "held_accrual",
+    "suspense", "backfill", "ordered", "prefetch", "batch_size", "latency", "dlq", "dlq_route", "results_out",
+    "manifest", "output_sidecar", "unknown_types", "filter", "ledger", "script", "account_store",
+];
 
-        //check if record contains error
-        //if yes - abort
-        let transaction = record?;
+/// Options of the default run given together with run mode which doesn't apply them are rejected,
+/// rather than silently ignored, `other` are options of other run modes
+fn check_run_mode(matches: &ArgMatches, mode: &str, other: &[&str]) -> Result<(), CliError> {
+    let unsupported: Vec<String> = RUN_OPTIONS.iter().chain(other)
+        .filter(|it| matches.try_contains_id(it).unwrap_or(false) && matches.occurrences_of(it) > 0)
+        .map(|it| format!("--{}", it.replace('_', "-")))
+        .collect();
+    match unsupported.is_empty() {
+        true => Ok(()),
+        false => Err(CliError::new(ErrorType::CliParseError, format!("Options {} are not supported with {}", unsupported.join(", "), mode))),
+    }
+}
 
-        // send transaction for processing
-        if let Err(e) = processor.process(transaction) {
-            let _ = e;
-            // in real world scenario we must do something with error case
-            // we can put transaction with process error to dlq repository
-            // or print to stderr or save to error file or do something else or do nothing (just kidding :) )
+// listener of processor events (`ExpiryListener`, `StatusListener`)
+type EventListener<T> = Box<dyn FnMut(&T) + Send>;
+
+/// Events (expired disputes, status changes) are written as json lines, file is written while processing (not atomically)
+fn event_log<T: serde::Serialize>(file_path: &str, overwrite: bool, kind: &'static str) -> Result<EventListener<T>, CliError> {
+    check_overwrite(file_path, overwrite)?;
+    let mut writer = LineWriter::new(File::create(file_path)?);
+    Ok(Box::new(move |event| {
+        // event log error must not stop processing
+        let result = serde_json::to_writer(&mut writer, event).map_err(io::Error::from).and_then(|_| writer.write_all(b"\n"));
+        if let Err(e) = result {
+            eprintln!("Can't write {} event: {}", kind, e);
         }
+    }))
+}
+
+/// Csv parsing options from command line
+fn reader_options(matches: &ArgMatches) -> Result<ReaderOptions, CliError> {
+    let mut options = ReaderOptions {
+        delimiter: matches.value_of("delimiter").unwrap_or("comma").parse()?,
+        ..Default::default()
+    };
+
+    // vendor specific type names, e.g. CREDIT=deposit
+    for mapping in matches.values_of("type_map").into_iter().flatten() {
+        let (name, r#type) = mapping.split_once('=')
+            .and_then(|(name, r#type)| Some((name, TransactionType::from_name(r#type.as_bytes())?)))
+            .ok_or_else(|| CliError::new(ErrorType::CliParseError, format!("Invalid type map '{}', expected <name>=<transaction type>", mapping)))?;
+        options.type_aliases.insert(name, r#type);
     }
 
-    Ok(processor.into_iter())
-}
\ No newline at end of file
+    // selected extra columns are kept with transactions and written to DLQ
+    #[cfg(feature = "metadata")]
+    if let Some(columns) = matches.value_of("metadata_columns") {
+        options.metadata_columns = columns.split(',').map(|it| it.trim().to_string()).filter(|it| !it.is_empty()).collect();
+    }
+
+    Ok(options)
+}
@@ -1,22 +1,196 @@
-use std::fs::File;
 use std::io;
-use std::path::Path;
-use core::transaction::Transaction;
+use std::io::BufReader;
+use csv::ByteRecord;
+use core::transaction::{Transaction, TransactionRef};
+use core::transaction_type::TypeAliases;
+use crate::source::{ReaderOptions, SourceError};
 
-pub fn read_from_file<T>(file_path: T) -> Result<impl Iterator<Item=Result<Transaction, csv::Error>>, io::Error>
-    where T: AsRef<Path>
-{
-    read_from_csv(File::open(file_path)?)
+const COLUMNS: [&[u8]; 7] = [b"type", b"client", b"tx", b"amount", b"currency", b"reference", b"tenant"];
+
+/// Csv reader which reuses single ByteRecord for all rows.
+/// Rows are parsed into TransactionRef borrowing amount from the record buffer,
+/// so there is no per-row String/enum allocation.
+pub struct RecordReader<R> {
+    reader: csv::Reader<R>,
+    record: ByteRecord,
+    columns: Columns,
 }
 
-pub fn read_from_csv<R>(reader: R) -> Result<impl Iterator<Item=Result<Transaction, csv::Error>>, io::Error>
+/// Columns of header, converts records of csv reader (sync or async) into transactions
+pub(crate) struct Columns {
+    // positions of type, client, tx, amount, currency, reference, tenant columns
+    positions: [Option<usize>; 7],
+    // names and positions of columns captured into transaction metadata
+    #[cfg_attr(not(feature = "metadata"), allow(dead_code))]
+    metadata: Vec<(String, usize)>,
+    // vendor specific type names and built-in aliases
+    type_aliases: TypeAliases,
+}
+
+pub fn read_from_csv<R>(reader: R, options: &ReaderOptions) -> Result<RecordReader<BufReader<R>>, SourceError>
     where R: io::Read
 {
-    let iter = csv::ReaderBuilder::new()
+    let mut reader = BufReader::new(reader);
+    let delimiter = options.delimiter.resolve(&mut reader)?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
         .trim(csv::Trim::All)
         .has_headers(true)
-        .from_reader(reader)
-        .into_deserialize();
+        .flexible(true)
+        .from_reader(reader);
+
+    let columns = Columns::new(reader.byte_headers()?, options)?;
+    Ok(RecordReader {
+        reader,
+        record: ByteRecord::new(),
+        columns,
+    })
+}
+
+impl Columns {
+    pub(crate) fn new(headers: &ByteRecord, options: &ReaderOptions) -> Result<Self, SourceError> {
+        let mut positions = [None; 7];
+        for (position, name) in positions.iter_mut().zip(COLUMNS.iter()) {
+            *position = headers.iter().position(|it| it == *name);
+        }
+
+        // amount, currency, reference and tenant are optional, rest is required
+        if positions[..3].iter().any(|it| it.is_none()) {
+            return Err(SourceError::Parse { line: 1, message: "Expected header: type,client,tx,amount".to_string() });
+        }
+
+        let metadata = options.metadata_columns.iter()
+            .map(|name| match headers.iter().position(|it| it == name.as_bytes()) {
+                Some(position) => Ok((name.clone(), position)),
+                None => Err(SourceError::Parse { line: 1, message: format!("Metadata column '{}' not found in header", name) }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Columns { positions, metadata, type_aliases: options.type_aliases.clone() })
+    }
+
+    // value of optional text column, empty value means no value
+    fn text(&self, record: &ByteRecord, column: usize) -> Option<String> {
+        self.positions[column]
+            .and_then(|it| record.get(it))
+            .filter(|it| !it.is_empty())
+            .map(|it| String::from_utf8_lossy(it).into_owned())
+    }
+
+    // metadata columns of record, empty values are skipped
+    #[cfg(feature = "metadata")]
+    fn metadata(&self, record: &ByteRecord) -> std::collections::HashMap<String, String> {
+        self.metadata.iter()
+            .filter_map(|(name, position)| record.get(*position).filter(|it| !it.is_empty()).map(|it| (name, it)))
+            .map(|(name, value)| (name.clone(), String::from_utf8_lossy(value).into_owned()))
+            .collect()
+    }
+
+    fn parse<'r>(&self, record: &'r ByteRecord) -> Result<TransactionRef<'r>, SourceError> {
+        let line = line(record);
+        let error = |message: &str| SourceError::Parse { line, message: message.to_string() };
+        let field = |column: Option<usize>| column.and_then(|it| record.get(it));
+
+        let r#type = field(self.positions[0]).ok_or_else(|| error("invalid transaction type"))?;
+        let r#type = self.type_aliases.resolve(r#type)
+            .ok_or_else(|| SourceError::UnknownType { line, value: String::from_utf8_lossy(r#type).into_owned() })?;
+        let client = field(self.positions[1])
+            .and_then(parse_int)
+            .ok_or_else(|| error("invalid client"))?;
+        let tx = field(self.positions[2])
+            .and_then(parse_int)
+            .ok_or_else(|| error("invalid tx"))?;
+
+        Ok(TransactionRef::new(r#type, client, tx, field(self.positions[3])))
+    }
+
+    /// Owned transaction of record with reference and metadata columns
+    pub(crate) fn transaction(&self, record: &ByteRecord) -> Result<Transaction, SourceError> {
+        let transaction = match self.parse(record).map(|it| it.to_transaction()) {
+            Ok(Ok(transaction)) => Ok(transaction),
+            // malformed amount, same as csv deserialize error, reported with line of current record
+            Ok(Err(_)) => Err(SourceError::Parse { line: line(record), message: "invalid amount".to_string() }),
+            Err(e) => Err(e),
+        };
+        let transaction = match self.text(record, 5) {
+            Some(reference) => transaction.map(|it| it.with_reference(reference)),
+            None => transaction,
+        };
+
+        #[cfg(feature = "metadata")]
+        let transaction = transaction.map(|it| it.with_metadata(self.metadata(record)));
+
+        transaction
+    }
+}
+
+impl<R: io::Read> RecordReader<R> {
+    /// Read next row. Returned transaction borrows from reader, so it must be processed
+    /// before next call.
+    pub fn next_ref(&mut self) -> Option<Result<TransactionRef<'_>, SourceError>> {
+        match self.reader.read_byte_record(&mut self.record) {
+            Ok(false) => None,
+            Ok(true) => Some(self.columns.parse(&self.record)),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+
+    /// Iterate over transactions together with value of optional `currency` column
+    pub fn with_currency(self) -> impl Iterator<Item=Result<(Option<String>, Transaction), SourceError>> {
+        let mut reader = self;
+        std::iter::from_fn(move || {
+            let transaction = reader.next()?;
+            Some(transaction.map(|it| (reader.columns.text(&reader.record, 4), it)))
+        })
+    }
+
+    /// Iterate over transactions together with value of optional `tenant` column
+    pub fn with_tenant(self) -> impl Iterator<Item=Result<(Option<String>, Transaction), SourceError>> {
+        let mut reader = self;
+        std::iter::from_fn(move || {
+            let transaction = reader.next()?;
+            Some(transaction.map(|it| (reader.columns.text(&reader.record, 6), it)))
+        })
+    }
+}
+
+impl<R: io::Read> Iterator for RecordReader<R> {
+    type Item = Result<Transaction, SourceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_byte_record(&mut self.record) {
+            Ok(false) => None,
+            Ok(true) => Some(self.columns.transaction(&self.record)),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+// line where record starts
+fn line(record: &ByteRecord) -> u64 {
+    record.position().map_or(0, |it| it.line())
+}
+
+fn parse_int<T>(bytes: &[u8]) -> Option<T>
+    where T: std::str::FromStr
+{
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::read_from_csv;
+    use crate::source::{ReaderOptions, SourceError};
+
+    #[test]
+    fn invalid_amount_then_error_with_line_of_record() {
+        // the first record spans two lines, so line of the second one is not previous line + 1
+        let input = "type,client,tx,amount\ndeposit,1,1,\"1\n.0\"\ndeposit,1,2,1.x\ndeposit,1,3,2.0\n";
+        let transactions: Vec<_> = read_from_csv(input.as_bytes(), &ReaderOptions::default()).unwrap().collect();
 
-    Ok(iter)
+        assert!(matches!(&transactions[0], Err(SourceError::Parse { line: 2, .. })));
+        assert!(matches!(&transactions[1], Err(SourceError::Parse { line: 4, message }) if message == "invalid amount"));
+        assert_eq!(transactions[2].as_ref().unwrap().tx_id(), 3);
+    }
 }
@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+use clap::ArgMatches;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use core::account::view::AccountView;
+use core::client::Client;
+use core::transaction::Transaction;
+use crate::{dlq, source, CliError, ErrorType};
+use crate::source::ReaderOptions;
+
+/// External statement row, held and total columns are optional and compared only when present
+#[derive(Debug, Deserialize)]
+struct StatementBalance {
+    client: Client,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    held: Option<Decimal>,
+
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    total: Option<Decimal>,
+}
+
+/// Balance of client which differs between statement and accounts output
+#[derive(Debug, Serialize)]
+struct Mismatch {
+    client: Client,
+
+    // differences by field (available, held, total)
+    fields: BTreeMap<&'static str, Difference>,
+
+    // input transactions of client in input order, only with --transactions
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    transactions: Vec<Transaction>,
+}
+
+/// Values of field in statement and in output, None for missing account
+#[derive(Debug, Serialize)]
+struct Difference {
+    statement: Option<Decimal>,
+    output: Option<Decimal>,
+}
+
+/// `reconcile` subcommand: match balances of external statement with accounts output per client
+/// and report mismatches (also accounts missing on either side) with transactions of client from
+/// `--transactions` input. Fails when any balance doesn't match.
+pub fn reconcile(matches: &ArgMatches) -> Result<(), CliError> {
+    let statement = matches.value_of("statement")
+        .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg statement not found"))?;
+    let accounts = matches.value_of("accounts")
+        .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg accounts not found"))?;
+
+    let statement = read_statement(statement)?;
+    let accounts: BTreeMap<_, _> = dlq::read_accounts(accounts)?.into_iter().map(|it| (*it.client(), it)).collect();
+
+    let clients: BTreeSet<_> = statement.keys().chain(accounts.keys()).copied().collect();
+    let total = clients.len();
+    let mut mismatches: Vec<_> = clients.into_iter()
+        .filter_map(|client| mismatch(client, statement.get(&client), accounts.get(&client)))
+        .collect();
+
+    // contributing transactions are looked up in one pass over input
+    if let Some(transactions) = matches.value_of("transactions") {
+        let index: BTreeMap<_, _> = mismatches.iter().enumerate().map(|(index, it)| (it.client, index)).collect();
+        for (sequence, transaction) in (1..).zip(source::open(transactions, &ReaderOptions::default())?) {
+            let transaction = transaction?;
+            if let Some(index) = index.get(&transaction.client()) {
+                mismatches[*index].transactions.push(transaction.with_sequence(sequence));
+            }
+        }
+    }
+
+    let stdout = std::io::stdout();
+    match matches.value_of("format").unwrap_or("text") {
+        "json" => serde_json::to_writer_pretty(stdout.lock(), &mismatches)
+            .map_err(|e| CliError::from_source(ErrorType::IO, e))?,
+        _ => write_text(&mismatches, &mut stdout.lock())?,
+    }
+
+    match mismatches.len() {
+        0 => {
+            eprintln!("All {} clients reconcile", total);
+            Ok(())
+        }
+        len => Err(CliError::new(ErrorType::Integrity, format!("{} of {} clients don't reconcile", len, total))),
+    }
+}
+
+fn read_statement(file_path: &str) -> Result<BTreeMap<Client, StatementBalance>, CliError> {
+    let mut balances = BTreeMap::new();
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(File::open(file_path)?);
+    for (line, record) in (2..).zip(reader.deserialize::<StatementBalance>()) {
+        let balance = record?;
+        if balances.contains_key(&balance.client) {
+            return Err(CliError::new(ErrorType::CsvRead, format!("Invalid statement at line {}: duplicate client {}", line, balance.client)));
+        }
+        balances.insert(balance.client, balance);
+    }
+    Ok(balances)
+}
+
+// fields which differ, None when balances match
+fn mismatch(client: Client, statement: Option<&StatementBalance>, account: Option<&AccountView>) -> Option<Mismatch> {
+    let fields = [
+        ("available", statement.map(|it| Some(it.available)), account.map(|it| *it.available())),
+        ("held", statement.map(|it| it.held), account.map(|it| *it.held())),
+        ("total", statement.map(|it| it.total), account.map(|it| *it.total())),
+    ];
+
+    let fields: BTreeMap<_, _> = fields.into_iter()
+        .filter_map(|(name, expected, actual)| match (expected, actual) {
+            // column not present in statement
+            (Some(None), _) => None,
+            (Some(Some(expected)), Some(actual)) if expected == actual => None,
+            (expected, actual) => Some((name, Difference { statement: expected.flatten(), output: actual })),
+        })
+        .collect();
+
+    match fields.is_empty() {
+        true => None,
+        false => Some(Mismatch { client, fields, transactions: Vec::new() }),
+    }
+}
+
+fn write_text<W: Write>(mismatches: &[Mismatch], writer: &mut W) -> Result<(), CliError> {
+    let value = |it: &Option<Decimal>| it.map_or("missing".to_string(), |it| it.to_string());
+    for mismatch in mismatches {
+        writeln!(writer, "Client {}:", mismatch.client)?;
+        for (name, difference) in &mismatch.fields {
+            writeln!(writer, "  {:<10} statement {}, output {}", name, value(&difference.statement), value(&difference.output))?;
+        }
+        for transaction in &mismatch.transactions {
+            let amount = transaction.amount().map_or(String::new(), |it| it.to_string());
+            writeln!(writer, "  {:>8}  {:<10} tx {:<10} {}", transaction.sequence(), transaction.r#type().name(), transaction.tx_id(), amount)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use object_store::aws::AmazonS3Builder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+
+const S3_PREFIX: &str = "s3://";
+const GCS_PREFIX: &str = "gs://";
+
+/// Whether path is object store url (`s3://bucket/key` or `gs://bucket/key`)
+pub fn is_remote<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().to_str().is_some_and(|it| it.starts_with(S3_PREFIX) || it.starts_with(GCS_PREFIX))
+}
+
+/// Download object into memory, object stores are read in batch jobs where inputs fit in memory
+pub fn open<P: AsRef<Path>>(url: P) -> io::Result<io::Cursor<Vec<u8>>> {
+    let (store, path) = store(url.as_ref())?;
+    let bytes = block_on(async { store.get(&path).await?.bytes().await })?;
+    Ok(io::Cursor::new(bytes.to_vec()))
+}
+
+pub fn exists<P: AsRef<Path>>(url: P) -> io::Result<bool> {
+    let (store, path) = store(url.as_ref())?;
+    block_on(async {
+        match store.head(&path).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// Upload local file as object. Object store put is atomic, readers never see partial object.
+pub fn upload<P: AsRef<Path>>(local: &Path, url: P) -> io::Result<()> {
+    let (store, path) = store(url.as_ref())?;
+    let payload = PutPayload::from(std::fs::read(local)?);
+    block_on(store.put(&path, payload))?;
+    Ok(())
+}
+
+// credentials and region are read from environment (AWS_*, GOOGLE_*)
+fn store(url: &Path) -> io::Result<(Arc<dyn ObjectStore>, ObjectPath)> {
+    let url = url.to_str().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Object store url is not valid UTF-8"))?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid object store url '{}', expected s3://bucket/key or gs://bucket/key", url));
+
+    let (scheme, location) = url.split_once("://").ok_or_else(invalid)?;
+    let (bucket, key) = location.split_once('/').filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty()).ok_or_else(invalid)?;
+
+    let store: Arc<dyn ObjectStore> = match scheme {
+        "s3" => Arc::new(AmazonS3Builder::from_env().with_bucket_name(bucket).build().map_err(io::Error::other)?),
+        "gs" => Arc::new(GoogleCloudStorageBuilder::from_env().with_bucket_name(bucket).build().map_err(io::Error::other)?),
+        _ => return Err(invalid()),
+    };
+
+    Ok((store, ObjectPath::from(key)))
+}
+
+fn block_on<F, T>(future: F) -> io::Result<T>
+    where F: Future<Output=Result<T, object_store::Error>>
+{
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(future)
+        .map_err(io::Error::other)
+}
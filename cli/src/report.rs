@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+use clap::ArgMatches;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use core::account::view::AccountView;
+use core::BasicProcessor;
+use core::pipeline::{run, RunOptions};
+use core::summary::{AccountsSummary, SummaryCollector};
+use crate::{hierarchy, pii, source, CliError, ErrorType};
+use crate::pii::{Anomaly, AnomalySink, PseudonymKey};
+use crate::output::{write_output, OutputFormat};
+use crate::source::ReaderOptions;
+
+/// `report` subcommand: process transactions and write aggregates (funds in system, top N accounts,
+/// locked accounts, balance histogram) instead of per-account output. With `--hierarchy` consolidated
+/// accounts of top-level parents (parent and all sub-accounts) are added.
+/// With `--minimize-pii` report has no per-client balances (no top accounts), only aggregates and anomalies
+/// identified by pseudonyms, pseudonym to client lookup is written to separate file.
+pub fn report(matches: &ArgMatches) -> Result<(), CliError> {
+    let file_path = matches.value_of("file_path").ok_or_else(|| {
+        // this should not happen at this stage
+        CliError::new(ErrorType::CliParseError, "Arg file path not found")
+    })?;
+    let lookup = matches.value_of("minimize_pii");
+    let top = match lookup {
+        Some(_) => 0,
+        None => matches.value_of("top").unwrap_or("10").parse::<usize>()
+            .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg top must be a number"))?,
+    };
+
+    let mut collector = match matches.value_of("buckets") {
+        Some(buckets) => {
+            let bounds = buckets.split(',')
+                .map(|it| Decimal::from_str(it.trim()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg buckets must be a comma separated list of numbers"))?;
+            SummaryCollector::with_bounds(top, bounds)
+        }
+        None => SummaryCollector::new(top),
+    };
+
+    let source = source::open(file_path, &ReaderOptions::default())?;
+    let mut anomalies = Vec::new();
+    let rollups = match (matches.value_of("hierarchy"), lookup) {
+        (Some(hierarchy), _) => {
+            let hierarchy = hierarchy::read_hierarchy(hierarchy)?;
+
+            // consolidated accounts need all accounts, so they are kept in memory
+            let mut accounts = Vec::new();
+            run(source, BasicProcessor::new(), &mut accounts, &RunOptions::default())?;
+            let accounts: HashMap<_, _> = accounts.into_iter().map(|it| (*it.client(), it)).collect();
+
+            let rollups = hierarchy.roots().into_iter()
+                .map(|parent| hierarchy.rollup(parent, |client| accounts.get(&client).cloned()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| CliError::from_source(ErrorType::Process, e))?;
+            accounts.into_values().for_each(|it| collector.add(it));
+            rollups
+        }
+        (None, Some(lookup)) => {
+            let key = matches.value_of("pii_key").map(PseudonymKey::load)
+                .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg pii-key not found"))??;
+            let mut sink = AnomalySink::new(&mut collector, &key);
+            run(source, BasicProcessor::new(), &mut sink, &RunOptions::default())?;
+
+            let (_, found) = sink.into_parts();
+            pii::write_lookup(lookup, matches.is_present("force"), &found)?;
+            anomalies = found.into_iter().map(|(_, anomaly)| anomaly).collect();
+            Vec::new()
+        }
+        (None, None) => {
+            run(source, BasicProcessor::new(), &mut collector, &RunOptions::default())?;
+            Vec::new()
+        }
+    };
+    let report = Report { summary: collector.summary(), rollups, anomalies };
+
+    let stdout = std::io::stdout();
+    match matches.value_of("format").unwrap_or("text") {
+        "json" => serde_json::to_writer_pretty(stdout.lock(), &report)
+            .map_err(|e| CliError::from_source(ErrorType::IO, e)),
+        _ => write_text(report, &mut stdout.lock()),
+    }
+}
+
+/// Accounts summary with consolidated accounts of parents
+#[derive(Serialize)]
+struct Report {
+    #[serde(flatten)]
+    summary: AccountsSummary,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rollups: Vec<AccountView>,
+
+    // only with --minimize-pii
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    anomalies: Vec<Anomaly>,
+}
+
+fn write_text<W: Write>(report: Report, writer: &mut W) -> Result<(), CliError> {
+    let summary = report.summary;
+    writeln!(writer, "Accounts:        {} ({} locked, {} closed)", summary.accounts, summary.locked, summary.closed)?;
+    writeln!(writer, "Total funds:     {}", summary.total_funds)?;
+    writeln!(writer, "Total available: {}", summary.total_available)?;
+    writeln!(writer, "Total held:      {}", summary.total_held)?;
+
+    if !summary.top.is_empty() {
+        writeln!(writer, "\nTop {} accounts by total:", summary.top.len())?;
+        write_output(OutputFormat::Table.writer(&mut *writer).as_mut(), summary.top.into_iter())?;
+    }
+
+    writeln!(writer, "\nDistribution of totals:")?;
+    for bucket in summary.histogram {
+        let range = match (bucket.from, bucket.to) {
+            (Some(from), Some(to)) => format!("{} - {}", from, to),
+            (Some(from), None) => format!(">= {}", from),
+            (None, Some(to)) => format!("< {}", to),
+            (None, None) => "all".to_owned(),
+        };
+        writeln!(writer, "{:<24} {}", range, bucket.count)?;
+    }
+
+    if !report.anomalies.is_empty() {
+        writeln!(writer, "\nAnomalies:")?;
+        for anomaly in report.anomalies {
+            writeln!(writer, "{:<34} {}", anomaly.key(), anomaly.kinds().join(", "))?;
+        }
+    }
+
+    if !report.rollups.is_empty() {
+        writeln!(writer, "\nConsolidated accounts:")?;
+        write_output(OutputFormat::Table.writer(&mut *writer).as_mut(), report.rollups.into_iter())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use core::pipeline::{Sink, TransactionResult};
+use crate::{CliError, ErrorType};
+use crate::write::TempFile;
+
+/// Per-transaction results file (csv: sequence, type, client, tx, outcome, code) in input order.
+/// File is written atomically (see `TempFile`), it appears at file path on `finish`.
+/// File is encrypted with `--encryption-key` (see `TempFile::create_sensitive`).
+pub struct ResultsWriter {
+    // None after finish
+    writer: Option<csv::Writer<TempFile>>,
+}
+
+impl ResultsWriter {
+    pub fn create(file_path: &str, overwrite: bool) -> Result<Self, CliError> {
+        Ok(ResultsWriter {
+            writer: Some(csv::Writer::from_writer(TempFile::create_sensitive(file_path, overwrite)?)),
+        })
+    }
+}
+
+impl Sink<TransactionResult> for ResultsWriter {
+    type Error = CliError;
+
+    fn write(&mut self, record: TransactionResult) -> Result<(), Self::Error> {
+        let writer = self.writer.as_mut()
+            .ok_or_else(|| CliError::new(ErrorType::IO, "Results file already finished"))?;
+        writer.serialize(record)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        if let Some(writer) = self.writer.take() {
+            writer.into_inner().map_err(|e| e.into_error())?.persist()?;
+        }
+        Ok(())
+    }
+}
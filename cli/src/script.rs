@@ -0,0 +1,78 @@
+use std::fs;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use core::account::view::AccountView;
+use core::error::ProcessError;
+use core::processor::basic_processor::Rule;
+use core::transaction::Transaction;
+use crate::{CliError, ErrorType};
+
+// guard against endless loops in user script
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Compile Rhai script (`--script`) into processor rule. Script is evaluated for every transaction with
+/// `tx` (type, client, tx, amount) and `account` (available, held, total, locked, closed, status, or `()` for new client)
+/// variables. Script accepts transaction with `true` or `()`, rejects it with `false` or reason string
+/// (also `throw "reason"`), e.g. `if tx.type == "withdrawal" && tx.amount > 1000 { "withdrawal over limit" }`.
+/// Reason prefixed with `review:` also puts account under review, e.g. `"review: withdrawal over limit"`.
+pub fn compile(file_path: &str) -> Result<Rule, CliError> {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let ast = engine.compile(fs::read_to_string(file_path)?)
+        .map_err(|e| CliError::new(ErrorType::CliParseError, format!("Invalid script '{}': {}", file_path, e)))?;
+
+    Ok(Box::new(move |transaction, account| evaluate(&engine, &ast, transaction, account)))
+}
+
+fn evaluate(engine: &Engine, ast: &AST, transaction: &Transaction, account: Option<&AccountView>) -> Result<(), ProcessError> {
+    let mut scope = Scope::new();
+    scope.push_constant("tx", transaction_map(transaction));
+    scope.push_constant("account", account.map_or(Dynamic::UNIT, |it| Dynamic::from_map(account_map(it))));
+
+    let result = match engine.eval_ast_with_scope::<Dynamic>(&mut scope, ast) {
+        Ok(result) => result,
+        // thrown value is rejection reason
+        Err(e) => return match *e {
+            EvalAltResult::ErrorRuntime(reason, _) => Err(rejection(reason.to_string())),
+            e => Err(ProcessError::RuleError(e.to_string())),
+        },
+    };
+
+    if result.is_unit() || result.as_bool() == Ok(true) {
+        Ok(())
+    } else if result.as_bool() == Ok(false) {
+        Err(ProcessError::RuleViolation("rejected by script".to_string()))
+    } else if result.is_string() {
+        Err(rejection(result.to_string()))
+    } else {
+        Err(ProcessError::RuleError(format!("script returned {}, expected bool, string or ()", result.type_name())))
+    }
+}
+
+// rejection reason of script, `review:` prefix puts account under review
+fn rejection(reason: String) -> ProcessError {
+    match reason.strip_prefix("review:") {
+        Some(reason) => ProcessError::ReviewRequired(reason.trim().to_string()),
+        None => ProcessError::RuleViolation(reason),
+    }
+}
+
+fn transaction_map(transaction: &Transaction) -> Map {
+    let mut map = Map::new();
+    map.insert("type".into(), transaction.r#type().name().into());
+    map.insert("client".into(), Dynamic::from_int(transaction.client().into()));
+    map.insert("tx".into(), Dynamic::from_int(transaction.tx_id().into()));
+    map.insert("amount".into(), transaction.amount().map_or(Dynamic::UNIT, Dynamic::from_decimal));
+    map
+}
+
+fn account_map(account: &AccountView) -> Map {
+    let mut map = Map::new();
+    map.insert("available".into(), Dynamic::from_decimal(*account.available()));
+    map.insert("held".into(), Dynamic::from_decimal(*account.held()));
+    map.insert("total".into(), Dynamic::from_decimal(*account.total()));
+    map.insert("locked".into(), account.locked().into());
+    map.insert("closed".into(), account.closed().into());
+    map.insert("status".into(), account.status().name().into());
+    map
+}
@@ -0,0 +1,22 @@
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::flag;
+use core::pipeline::{CancellationToken, DumpTrigger};
+use crate::CliError;
+
+/// Cancel token on SIGINT/SIGTERM, so pipeline stops ingestion and flushes partial output.
+/// Second signal terminates process immediately.
+pub fn register_shutdown(cancel: &CancellationToken) -> Result<(), CliError> {
+    for signal in [SIGINT, SIGTERM] {
+        // order matters: conditional shutdown must see flag before it is set by this signal
+        flag::register_conditional_shutdown(signal, 1, cancel.flag())?;
+        flag::register(signal, cancel.flag())?;
+    }
+
+    Ok(())
+}
+
+/// Request state dump on SIGHUP
+pub fn register_dump(dump: &DumpTrigger) -> Result<(), CliError> {
+    flag::register(SIGHUP, dump.flag())?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+use clap::ArgMatches;
+use core::account::view::AccountView;
+use crate::{delta, dlq, CliError, ErrorType};
+use crate::output::{write_output, OutputFormat};
+
+/// `snapshot` subcommand: tooling for accounts snapshots (csv output of runs)
+pub fn snapshot(matches: &ArgMatches) -> Result<(), CliError> {
+    match matches.subcommand() {
+        Some(("diff", matches)) => diff(matches),
+        Some(("merge", matches)) => merge(matches),
+        // this should not happen at this stage, clap requires subcommand
+        _ => Err(CliError::new(ErrorType::CliParseError, "Snapshot subcommand not found")),
+    }
+}
+
+/// `snapshot diff`: changed accounts between two snapshots (same rows as `delta`), fails when snapshots differ
+fn diff(matches: &ArgMatches) -> Result<(), CliError> {
+    let format = matches.value_of("format").unwrap_or("csv").parse::<OutputFormat>()?;
+    let deltas = delta::read_deltas(matches)?;
+    let len = deltas.len();
+
+    let stdout = std::io::stdout();
+    write_output(format.writer(stdout.lock()).as_mut(), deltas.into_iter())?;
+
+    match len {
+        0 => Ok(()),
+        len => Err(CliError::new(ErrorType::Integrity, format!("{} accounts differ", len))),
+    }
+}
+
+/// `snapshot merge`: accounts of snapshots with disjoint client sets (e.g. of runs over input sharded by client)
+/// in ascending client order. Fails when client is found in more than one snapshot.
+fn merge(matches: &ArgMatches) -> Result<(), CliError> {
+    let format = matches.value_of("format").unwrap_or("csv").parse::<OutputFormat>()?;
+    let accounts = merge_snapshots(matches.values_of("snapshots").into_iter().flatten())?;
+
+    let stdout = std::io::stdout();
+    write_output(format.writer(stdout.lock()).as_mut(), accounts.into_iter())
+}
+
+/// Accounts of snapshots with disjoint client sets in ascending client order
+pub fn merge_snapshots<'a, I>(file_paths: I) -> Result<Vec<AccountView>, CliError>
+    where I: IntoIterator<Item=&'a str>
+{
+    let mut accounts = BTreeMap::new();
+    for file_path in file_paths {
+        for account in dlq::read_accounts(file_path)? {
+            let client = *account.client();
+            if let Some((other, _)) = accounts.insert(client, (file_path, account)) {
+                return Err(CliError::new(ErrorType::Integrity, format!("Client {} found in {} and {}, snapshots are not disjoint", client, other, file_path)));
+            }
+        }
+    }
+
+    Ok(accounts.into_values().map(|(_, it)| it).collect())
+}
@@ -0,0 +1,306 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::net::TcpStream;
+use std::str::FromStr;
+use flate2::read::MultiGzDecoder;
+use core::transaction::Transaction;
+use core::transaction_type::TypeAliases;
+use crate::{CliError, ErrorType};
+
+pub const STDIN: &str = "-";
+pub const TCP_PREFIX: &str = "tcp://";
+const GZIP_SUFFIX: &str = ".gz";
+
+/// Csv parsing options
+#[derive(Debug, Clone, Default)]
+pub struct ReaderOptions {
+    // extra columns captured into transaction metadata (`metadata` feature), other extra columns are ignored
+    pub metadata_columns: Vec<String>,
+
+    // vendor specific type names translated to transaction types (`--type-map`),
+    // type names are matched case-insensitively with built-in aliases (e.g. withdraw, charge_back)
+    pub type_aliases: TypeAliases,
+
+    // field delimiter (`--delimiter`), comma by default
+    pub delimiter: Delimiter,
+}
+
+/// Field delimiter of input. With `Auto` delimiter is detected from the first line:
+/// the most frequent of comma, semicolon, tab and pipe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Auto,
+    Byte(u8),
+}
+
+const DELIMITER_CANDIDATES: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+impl Default for Delimiter {
+    fn default() -> Self {
+        Delimiter::Byte(b',')
+    }
+}
+
+impl FromStr for Delimiter {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Delimiter::Auto),
+            "comma" => Ok(Delimiter::Byte(b',')),
+            "semicolon" => Ok(Delimiter::Byte(b';')),
+            "tab" | "\\t" => Ok(Delimiter::Byte(b'\t')),
+            "pipe" => Ok(Delimiter::Byte(b'|')),
+            _ if s.len() == 1 && s.is_ascii() => Ok(Delimiter::Byte(s.as_bytes()[0])),
+            _ => Err(CliError::new(ErrorType::CliParseError, format!("Invalid delimiter '{}', expected auto, comma, semicolon, tab, pipe or single character", s))),
+        }
+    }
+}
+
+impl Delimiter {
+    /// Delimiter byte, with `Auto` the first line is peeked in buffer (not consumed)
+    pub fn resolve<R: io::BufRead>(&self, reader: &mut R) -> Result<u8, io::Error> {
+        match self {
+            Delimiter::Byte(it) => Ok(*it),
+            Delimiter::Auto => {
+                let buffer = reader.fill_buf()?;
+                let line = buffer.split(|it| *it == b'\n').next().unwrap_or_default();
+                let count = |delimiter: &u8| line.iter().filter(|it| *it == delimiter).count();
+
+                Ok(DELIMITER_CANDIDATES.iter()
+                    .copied()
+                    .filter(|it| count(it) > 0)
+                    // first candidate (comma) wins ties
+                    .max_by_key(|it| (count(it), std::cmp::Reverse(DELIMITER_CANDIDATES.iter().position(|c| c == it))))
+                    .unwrap_or(b','))
+            }
+        }
+    }
+}
+
+/// Source of transactions. Any iterator of `Result<Transaction, SourceError>` is a source,
+/// so new sources can be added without changes in processing code.
+pub trait TransactionSource: Iterator<Item=Result<Transaction, SourceError>> {}
+
+impl<T> TransactionSource for T where T: Iterator<Item=Result<Transaction, SourceError>> {}
+
+#[derive(Debug)]
+pub enum SourceError {
+    // Can't read from underlying stream
+    Io(io::Error),
+
+    // Malformed row
+    Parse { line: u64, message: String },
+
+    // Row with transaction type which is neither canonical nor mapped
+    UnknownType { line: u64, value: String },
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceError::Io(e) => write!(f, "{}", e),
+            SourceError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            SourceError::UnknownType { line, value } => write!(f, "line {}: unknown transaction type '{}'", line, value),
+        }
+    }
+}
+
+impl Error for SourceError {}
+
+impl From<io::Error> for SourceError {
+    fn from(e: io::Error) -> Self {
+        SourceError::Io(e)
+    }
+}
+
+impl From<csv::Error> for SourceError {
+    fn from(e: csv::Error) -> Self {
+        let line = e.position().map_or(0, |it| it.line());
+        let message = e.to_string();
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => SourceError::Io(e),
+            _ => SourceError::Parse { line, message },
+        }
+    }
+}
+
+impl From<SourceError> for CliError {
+    fn from(e: SourceError) -> Self {
+        match e {
+            SourceError::Io(e) => CliError::from_source(ErrorType::IO, e),
+            e => CliError::from_source(ErrorType::CsvRead, e),
+        }
+    }
+}
+
+/// Collects rows with unknown transaction type (`--unknown-types skip`) instead of failing the whole run
+#[derive(Debug, Default)]
+pub struct UnknownTypes {
+    // line numbers by type value
+    rows: RefCell<BTreeMap<String, Vec<u64>>>,
+}
+
+impl UnknownTypes {
+    /// Source without rows with unknown transaction type, skipped rows are collected
+    pub fn skip<'a, I>(&'a self, source: I) -> impl TransactionSource + 'a
+        where I: TransactionSource + 'a
+    {
+        source.filter(move |it| match it {
+            Err(SourceError::UnknownType { line, value }) => {
+                self.rows.borrow_mut().entry(value.clone()).or_default().push(*line);
+                false
+            }
+            _ => true,
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.rows.borrow().values().map(Vec::len).sum()
+    }
+
+    /// Summary of skipped rows, at most `limit` line numbers for every type value
+    pub fn summary(&self, limit: usize) -> String {
+        self.rows.borrow().iter()
+            .map(|(value, lines)| {
+                let shown = lines.iter().take(limit).map(u64::to_string).collect::<Vec<_>>().join(", ");
+                match lines.len() > limit {
+                    true => format!("'{}' (lines {}, ... {} more)", value, shown, lines.len() - limit),
+                    false => format!("'{}' (lines {})", value, shown),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Open transaction source:
+/// - `-` reads from stdin,
+/// - `tcp://host:port` connects and reads until the peer closes connection,
+/// - `s3://bucket/key` or `gs://bucket/key` downloads object (`object-store` feature),
+/// - `*.gz` file is decompressed on the fly,
+/// - otherwise plain file.
+pub fn open(path: &str, options: &ReaderOptions) -> Result<Box<dyn TransactionSource>, SourceError> {
+    from_reader(open_stream(path)?, options)
+}
+
+/// Open raw (decompressed) input stream, same rules as in `open`
+pub fn open_stream(path: &str) -> Result<Box<dyn io::Read>, SourceError> {
+    Ok(decode(path, open_raw(path)?))
+}
+
+/// Open input stream as stored (gzip file is not decompressed), for example to compute checksum
+pub fn open_raw(path: &str) -> Result<Box<dyn io::Read>, SourceError> {
+    let stream: Box<dyn io::Read> = match path {
+        STDIN => Box::new(io::stdin()),
+        _ if path.starts_with(TCP_PREFIX) => Box::new(TcpStream::connect(&path[TCP_PREFIX.len()..])?),
+        #[cfg(feature = "object-store")]
+        _ if crate::remote::is_remote(path) => Box::new(crate::remote::open(path)?),
+        _ => Box::new(File::open(path)?),
+    };
+
+    Ok(stream)
+}
+
+/// Decompress raw stream opened with `open_raw` if needed
+pub fn decode<R>(path: &str, raw: R) -> Box<dyn io::Read>
+    where R: io::Read + 'static
+{
+    match path.ends_with(GZIP_SUFFIX) && !path.starts_with(TCP_PREFIX) {
+        true => Box::new(MultiGzDecoder::new(BufReader::new(raw))),
+        false => Box::new(raw),
+    }
+}
+
+/// Parse csv from any reader
+#[cfg(not(feature = "fast-parse"))]
+pub fn from_reader<R>(reader: R, options: &ReaderOptions) -> Result<Box<dyn TransactionSource>, SourceError>
+    where R: io::Read + 'static
+{
+    Ok(Box::new(crate::reader::read_from_csv(reader, options)?))
+}
+
+/// Parse csv from any reader with byte level parser for fixed schema, other schemas fall back to regular reader
+#[cfg(feature = "fast-parse")]
+pub fn from_reader<R>(reader: R, options: &ReaderOptions) -> Result<Box<dyn TransactionSource>, SourceError>
+    where R: io::Read + 'static
+{
+    crate::fast_reader::read_from_csv(reader, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::transaction_type::TransactionType;
+    use crate::reader::read_from_csv;
+    use crate::source::{Delimiter, ReaderOptions, SourceError, UnknownTypes};
+
+    const INPUT: &str = "type,client,tx,amount\nCREDIT,1,1,5.0\nbonus,1,2,1.0\nDebit,1,3,2.0\nbonus,2,4,1\nfee,2,5,1\nbonus,2,6,1\n";
+
+    fn type_map() -> ReaderOptions {
+        let mut options = ReaderOptions::default();
+        options.type_aliases.insert("CREDIT", TransactionType::Deposit);
+        options.type_aliases.insert("debit", TransactionType::Withdrawal);
+        options
+    }
+
+    #[test]
+    fn unknown_type_then_error_with_line_and_value() {
+        let transactions: Vec<_> = read_from_csv(INPUT.as_bytes(), &ReaderOptions::default()).unwrap().collect();
+        assert!(matches!(&transactions[0], Err(SourceError::UnknownType { line: 2, value }) if value == "CREDIT"));
+    }
+
+    #[test]
+    fn type_map_then_vendor_names_translated_case_insensitively() {
+        let transactions: Vec<_> = read_from_csv(INPUT.as_bytes(), &type_map()).unwrap().collect();
+        assert!(matches!(transactions[0].as_ref().unwrap().r#type(), TransactionType::Deposit));
+        assert!(matches!(transactions[2].as_ref().unwrap().r#type(), TransactionType::Withdrawal));
+    }
+
+    #[test]
+    fn skip_unknown_types_then_rows_reported_by_type() {
+        let unknown_types = UnknownTypes::default();
+        let transactions: Vec<_> = unknown_types.skip(read_from_csv(INPUT.as_bytes(), &type_map()).unwrap())
+            .map(|it| it.unwrap().tx_id())
+            .collect();
+
+        assert_eq!(transactions, [1, 3]);
+        assert_eq!(unknown_types.count(), 4);
+        assert_eq!(unknown_types.summary(10), "'bonus' (lines 3, 5, 7), 'fee' (lines 6)");
+        assert_eq!(unknown_types.summary(2), "'bonus' (lines 3, 5, ... 1 more), 'fee' (lines 6)");
+    }
+
+    #[test]
+    fn delimiter_names_then_bytes() {
+        assert_eq!("auto".parse::<Delimiter>().unwrap(), Delimiter::Auto);
+        assert_eq!("semicolon".parse::<Delimiter>().unwrap(), Delimiter::Byte(b';'));
+        assert_eq!("\\t".parse::<Delimiter>().unwrap(), Delimiter::Byte(b'\t'));
+        assert_eq!(":".parse::<Delimiter>().unwrap(), Delimiter::Byte(b':'));
+        assert!("ab".parse::<Delimiter>().is_err());
+    }
+
+    #[test]
+    fn auto_delimiter_then_most_frequent_in_first_line() {
+        let resolve = |input: &str| Delimiter::Auto.resolve(&mut input.as_bytes()).unwrap();
+        assert_eq!(resolve("type;client;tx;amount\ndeposit,1,1,1.0,2,3,4"), b';');
+        assert_eq!(resolve("type\tclient\ttx\tamount"), b'\t');
+        assert_eq!(resolve("type|client|tx,amount"), b'|');
+        // ties go to comma, no candidate is comma
+        assert_eq!(resolve("type,client;tx"), b',');
+        assert_eq!(resolve("type"), b',');
+        assert_eq!(Delimiter::Byte(b':').resolve(&mut "a;b;c".as_bytes()).unwrap(), b':');
+    }
+
+    #[test]
+    fn auto_delimiter_then_first_line_not_consumed() {
+        let options = ReaderOptions { delimiter: Delimiter::Auto, ..ReaderOptions::default() };
+        let transactions: Vec<_> = read_from_csv("type ; client ; tx ; amount\ndeposit;1;1;1.0\nwithdrawal;1;2;0.5\n".as_bytes(), &options).unwrap()
+            .map(|it| it.unwrap().tx_id())
+            .collect();
+        assert_eq!(transactions, [1, 2]);
+    }
+}
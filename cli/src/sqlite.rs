@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
+use clap::ArgMatches;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use core::account::view::AccountView;
+use core::client::Client;
+use core::error::ProcessError;
+use core::store::AccountStore;
+use core::BasicProcessor;
+use core::pipeline::{run, CancellationToken, RunOptions, Sink};
+use core::transaction::Transaction;
+use core::transaction_type::TypeAliases;
+use crate::{signal, CliError, ErrorType};
+use crate::source::SourceError;
+
+pub const SQLITE_FORMAT: &str = "sqlite";
+
+// how often `relay-outbox --follow` checks for new events
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// table of `--account-store`
+const ACCOUNT_STORE_TABLE: &str = "account_store";
+
+/// `--format sqlite`: read transactions (type, client, tx, amount columns) from `source_table`
+/// and write accounts to `results_table` in the same SQLite file. Results are written in one
+/// database transaction, so the table is replaced only when the whole run succeeds.
+/// Existing results table is replaced only with `overwrite` (`--force`).
+/// With `outbox_table` every account changed by the run (new or different from the replaced results
+/// table) is also written to outbox table in the same database transaction, see `relay`.
+pub fn execute(file_path: &str, source_table: &str, results_table: &str, outbox_table: Option<&str>, type_aliases: &TypeAliases, overwrite: bool, cancel: &CancellationToken) -> Result<(), CliError> {
+    let connection = Connection::open(file_path).map_err(sqlite_error)?;
+    connection.execute_batch("BEGIN").map_err(sqlite_error)?;
+
+    let exists = connection.query_row("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1", [results_table], |_| Ok(()))
+        .optional()
+        .map_err(sqlite_error)?
+        .is_some();
+    if exists && !overwrite {
+        return Err(CliError::new(ErrorType::IO, format!("Table '{}' already exists, use --force to overwrite", results_table)));
+    }
+
+    // previous state of accounts, only changed accounts are written to outbox
+    let outbox = match outbox_table {
+        Some(outbox_table) => {
+            create_outbox(&connection, outbox_table)?;
+            let previous = match exists {
+                true => read_accounts(&connection, results_table)?,
+                false => HashMap::new(),
+            };
+            Some(Outbox { table: quote(outbox_table), previous })
+        }
+        None => None,
+    };
+
+    connection.execute_batch(&format!(
+        "DROP TABLE IF EXISTS {table};
+         CREATE TABLE {table} (client INTEGER PRIMARY KEY, available TEXT NOT NULL, held TEXT NOT NULL, total TEXT NOT NULL, locked INTEGER NOT NULL, closed INTEGER NOT NULL);",
+        table = quote(results_table)
+    )).map_err(sqlite_error)?;
+
+    // rowid is reported as line number of malformed row
+    let mut statement = connection.prepare(&format!("SELECT rowid, type, client, tx, amount FROM {} ORDER BY rowid", quote(source_table)))
+        .map_err(sqlite_error)?;
+    let source = statement.query_map([], |row| Ok(read_transaction(row, type_aliases)))
+        .map_err(sqlite_error)?
+        .map(|it| it.map_err(|e| SourceError::Parse { line: 0, message: e.to_string() }).and_then(|it| it));
+
+    let mut sink = SqliteSink { connection: &connection, table: quote(results_table), outbox };
+    let options = RunOptions { cancel: Some(cancel.clone()), ..Default::default() };
+    run(source, BasicProcessor::new(), &mut sink, &options)?;
+
+    Ok(())
+}
+
+/// Accounts inserted into results table (and changed accounts into outbox), committed on finish
+struct SqliteSink<'a> {
+    connection: &'a Connection,
+    table: String,
+    outbox: Option<Outbox>,
+}
+
+/// Outbox table with state of accounts before the run
+struct Outbox {
+    table: String,
+    previous: HashMap<u16, [String; 5]>,
+}
+
+impl Sink<AccountView> for SqliteSink<'_> {
+    type Error = CliError;
+
+    fn write(&mut self, account: AccountView) -> Result<(), Self::Error> {
+        let sql = format!("INSERT INTO {} (client, available, held, total, locked, closed) VALUES (?1, ?2, ?3, ?4, ?5, ?6)", self.table);
+        self.connection.prepare_cached(&sql)
+            .and_then(|mut it| it.execute(params![
+                account.client(),
+                account.available().normalize().to_string(),
+                account.held().normalize().to_string(),
+                account.total().normalize().to_string(),
+                account.locked(),
+                account.closed(),
+            ]))
+            .map_err(sqlite_error)?;
+
+        if let Some(outbox) = &self.outbox {
+            if outbox.previous.get(account.client()) != Some(&columns(&account)) {
+                let payload = serde_json::to_string(&account).map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+                let sql = format!("INSERT INTO {} (client, account) VALUES (?1, ?2)", outbox.table);
+                self.connection.prepare_cached(&sql)
+                    .and_then(|mut it| it.execute(params![account.client(), payload]))
+                    .map_err(sqlite_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.connection.execute_batch("COMMIT").map_err(sqlite_error)
+    }
+}
+
+// account columns of results table, as stored
+fn columns(account: &AccountView) -> [String; 5] {
+    [
+        account.available().normalize().to_string(),
+        account.held().normalize().to_string(),
+        account.total().normalize().to_string(),
+        (account.locked() as u8).to_string(),
+        (account.closed() as u8).to_string(),
+    ]
+}
+
+fn read_accounts(connection: &Connection, table: &str) -> Result<HashMap<u16, [String; 5]>, CliError> {
+    let mut statement = connection.prepare(&format!("SELECT client, available, held, total, locked, closed FROM {}", quote(table)))
+        .map_err(sqlite_error)?;
+    let rows = statement.query_map([], |row| {
+        Ok((row.get(0)?, [row.get(1)?, row.get(2)?, row.get(3)?, row.get::<_, i64>(4)?.to_string(), row.get::<_, i64>(5)?.to_string()]))
+    }).map_err(sqlite_error)?;
+    rows.collect::<Result<_, _>>().map_err(sqlite_error)
+}
+
+fn create_outbox(connection: &Connection, table: &str) -> Result<(), CliError> {
+    connection.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY AUTOINCREMENT, client INTEGER NOT NULL, account TEXT NOT NULL, published INTEGER NOT NULL DEFAULT 0)",
+        quote(table)
+    )).map_err(sqlite_error)
+}
+
+/// `relay-outbox` subcommand: publish unpublished outbox events (JSON lines `{"event_id": .., "account": {..}}`)
+/// to stdout in event order. Events are marked published only after the batch is flushed, so events are never
+/// lost; after a crash the last batch may be published again with the same `event_id`, consumers deduplicate by it.
+/// With `--follow` outbox is polled until Ctrl-C.
+pub fn relay(matches: &ArgMatches) -> Result<(), CliError> {
+    let database = matches.value_of("database").ok_or_else(|| {
+        // this should not happen at this stage
+        CliError::new(ErrorType::CliParseError, "Arg database not found")
+    })?;
+    let table = quote(matches.value_of("outbox_table").unwrap_or("outbox"));
+    let batch_size = matches.value_of("batch_size").unwrap_or("100").parse::<u32>()
+        .map_err(|_| CliError::new(ErrorType::CliParseError, "Arg batch-size must be a number"))?;
+
+    let cancel = CancellationToken::new();
+    signal::register_shutdown(&cancel)?;
+
+    let connection = Connection::open(database).map_err(sqlite_error)?;
+    let stdout = std::io::stdout();
+    let mut writer = stdout.lock();
+    while !cancel.is_cancelled() {
+        let mut statement = connection.prepare_cached(&format!("SELECT id, account FROM {} WHERE published = 0 ORDER BY id LIMIT ?1", table))
+            .map_err(sqlite_error)?;
+        let events = statement.query_map([batch_size], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+            .map_err(sqlite_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_error)?;
+
+        let last = match events.last() {
+            Some((id, _)) => *id,
+            None if matches.is_present("follow") => {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            None => break,
+        };
+        for (id, account) in &events {
+            writeln!(writer, "{{\"event_id\":{},\"account\":{}}}", id, account)?;
+        }
+        writer.flush()?;
+
+        connection.execute(&format!("UPDATE {} SET published = 1 WHERE published = 0 AND id <= ?1", table), [last])
+            .map_err(sqlite_error)?;
+    }
+    Ok(())
+}
+
+/// `--account-store`: accounts kept in `account_store` table of SQLite file (client, account as JSON, activity and
+/// version). Activity is increasing counter of saves, so the most recently changed accounts are warmed up first.
+/// Version is incremented by every save and compared with the version this instance loaded by save and delete
+/// (compare-and-swap), so instances sharing one file don't overwrite or delete each other's changes, the loser
+/// gets `VersionConflict`.
+pub struct SqliteAccountStore {
+    connection: Connection,
+
+    // activity of the last save
+    activity: i64,
+
+    // version of every account loaded or saved by this instance
+    versions: HashMap<Client, i64>,
+}
+
+impl SqliteAccountStore {
+    pub fn open(file_path: &str) -> Result<Self, CliError> {
+        let connection = Connection::open(file_path).map_err(sqlite_error)?;
+        connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (client INTEGER PRIMARY KEY, account TEXT NOT NULL, activity INTEGER NOT NULL, version INTEGER NOT NULL DEFAULT 0);
+             CREATE INDEX IF NOT EXISTS {table}_activity ON {table} (activity);",
+            table = ACCOUNT_STORE_TABLE
+        )).map_err(sqlite_error)?;
+
+        // table created before accounts were versioned
+        if connection.prepare(&format!("SELECT version FROM {} LIMIT 0", ACCOUNT_STORE_TABLE)).is_err() {
+            connection.execute_batch(&format!("ALTER TABLE {} ADD COLUMN version INTEGER NOT NULL DEFAULT 0", ACCOUNT_STORE_TABLE))
+                .map_err(sqlite_error)?;
+        }
+
+        let activity = connection.query_row(&format!("SELECT COALESCE(MAX(activity), 0) FROM {}", ACCOUNT_STORE_TABLE), [], |row| row.get(0))
+            .map_err(sqlite_error)?;
+        Ok(SqliteAccountStore { connection, activity, versions: HashMap::new() })
+    }
+
+    // account is stored only if it has version this instance knows, new account only if no other instance stored it
+    fn compare_and_save(&mut self, account: &AccountView) -> Result<(), ProcessError> {
+        let client = *account.client();
+        let payload = serde_json::to_string(account).map_err(store_error)?;
+        let (sql, expected) = match self.versions.get(&client) {
+            Some(version) => (format!("UPDATE {} SET account = ?2, activity = ?3, version = ?4 + 1 WHERE client = ?1 AND version = ?4", ACCOUNT_STORE_TABLE), *version),
+            None => (format!("INSERT INTO {} (client, account, activity, version) VALUES (?1, ?2, ?3, ?4 + 1) ON CONFLICT (client) DO NOTHING", ACCOUNT_STORE_TABLE), 0),
+        };
+        let saved = self.connection.prepare_cached(&sql)
+            .and_then(|mut it| it.execute(params![client, payload, self.activity + 1, expected]))
+            .map_err(store_error)?;
+        if saved == 0 {
+            // stale version is forgotten, account is loaded again with current one
+            self.versions.remove(&client);
+            return Err(ProcessError::VersionConflict);
+        }
+
+        self.versions.insert(client, expected + 1);
+        self.activity += 1;
+        Ok(())
+    }
+}
+
+impl AccountStore for SqliteAccountStore {
+    fn load(&mut self, client: Client) -> Result<Option<AccountView>, ProcessError> {
+        let row = self.connection.prepare_cached(&format!("SELECT account, version FROM {} WHERE client = ?1", ACCOUNT_STORE_TABLE))
+            .and_then(|mut it| it.query_row([client], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))).optional())
+            .map_err(store_error)?;
+        match row {
+            Some((account, version)) => {
+                self.versions.insert(client, version);
+                serde_json::from_str(&account).map(Some).map_err(store_error)
+            }
+            None => {
+                self.versions.remove(&client);
+                Ok(None)
+            }
+        }
+    }
+
+    fn load_recent(&mut self, limit: usize) -> Result<Vec<AccountView>, ProcessError> {
+        let mut statement = self.connection.prepare_cached(&format!("SELECT account, version FROM {} ORDER BY activity DESC LIMIT ?1", ACCOUNT_STORE_TABLE))
+            .map_err(store_error)?;
+        let rows = statement.query_map([limit as i64], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(store_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(store_error)?;
+        drop(statement);
+
+        rows.into_iter()
+            .map(|(account, version)| {
+                let account: AccountView = serde_json::from_str(&account).map_err(store_error)?;
+                self.versions.insert(*account.client(), version);
+                Ok(account)
+            })
+            .collect()
+    }
+
+    fn save(&mut self, account: &AccountView) -> Result<(), ProcessError> {
+        self.compare_and_save(account)
+    }
+
+    // batch (write-behind flush) is written in one database transaction, conflict of any account rolls back all
+    fn save_all(&mut self, accounts: &[AccountView]) -> Result<(), ProcessError> {
+        let (versions, activity) = (self.versions.clone(), self.activity);
+        self.connection.execute_batch("BEGIN").map_err(store_error)?;
+        if let Err(e) = accounts.iter().try_for_each(|it| self.compare_and_save(it)) {
+            let _ = self.connection.execute_batch("ROLLBACK");
+            self.versions = versions;
+            self.activity = activity;
+            return Err(e);
+        }
+        self.connection.execute_batch("COMMIT").map_err(store_error)
+    }
+
+    // account is deleted only if it has version this instance knows, account not known by this instance
+    // only if no other instance stored it
+    fn delete(&mut self, client: Client) -> Result<(), ProcessError> {
+        let conflict = match self.versions.remove(&client) {
+            Some(version) => self.connection.prepare_cached(&format!("DELETE FROM {} WHERE client = ?1 AND version = ?2", ACCOUNT_STORE_TABLE))
+                .and_then(|mut it| it.execute(params![client, version]))
+                .map_err(store_error)? == 0,
+            None => self.connection.prepare_cached(&format!("SELECT 1 FROM {} WHERE client = ?1", ACCOUNT_STORE_TABLE))
+                .and_then(|mut it| it.exists([client]))
+                .map_err(store_error)?,
+        };
+        match conflict {
+            true => Err(ProcessError::VersionConflict),
+            false => Ok(()),
+        }
+    }
+
+    // merged account is saved and source account deleted in one database transaction
+    fn save_merged(&mut self, to: &AccountView, _: Option<&AccountView>, from: Client) -> Result<(), ProcessError> {
+        let (versions, activity) = (self.versions.clone(), self.activity);
+        self.connection.execute_batch("BEGIN").map_err(store_error)?;
+        if let Err(e) = self.compare_and_save(to).and_then(|_| self.delete(from)) {
+            let _ = self.connection.execute_batch("ROLLBACK");
+            self.versions = versions;
+            self.activity = activity;
+            return Err(e);
+        }
+        self.connection.execute_batch("COMMIT").map_err(store_error)
+    }
+}
+
+fn read_transaction(row: &Row, type_aliases: &TypeAliases) -> Result<Transaction, SourceError> {
+    let line = row.get::<_, i64>(0).unwrap_or_default() as u64;
+    let error = |message: String| SourceError::Parse { line, message };
+
+    let r#type = match row.get_ref(1).map_err(|e| error(e.to_string()))? {
+        ValueRef::Text(it) => type_aliases.resolve(it)
+            .ok_or_else(|| SourceError::UnknownType { line, value: String::from_utf8_lossy(it).into_owned() })?,
+        _ => return Err(error("invalid transaction type".to_string())),
+    };
+    let client = row.get(2).map_err(|_| error("invalid client".to_string()))?;
+    let tx = row.get(3).map_err(|_| error("invalid tx".to_string()))?;
+
+    // amount stored as text keeps exact value, integer and real are accepted too
+    let amount = match row.get_ref(4).map_err(|e| error(e.to_string()))? {
+        ValueRef::Null => None,
+        ValueRef::Integer(it) => Some(Decimal::from(it)),
+        ValueRef::Real(it) => Some(Decimal::try_from(it).map_err(|_| error("invalid amount".to_string()))?),
+        ValueRef::Text(it) => match std::str::from_utf8(it).map(str::trim) {
+            Ok("") => None,
+            Ok(it) => Some(Decimal::from_str(it).map_err(|_| error("invalid amount".to_string()))?),
+            Err(_) => return Err(error("invalid amount".to_string())),
+        },
+        ValueRef::Blob(_) => return Err(error("invalid amount".to_string())),
+    };
+
+    Ok(Transaction::new(r#type, client, tx, amount))
+}
+
+// quoted identifier, table names come from command line
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn sqlite_error(e: rusqlite::Error) -> CliError {
+    CliError::from_source(ErrorType::IO, e)
+}
+
+// account store errors are storage errors of processor (retryable)
+fn store_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> ProcessError {
+    ProcessError::StorageError(std::io::Error::other(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use core::account::view::AccountView;
+    use core::error::ProcessError;
+    use core::store::AccountStore;
+    use core::transaction::Transaction;
+    use core::transaction_type::TransactionType;
+    use core::transaction_type::TypeAliases;
+    use core::{BasicProcessor, TransactionProcessor};
+    use rusqlite::Connection;
+    use crate::sqlite::{read_transaction, SqliteAccountStore};
+    use crate::source::SourceError;
+
+    // in-memory database shared by stores opened with the same name, the way instances share one file
+    fn database(name: &str) -> String {
+        format!("file:{}-{}?mode=memory&cache=shared", name, std::process::id())
+    }
+
+    fn account(client: u16, available: u64) -> AccountView {
+        AccountView::new(client, Decimal::from(available), Decimal::ZERO, false, false).unwrap()
+    }
+
+    // rows of source table without column types, so values are stored as inserted
+    fn read(rows: &str, type_aliases: &TypeAliases) -> Vec<Result<Transaction, SourceError>> {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(&format!("CREATE TABLE transactions (type, client, tx, amount); INSERT INTO transactions VALUES {};", rows)).unwrap();
+        let mut statement = connection.prepare("SELECT rowid, type, client, tx, amount FROM transactions ORDER BY rowid").unwrap();
+        let transactions = statement.query_map([], |row| Ok(read_transaction(row, type_aliases))).unwrap()
+            .map(Result::unwrap)
+            .collect();
+        transactions
+    }
+
+    #[test]
+    fn integer_real_text_and_blank_amounts_then_read() {
+        let transactions = read("('deposit', 1, 1, 10), ('deposit', 1, 2, 2.5), ('withdrawal', 1, 3, ' 1.2345 '), \
+            ('dispute', 1, 1, ''), ('resolve', 1, 1, NULL)", &TypeAliases::new());
+
+        let amounts: Vec<_> = transactions.iter().map(|it| it.as_ref().unwrap().amount()).collect();
+        assert_eq!(amounts, [Some(Decimal::from(10)), Some(Decimal::new(25, 1)), Some(Decimal::new(12345, 4)), None, None]);
+        assert_eq!(*transactions[2].as_ref().unwrap().r#type(), TransactionType::Withdrawal);
+        assert_eq!(transactions[4].as_ref().unwrap().tx_id(), 1);
+    }
+
+    #[test]
+    fn blob_and_invalid_text_amounts_then_error_with_rowid() {
+        let transactions = read("('deposit', 1, 1, X'31'), ('deposit', 1, 2, '1.x'), ('deposit', 1, 3, 3)", &TypeAliases::new());
+
+        assert!(matches!(&transactions[0], Err(SourceError::Parse { line: 1, message }) if message == "invalid amount"));
+        assert!(matches!(&transactions[1], Err(SourceError::Parse { line: 2, message }) if message == "invalid amount"));
+        assert_eq!(transactions[2].as_ref().unwrap().tx_id(), 3);
+    }
+
+    #[test]
+    fn aliased_and_unknown_types_then_resolved_or_error() {
+        let mut type_aliases = TypeAliases::new();
+        type_aliases.insert("CREDIT", TransactionType::Deposit);
+        let transactions = read("('credit', 1, 1, 1), ('bonus', 1, 2, 1), (7, 1, 3, 1)", &type_aliases);
+
+        assert_eq!(*transactions[0].as_ref().unwrap().r#type(), TransactionType::Deposit);
+        assert!(matches!(&transactions[1], Err(SourceError::UnknownType { line: 2, value }) if value == "bonus"));
+        assert!(matches!(&transactions[2], Err(SourceError::Parse { line: 3, message }) if message == "invalid transaction type"));
+    }
+
+    #[test]
+    fn stale_save_then_version_conflict() {
+        let database = database("stale-save");
+        let (mut first, mut second) = (SqliteAccountStore::open(&database).unwrap(), SqliteAccountStore::open(&database).unwrap());
+        assert!(first.load(1).unwrap().is_none());
+        assert!(second.load(1).unwrap().is_none());
+        first.save(&account(1, 10)).unwrap();
+
+        // new account stored by other instance in the meantime
+        assert!(matches!(second.save(&account(1, 30)), Err(ProcessError::VersionConflict)));
+        assert_eq!(second.load(1).unwrap(), Some(account(1, 10)));
+
+        // the second instance has current version now, the first one is stale
+        second.save(&account(1, 20)).unwrap();
+        assert!(matches!(first.save(&account(1, 40)), Err(ProcessError::VersionConflict)));
+        assert_eq!(first.load(1).unwrap(), Some(account(1, 20)));
+        first.save(&account(1, 50)).unwrap();
+        assert_eq!(second.load(1).unwrap(), Some(account(1, 50)));
+    }
+
+    #[test]
+    fn stale_delete_then_version_conflict() {
+        let database = database("stale-delete");
+        let (mut first, mut second) = (SqliteAccountStore::open(&database).unwrap(), SqliteAccountStore::open(&database).unwrap());
+        first.save(&account(1, 10)).unwrap();
+        assert!(second.load(1).unwrap().is_some());
+        first.save(&account(1, 20)).unwrap();
+
+        // account changed since it was loaded, or stored by other instance and never loaded
+        assert!(matches!(second.delete(1), Err(ProcessError::VersionConflict)));
+        assert!(matches!(SqliteAccountStore::open(&database).unwrap().delete(1), Err(ProcessError::VersionConflict)));
+        assert_eq!(second.load(1).unwrap(), Some(account(1, 20)));
+
+        second.delete(1).unwrap();
+        assert!(first.load(1).unwrap().is_none());
+        // account not stored by anyone
+        first.delete(2).unwrap();
+    }
+
+    #[test]
+    fn merge_of_account_changed_by_other_instance_then_nothing_stored() {
+        let database = database("merge");
+        let mut processor = BasicProcessor::new().with_account_store(SqliteAccountStore::open(&database).unwrap());
+        processor.process(Transaction::new(TransactionType::Deposit, 1, 1, Some(Decimal::from(10)))).unwrap();
+        processor.process(Transaction::new(TransactionType::Deposit, 2, 2, Some(Decimal::from(1)))).unwrap();
+
+        // other instance changes source account after processor cached it
+        let mut other = SqliteAccountStore::open(&database).unwrap();
+        assert_eq!(other.load(2).unwrap(), Some(account(2, 1)));
+        other.save(&account(2, 5)).unwrap();
+
+        assert!(matches!(processor.merge_accounts(2, 1), Err(ProcessError::VersionConflict)));
+        assert!(processor.merges().is_empty());
+        // funds of neither account are lost or stored twice
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 10)));
+        assert_eq!(other.load(2).unwrap(), Some(account(2, 5)));
+
+        // target account is loaded again, source account is merged with its current state
+        processor.merge_accounts(2, 1).unwrap();
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 15)));
+        assert!(other.load(2).unwrap().is_none());
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use clap::ArgMatches;
+use serde::Serialize;
+use core::metrics::{Labels, Recorder, ACCOUNTS, OPEN_DISPUTES, TRANSACTIONS_APPLIED, TRANSACTIONS_REJECTED};
+use crate::{CliError, ErrorType};
+
+// version of dumped document, incremented when fields are renamed or removed
+const SCHEMA_VERSION: u32 = 1;
+
+/// Messages handled by consumer since start
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct ConsumeReport {
+    pub read: u64,
+    pub processed: u64,
+    pub rejected: u64,
+    pub retried: u64,
+}
+
+/// Processor metrics kept for stats dump, shared by processor (`with_recorder`) and `StatsDump`
+#[derive(Debug, Default, Clone)]
+pub struct StatsRecorder(Arc<Mutex<ProcessorStats>>);
+
+#[derive(Debug, Default, Clone)]
+struct ProcessorStats {
+    // applied transactions by type
+    applied: BTreeMap<&'static str, u64>,
+
+    // rejected transactions by error code
+    rejected: BTreeMap<&'static str, u64>,
+    accounts: u64,
+    open_disputes: u64,
+}
+
+impl Recorder for StatsRecorder {
+    fn counter(&self, name: &'static str, value: u64, labels: Labels) {
+        let label = |key: &str| labels.iter().find(|(name, _)| *name == key).map(|(_, value)| *value);
+        let Ok(mut stats) = self.0.lock() else {
+            return;
+        };
+        match (name, label("type"), label("code")) {
+            (TRANSACTIONS_APPLIED, Some(r#type), _) => *stats.applied.entry(r#type).or_default() += value,
+            (TRANSACTIONS_REJECTED, _, Some(code)) => *stats.rejected.entry(code).or_default() += value,
+            _ => {}
+        }
+    }
+
+    fn gauge(&self, name: &'static str, value: f64, _labels: Labels) {
+        let Ok(mut stats) = self.0.lock() else {
+            return;
+        };
+        match name {
+            ACCOUNTS => stats.accounts = value as u64,
+            OPEN_DISPUTES => stats.open_disputes = value as u64,
+            _ => {}
+        }
+    }
+}
+
+/// Document written by `StatsDump`, flat counters and maps keyed by transaction type and error code,
+/// so JSON datasources of dashboards (e.g. Grafana Infinity) can select fields by path
+#[derive(Debug, Serialize)]
+struct StatsDocument<'a> {
+    schema: u32,
+    mode: &'a str,
+
+    // unix time of dump in seconds
+    timestamp: u64,
+    uptime_seconds: f64,
+    messages: ConsumeReport,
+    transactions_applied: BTreeMap<&'static str, u64>,
+    transactions_rejected: BTreeMap<&'static str, u64>,
+    accounts: u64,
+    open_disputes: u64,
+}
+
+/// Periodic JSON dump of run stats (`--stats-json`, `--stats-url` with `stats-push` feature) every
+/// `--stats-interval` seconds and on shutdown, for teams without metrics stack. File is replaced
+/// atomically, so scraper never reads half-written document. Without target nothing is dumped.
+pub struct StatsDump {
+    mode: &'static str,
+    recorder: StatsRecorder,
+    path: Option<PathBuf>,
+    #[cfg(feature = "stats-push")]
+    url: Option<String>,
+    interval: Duration,
+    started: Instant,
+    last: Instant,
+}
+
+impl StatsDump {
+    /// `mode` is name of subcommand reported in document
+    pub fn from_matches(matches: &ArgMatches, mode: &'static str) -> Result<Self, CliError> {
+        let interval = matches.value_of("stats_interval").unwrap_or("10").parse::<u64>()
+            .ok()
+            .filter(|it| *it > 0)
+            .ok_or_else(|| CliError::new(ErrorType::CliParseError, "Arg stats-interval must be a positive number of seconds"))?;
+
+        Ok(StatsDump {
+            mode,
+            recorder: StatsRecorder::default(),
+            path: matches.value_of("stats_json").map(PathBuf::from),
+            #[cfg(feature = "stats-push")]
+            url: matches.value_of("stats_url").map(str::to_string),
+            interval: Duration::from_secs(interval),
+            started: Instant::now(),
+            last: Instant::now(),
+        })
+    }
+
+    /// Recorder for processor metrics (accounts, open disputes, transactions by type and error code)
+    pub fn recorder(&self) -> StatsRecorder {
+        self.recorder.clone()
+    }
+
+    fn enabled(&self) -> bool {
+        #[cfg(feature = "stats-push")]
+        if self.url.is_some() {
+            return true;
+        }
+        self.path.is_some()
+    }
+
+    /// Dump stats when interval passed since the last dump, failed dump is reported and tried again with next interval
+    pub fn tick(&mut self, report: &ConsumeReport) {
+        if self.enabled() && self.last.elapsed() >= self.interval {
+            if let Err(e) = self.dump(report) {
+                eprintln!("Can't dump stats: {}", e);
+            }
+            self.last = Instant::now();
+        }
+    }
+
+    /// Final dump on shutdown
+    pub fn finish(&self, report: &ConsumeReport) -> Result<(), CliError> {
+        match self.enabled() {
+            true => self.dump(report),
+            false => Ok(()),
+        }
+    }
+
+    fn dump(&self, report: &ConsumeReport) -> Result<(), CliError> {
+        let stats = self.recorder.0.lock()
+            .map(|it| it.clone())
+            .map_err(|_| CliError::new(ErrorType::Process, "Stats recorder lock poisoned"))?;
+        let document = StatsDocument {
+            schema: SCHEMA_VERSION,
+            mode: self.mode,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|it| it.as_secs()).unwrap_or_default(),
+            uptime_seconds: self.started.elapsed().as_secs_f64(),
+            messages: *report,
+            transactions_applied: stats.applied,
+            transactions_rejected: stats.rejected,
+            accounts: stats.accounts,
+            open_disputes: stats.open_disputes,
+        };
+        let payload = serde_json::to_vec(&document).map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+
+        if let Some(path) = &self.path {
+            // rename is atomic, readers see previous or new document
+            let mut tmp = path.clone().into_os_string();
+            tmp.push(".tmp");
+            fs::write(&tmp, &payload)?;
+            fs::rename(&tmp, path)?;
+        }
+
+        #[cfg(feature = "stats-push")]
+        if let Some(url) = &self.url {
+            ureq::post(url)
+                .set("Content-Type", "application/json")
+                .send_bytes(&payload)
+                .map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+        }
+        Ok(())
+    }
+}
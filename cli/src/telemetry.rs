@@ -0,0 +1,156 @@
+use std::fmt::Display;
+use clap::ArgMatches;
+use core::error::ProcessError;
+use core::transaction::Transaction;
+use crate::CliError;
+#[cfg(feature = "otel")]
+use std::collections::HashMap;
+#[cfg(feature = "otel")]
+use opentelemetry::propagation::TextMapPropagator;
+#[cfg(feature = "otel")]
+use opentelemetry::trace::{Span, Status, TraceContextExt, Tracer, TracerProvider};
+#[cfg(feature = "otel")]
+use opentelemetry::{Context, KeyValue};
+#[cfg(feature = "otel")]
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::Resource;
+#[cfg(feature = "otel")]
+use crate::ErrorType;
+
+// service name of exported spans
+#[cfg(feature = "otel")]
+const SERVICE_NAME: &str = "csv-cli-analyzer";
+
+/// Trace export of consumed transactions. With `otel` feature and `--otlp-endpoint` every transaction
+/// gets `transaction` span (child of W3C `traceparent` message header when present) with `ingest`,
+/// `validate` and `apply` stages, exported with OTLP/HTTP. Otherwise nothing is recorded.
+#[derive(Default)]
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    exporter: Option<(SdkTracerProvider, SdkTracer)>,
+}
+
+impl Telemetry {
+    #[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, CliError> {
+        #[cfg(feature = "otel")]
+        if let Some(endpoint) = matches.value_of("otlp_endpoint") {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint)
+                .build()
+                .map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+            let provider = SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+                .build();
+            let tracer = provider.tracer(SERVICE_NAME);
+            return Ok(Telemetry { exporter: Some((provider, tracer)) });
+        }
+
+        Ok(Telemetry::default())
+    }
+
+    /// Start trace of consumed message, `headers` are message headers (name, value)
+    #[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+    pub fn start<'a>(&self, headers: impl Iterator<Item=(&'a str, String)>) -> TransactionTrace<'_> {
+        #[cfg(feature = "otel")]
+        if let Some((_, tracer)) = &self.exporter {
+            let headers: HashMap<String, String> = headers.map(|(name, value)| (name.to_lowercase(), value)).collect();
+            let parent = TraceContextPropagator::new().extract(&headers);
+            let span = tracer.start_with_context("transaction", &parent);
+            return TransactionTrace { tracer: Some(tracer), context: parent.with_span(span) };
+        }
+
+        TransactionTrace::default()
+    }
+
+    /// Export remaining spans, must be called outside of async runtime (exporter uses blocking client)
+    pub fn shutdown(self) -> Result<(), CliError> {
+        #[cfg(feature = "otel")]
+        if let Some((provider, _)) = self.exporter {
+            provider.shutdown().map_err(|e| CliError::from_source(ErrorType::IO, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Spans of single transaction, stage spans are children of `transaction` span
+#[derive(Default)]
+pub struct TransactionTrace<'a> {
+    #[cfg(feature = "otel")]
+    tracer: Option<&'a SdkTracer>,
+    #[cfg(feature = "otel")]
+    context: Context,
+
+    #[cfg(not(feature = "otel"))]
+    _tracer: std::marker::PhantomData<&'a ()>,
+}
+
+impl TransactionTrace<'_> {
+    /// Decode message payload: JSON document (`ingest` stage) into transaction (`validate` stage)
+    pub fn decode(&self, payload: &[u8]) -> Result<Transaction, serde_json::Error> {
+        let document = self.stage("ingest", || serde_json::from_slice::<serde_json::Value>(payload))?;
+        let transaction = self.stage("validate", || serde_json::from_value::<Transaction>(document))?;
+
+        #[cfg(feature = "otel")]
+        if self.tracer.is_some() {
+            self.context.span().set_attributes([
+                KeyValue::new("transaction.type", transaction.r#type().name()),
+                KeyValue::new("transaction.client", i64::from(transaction.client())),
+                KeyValue::new("transaction.tx", i64::from(transaction.tx_id())),
+            ]);
+        }
+
+        Ok(transaction)
+    }
+
+    /// Run `apply` stage, error code of rejection is recorded in `transaction` span
+    pub fn apply(&self, apply: impl FnOnce() -> Result<(), ProcessError>) -> Result<(), ProcessError> {
+        let result = self.stage("apply", apply);
+
+        #[cfg(feature = "otel")]
+        if let (Some(_), Err(e)) = (self.tracer, &result) {
+            self.context.span().set_attribute(KeyValue::new("error.code", e.code()));
+        }
+
+        result
+    }
+
+    // run stage in its own span, error sets span error status
+    #[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+    fn stage<T, E: Display>(&self, name: &'static str, stage: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        #[cfg(feature = "otel")]
+        let mut span = self.tracer.map(|it| it.start_with_context(name, &self.context));
+
+        let result = stage();
+
+        #[cfg(feature = "otel")]
+        if let Some(span) = span.as_mut() {
+            if let Err(e) = &result {
+                span.set_status(Status::error(e.to_string()));
+            }
+            span.end();
+        }
+        result
+    }
+
+    /// End `transaction` span, `error` is reason of rejected or malformed transaction
+    #[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+    pub fn end(self, error: Option<&dyn Display>) {
+        #[cfg(feature = "otel")]
+        if self.tracer.is_some() {
+            let span = self.context.span();
+            if let Some(e) = error {
+                span.set_status(Status::error(e.to_string()));
+            }
+            span.end();
+        }
+    }
+}
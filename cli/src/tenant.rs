@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use core::MultiTenantProcessor;
+use core::pipeline::CancellationToken;
+use core::TransactionProcessor;
+use crate::{source, CliError, ErrorType};
+use crate::output::OutputFormat;
+use crate::reader::read_from_csv;
+use crate::source::{ReaderOptions, SourceError};
+use crate::write::{write_json, write_records};
+
+/// Multi-tenant processing from command line
+pub struct TenantOptions<'a> {
+    // tenant of rows without tenant value (per-file setting)
+    pub default_tenant: Option<&'a str>,
+
+    // max transactions of every tenant
+    pub quota: Option<u64>,
+
+    // json file with stats per tenant
+    pub stats: Option<&'a str>,
+
+    pub out_dir: &'a str,
+}
+
+/// Process multi-tenant input (optional `tenant` column) with separate accounts per tenant and write
+/// accounts of each tenant to `<out_dir>/<tenant>.<ext>`. Rows without tenant belong to `default_tenant`
+/// (per-file setting), input with such rows is rejected when it is not set.
+/// Transactions over quota of tenant are rejected, so one noisy tenant can't starve others.
+pub fn execute(file_path: &str, reader_options: &ReaderOptions, options: &TenantOptions, format: OutputFormat, overwrite: bool, cancel: &CancellationToken) -> Result<(), CliError> {
+    let default_tenant = options.default_tenant;
+    if let Some(tenant) = default_tenant {
+        check_tenant(tenant)?;
+    }
+
+    let reader = read_from_csv(source::open_stream(file_path)?, reader_options)?;
+    let mut processor: MultiTenantProcessor = MultiTenantProcessor::new(default_tenant.unwrap_or_default().to_string());
+    if let Some(quota) = options.quota {
+        processor = processor.with_quota(quota);
+    }
+
+    for (sequence, record) in (1..).zip(reader.with_tenant()) {
+
+        //check if record contains error
+        //if yes - abort
+        let (tenant, transaction) = record?;
+        let transaction = transaction.with_sequence(sequence);
+
+        // same as in pipeline, process errors are ignored
+        let _ = match (tenant, default_tenant) {
+            (Some(tenant), _) => {
+                check_tenant(&tenant)?;
+                processor.process_in(&tenant, transaction)
+            }
+            (None, Some(_)) => processor.process(transaction),
+            (None, None) => return Err(SourceError::Parse { line: sequence + 1, message: "missing tenant, use --tenant for rows without tenant".to_string() }.into()),
+        };
+
+        if cancel.is_cancelled() {
+            eprintln!("Interrupted, partial output written");
+            break;
+        }
+    }
+
+    let stats: BTreeMap<_, _> = processor.all_stats().map(|(tenant, stats)| (tenant.to_string(), stats.clone())).collect();
+    for (tenant, stats) in stats.iter().filter(|(_, it)| it.throttled() > 0) {
+        eprintln!("Tenant {} exceeded quota, {} transactions rejected", tenant, stats.throttled());
+    }
+    if let Some(file_path) = options.stats {
+        write_json(file_path, overwrite, &stats)?;
+    }
+
+    for (tenant, accounts) in processor.finalize() {
+        let path = Path::new(options.out_dir).join(format!("{}.{}", tenant, format.extension()));
+        write_records(path, overwrite, format, accounts)?;
+    }
+
+    Ok(())
+}
+
+// tenant is used as file name, only letters, digits, '-' and '_' are allowed
+fn check_tenant(tenant: &str) -> Result<(), CliError> {
+    match tenant.bytes().all(|it| it.is_ascii_alphanumeric() || it == b'-' || it == b'_') {
+        true => Ok(()),
+        false => Err(CliError::new(ErrorType::CsvRead, format!("Invalid tenant '{}', expected letters, digits, '-' or '_'", tenant))),
+    }
+}
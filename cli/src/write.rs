@@ -1,27 +1,355 @@
-use crate::CliError;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use core::account::view::AccountView;
+use core::pipeline::Sink;
+use core::shard::ShardRouter;
+use core::snapshot::SNAPSHOT_VERSION;
+use crate::{CliError, ErrorType};
+use crate::output::{write_output, OutputFormat};
 
-pub fn write<T>(iter: impl Iterator<Item=T>) -> Result<(), CliError>
-    where T: serde::Serialize
+/// First line of csv snapshot, followed by snapshot version
+pub const SNAPSHOT_VERSION_PREFIX: &str = "#snapshot-version=";
+
+/// Output file written to temporary `<path>.tmp` and renamed to `path` by `persist`,
+/// so readers never see partially written file, even after crash.
+/// Temporary file is removed when dropped without `persist` (e.g. on error).
+/// Object store url (`object-store` feature) is written to local temporary file and uploaded by `persist`.
+pub struct TempFile {
+    file: File,
+    path: PathBuf,
+    tmp_path: PathBuf,
+    persisted: bool,
+
+    // content of sensitive file is encrypted while written
+    #[cfg(feature = "encryption")]
+    encryptor: Option<crate::encryption::Encryptor>,
+}
+
+impl TempFile {
+    /// Create temporary file for `path`. Existing `path` is overwritten only with `overwrite`.
+    pub fn create<P: AsRef<Path>>(path: P, overwrite: bool) -> Result<Self, CliError> {
+        let path = path.as_ref();
+        check_overwrite(path, overwrite)?;
+
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        #[cfg(feature = "object-store")]
+        if crate::remote::is_remote(path) {
+            tmp_path = std::env::temp_dir().join(format!("csv-cli-analyzer-{}-{}", std::process::id(), remote_tmp_id())).into();
+        }
+
+        Ok(TempFile {
+            file: File::create(&tmp_path)?,
+            path: path.to_owned(),
+            tmp_path: tmp_path.into(),
+            persisted: false,
+            #[cfg(feature = "encryption")]
+            encryptor: None,
+        })
+    }
+
+    /// Same as `create`, but for file with customer data (snapshot, DLQ, audit log), which is encrypted
+    /// when `--encryption-key` is set (`encryption` feature)
+    pub fn create_sensitive<P: AsRef<Path>>(path: P, overwrite: bool) -> Result<Self, CliError> {
+        #[allow(unused_mut)]
+        let mut file = Self::create(path, overwrite)?;
+
+        #[cfg(feature = "encryption")]
+        if let Some(key) = crate::encryption::key() {
+            file.encryptor = Some(crate::encryption::Encryptor::new(key, &mut file.file)?);
+        }
+
+        Ok(file)
+    }
+
+    /// Sync data to disk and rename temporary file to target path
+    pub fn persist(mut self) -> Result<(), CliError> {
+        #[cfg(feature = "encryption")]
+        if let Some(encryptor) = self.encryptor.as_mut() {
+            encryptor.finish(&mut self.file)?;
+        }
+        self.file.sync_all()?;
+
+        // local temporary file is removed on drop
+        #[cfg(feature = "object-store")]
+        if crate::remote::is_remote(&self.path) {
+            return Ok(crate::remote::upload(&self.tmp_path, &self.path)?);
+        }
+
+        std::fs::rename(&self.tmp_path, &self.path)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        #[cfg(feature = "encryption")]
+        if let Some(encryptor) = self.encryptor.as_mut() {
+            encryptor.write(&mut self.file, buf)?;
+            return Ok(buf.len());
+        }
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+// unique name of local temporary file for object store upload
+#[cfg(feature = "object-store")]
+fn remote_tmp_id() -> u64 {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Existing output is not overwritten without `--force`
+pub fn check_overwrite<P: AsRef<Path>>(path: P, overwrite: bool) -> Result<(), CliError> {
+    let path = path.as_ref();
+    if overwrite {
+        return Ok(());
+    }
+
+    #[cfg(feature = "object-store")]
+    let exists = match crate::remote::is_remote(path) {
+        true => crate::remote::exists(path)?,
+        false => path.exists(),
+    };
+    #[cfg(not(feature = "object-store"))]
+    let exists = path.exists();
+
+    match exists {
+        true => Err(CliError::new(ErrorType::IO, format!("Output file '{}' already exists, use --force to overwrite", path.display()))),
+        false => Ok(()),
+    }
+}
+
+/// Write file atomically (see `TempFile`)
+pub fn write_atomic<P, F>(path: P, overwrite: bool, write: F) -> Result<(), CliError>
+    where P: AsRef<Path>,
+          F: FnOnce(&mut TempFile) -> Result<(), CliError>
+{
+    let mut file = TempFile::create(path, overwrite)?;
+    write(&mut file)?;
+    file.persist()
+}
+
+/// Write file with customer data atomically, encrypted with `--encryption-key` (see `TempFile::create_sensitive`)
+pub fn write_sensitive<P, F>(path: P, overwrite: bool, write: F) -> Result<(), CliError>
+    where P: AsRef<Path>,
+          F: FnOnce(&mut TempFile) -> Result<(), CliError>
 {
-    //we can easy change writer to other types
-    write_csv(std::io::stdout(), iter)?;
-    Ok(())
+    let mut file = TempFile::create_sensitive(path, overwrite)?;
+    write(&mut file)?;
+    file.persist()
 }
 
-pub fn write_csv<W, T>(writer: W, iter: impl Iterator<Item=T>) -> Result<(), csv::Error>
-    where
-        W: std::io::Write,
-        T: serde::Serialize,
+/// Write pretty printed json file atomically (see `TempFile`)
+pub fn write_json<P, T>(path: P, overwrite: bool, value: &T) -> Result<(), CliError>
+    where P: AsRef<Path>,
+          T: serde::Serialize
 {
-    let mut writer = csv::WriterBuilder::new()
-        .has_headers(true)
-        .from_writer(writer);
+    write_atomic(path, overwrite, |file| {
+        serde_json::to_writer_pretty(file, value).map_err(|e| CliError::from_source(ErrorType::IO, e))
+    })
+}
+
+/// Write accounts snapshot to file atomically, encrypted with `--encryption-key` (see `TempFile::create_sensitive`).
+/// Csv snapshot starts with snapshot version line (see `dlq::read_snapshot`). Existing file is overwritten.
+pub fn write_snapshot<P, T>(path: P, format: OutputFormat, iter: impl Iterator<Item=T>) -> Result<(), CliError>
+    where P: AsRef<Path>,
+          T: serde::Serialize
+{
+    write_sensitive(path, true, |file| {
+        if format == OutputFormat::Csv {
+            writeln!(file, "{}{}", SNAPSHOT_VERSION_PREFIX, SNAPSHOT_VERSION)?;
+        }
+        let mut writer = format.writer(file);
+        write_output(writer.as_mut(), iter)
+    })
+}
 
-    for record in iter {
-        writer.serialize(record)?;
+/// Write records to file atomically (see `TempFile`), existing file is overwritten only with `overwrite`
+pub fn write_records<P, T>(path: P, overwrite: bool, format: OutputFormat, iter: impl Iterator<Item=T>) -> Result<(), CliError>
+    where P: AsRef<Path>,
+          T: serde::Serialize
+{
+    write_atomic(path, overwrite, |file| {
+        let mut writer = format.writer(file);
+        write_output(writer.as_mut(), iter)
+    })
+}
+
+/// Split accounts into partitions and write each partition to `<out_dir>/<name>.<ext>` from its own thread.
+pub struct ShardSink {
+    format: OutputFormat,
+    out_dir: PathBuf,
+
+    // file name (without extension) of every partition
+    names: Vec<String>,
+    // partition of account, called with number of partitions
+    partitioner: fn(&AccountView, usize) -> usize,
+    overwrite: bool,
+    partitions: Vec<Vec<AccountView>>,
+
+    // records written to each partition
+    rows: Vec<u64>,
+}
+
+impl ShardSink {
+    /// `shards` partitions by client id (`ShardRouter`), written to `accounts_<shard>.<ext>`
+    pub fn new<P>(format: OutputFormat, shards: usize, out_dir: P) -> Self
+        where P: Into<PathBuf>
+    {
+        let names = (0..shards).map(|shard| format!("accounts_{}", shard)).collect();
+        Self::with_partitioner(format, out_dir, names, |account, shards| {
+            ShardRouter::new(shards).map_or(0, |it| it.shard(*account.client()))
+        })
+    }
+
+    /// Unlocked and locked accounts, written to `accounts_unlocked.<ext>` and `accounts_locked.<ext>`
+    pub fn split_locked<P>(format: OutputFormat, out_dir: P) -> Self
+        where P: Into<PathBuf>
+    {
+        let names = vec!["accounts_unlocked".to_owned(), "accounts_locked".to_owned()];
+        Self::with_partitioner(format, out_dir, names, |account, _| account.locked() as usize)
+    }
+
+    fn with_partitioner<P>(format: OutputFormat, out_dir: P, names: Vec<String>, partitioner: fn(&AccountView, usize) -> usize) -> Self
+        where P: Into<PathBuf>
+    {
+        ShardSink {
+            format,
+            out_dir: out_dir.into(),
+            partitions: names.iter().map(|_| Vec::new()).collect(),
+            rows: vec![0; names.len()],
+            names,
+            partitioner,
+            overwrite: false,
+        }
+    }
+
+    /// Overwrite existing partition files
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Number of records in each partition, in partition order
+    pub fn rows(&self) -> &[u64] {
+        &self.rows
+    }
+
+    /// Paths of partition files, in partition order
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.names.iter()
+            .map(|name| self.out_dir.join(format!("{}.{}", name, self.format.extension())))
+            .collect()
+    }
+}
+
+impl Sink<AccountView> for ShardSink {
+    type Error = CliError;
+
+    fn write(&mut self, account: AccountView) -> Result<(), Self::Error> {
+        let partition = (self.partitioner)(&account, self.partitions.len());
+        self.partitions[partition].push(account);
+        self.rows[partition] += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        let partitions = std::mem::take(&mut self.partitions);
+        let format = self.format;
+        let overwrite = self.overwrite;
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = partitions.into_iter().zip(self.paths()).map(|(partition, path)| {
+                scope.spawn(move || write_records(path, overwrite, format, partition.into_iter()))
+            }).collect();
+
+            for handle in handles {
+                handle.join().map_err(|_| CliError::new(ErrorType::IO, "Shard writer thread panicked"))??;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use crate::write::write_atomic;
+    use crate::{CliError, ErrorType};
+
+    // empty directory for files of one test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("csv-cli-analyzer-write-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
     }
 
-    writer.flush()?;
+    fn files(dir: &PathBuf) -> Vec<String> {
+        let mut files: Vec<_> = fs::read_dir(dir).unwrap().map(|it| it.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn write_atomic_then_no_temporary_file_left() {
+        let dir = temp_dir("atomic");
+        let path = dir.join("out.csv");
+        write_atomic(&path, false, |file| Ok(file.write_all(b"client\n1\n")?)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "client\n1\n");
+        assert_eq!(files(&dir), ["out.csv"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn existing_file_then_overwritten_only_with_force() {
+        let dir = temp_dir("overwrite");
+        let path = dir.join("out.csv");
+        fs::write(&path, "old").unwrap();
+
+        let error = write_atomic(&path, false, |file| Ok(file.write_all(b"new")?)).unwrap_err();
+        assert!(error.to_string().contains("already exists, use --force to overwrite"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
 
-    Ok(())
-}
\ No newline at end of file
+        write_atomic(&path, true, |file| Ok(file.write_all(b"new")?)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(files(&dir), ["out.csv"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_write_then_target_unchanged_and_temporary_file_removed() {
+        let dir = temp_dir("failed");
+        let path = dir.join("out.csv");
+        fs::write(&path, "old").unwrap();
+
+        let result = write_atomic(&path, true, |file| {
+            file.write_all(b"partial")?;
+            Err(CliError::new(ErrorType::IO, "failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(files(&dir), ["out.csv"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
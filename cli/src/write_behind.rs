@@ -0,0 +1,408 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use core::account::view::AccountView;
+use core::client::Client;
+use core::error::ProcessError;
+use core::store::AccountStore;
+use crate::config::LogLevel;
+use crate::{CliError, ErrorType};
+
+/// Write-behind buffer in front of account store (`--store-flush-every`, `--store-flush-ms`). Saved accounts are
+/// kept as dirty (the latest state per client) and written to store in one batch (`AccountStore::save_all`) every
+/// `max_pending` saves (about one per transaction) or when `max_delay` passed since the last flush, so the store
+/// gets one round trip per batch instead of one per transaction. Every save is appended to write-ahead log (WAL)
+/// and synced to disk before it is buffered. The WAL is truncated after flush and replayed into store when buffer
+/// is opened again, so accounts buffered by crashed run are not lost. WAL line keeps stored state the account was
+/// changed from, account changed in store by other instance in the meantime is not replayed. Save succeeds once
+/// the account is in WAL, failed flush is reported and retried with the next flush. Buffered account changed by
+/// other instance (`VersionConflict` of versioned store) is dropped and the next save of its client fails, so
+/// processor loads it again. Write-behind buffer is meant for a single instance writing the store, instances
+/// sharing store should write through.
+pub struct WriteBehind<S: AccountStore> {
+    store: S,
+    dirty: BTreeMap<Client, AccountView>,
+
+    // stored state of accounts loaded or flushed by this buffer, None for account not stored
+    stored: HashMap<Client, Option<AccountView>>,
+
+    // clients of buffered accounts dropped on conflict, their next save fails
+    conflicts: BTreeSet<Client>,
+
+    // saves since the last flush
+    pending: usize,
+    max_pending: usize,
+    max_delay: Option<Duration>,
+    last_flush: Instant,
+
+    // json lines of saved accounts not flushed yet
+    wal: File,
+    log_level: LogLevel,
+}
+
+// line of WAL, saved account with its stored state
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    account: T,
+    stored: Option<T>,
+}
+
+impl<S: AccountStore> WriteBehind<S> {
+    /// Buffer in front of `store`, accounts left in WAL by previous run are written to store first
+    pub fn open(mut store: S, wal_path: &Path, max_pending: usize, max_delay: Option<Duration>, log_level: LogLevel) -> Result<Self, CliError> {
+        let recovered = read_wal(wal_path)?;
+        if !recovered.is_empty() {
+            // only accounts still stored in the state they were changed from, current versions are loaded with them
+            let mut accounts = Vec::new();
+            let mut changed = Vec::new();
+            for entry in recovered {
+                let client = *entry.account.client();
+                let current = store.load(client).map_err(|e| CliError::from_source(ErrorType::Process, e))?;
+                match current {
+                    // flushed before crash, WAL was not truncated
+                    Some(current) if current == entry.account => {}
+                    current if current == entry.stored => accounts.push(entry.account),
+                    _ => changed.push(client),
+                }
+            }
+            store.save_all(&accounts).map_err(|e| CliError::from_source(ErrorType::Process, e))?;
+            if LogLevel::Info <= log_level {
+                eprintln!("Recovered {} accounts from write-ahead log {}", accounts.len(), wal_path.display());
+            }
+            if LogLevel::Warn <= log_level && !changed.is_empty() {
+                eprintln!("Accounts of clients {:?} in write-ahead log not recovered, they were changed by other instance", changed);
+            }
+        }
+
+        let wal = OpenOptions::new().create(true).append(true).open(wal_path)?;
+        wal.set_len(0)?;
+        Ok(WriteBehind {
+            store,
+            dirty: BTreeMap::new(),
+            stored: HashMap::new(),
+            conflicts: BTreeSet::new(),
+            pending: 0,
+            max_pending,
+            max_delay,
+            last_flush: Instant::now(),
+            wal,
+            log_level,
+        })
+    }
+
+    // message of `level` is written
+    fn logs(&self, level: LogLevel) -> bool {
+        level <= self.log_level
+    }
+
+    fn due(&self) -> bool {
+        self.pending >= self.max_pending || self.max_delay.is_some_and(|it| self.last_flush.elapsed() >= it)
+    }
+
+    // accounts changed by other instance are dropped from buffer and remembered in `conflicts`
+    fn flush(&mut self) -> Result<(), ProcessError> {
+        if !self.dirty.is_empty() {
+            let accounts: Vec<_> = self.dirty.values().cloned().collect();
+            let mut changed = Vec::new();
+            match self.store.save_all(&accounts) {
+                Ok(()) => {}
+                // batch is rolled back, accounts are saved one by one to find the changed ones
+                Err(ProcessError::VersionConflict) => {
+                    for account in &accounts {
+                        match self.store.save(account) {
+                            Ok(()) => {}
+                            Err(ProcessError::VersionConflict) => changed.push(*account.client()),
+                            Err(e) => return Err(e),
+                        }
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+
+            for account in accounts {
+                let client = *account.client();
+                match changed.contains(&client) {
+                    true => self.stored.remove(&client),
+                    false => self.stored.insert(client, Some(account)),
+                };
+            }
+            if self.logs(LogLevel::Warn) && !changed.is_empty() {
+                eprintln!("Buffered accounts of clients {:?} not flushed, they were changed by other instance", changed);
+            }
+            self.conflicts.extend(changed);
+            self.dirty.clear();
+        }
+        self.wal.set_len(0).map_err(ProcessError::StorageError)?;
+        self.pending = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+impl<S: AccountStore> AccountStore for WriteBehind<S> {
+    fn load(&mut self, client: Client) -> Result<Option<AccountView>, ProcessError> {
+        if let Some(account) = self.dirty.get(&client) {
+            return Ok(Some(account.clone()));
+        }
+        let account = self.store.load(client)?;
+        self.stored.insert(client, account.clone());
+        self.conflicts.remove(&client);
+        Ok(account)
+    }
+
+    fn load_recent(&mut self, limit: usize) -> Result<Vec<AccountView>, ProcessError> {
+        self.flush()?;
+        let accounts = self.store.load_recent(limit)?;
+        for account in &accounts {
+            self.stored.insert(*account.client(), Some(account.clone()));
+            self.conflicts.remove(account.client());
+        }
+        Ok(accounts)
+    }
+
+    fn save(&mut self, account: &AccountView) -> Result<(), ProcessError> {
+        let client = *account.client();
+        if self.conflicts.remove(&client) {
+            return Err(ProcessError::VersionConflict);
+        }
+
+        let entry = Entry { account, stored: self.stored.get(&client).and_then(Option::as_ref) };
+        let mut line = serde_json::to_vec(&entry).map_err(|e| ProcessError::StorageError(e.into()))?;
+        line.push(b'\n');
+        self.wal.write_all(&line).map_err(ProcessError::StorageError)?;
+        self.wal.sync_data().map_err(ProcessError::StorageError)?;
+
+        self.dirty.insert(client, account.clone());
+        self.pending += 1;
+        if self.due() {
+            // account is safe in WAL, buffer is kept and flushed again later
+            if let Err(e) = self.flush() {
+                if self.logs(LogLevel::Error) {
+                    eprintln!("Can't flush accounts to store, retrying with the next flush: {}", e);
+                }
+                self.pending = 0;
+                self.last_flush = Instant::now();
+            }
+            if self.conflicts.remove(&client) {
+                return Err(ProcessError::VersionConflict);
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, client: Client) -> Result<(), ProcessError> {
+        // buffered accounts are flushed first, so WAL replay can't bring deleted account back
+        self.flush()?;
+        self.store.delete(client)?;
+        self.stored.insert(client, None);
+        Ok(())
+    }
+
+    // buffered accounts are flushed first, merge is stored at once by store
+    fn save_merged(&mut self, to: &AccountView, before: Option<&AccountView>, from: Client) -> Result<(), ProcessError> {
+        self.flush()?;
+        if [from, *to.client()].iter().any(|it| self.conflicts.contains(it)) {
+            return Err(ProcessError::VersionConflict);
+        }
+        self.store.save_merged(to, before, from)?;
+        self.stored.insert(*to.client(), Some(to.clone()));
+        self.stored.insert(from, None);
+        Ok(())
+    }
+}
+
+impl<S: AccountStore> Drop for WriteBehind<S> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            if self.logs(LogLevel::Error) {
+                eprintln!("Can't flush accounts to store, they are recovered from write-ahead log by next run: {}", e);
+            }
+        }
+    }
+}
+
+// the latest entry of every client in WAL, incomplete last line (crash while writing) is skipped
+fn read_wal(wal_path: &Path) -> Result<Vec<Entry<AccountView>>, CliError> {
+    let file = match File::open(wal_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    // stored state is the same in every entry of client, WAL is truncated when it changes
+    let mut entries = BTreeMap::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(entry) = serde_json::from_str::<Entry<AccountView>>(&line?) {
+            entries.insert(*entry.account.client(), entry);
+        }
+    }
+    Ok(entries.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use rust_decimal::Decimal;
+    use core::account::view::AccountView;
+    use core::error::ProcessError;
+    use core::store::AccountStore;
+    use crate::config::LogLevel;
+    use crate::sqlite::SqliteAccountStore;
+    use crate::write_behind::WriteBehind;
+
+    // in-memory database shared by stores opened with the same name and path of WAL
+    fn store(name: &str) -> (String, PathBuf) {
+        let wal_path = std::env::temp_dir().join(format!("csv-cli-analyzer-write-behind-{}-{}.wal", name, std::process::id()));
+        let _ = fs::remove_file(&wal_path);
+        (format!("file:write-behind-{}-{}?mode=memory&cache=shared", name, std::process::id()), wal_path)
+    }
+
+    fn account(client: u16, available: u64) -> AccountView {
+        AccountView::new(client, Decimal::from(available), Decimal::ZERO, false, false).unwrap()
+    }
+
+    fn open(database: &str, wal_path: &Path, max_pending: usize) -> WriteBehind<SqliteAccountStore> {
+        WriteBehind::open(SqliteAccountStore::open(database).unwrap(), wal_path, max_pending, None, LogLevel::Error).unwrap()
+    }
+
+    #[test]
+    fn flush_then_accounts_stored_and_wal_truncated() {
+        let (database, wal_path) = store("flush");
+        let mut other = SqliteAccountStore::open(&database).unwrap();
+        let mut buffer = open(&database, &wal_path, 3);
+        buffer.load(1).unwrap();
+        buffer.save(&account(1, 10)).unwrap();
+        buffer.save(&account(2, 5)).unwrap();
+
+        // buffered in WAL, not in store
+        assert!(fs::metadata(&wal_path).unwrap().len() > 0);
+        assert!(other.load(1).unwrap().is_none());
+        assert_eq!(buffer.load(1).unwrap(), Some(account(1, 10)));
+
+        buffer.save(&account(1, 20)).unwrap();
+        assert_eq!(fs::metadata(&wal_path).unwrap().len(), 0);
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 20)));
+        assert_eq!(other.load(2).unwrap(), Some(account(2, 5)));
+
+        drop(buffer);
+        fs::remove_file(wal_path).unwrap();
+    }
+
+    #[test]
+    fn crash_then_buffered_accounts_replayed_from_wal() {
+        let (database, wal_path) = store("crash");
+        let mut other = SqliteAccountStore::open(&database).unwrap();
+        other.save(&account(1, 10)).unwrap();
+
+        let mut buffer = open(&database, &wal_path, usize::MAX);
+        assert_eq!(buffer.load(1).unwrap(), Some(account(1, 10)));
+        buffer.save(&account(1, 15)).unwrap();
+        buffer.save(&account(1, 20)).unwrap();
+        buffer.save(&account(2, 5)).unwrap();
+        // crash, buffer is not flushed
+        std::mem::forget(buffer);
+
+        drop(open(&database, &wal_path, usize::MAX));
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 20)));
+        assert_eq!(other.load(2).unwrap(), Some(account(2, 5)));
+        assert_eq!(fs::metadata(&wal_path).unwrap().len(), 0);
+
+        fs::remove_file(wal_path).unwrap();
+    }
+
+    #[test]
+    fn torn_last_line_of_wal_then_skipped() {
+        let (database, wal_path) = store("torn");
+        let mut buffer = open(&database, &wal_path, usize::MAX);
+        buffer.load(1).unwrap();
+        buffer.save(&account(1, 10)).unwrap();
+        std::mem::forget(buffer);
+
+        // crash while the second account was written
+        let mut content = fs::read(&wal_path).unwrap();
+        content.extend_from_slice(br#"{"account":{"client":2,"avail"#);
+        fs::write(&wal_path, content).unwrap();
+
+        drop(open(&database, &wal_path, usize::MAX));
+        let mut other = SqliteAccountStore::open(&database).unwrap();
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 10)));
+        assert!(other.load(2).unwrap().is_none());
+
+        fs::remove_file(wal_path).unwrap();
+    }
+
+    #[test]
+    fn wal_of_account_changed_by_other_instance_then_not_replayed() {
+        let (database, wal_path) = store("changed");
+        let mut other = SqliteAccountStore::open(&database).unwrap();
+        other.save(&account(1, 10)).unwrap();
+
+        let mut buffer = open(&database, &wal_path, usize::MAX);
+        buffer.load(1).unwrap();
+        buffer.save(&account(1, 20)).unwrap();
+        buffer.save(&account(2, 5)).unwrap();
+        std::mem::forget(buffer);
+
+        // other instance wrote both accounts after they were buffered
+        other.save(&account(1, 30)).unwrap();
+        other.save(&account(2, 7)).unwrap();
+
+        drop(open(&database, &wal_path, usize::MAX));
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 30)));
+        assert_eq!(other.load(2).unwrap(), Some(account(2, 7)));
+
+        fs::remove_file(wal_path).unwrap();
+    }
+
+    #[test]
+    fn conflicting_flush_then_account_dropped_and_next_save_fails() {
+        let (database, wal_path) = store("conflict");
+        let mut other = SqliteAccountStore::open(&database).unwrap();
+        other.save(&account(1, 10)).unwrap();
+
+        let mut buffer = open(&database, &wal_path, 2);
+        buffer.load(1).unwrap();
+        buffer.load(2).unwrap();
+        other.load(1).unwrap();
+        other.save(&account(1, 30)).unwrap();
+
+        // flush conflicts on client 1 only, account of client 2 is stored
+        buffer.save(&account(2, 5)).unwrap();
+        assert!(matches!(buffer.save(&account(1, 20)), Err(ProcessError::VersionConflict)));
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 30)));
+        assert_eq!(other.load(2).unwrap(), Some(account(2, 5)));
+
+        // conflict is not retried, account loaded again is saved
+        buffer.save(&account(2, 6)).unwrap();
+        assert_eq!(buffer.load(1).unwrap(), Some(account(1, 30)));
+        buffer.save(&account(1, 40)).unwrap();
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 40)));
+        assert_eq!(other.load(2).unwrap(), Some(account(2, 6)));
+
+        drop(buffer);
+        fs::remove_file(wal_path).unwrap();
+    }
+
+    #[test]
+    fn save_merged_then_buffer_flushed_and_merge_stored_at_once() {
+        let (database, wal_path) = store("merge");
+        let mut other = SqliteAccountStore::open(&database).unwrap();
+        let mut buffer = open(&database, &wal_path, usize::MAX);
+        buffer.load(1).unwrap();
+        buffer.load(2).unwrap();
+        buffer.save(&account(1, 10)).unwrap();
+        buffer.save(&account(2, 5)).unwrap();
+
+        buffer.save_merged(&account(1, 15), Some(&account(1, 10)), 2).unwrap();
+        assert_eq!(fs::metadata(&wal_path).unwrap().len(), 0);
+        assert_eq!(other.load(1).unwrap(), Some(account(1, 15)));
+        assert!(other.load(2).unwrap().is_none());
+
+        drop(buffer);
+        fs::remove_file(wal_path).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::path::Path;
+use common::{run, temp_dir};
+
+mod common;
+
+// hash-chained audit log of admin operations (applied, denied and failed) and run manifest with its root hash
+fn audit_log(dir: &Path) -> (String, String) {
+    let [input, ops, log, manifest] = ["input.csv", "ops.ndjson", "audit.ndjson", "manifest.json"].map(|it| dir.join(it));
+    fs::write(&input, "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,2,2,3.0\n").unwrap();
+    fs::write(&ops, r#"{"principal":"alice","op":"adjust","client":1,"amount":"1.5"}
+{"principal":"mallory","op":"unlock","client":1}
+{"principal":"alice","op":"unlock","client":2}
+{"principal":"alice","op":"adjust","client":2,"amount":"-0.5"}
+"#).unwrap();
+
+    let output = run(&[input.to_str().unwrap(), "--admin-ops", ops.to_str().unwrap(), "--admin", "alice",
+        "--audit-log", log.to_str().unwrap(), "--audit-chain", "--manifest", manifest.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    (log.to_str().unwrap().to_string(), manifest.to_str().unwrap().to_string())
+}
+
+fn lines(path: &str) -> Vec<String> {
+    fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+}
+
+fn write_lines(path: &str, lines: &[String]) {
+    fs::write(path, lines.iter().map(|it| format!("{}\n", it)).collect::<String>()).unwrap();
+}
+
+// stderr of failed verification
+fn rejected(log: &str, manifest: &str) -> String {
+    let output = run(&["verify-audit", log, "--manifest", manifest]);
+    assert!(!output.status.success());
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn untouched_log_then_verified_with_manifest_root_hash() {
+    let dir = temp_dir("audit-untouched");
+    let (log, manifest) = audit_log(&dir);
+    let root = serde_json::from_slice::<serde_json::Value>(&fs::read(&manifest).unwrap()).unwrap()["audit_log"]["root_hash"].as_str().unwrap().to_string();
+
+    let output = run(&["verify-audit", &log, "--manifest", &manifest]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), format!("4 events, root hash {}\n", root));
+    assert!(run(&["verify-audit", &log, "--root-hash", &root.to_uppercase()]).status.success());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn edited_record_then_rejected() {
+    let dir = temp_dir("audit-edited");
+    let (log, manifest) = audit_log(&dir);
+    let mut records = lines(&log);
+    assert!(records[0].contains(r#""amount":"1.5""#));
+    records[0] = records[0].replace(r#""amount":"1.5""#, r#""amount":"150""#);
+    write_lines(&log, &records);
+
+    let stderr = rejected(&log, &manifest);
+    assert!(stderr.contains("broken at line 1: hash mismatch"), "{}", stderr);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reordered_records_then_rejected() {
+    let dir = temp_dir("audit-reordered");
+    let (log, manifest) = audit_log(&dir);
+    let mut records = lines(&log);
+    records.swap(1, 2);
+    write_lines(&log, &records);
+
+    let stderr = rejected(&log, &manifest);
+    assert!(stderr.contains("broken at line 2: previous hash mismatch"), "{}", stderr);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn deleted_record_then_rejected() {
+    let dir = temp_dir("audit-deleted");
+    let (log, manifest) = audit_log(&dir);
+    let mut records = lines(&log);
+    // denied operation removed from log
+    assert!(records[1].contains("E214"));
+    records.remove(1);
+    write_lines(&log, &records);
+
+    let stderr = rejected(&log, &manifest);
+    assert!(stderr.contains("broken at line 2: previous hash mismatch"), "{}", stderr);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn truncated_tail_then_rejected() {
+    let dir = temp_dir("audit-truncated");
+    let (log, manifest) = audit_log(&dir);
+    let mut records = lines(&log);
+
+    // remaining records are a valid chain, only root hash of manifest tells the last one is missing
+    records.pop();
+    write_lines(&log, &records);
+    let stderr = rejected(&log, &manifest);
+    assert!(stderr.contains("root hash mismatch"), "{}", stderr);
+
+    // cut inside the last record
+    let content = fs::read_to_string(&log).unwrap();
+    fs::write(&log, &content[..content.len() - 20]).unwrap();
+    let stderr = rejected(&log, &manifest);
+    assert!(stderr.contains("broken at line 3: not a json object"), "{}", stderr);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn manifest_root_hash_mismatch_then_rejected() {
+    let dir = temp_dir("audit-manifest");
+    let (log, manifest) = audit_log(&dir);
+    let mut content: serde_json::Value = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+    content["audit_log"]["root_hash"] = serde_json::Value::from("0".repeat(64));
+    fs::write(&manifest, content.to_string()).unwrap();
+
+    let stderr = rejected(&log, &manifest);
+    assert!(stderr.contains("root hash mismatch"), "{}", stderr);
+    assert!(!run(&["verify-audit", &log, "--root-hash", "abc"]).status.success());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::Path;
+use common::{run, temp_dir};
+
+mod common;
+
+// semicolon delimited input with deposits, withdrawals, disputes and chargebacks of 20 clients
+fn input(dir: &Path) -> String {
+    let mut input = String::from("type;client;tx;amount\n");
+    for tx in 1..=200_u32 {
+        let client = tx * 7 % 20;
+        input.push_str(&match tx % 10 {
+            3 => format!("withdrawal;{};{};{}.5\n", client, tx, tx % 4),
+            6 => format!("dispute;{};{};\n", client, tx - 5),
+            9 if tx % 20 == 9 => format!("chargeback;{};{};\n", (tx - 3) * 7 % 20, tx - 8),
+            _ => format!("deposit;{};{};{}.25\n", client, tx, tx % 9),
+        });
+    }
+    let path = dir.join("input.csv");
+    fs::write(&path, input).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+// rows of accounts output in client order
+fn sorted(stdout: &[u8]) -> Vec<String> {
+    let mut rows: Vec<_> = String::from_utf8_lossy(stdout).lines().map(str::to_string).collect();
+    rows[1..].sort_by_key(|it| it.split(',').next().unwrap().parse::<u16>().unwrap());
+    rows
+}
+
+#[test]
+fn distribute_then_same_accounts_as_single_run() {
+    let dir = temp_dir("distribute");
+    let input = input(&dir);
+    let work_dir = dir.join("work");
+    fs::create_dir(&work_dir).unwrap();
+    let work_dir = work_dir.to_str().unwrap();
+
+    let single = run(&[&input, "--delimiter", "semicolon"]);
+    assert!(single.status.success());
+
+    let distributed = run(&["distribute", &input, "--workers", "3", "--work-dir", work_dir, "--delimiter", "auto"]);
+    assert!(distributed.status.success());
+    assert_eq!(String::from_utf8_lossy(&distributed.stdout).lines().collect::<Vec<_>>(), sorted(&single.stdout));
+    for shard in 0..3 {
+        assert!(dir.join("work").join(format!("shard_{}.accounts.csv", shard)).exists());
+    }
+
+    // shard files of previous run are kept without --force
+    let output = run(&["distribute", &input, "--workers", "3", "--work-dir", work_dir, "--delimiter", "auto"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists, use --force to overwrite"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn split_only_then_worker_results_merged() {
+    let dir = temp_dir("distribute-split");
+    let input = input(&dir);
+    let work_dir = dir.to_str().unwrap();
+
+    let output = run(&["distribute", &input, "--workers", "2", "--work-dir", work_dir, "--delimiter", "semicolon", "--split-only"]);
+    assert!(output.status.success());
+    let shards: Vec<String> = String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect();
+    assert_eq!(shards.len(), 2);
+
+    // workers run elsewhere
+    let results: Vec<String> = shards.iter().enumerate()
+        .map(|(index, shard)| {
+            let output = run(&[shard]);
+            assert!(output.status.success());
+            let result = dir.join(format!("result_{}.csv", index));
+            fs::write(&result, output.stdout).unwrap();
+            result.to_str().unwrap().to_string()
+        })
+        .collect();
+
+    let mut args = vec!["distribute", "--worker-results"];
+    args.extend(results.iter().map(String::as_str));
+    let merged = run(&args);
+    assert!(merged.status.success());
+    let single = run(&[&input, "--delimiter", "semicolon"]);
+    assert_eq!(String::from_utf8_lossy(&merged.stdout).lines().collect::<Vec<_>>(), sorted(&single.stdout));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failed_workers_then_error() {
+    let dir = temp_dir("distribute-failed");
+    let input = input(&dir);
+
+    let output = run(&["distribute", &input, "--workers", "2", "--work-dir", dir.to_str().unwrap(), "--delimiter", "semicolon", "--", "--no-such-option"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Workers failed: "));
+
+    fs::remove_dir_all(dir).unwrap();
+}
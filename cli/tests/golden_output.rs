@@ -0,0 +1,38 @@
+use common::run_with_input;
+
+mod common;
+
+const INPUT: &str = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, 2, 2, 2.0
+deposit, 1, 3, 2.0000
+withdrawal, 1, 4, 1.5
+withdrawal, 2, 5, 3.0
+dispute, 1, 1,
+";
+
+// accounts output of the binary for input read from stdin
+fn run(args: &[&str]) -> String {
+    let output = run_with_input(&[&["-"], args].concat(), INPUT);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn csv_output_then_normalized_amounts() {
+    assert_eq!(run(&[]), "\
+client,available,held,total,locked,closed,status
+1,0.5,1,1.5,false,false,active
+2,2,0,2,false,false,active
+");
+}
+
+#[test]
+fn json_output_then_normalized_amounts() {
+    assert_eq!(run(&["--format", "json"]), r#"[
+{"client":1,"available":"0.5","held":"1","total":"1.5","locked":false,"closed":false,"status":"active"},
+{"client":2,"available":"2","held":"0","total":"2","locked":false,"closed":false,"status":"active"}
+]
+"#);
+}
@@ -0,0 +1,47 @@
+use std::fs;
+use common::{run, temp_dir};
+
+mod common;
+
+#[test]
+fn unknown_types_then_failed_or_skipped_with_type_map() {
+    let dir = temp_dir("unknown-types");
+    let input = dir.join("input.csv");
+    fs::write(&input, "type,client,tx,amount\nCREDIT,1,1,5.0\nbonus,1,2,1.0\nDEBIT,1,3,2.0\nbonus,2,4,1\n").unwrap();
+    let input = input.to_str().unwrap();
+
+    let output = run(&[input]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("line 2: unknown transaction type 'CREDIT'"));
+
+    let output = run(&[input, "--unknown-types", "skip", "--type-map", "CREDIT=deposit", "--type-map", "debit=withdrawal"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "client,available,held,total,locked,closed,status\n1,3,0,3,false,false,active\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Skipped 2 rows with unknown transaction type: 'bonus' (lines 3, 5)"));
+
+    let output = run(&[input, "--type-map", "CREDIT"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid type map 'CREDIT'"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn auto_delimiter_then_same_accounts_as_comma() {
+    let dir = temp_dir("delimiter");
+    let [comma, semicolon, tab] = ["comma.csv", "semicolon.csv", "tab.tsv"].map(|it| dir.join(it));
+    let rows = ["type,client,tx,amount", "deposit,1,1,5.0", "withdrawal,1,2,2.0", "deposit,2,3,1.5"];
+    fs::write(&comma, rows.join("\n")).unwrap();
+    fs::write(&semicolon, rows.join("\n").replace(',', ";")).unwrap();
+    fs::write(&tab, rows.join("\n").replace(',', "\t")).unwrap();
+
+    let expected = run(&[comma.to_str().unwrap()]);
+    assert!(expected.status.success());
+    for input in [&comma, &semicolon, &tab] {
+        assert_eq!(run(&[input.to_str().unwrap(), "--delimiter", "auto"]).stdout, expected.stdout);
+    }
+    assert_eq!(run(&[semicolon.to_str().unwrap(), "--delimiter", "semicolon"]).stdout, expected.stdout);
+    assert!(!run(&[semicolon.to_str().unwrap()]).status.success());
+
+    fs::remove_dir_all(dir).unwrap();
+}
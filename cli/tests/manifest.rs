@@ -0,0 +1,36 @@
+use std::fs;
+use std::process::Command;
+use common::{sha256, temp_dir};
+
+mod common;
+
+#[test]
+fn manifest_then_checksum_of_every_written_file() {
+    let dir = temp_dir("manifest");
+    let input = dir.join("input.csv");
+    fs::write(&input, "type,client,tx,amount\ndeposit,1,1,1.0\nwithdrawal,1,2,5.0\ndeposit,2,3,1.0\n").unwrap();
+    let [manifest, dlq, results, ledger, sidecar] = ["manifest.json", "dlq.ndjson", "results.csv", "ledger.json", "sidecar.json"].map(|it| dir.join(it));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_csv-cli-analyzer"))
+        .arg(&input)
+        .arg("--manifest").arg(&manifest)
+        .arg("--dlq").arg(&dlq)
+        .arg("--results-out").arg(&results)
+        .arg("--ledger").arg(&ledger)
+        .arg("--output-sidecar").arg(&sidecar)
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let manifest: serde_json::Value = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+    assert_eq!(manifest["outputs"][0]["path"], "-");
+    assert_eq!(manifest["outputs"][0]["rows"], 2);
+
+    let files = manifest["files"].as_array().unwrap();
+    let checksums: Vec<(&str, &str)> = files.iter().map(|it| (it["path"].as_str().unwrap(), it["sha256"].as_str().unwrap())).collect();
+    let expected = [&dlq, &results, &ledger, &sidecar].map(|it| (it.to_str().unwrap(), sha256(it)));
+    assert_eq!(checksums, expected.iter().map(|(path, sha256)| (*path, sha256.as_str())).collect::<Vec<_>>());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,29 @@
+use std::fs;
+use common::{run, temp_dir};
+
+mod common;
+
+#[test]
+fn existing_dlq_then_overwritten_only_with_force() {
+    let dir = temp_dir("overwrite");
+    let input = dir.join("input.csv");
+    fs::write(&input, "type,client,tx,amount\ndeposit,1,1,1.0\nwithdrawal,1,2,5.0\n").unwrap();
+    let dlq = dir.join("dlq.ndjson");
+    fs::write(&dlq, "old").unwrap();
+    let args = [input.to_str().unwrap(), "--dlq", dlq.to_str().unwrap()];
+
+    let output = run(&args);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists, use --force to overwrite"));
+    assert!(output.stdout.is_empty());
+    assert_eq!(fs::read_to_string(&dlq).unwrap(), "old");
+
+    let output = run(&[&args[..], &["--force"]].concat());
+    assert!(output.status.success());
+    let dead_letters = fs::read_to_string(&dlq).unwrap();
+    assert_eq!(dead_letters.lines().count(), 1);
+    assert!(dead_letters.contains("\"tx\":2"));
+    assert!(!dir.join("dlq.ndjson.tmp").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
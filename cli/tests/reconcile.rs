@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::Path;
+use common::{run, temp_dir};
+
+mod common;
+
+// input, accounts output of input and statement in `dir`
+fn files(dir: &Path, statement: &str) -> [String; 3] {
+    let [input, accounts, path] = ["input.csv", "accounts.csv", "statement.csv"].map(|it| dir.join(it));
+    fs::write(&input, "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,2,2,3.0\nwithdrawal,2,3,1.0\ndispute,2,3,\ndeposit,4,4,1.0\n").unwrap();
+    let output = run(&[input.to_str().unwrap()]);
+    assert!(output.status.success());
+    fs::write(&accounts, output.stdout).unwrap();
+    fs::write(&path, statement).unwrap();
+    [input, accounts, path].map(|it| it.to_str().unwrap().to_string())
+}
+
+#[test]
+fn matching_statement_then_success() {
+    let dir = temp_dir("reconcile-match");
+    // held and total are optional
+    let [_, accounts, statement] = files(&dir, "client,available,held\n1,5.0,0\n2,2,1\n4,1,\n");
+
+    let output = run(&["reconcile", "--statement", &statement, "--accounts", &accounts]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("All 3 clients reconcile"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn mismatched_statement_then_differences_with_transactions() {
+    let dir = temp_dir("reconcile-mismatch");
+    let [input, accounts, statement] = files(&dir, "client,available,held,total\n1,5.0,0,5\n2,2.0,0,2.0\n3,1,0,1\n");
+
+    let output = run(&["reconcile", "--statement", &statement, "--accounts", &accounts, "--transactions", &input]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("3 of 4 clients don't reconcile"));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Client 2:\n  held       statement 0, output 1\n  total      statement 2.0, output 3\n"));
+    assert!(stdout.contains("Client 3:\n  available  statement 1, output missing\n"));
+    assert!(stdout.contains("Client 4:\n  available  statement missing, output 1\n"));
+    assert!(!stdout.contains("Client 1:"));
+
+    let output = run(&["reconcile", "--statement", &statement, "--accounts", &accounts, "--transactions", &input, "--format", "json"]);
+    let mismatches: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(mismatches.as_array().unwrap().len(), 3);
+    assert_eq!(mismatches[0]["client"], 2);
+    assert!(mismatches[0]["fields"]["available"].is_null());
+    assert_eq!(mismatches[0]["fields"]["held"]["output"], "1");
+    assert_eq!(mismatches[0]["transactions"].as_array().unwrap().iter().map(|it| it["tx"].as_u64().unwrap()).collect::<Vec<_>>(), [2, 3, 3]);
+    assert!(mismatches[2]["fields"]["total"]["statement"].is_null());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn duplicate_client_in_statement_then_error() {
+    let dir = temp_dir("reconcile-duplicate");
+    let [_, accounts, statement] = files(&dir, "client,available\n1,5.0\n1,5.0\n");
+
+    let output = run(&["reconcile", "--statement", &statement, "--accounts", &accounts]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid statement at line 3: duplicate client 1"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,41 @@
+use std::fs;
+use common::{run, sha256, temp_dir};
+
+mod common;
+
+#[test]
+fn default_run_options_with_other_run_mode_then_rejected() {
+    let dir = temp_dir("run-modes");
+    let input = dir.join("input.csv");
+    fs::write(&input, "type,client,tx,amount\ndeposit,1,1,1.0\n").unwrap();
+    let input = input.to_str().unwrap();
+
+    let output = run(&[input, "--fx-table", "fx.csv", "--reporting-currency", "USD", "--ledger", "ledger.json", "--ordered"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("CliParseError, Message: Options --ordered, --ledger are not supported with --fx-table"));
+
+    let out_dir = dir.join("tenants");
+    let output = run(&[input, "--by-tenant", "--out-dir", out_dir.to_str().unwrap(), "--prefetch", "10"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Options --prefetch are not supported with --by-tenant"));
+    assert!(!out_dir.exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn prefetch_with_manifest_then_input_checksum() {
+    let dir = temp_dir("prefetch-manifest");
+    let input = dir.join("input.csv");
+    fs::write(&input, "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,2,2,2.0\n").unwrap();
+    let manifest = dir.join("manifest.json");
+
+    let output = run(&[input.to_str().unwrap(), "--prefetch", "1", "--manifest", manifest.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let manifest: serde_json::Value = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+    assert_eq!(manifest["input"]["sha256"], sha256(&input));
+    assert_eq!(manifest["report"]["processed"], 2);
+
+    fs::remove_dir_all(dir).unwrap();
+}
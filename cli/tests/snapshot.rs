@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::Path;
+use common::{run, run_with_input, temp_dir};
+
+mod common;
+
+// accounts snapshot (csv output) of input transactions
+fn snapshot(path: &Path, input: &str) -> String {
+    let output = run_with_input(&["-"], input);
+    assert!(output.status.success());
+    fs::write(path, output.stdout).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn snapshot_diff_then_changed_accounts_and_failure() {
+    let dir = temp_dir("snapshot-diff");
+    let a = snapshot(&dir.join("a.csv"), "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,2,2,3.0\n");
+    let b = snapshot(&dir.join("b.csv"), "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,3,2,3.0\ndispute,3,2,\n");
+
+    let output = run(&["snapshot", "diff", &a, &b]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("2 accounts differ"));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "\
+client,change,available,held,total,newly_locked
+2,removed,-3,0,-3,false
+3,new,0,3,3,false
+");
+
+    let output = run(&["snapshot", "diff", &a, &a]);
+    assert!(output.status.success());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn snapshot_merge_then_accounts_in_client_order() {
+    let dir = temp_dir("snapshot-merge");
+    let a = snapshot(&dir.join("a.csv"), "type,client,tx,amount\ndeposit,3,1,5.0\ndeposit,1,2,3.0\n");
+    let b = snapshot(&dir.join("b.csv"), "type,client,tx,amount\ndeposit,2,3,1.0\n");
+    let c = snapshot(&dir.join("c.csv"), "type,client,tx,amount\ndeposit,1,4,1.0\n");
+
+    let output = run(&["snapshot", "merge", &a, &b]);
+    assert!(output.status.success());
+    let clients: Vec<_> = String::from_utf8_lossy(&output.stdout).lines().skip(1).map(|it| it.split(',').next().unwrap().to_string()).collect();
+    assert_eq!(clients, ["1", "2", "3"]);
+
+    let output = run(&["snapshot", "merge", &a, &b, &c]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Client 1 found in"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("snapshots are not disjoint"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
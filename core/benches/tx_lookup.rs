@@ -0,0 +1,82 @@
+//! Lookups of retained transactions (duplicate detection of deposits, `exist_by_tx_id`) on hit and miss
+//! workloads. Hybrid repository lookup without and with Bloom filter of stored tx ids is compared by
+//! criterion baselines: `cargo bench -p core --bench tx_lookup --features spill -- --save-baseline spill`,
+//! then `cargo bench -p core --bench tx_lookup --features bloom -- --baseline spill`
+//! (in-memory repository without features is the reference).
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use rust_decimal::Decimal;
+use core::error::ProcessError;
+use core::transaction::{Transaction, TxId};
+use core::transaction_type::TransactionType::{Close, Deposit};
+use core::validation::{TransactionValidator, ValidationContext};
+use core::{BasicProcessor, TransactionProcessor};
+
+// more than in memory part (hot tier) of hybrid repository
+const RETAINED: u32 = 2_000_000;
+const PROBES: u32 = 10_000;
+
+// close transaction of client 0 probes retained tx ids, of client 1 not retained tx ids between them,
+// of client 2 new tx ids (above retained ones), then it is rejected
+struct Probe {
+    hits: Vec<TxId>,
+    misses: Vec<TxId>,
+    new: Vec<TxId>,
+}
+
+impl TransactionValidator for Probe {
+    fn validate(&mut self, transaction: &Transaction, context: &ValidationContext<'_>) -> Result<(), ProcessError> {
+        if *transaction.r#type() != Close {
+            return Ok(());
+        }
+        let tx_ids = match transaction.client() {
+            0 => &self.hits,
+            1 => &self.misses,
+            _ => &self.new,
+        };
+        let found = tx_ids.iter().filter(|it| matches!(context.transaction_exists(**it), Ok(true))).count();
+        Err(ProcessError::RuleViolation(black_box(found).to_string()))
+    }
+}
+
+// every `step`-th tx id from `from`, spread over whole range, so probes don't follow insertion order
+fn probes(from: u32, step: u32) -> Vec<TxId> {
+    (0..PROBES).map(|it| from + step * (it.wrapping_mul(2_654_435_761) % RETAINED)).collect()
+}
+
+// RETAINED deposits with every `step`-th tx id
+fn processor(step: u32) -> BasicProcessor {
+    let mut processor = BasicProcessor::new();
+    for tx_id in (0..step * RETAINED).step_by(step as usize) {
+        let _ = processor.process(Transaction::new(Deposit, (tx_id % 1000) as u16 + 3, tx_id, Some(Decimal::ONE)));
+    }
+    let misses = match step {
+        1 => Vec::new(),
+        _ => probes(1, step),
+    };
+    processor.add_validator(Probe { hits: probes(0, step), misses, new: probes(step * RETAINED, 1) });
+    processor
+}
+
+fn tx_lookup(c: &mut Criterion) {
+    // sequential tx ids without gaps
+    let mut processor = self::processor(1);
+    let mut group = c.benchmark_group("tx_lookup_dense");
+    group.throughput(Throughput::Elements(PROBES as u64));
+    group.bench_function("hit", |b| b.iter(|| processor.process(Transaction::new(Close, 0, 0, None))));
+    group.bench_function("new", |b| b.iter(|| processor.process(Transaction::new(Close, 2, 0, None))));
+    group.finish();
+    drop(processor);
+
+    // every other tx id, e.g. ids shared with other sources
+    let mut processor = self::processor(2);
+    let mut group = c.benchmark_group("tx_lookup_sparse");
+    group.throughput(Throughput::Elements(PROBES as u64));
+    group.bench_function("hit", |b| b.iter(|| processor.process(Transaction::new(Close, 0, 0, None))));
+    group.bench_function("miss", |b| b.iter(|| processor.process(Transaction::new(Close, 1, 0, None))));
+    group.bench_function("new", |b| b.iter(|| processor.process(Transaction::new(Close, 2, 0, None))));
+    group.finish();
+}
+
+criterion_group!(benches, tx_lookup);
+criterion_main!(benches);
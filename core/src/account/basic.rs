@@ -1,8 +1,11 @@
 use rust_decimal::Decimal;
 use crate::client::Client;
-use serde::Serialize;
+use crate::account::status::AccountStatus;
+use crate::account::view::AccountView;
+use serde::{Deserialize, Serialize};
 use crate::ProcessError;
-use crate::ProcessError::{DecimalAmountOverflow, NegativeAmount, NotSufficientAvailableFunds, NotSufficientHeldFunds};
+use crate::ProcessError::{AccountClosed, AccountUnderReview, DecimalAmountOverflow, InvalidStatusTransition, NegativeAmount, NotSufficientAvailableFunds, NotSufficientHeldFunds};
+use crate::prelude::*;
 
 /// As alternative we can use custom serializer for Decimal type.
 /// This serializer will format as four places past the decimal.
@@ -19,7 +22,7 @@ fn four_place_decimal_serializer<S>(value: &Decimal, serializer: S) -> Result<S:
 /// There are multiple clients. Transactions reference clients.
 /// Clients are represented by u16 integers. No names, addresses, or complex client profile info;
 /// If a chargeback occurs the client's account should be immediately frozen.
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct BasicAccount {
     client: Client,
 
@@ -44,8 +47,27 @@ pub struct BasicAccount {
     #[serde(with = "rust_decimal::serde::str")]
     total: Decimal,
 
-    // Whether the account is locked. An account is locked if a charge back occurs
-    locked: bool,
+    // Lifecycle status of the account. An account is locked if a charge back occurs
+    status: AccountStatus,
+
+    // Status of the account before it was locked, unlock restores it,
+    // so closed account stays closed while it is locked
+    #[serde(default, skip_serializing_if = "AccountStatus::is_active")]
+    unlock_status: AccountStatus,
+
+    // Sequence number of the last transaction applied to the account
+    #[serde(skip)]
+    last_activity: u64,
+
+    // Whether the account is dormant (no activity in last N transactions).
+    // Written only when dormant threshold is configured in processor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dormant: Option<bool>,
+
+    // Interest or penalty accrued on held funds, not part of available or total funds.
+    // Written only when held accrual is configured in processor
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::str_option")]
+    accrued: Option<Decimal>,
 }
 
 impl BasicAccount {
@@ -64,8 +86,51 @@ impl BasicAccount {
     pub fn total(&self) -> &Decimal {
         &self.total
     }
+    pub fn status(&self) -> AccountStatus {
+        self.status
+    }
     pub fn locked(&self) -> bool {
-        self.locked
+        self.status.is_locked()
+    }
+    /// Whether the account is closed, also when closed account is locked
+    pub fn closed(&self) -> bool {
+        match self.status {
+            AccountStatus::Locked => self.unlock_status.is_closed(),
+            status => status.is_closed(),
+        }
+    }
+    pub fn last_activity(&self) -> u64 {
+        self.last_activity
+    }
+    pub fn dormant(&self) -> Option<bool> {
+        self.dormant
+    }
+    pub fn accrued(&self) -> Option<&Decimal> {
+        self.accrued.as_ref()
+    }
+
+    /// Account is dormant when no transaction was applied in last `threshold` transactions
+    pub fn is_dormant(&self, sequence: u64, threshold: u64) -> bool {
+        sequence.saturating_sub(self.last_activity) >= threshold
+    }
+
+    pub(crate) fn touch(&mut self, sequence: u64) {
+        self.last_activity = sequence;
+    }
+
+    pub(crate) fn mark_dormant(&mut self, sequence: u64, threshold: u64) {
+        self.dormant = Some(self.is_dormant(sequence, threshold));
+    }
+
+    /// Post interest (positive) or penalty (negative) on held funds, see `HeldAccrual`
+    pub(crate) fn accrue(&mut self, amount: Decimal) {
+        let accrued = self.accrued.get_or_insert(Decimal::ZERO);
+        *accrued = accrued.saturating_add(amount);
+    }
+
+    // accrued column is written for every account when held accrual is configured
+    pub(crate) fn mark_accrual(&mut self) {
+        self.accrued.get_or_insert(Decimal::ZERO);
     }
     pub fn client(&self) -> &Client {
         &self.client
@@ -74,6 +139,10 @@ impl BasicAccount {
     /// A deposit is a credit to the client's asset account, meaning it should increase the available and
     /// total funds of the client account
     pub fn deposit(&mut self, amount: &Decimal) -> Result<(), ProcessError> {
+        if self.closed() {
+            return Err(AccountClosed);
+        }
+
         if amount.is_sign_negative() {
             return Err(NegativeAmount);
         }
@@ -89,9 +158,40 @@ impl BasicAccount {
         Ok(())
     }
 
+    /// Many deposits applied with one balance update. All deposits are applied or none,
+    /// result is the same as of consecutive `deposit` calls which all succeed.
+    pub fn deposit_batch(&mut self, amounts: &[Decimal]) -> Result<(), ProcessError> {
+        if self.closed() {
+            return Err(AccountClosed);
+        }
+
+        if amounts.iter().any(|it| it.is_sign_negative()) {
+            return Err(NegativeAmount);
+        }
+
+        // check for overflow
+        let sum = amounts.iter().try_fold(Decimal::ZERO, |sum, it| sum.checked_add(*it)).ok_or(DecimalAmountOverflow)?;
+        let available = self.available.checked_add(sum).ok_or(DecimalAmountOverflow)?;
+        let total = available.checked_add(self.held).ok_or(DecimalAmountOverflow)?;
+
+        //no overflow, we can update values
+        self.available = available;
+        self.total = total;
+
+        Ok(())
+    }
+
     /// A withdraw is a debit to the client's asset account, meaning it should decrease the available and
     /// total funds of the client account
     pub fn withdrawal(&mut self, amount: &Decimal) -> Result<(), ProcessError> {
+        if self.closed() {
+            return Err(AccountClosed);
+        }
+
+        if self.status == AccountStatus::UnderReview {
+            return Err(AccountUnderReview);
+        }
+
         if amount.is_sign_negative() {
             return Err(NegativeAmount);
         }
@@ -190,6 +290,22 @@ impl BasicAccount {
     /// total funds should decrease by the amount previously disputed. If a chargeback occurs the
     /// client's account should be immediately frozen.
     pub fn chargeback(&mut self, amount: &Decimal) -> Result<(), ProcessError> {
+        self.chargeback_unlocked(amount)?;
+
+        // account must be locked, every status can be locked
+        if !self.status.is_locked() {
+            self.unlock_status = match self.status {
+                AccountStatus::Closed => AccountStatus::Closed,
+                _ => AccountStatus::Active,
+            };
+            self.status = self.status.transition(AccountStatus::Locked)?;
+        }
+
+        Ok(())
+    }
+
+    /// Chargeback of held funds without freezing account, e.g. historical chargeback reloaded in backfill
+    pub fn chargeback_unlocked(&mut self, amount: &Decimal) -> Result<(), ProcessError> {
         if amount.is_sign_negative() {
             return Err(NegativeAmount);
         }
@@ -207,11 +323,111 @@ impl BasicAccount {
         self.held = held;
         self.total = total;
 
-        // account must be locked
-        self.locked = true;
+        Ok(())
+    }
+
+    /// A close marks account as closed. Closed account rejects deposits and withdrawals,
+    /// but disputes, resolves and chargebacks are still allowed.
+    pub fn close(&mut self) -> Result<(), ProcessError> {
+        if self.closed() {
+            return Err(AccountClosed);
+        }
+
+        // locked account stays locked, it is closed when unlocked
+        match self.status.is_locked() {
+            true => self.unlock_status = AccountStatus::Closed,
+            false => self.status = self.status.transition(AccountStatus::Closed)?,
+        }
 
         Ok(())
     }
+
+    /// Unlock account locked after chargeback, e.g. after manual review. Account closed before lock
+    /// is closed again, other accounts become active.
+    pub fn unlock(&mut self) {
+        if self.status.is_locked() {
+            // locked account can be unlocked to active or closed
+            self.status = core::mem::take(&mut self.unlock_status);
+        }
+    }
+
+    /// Put account on hold by risk rule, withdrawals are rejected until review is released.
+    /// Only active account can be put under review.
+    pub fn review(&mut self) -> Result<(), ProcessError> {
+        self.status = self.status.transition(AccountStatus::UnderReview)?;
+        Ok(())
+    }
+
+    /// Release review of account, account becomes active again. Locked account is unlocked with `unlock`.
+    pub fn release_review(&mut self) -> Result<(), ProcessError> {
+        match self.status {
+            AccountStatus::UnderReview => {
+                self.status = AccountStatus::Active;
+                Ok(())
+            }
+            _ => Err(InvalidStatusTransition),
+        }
+    }
+
+    /// Manual correction of available funds, negative `amount` decreases them. Available funds can't
+    /// become negative. Allowed for locked and closed accounts.
+    pub fn adjust(&mut self, amount: &Decimal) -> Result<(), ProcessError> {
+        // check for overflow
+        let available = self.available.checked_add(*amount).ok_or(DecimalAmountOverflow)?;
+        if available.is_sign_negative() && !available.is_zero() {
+            return Err(NotSufficientAvailableFunds);
+        }
+        let total = available.checked_add(self.held).ok_or(DecimalAmountOverflow)?;
+
+        //no overflow, we can update values
+        self.available = available;
+        self.total = total;
+
+        Ok(())
+    }
+
+    /// A merge moves available and held funds of `other` account into this account, total funds of
+    /// this account increase by total of `other`. Closed account can't receive funds.
+    pub fn merge(&mut self, other: &BasicAccount) -> Result<(), ProcessError> {
+        if self.closed() {
+            return Err(AccountClosed);
+        }
+
+        // check for overflow
+        let available = self.available.checked_add(other.available).ok_or(DecimalAmountOverflow)?;
+        let held = self.held.checked_add(other.held).ok_or(DecimalAmountOverflow)?;
+        let total = available.checked_add(held).ok_or(DecimalAmountOverflow)?;
+
+        //no overflow, we can update values
+        self.available = available;
+        self.held = held;
+        self.total = total;
+        if let Some(accrued) = other.accrued {
+            self.accrue(accrued);
+        }
+
+        Ok(())
+    }
+}
+
+/// Account restored from its public view, for example from accounts snapshot.
+/// Activity clock and dormant flag are not part of view and start from zero.
+impl From<AccountView> for BasicAccount {
+    fn from(view: AccountView) -> Self {
+        BasicAccount {
+            client: *view.client(),
+            available: *view.available(),
+            held: *view.held(),
+            total: *view.total(),
+            status: view.status(),
+            unlock_status: match view.status().is_locked() && view.closed() {
+                true => AccountStatus::Closed,
+                false => AccountStatus::Active,
+            },
+            accrued: view.accrued().copied(),
+            ..Default::default()
+        }
+    }
 }
 
 
@@ -219,6 +435,7 @@ impl BasicAccount {
 mod tests {
     use rust_decimal::Decimal;
     use crate::account::basic::BasicAccount;
+    use crate::account::status::AccountStatus;
 
     #[test]
     fn deposit_then_withdrawal() {
@@ -343,4 +560,39 @@ mod tests {
         assert_eq!(account.available(), &Decimal::from(0_u64));
         assert!(!account.locked());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn deposit_close_then_withdrawal() {
+        let mut account = BasicAccount::new(1);
+
+        assert!(account.deposit(&Decimal::from(100_u64)).is_ok());
+        assert!(account.close().is_ok());
+        assert!(account.close().is_err());
+        assert!(account.withdrawal(&Decimal::from(50_u64)).is_err());
+        assert!(account.deposit(&Decimal::from(50_u64)).is_err());
+
+        assert_eq!(account.total(), &Decimal::from(100_u64));
+        assert!(account.closed());
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn chargeback_close_unlock_then_closed_status() {
+        let mut account = BasicAccount::new(1);
+
+        assert!(account.deposit(&Decimal::from(100_u64)).is_ok());
+        assert!(account.dispute_deposit(&Decimal::from(40_u64)).is_ok());
+        assert!(account.chargeback(&Decimal::from(40_u64)).is_ok());
+        assert_eq!((account.status(), account.closed()), (AccountStatus::Locked, false));
+
+        assert!(account.close().is_ok());
+        assert!(account.close().is_err());
+        assert_eq!((account.status(), account.closed()), (AccountStatus::Locked, true));
+        assert!(account.deposit(&Decimal::from(50_u64)).is_err());
+
+        account.unlock();
+        assert_eq!((account.status(), account.closed()), (AccountStatus::Closed, true));
+        assert!(account.withdrawal(&Decimal::from(10_u64)).is_err());
+        assert_eq!(account.total(), &Decimal::from(60_u64));
+    }
+}
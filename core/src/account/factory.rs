@@ -0,0 +1,17 @@
+use crate::account::basic::BasicAccount;
+use crate::client::Client;
+
+/// Creates account when client is seen the first time, for example with starting balance
+/// from a registry. Created account must belong to given client.
+/// Any `Fn(Client) -> BasicAccount` closure is a factory.
+pub trait AccountFactory: Send {
+    fn create(&self, client: Client) -> BasicAccount;
+}
+
+impl<F> AccountFactory for F
+    where F: Fn(Client) -> BasicAccount + Send
+{
+    fn create(&self, client: Client) -> BasicAccount {
+        self(client)
+    }
+}
@@ -0,0 +1,266 @@
+use core::error::Error;
+use core::fmt::{Display, Formatter};
+use rust_decimal::Decimal;
+use crate::account::status::AccountStatus;
+use crate::client::Client;
+use crate::ProcessError;
+use crate::ProcessError::{AccountClosed, AccountUnderReview, DecimalAmountOverflow, NegativeAmount, NotSufficientAvailableFunds, NotSufficientHeldFunds};
+
+/// Number of implied decimal places of `Fixed`
+pub const SCALE: u32 = 4;
+
+/// Fixed-point amount: i64 with 4 implied decimal places (1.5 is 15000).
+/// Range is about +/- 922 trillion, arithmetic is plain checked i64 arithmetic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i64);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+
+    pub fn from_raw(raw: i64) -> Self {
+        Fixed(raw)
+    }
+
+    /// Value scaled by 10^4
+    pub fn raw(&self) -> i64 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Fixed) -> Option<Fixed> {
+        self.0.checked_add(other.0).map(Fixed)
+    }
+
+    pub fn checked_sub(self, other: Fixed) -> Option<Fixed> {
+        self.0.checked_sub(other.0).map(Fixed)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+}
+
+/// Decimal with more than 4 (non-zero) decimal places or out of i64 range
+#[derive(Debug)]
+pub struct NotRepresentable(Decimal);
+
+impl Display for NotRepresentable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} can't be represented with {} decimal places in 64 bits", self.0, SCALE)
+    }
+}
+
+impl Error for NotRepresentable {}
+
+impl TryFrom<Decimal> for Fixed {
+    type Error = NotRepresentable;
+
+    // mantissa is rescaled without decimal arithmetic
+    fn try_from(value: Decimal) -> Result<Self, Self::Error> {
+        let (mantissa, scale) = (value.mantissa(), value.scale());
+        let raw = match scale.cmp(&SCALE) {
+            core::cmp::Ordering::Greater => {
+                let divisor = 10_i128.pow(scale - SCALE);
+                (mantissa % divisor == 0).then(|| mantissa / divisor)
+            }
+            _ => mantissa.checked_mul(10_i128.pow(SCALE - scale)),
+        };
+
+        raw.and_then(|it| i64::try_from(it).ok())
+            .map(Fixed)
+            .ok_or(NotRepresentable(value))
+    }
+}
+
+impl From<Fixed> for Decimal {
+    fn from(value: Fixed) -> Self {
+        Decimal::new(value.0, SCALE).normalize()
+    }
+}
+
+impl Display for Fixed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&Decimal::from(*self), f)
+    }
+}
+
+/// Compact account with fixed-point balances, same rules as `BasicAccount`.
+/// Total is not stored, every operation checks that available + held fits in range.
+#[derive(Debug, Default, Clone)]
+pub struct FixedAccount {
+    client: Client,
+    available: Fixed,
+    held: Fixed,
+    status: AccountStatus,
+
+    // closed account stays closed while it is locked
+    closed: bool,
+}
+
+impl FixedAccount {
+    pub fn new(client: Client) -> Self {
+        FixedAccount {
+            client,
+            ..Default::default()
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn available(&self) -> Fixed {
+        self.available
+    }
+    pub fn held(&self) -> Fixed {
+        self.held
+    }
+    pub fn total(&self) -> Fixed {
+        // checked by every operation
+        Fixed(self.available.0 + self.held.0)
+    }
+    pub fn status(&self) -> AccountStatus {
+        self.status
+    }
+    pub fn locked(&self) -> bool {
+        self.status.is_locked()
+    }
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn deposit(&mut self, amount: Fixed) -> Result<(), ProcessError> {
+        if self.closed {
+            return Err(AccountClosed);
+        }
+        if amount.is_negative() {
+            return Err(NegativeAmount);
+        }
+
+        let available = self.available.checked_add(amount).ok_or(DecimalAmountOverflow)?;
+        self.update(available, self.held)
+    }
+
+    pub fn withdrawal(&mut self, amount: Fixed) -> Result<(), ProcessError> {
+        if self.closed {
+            return Err(AccountClosed);
+        }
+        if self.status == AccountStatus::UnderReview {
+            return Err(AccountUnderReview);
+        }
+        if amount.is_negative() {
+            return Err(NegativeAmount);
+        }
+        if self.available < amount {
+            return Err(NotSufficientAvailableFunds);
+        }
+
+        let available = self.available.checked_sub(amount).ok_or(DecimalAmountOverflow)?;
+        self.update(available, self.held)
+    }
+
+    pub fn dispute_deposit(&mut self, amount: Fixed) -> Result<(), ProcessError> {
+        if amount.is_negative() {
+            return Err(NegativeAmount);
+        }
+        if self.available < amount {
+            return Err(NotSufficientAvailableFunds);
+        }
+
+        let available = self.available.checked_sub(amount).ok_or(DecimalAmountOverflow)?;
+        let held = self.held.checked_add(amount).ok_or(DecimalAmountOverflow)?;
+        self.update(available, held)
+    }
+
+    pub fn dispute_withdrawal(&mut self, amount: Fixed) -> Result<(), ProcessError> {
+        if amount.is_negative() {
+            return Err(NegativeAmount);
+        }
+
+        let held = self.held.checked_add(amount).ok_or(DecimalAmountOverflow)?;
+        self.update(self.available, held)
+    }
+
+    pub fn resolve(&mut self, amount: Fixed) -> Result<(), ProcessError> {
+        if amount.is_negative() {
+            return Err(NegativeAmount);
+        }
+        if self.held < amount {
+            return Err(NotSufficientHeldFunds);
+        }
+
+        let available = self.available.checked_add(amount).ok_or(DecimalAmountOverflow)?;
+        let held = self.held.checked_sub(amount).ok_or(DecimalAmountOverflow)?;
+        self.update(available, held)
+    }
+
+    pub fn chargeback(&mut self, amount: Fixed) -> Result<(), ProcessError> {
+        if amount.is_negative() {
+            return Err(NegativeAmount);
+        }
+        if self.held < amount {
+            return Err(NotSufficientHeldFunds);
+        }
+
+        let held = self.held.checked_sub(amount).ok_or(DecimalAmountOverflow)?;
+        self.update(self.available, held)?;
+
+        // account must be locked, every status can be locked
+        if !self.status.is_locked() {
+            self.status = self.status.transition(AccountStatus::Locked)?;
+        }
+        Ok(())
+    }
+
+    pub fn close(&mut self) -> Result<(), ProcessError> {
+        if self.closed {
+            return Err(AccountClosed);
+        }
+
+        // locked account stays locked
+        if !self.status.is_locked() {
+            self.status = self.status.transition(AccountStatus::Closed)?;
+        }
+        self.closed = true;
+        Ok(())
+    }
+
+    // new balances are set only when total is in range
+    fn update(&mut self, available: Fixed, held: Fixed) -> Result<(), ProcessError> {
+        available.checked_add(held).ok_or(DecimalAmountOverflow)?;
+        self.available = available;
+        self.held = held;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::account::fixed::{Fixed, FixedAccount};
+
+    #[test]
+    fn decimal_then_fixed_then_decimal() {
+        let fixed = Fixed::try_from(Decimal::new(15, 1)).unwrap();
+        assert_eq!(fixed.raw(), 15_000);
+        assert_eq!(Decimal::from(fixed), Decimal::new(15, 1));
+        assert_eq!(Fixed::try_from(Decimal::new(150_000, 5)).map(|it| it.raw()).unwrap(), 15_000);
+
+        assert!(Fixed::try_from(Decimal::new(150_001, 5)).is_err());
+        assert!(Fixed::try_from(Decimal::MAX).is_err());
+        assert_eq!(Fixed::from_raw(-5).to_string(), "-0.0005");
+    }
+
+    #[test]
+    fn deposit_dispute_then_chargeback() {
+        let mut account = FixedAccount::new(1);
+        account.deposit(Fixed::from_raw(100_000)).unwrap();
+        account.dispute_deposit(Fixed::from_raw(40_000)).unwrap();
+        assert_eq!((account.available().raw(), account.held().raw(), account.total().raw()), (60_000, 40_000, 100_000));
+
+        account.chargeback(Fixed::from_raw(40_000)).unwrap();
+        assert_eq!((account.total().raw(), account.locked()), (60_000, true));
+
+        assert!(account.withdrawal(Fixed::from_raw(70_000)).is_err());
+        assert!(account.deposit(Fixed::from_raw(i64::MAX)).is_err());
+        assert_eq!(account.available().raw(), 60_000);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::prelude::*;
+use rust_decimal::Decimal;
+use crate::account::view::AccountView;
+use crate::client::Client;
+use crate::ProcessError;
+
+/// Parent-child relationships of clients, e.g. business customer (parent) with sub-accounts (children),
+/// loaded from a registry. Every client has at most one parent and relationships can't form a cycle.
+#[derive(Debug, Default, Clone)]
+pub struct AccountHierarchy {
+    parents: IntMap<Client, Client>,
+    children: IntMap<Client, Vec<Client>>,
+}
+
+impl AccountHierarchy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add sub-account of `parent`. Returns false (and hierarchy is not changed) when `child` has parent
+    /// already or relationship would create a cycle.
+    pub fn insert(&mut self, child: Client, parent: Client) -> bool {
+        if self.parents.contains_key(&child) || child == parent || self.ancestors(parent).any(|it| it == child) {
+            return false;
+        }
+
+        self.parents.insert(child, parent);
+        self.children.entry(parent).or_default().push(child);
+        true
+    }
+
+    pub fn parent(&self, client: Client) -> Option<Client> {
+        self.parents.get(&client).copied()
+    }
+
+    /// Clients with sub-accounts and without parent, in ascending order
+    pub fn roots(&self) -> Vec<Client> {
+        let mut roots: Vec<_> = self.children.keys().copied().filter(|it| !self.parents.contains_key(it)).collect();
+        roots.sort_unstable();
+        roots
+    }
+
+    /// All sub-accounts of `parent`, also sub-accounts of sub-accounts
+    pub fn descendants(&self, parent: Client) -> Vec<Client> {
+        let mut descendants = Vec::new();
+        let mut stack = vec![parent];
+        while let Some(client) = stack.pop() {
+            if let Some(children) = self.children.get(&client) {
+                descendants.extend_from_slice(children);
+                stack.extend_from_slice(children);
+            }
+        }
+        descendants
+    }
+
+    /// Consolidated account of `parent`: funds of parent account and all its sub-accounts.
+    /// Locked and closed flags are taken from parent account, missing accounts count as empty.
+    pub fn rollup<F>(&self, parent: Client, find: F) -> Result<AccountView, ProcessError>
+        where F: Fn(Client) -> Option<AccountView>
+    {
+        let own = find(parent);
+        let (mut available, mut held) = own.as_ref().map_or((Decimal::ZERO, Decimal::ZERO), |it| (*it.available(), *it.held()));
+        for account in self.descendants(parent).into_iter().filter_map(&find) {
+            available = available.checked_add(*account.available()).ok_or(ProcessError::DecimalAmountOverflow)?;
+            held = held.checked_add(*account.held()).ok_or(ProcessError::DecimalAmountOverflow)?;
+        }
+
+        let (locked, closed) = own.as_ref().map_or((false, false), |it| (it.locked(), it.closed()));
+        AccountView::new(parent, available, held, locked, closed)
+    }
+
+    fn ancestors(&self, client: Client) -> impl Iterator<Item=Client> + '_ {
+        core::iter::successors(self.parent(client), |it| self.parent(*it))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::account::hierarchy::AccountHierarchy;
+    use crate::account::view::AccountView;
+
+    #[test]
+    fn insert_then_reject_cycle_and_second_parent() {
+        let mut hierarchy = AccountHierarchy::new();
+        assert!(hierarchy.insert(2, 1));
+        assert!(hierarchy.insert(3, 2));
+
+        assert!(!hierarchy.insert(1, 3));
+        assert!(!hierarchy.insert(3, 1));
+        assert!(!hierarchy.insert(4, 4));
+
+        assert_eq!(hierarchy.roots(), vec![1]);
+        assert_eq!(hierarchy.parent(3), Some(2));
+    }
+
+    #[test]
+    fn rollup_then_consolidated_total() {
+        let mut hierarchy = AccountHierarchy::new();
+        hierarchy.insert(2, 1);
+        hierarchy.insert(3, 2);
+
+        let accounts = [
+            AccountView::new(1, Decimal::from(10_u64), Decimal::ZERO, true, false).unwrap(),
+            AccountView::new(3, Decimal::from(5_u64), Decimal::from(2_u64), false, false).unwrap(),
+            AccountView::new(4, Decimal::from(100_u64), Decimal::ZERO, false, false).unwrap(),
+        ];
+        let find = |client| accounts.iter().find(|it| *it.client() == client).cloned();
+
+        let rollup = hierarchy.rollup(1, find).unwrap();
+        assert_eq!((*rollup.available(), *rollup.held(), *rollup.total()), (Decimal::from(15_u64), Decimal::from(2_u64), Decimal::from(17_u64)));
+        assert!(rollup.locked());
+
+        let rollup = hierarchy.rollup(2, find).unwrap();
+        assert_eq!((*rollup.total(), rollup.locked()), (Decimal::from(7_u64), false));
+    }
+}
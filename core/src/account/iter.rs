@@ -0,0 +1,234 @@
+use alloc::vec;
+use crate::account::basic::BasicAccount;
+use crate::account::view::AccountView;
+use crate::account::wrap::WrapAccount;
+#[cfg(feature = "fastmath")]
+use crate::account::fixed::FixedAccount;
+use crate::client::Client;
+use crate::prelude::*;
+use crate::repository::wrap_account_repository::AccountState;
+
+/// Accounts of repository in map order, or sorted by client in ordered mode
+pub(crate) enum Values<'a, A> {
+    Unordered(hash_map::Values<'a, Client, A>),
+    Ordered(vec::IntoIter<&'a A>),
+}
+
+impl<'a, A> Iterator for Values<'a, A> {
+    type Item = &'a A;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Values::Unordered(it) => it.next(),
+            Values::Ordered(it) => it.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Values::Unordered(it) => it.size_hint(),
+            Values::Ordered(it) => it.size_hint(),
+        }
+    }
+}
+
+/// Owned accounts of repository in map order, or sorted by client in ordered mode
+pub(crate) enum IntoValues<A> {
+    Unordered(hash_map::IntoValues<Client, A>),
+    Ordered(vec::IntoIter<A>),
+}
+
+impl<A> Iterator for IntoValues<A> {
+    type Item = A;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IntoValues::Unordered(it) => it.next(),
+            IntoValues::Ordered(it) => it.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            IntoValues::Unordered(it) => it.size_hint(),
+            IntoValues::Ordered(it) => it.size_hint(),
+        }
+    }
+}
+
+/// Current state of accounts, without consuming processor (`(&mut processor).into_iter()`)
+pub struct Accounts<'a> {
+    inner: Values<'a, BasicAccount>,
+
+    // held accrual is configured in processor, see `IntoAccounts::with_accrual`
+    accrual: bool,
+}
+
+impl<'a> Accounts<'a> {
+    pub(crate) fn new(inner: Values<'a, BasicAccount>) -> Self {
+        Accounts { inner, accrual: false }
+    }
+
+    pub(crate) fn with_accrual(mut self, accrual: bool) -> Self {
+        self.accrual = accrual;
+        self
+    }
+}
+
+impl Iterator for Accounts<'_> {
+    type Item = AccountView;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut account = AccountView::from(self.inner.next()?);
+        if self.accrual {
+            account.mark_accrual();
+        }
+        Some(account)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Per-client state of `WrapProcessor` accounts, see `WrapProcessor::accounts_state`
+pub struct AccountStates<'a> {
+    inner: Values<'a, WrapAccount>,
+}
+
+impl<'a> AccountStates<'a> {
+    pub(crate) fn new(inner: Values<'a, WrapAccount>) -> Self {
+        AccountStates { inner }
+    }
+}
+
+impl<'a> Iterator for AccountStates<'a> {
+    type Item = AccountState<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|it| {
+            (*it.client(), it.account(), it.dispute_tx_repository().len(), it.tx_repository().len())
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+// accounts of consumed processor
+enum Owned {
+    Basic(IntoValues<BasicAccount>),
+    Wrap(IntoValues<WrapAccount>),
+    #[cfg(feature = "fastmath")]
+    Fixed(hash_map::IntoValues<Client, FixedAccount>),
+}
+
+/// Final state of accounts, returned by `finalize` (or `into_iter`) of processors.
+/// With dormant threshold set, accounts are marked dormant while iterated.
+pub struct IntoAccounts {
+    inner: Owned,
+
+    // (sequence, threshold) of processor, see `set_dormant_threshold`
+    dormant: Option<(u64, u64)>,
+
+    // held accrual is configured in processor, accrued column is written for every account
+    accrual: bool,
+}
+
+impl IntoAccounts {
+    pub(crate) fn basic(inner: IntoValues<BasicAccount>, dormant: Option<(u64, u64)>) -> Self {
+        IntoAccounts { inner: Owned::Basic(inner), dormant, accrual: false }
+    }
+
+    pub(crate) fn wrap(inner: IntoValues<WrapAccount>, dormant: Option<(u64, u64)>) -> Self {
+        IntoAccounts { inner: Owned::Wrap(inner), dormant, accrual: false }
+    }
+
+    #[cfg(feature = "fastmath")]
+    pub(crate) fn fixed(inner: hash_map::IntoValues<Client, FixedAccount>) -> Self {
+        IntoAccounts { inner: Owned::Fixed(inner), dormant: None, accrual: false }
+    }
+
+    pub(crate) fn with_accrual(mut self, accrual: bool) -> Self {
+        self.accrual = accrual;
+        self
+    }
+}
+
+impl Iterator for IntoAccounts {
+    type Item = AccountView;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut account = match &mut self.inner {
+            Owned::Basic(it) => it.next()?,
+            Owned::Wrap(it) => it.next()?.into_account(),
+            #[cfg(feature = "fastmath")]
+            Owned::Fixed(it) => return it.next().map(|it| AccountView::from(&it)),
+        };
+
+        if let Some((sequence, threshold)) = self.dormant {
+            account.mark_dormant(sequence, threshold);
+        }
+        if self.accrual {
+            account.mark_accrual();
+        }
+        Some(AccountView::from(account))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            Owned::Basic(it) => it.size_hint(),
+            Owned::Wrap(it) => it.size_hint(),
+            #[cfg(feature = "fastmath")]
+            Owned::Fixed(it) => it.size_hint(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::{BasicProcessor, Transaction, TransactionProcessor, WrapProcessor};
+    use crate::account::iter::IntoAccounts;
+    use crate::transaction_type::TransactionType::Deposit;
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn finalize_then_exact_size_and_send() {
+        let mut basic = BasicProcessor::new();
+        let mut wrap = WrapProcessor::new();
+        for client in 1..=3 {
+            let transaction = Transaction::new(Deposit, client, client as u32, Some(Decimal::ONE));
+            assert!(basic.process(transaction.clone()).is_ok());
+            assert!(wrap.process(transaction).is_ok());
+        }
+
+        assert_eq!((&mut basic).into_iter().size_hint(), (3, Some(3)));
+        for accounts in [basic.finalize(), wrap.finalize()] {
+            assert_send(&accounts);
+            assert_eq!(accounts.size_hint(), (3, Some(3)));
+            assert_eq!(accounts.map(|it| *it.total()).sum::<Decimal>(), Decimal::from(3_u64));
+        }
+    }
+
+    #[test]
+    fn dormant_threshold_then_accounts_marked_while_iterated() {
+        let mut processor = BasicProcessor::new();
+        processor.set_dormant_threshold(Some(1));
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::ONE))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(Decimal::ONE))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 3, Some(Decimal::ONE))).is_ok());
+
+        let accounts: IntoAccounts = processor.finalize();
+        let mut dormant: Vec<_> = accounts.map(|it| (*it.client(), it.dormant())).collect();
+        dormant.sort_unstable();
+        assert_eq!(dormant, [(1, Some(true)), (2, Some(false))]);
+    }
+}
@@ -0,0 +1,42 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::client::Client;
+
+/// Record of account merge (`BasicProcessor::merge_accounts`): funds moved from one client to another,
+/// for example after identity deduplication.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountMerge {
+    from: Client,
+    to: Client,
+
+    // number of transactions processed before merge
+    sequence: u64,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+}
+
+impl AccountMerge {
+    pub fn new(from: Client, to: Client, sequence: u64, available: Decimal, held: Decimal) -> Self {
+        AccountMerge { from, to, sequence, available, held }
+    }
+
+    pub fn from(&self) -> Client {
+        self.from
+    }
+    pub fn to(&self) -> Client {
+        self.to
+    }
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+    pub fn available(&self) -> &Decimal {
+        &self.available
+    }
+    pub fn held(&self) -> &Decimal {
+        &self.held
+    }
+}
@@ -1,2 +1,10 @@
 pub mod basic;
-pub mod wrap;
\ No newline at end of file
+pub mod status;
+pub mod wrap;
+pub mod view;
+pub mod factory;
+pub mod merge;
+pub mod hierarchy;
+pub mod iter;
+#[cfg(feature = "fastmath")]
+pub mod fixed;
\ No newline at end of file
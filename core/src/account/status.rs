@@ -0,0 +1,117 @@
+//! Account lifecycle: status of account and allowed transitions between statuses.
+
+use serde::{Deserialize, Serialize};
+use crate::prelude::*;
+use crate::client::Client;
+use crate::error::ProcessError;
+
+/// Status of account. Deposits are allowed to active and under review accounts, withdrawals only to active
+/// accounts. Disputes, resolves and chargebacks are allowed to all accounts except locked ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+
+    // on hold until review is released
+    UnderReview,
+
+    // closed by close transaction
+    Closed,
+
+    // frozen after chargeback, only admin can unlock
+    Locked,
+}
+
+impl AccountStatus {
+    /// Whether account can move from this status to `next`:
+    /// - active account can be put under review, closed or locked,
+    /// - review is released to active, account under review can be closed or locked,
+    /// - closed account can be locked (chargeback of dispute opened before close),
+    /// - locked account is unlocked to active or closed (when it was closed before lock).
+    pub fn can_transition_to(self, next: AccountStatus) -> bool {
+        use AccountStatus::*;
+        matches!((self, next),
+            (Active, UnderReview | Closed | Locked)
+            | (UnderReview, Active | Closed | Locked)
+            | (Closed, Locked)
+            | (Locked, Active | Closed))
+    }
+
+    /// Status after transition to `next`, `InvalidStatusTransition` when transition is not allowed
+    pub fn transition(self, next: AccountStatus) -> Result<AccountStatus, ProcessError> {
+        match self.can_transition_to(next) {
+            true => Ok(next),
+            false => Err(ProcessError::InvalidStatusTransition),
+        }
+    }
+
+    /// Snake case status name, the same as serialized
+    pub fn name(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::UnderReview => "under_review",
+            AccountStatus::Closed => "closed",
+            AccountStatus::Locked => "locked",
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        *self == AccountStatus::Active
+    }
+    pub fn is_locked(self) -> bool {
+        self == AccountStatus::Locked
+    }
+    pub fn is_closed(self) -> bool {
+        self == AccountStatus::Closed
+    }
+}
+
+/// Event of account status change, e.g. account locked by chargeback or unlocked by admin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusChange {
+    client: Client,
+    from: AccountStatus,
+    to: AccountStatus,
+
+    // number of transactions processed before change
+    sequence: u64,
+}
+
+impl StatusChange {
+    pub fn new(client: Client, from: AccountStatus, to: AccountStatus, sequence: u64) -> Self {
+        StatusChange { client, from, to, sequence }
+    }
+
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn from(&self) -> AccountStatus {
+        self.from
+    }
+    pub fn to(&self) -> AccountStatus {
+        self.to
+    }
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+/// Receives events of account status changes, e.g. to write them to event log
+pub type StatusListener = Box<dyn FnMut(&StatusChange) + Send>;
+
+#[cfg(test)]
+mod tests {
+    use crate::account::status::AccountStatus::{Active, Closed, Locked, UnderReview};
+    use crate::error::ProcessError;
+
+    #[test]
+    fn transitions_then_only_allowed_applied() {
+        assert_eq!(Active.transition(UnderReview).and_then(|it| it.transition(Active)).ok(), Some(Active));
+        assert_eq!(Active.transition(Closed).and_then(|it| it.transition(Locked)).and_then(|it| it.transition(Closed)).ok(), Some(Closed));
+
+        assert!(matches!(Closed.transition(Active), Err(ProcessError::InvalidStatusTransition)));
+        assert!(matches!(Locked.transition(UnderReview), Err(ProcessError::InvalidStatusTransition)));
+        assert!([Active, UnderReview, Closed, Locked].iter().all(|it| !it.can_transition_to(*it)));
+    }
+}
@@ -0,0 +1,268 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::account::basic::BasicAccount;
+use crate::account::status::AccountStatus;
+#[cfg(feature = "fastmath")]
+use crate::account::fixed::FixedAccount;
+use crate::client::Client;
+use crate::ProcessError;
+use crate::prelude::*;
+
+/// Public account state returned by processors (output iterators and queries).
+/// View is decoupled from internal account structure, so accounts internals (counters, activity clock,
+/// multi-currency balances) can change without breaking serialized output or downstream code.
+/// Serialized as output/snapshot row: client, available, held, total, locked, closed, status and optional extended fields.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(into = "AccountRow", from = "AccountRow")]
+pub struct AccountView {
+    client: Client,
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    status: AccountStatus,
+
+    // closed account stays closed while it is locked
+    closed: bool,
+
+    // no activity in last N transactions (dormant threshold configured in processor)
+    dormant: Option<bool>,
+
+    // interest or penalty accrued on held funds (held accrual configured in processor)
+    accrued: Option<Decimal>,
+}
+
+// serialized account, locked and closed columns are kept for consumers of older output
+#[derive(Serialize, Deserialize)]
+struct AccountRow {
+    client: Client,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    total: Decimal,
+
+    locked: bool,
+
+    // older snapshots have no closed column
+    #[serde(default)]
+    closed: bool,
+
+    // older snapshots have no status column, status is derived from locked and closed
+    #[serde(default)]
+    status: Option<AccountStatus>,
+
+    // extended fields, written only when set
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dormant: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::str_option")]
+    accrued: Option<Decimal>,
+}
+
+// amounts are written normalized (`2`, not `2.0`) whatever scale of input amounts was
+impl From<AccountView> for AccountRow {
+    fn from(view: AccountView) -> Self {
+        AccountRow {
+            client: view.client,
+            available: view.available.normalize(),
+            held: view.held.normalize(),
+            total: view.total.normalize(),
+            locked: view.status.is_locked(),
+            closed: view.closed,
+            status: Some(view.status),
+            dormant: view.dormant,
+            accrued: view.accrued.map(|it| it.normalize()),
+        }
+    }
+}
+
+impl From<AccountRow> for AccountView {
+    fn from(row: AccountRow) -> Self {
+        AccountView {
+            client: row.client,
+            available: row.available,
+            held: row.held,
+            total: row.total,
+            status: row.status.unwrap_or_else(|| derived_status(row.locked, row.closed)),
+            closed: row.closed,
+            dormant: row.dormant,
+            accrued: row.accrued,
+        }
+    }
+}
+
+// status of account known only by locked and closed flags
+fn derived_status(locked: bool, closed: bool) -> AccountStatus {
+    match (locked, closed) {
+        (true, _) => AccountStatus::Locked,
+        (false, true) => AccountStatus::Closed,
+        (false, false) => AccountStatus::Active,
+    }
+}
+
+impl AccountView {
+    /// Account state with total computed from available and held funds, e.g. opening balance
+    pub fn new(client: Client, available: Decimal, held: Decimal, locked: bool, closed: bool) -> Result<Self, ProcessError> {
+        if held < Decimal::ZERO {
+            return Err(ProcessError::NegativeAmount);
+        }
+
+        Ok(AccountView {
+            client,
+            available,
+            held,
+            total: available.checked_add(held).ok_or(ProcessError::DecimalAmountOverflow)?,
+            status: derived_status(locked, closed),
+            closed,
+            dormant: None,
+            accrued: None,
+        })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn available(&self) -> &Decimal {
+        &self.available
+    }
+    pub fn held(&self) -> &Decimal {
+        &self.held
+    }
+    pub fn total(&self) -> &Decimal {
+        &self.total
+    }
+    pub fn status(&self) -> AccountStatus {
+        self.status
+    }
+    pub fn locked(&self) -> bool {
+        self.status.is_locked()
+    }
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+    pub fn dormant(&self) -> Option<bool> {
+        self.dormant
+    }
+    pub fn accrued(&self) -> Option<&Decimal> {
+        self.accrued.as_ref()
+    }
+
+    // accrued column is written for every account when held accrual is configured
+    pub(crate) fn mark_accrual(&mut self) {
+        self.accrued.get_or_insert(Decimal::ZERO);
+    }
+
+    // snapshot migration: version without closed accounts
+    pub(crate) fn reopened(mut self) -> Self {
+        self.closed = false;
+        self
+    }
+
+    // snapshot migration: version without status column
+    pub(crate) fn with_derived_status(mut self) -> Self {
+        self.status = derived_status(self.status.is_locked(), self.closed);
+        self
+    }
+}
+
+impl From<&BasicAccount> for AccountView {
+    fn from(account: &BasicAccount) -> Self {
+        AccountView {
+            client: *account.client(),
+            available: *account.available(),
+            held: *account.held(),
+            total: *account.total(),
+            status: account.status(),
+            closed: account.closed(),
+            dormant: account.dormant(),
+            accrued: account.accrued().copied(),
+        }
+    }
+}
+
+#[cfg(feature = "fastmath")]
+impl From<&FixedAccount> for AccountView {
+    fn from(account: &FixedAccount) -> Self {
+        AccountView {
+            client: *account.client(),
+            available: account.available().into(),
+            held: account.held().into(),
+            total: account.total().into(),
+            status: account.status(),
+            closed: account.closed(),
+            dormant: None,
+            accrued: None,
+        }
+    }
+}
+
+impl From<BasicAccount> for AccountView {
+    fn from(account: BasicAccount) -> Self {
+        AccountView::from(&account)
+    }
+}
+
+/// Page of accounts in ascending client order, see `accounts_page` of processors.
+/// Cursor is the last client of page, so pages stay consistent while accounts are added.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountsPage {
+    accounts: Vec<AccountView>,
+
+    // pass as `cursor` to get the next page, None on the last page
+    next_cursor: Option<Client>,
+}
+
+impl AccountsPage {
+    /// `accounts` must be sorted by client, page is full when it has `limit` accounts
+    pub(crate) fn new(accounts: Vec<AccountView>, limit: usize) -> Self {
+        let next_cursor = match accounts.len() {
+            len if len == limit => accounts.last().map(|it| *it.client()),
+            _ => None,
+        };
+        AccountsPage { accounts, next_cursor }
+    }
+
+    pub fn accounts(&self) -> &[AccountView] {
+        &self.accounts
+    }
+    pub fn next_cursor(&self) -> Option<Client> {
+        self.next_cursor
+    }
+    pub fn into_accounts(self) -> Vec<AccountView> {
+        self.accounts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::account::basic::BasicAccount;
+    use crate::account::view::AccountView;
+
+    #[test]
+    fn deposit_then_view_then_restore() {
+        let mut account = BasicAccount::new(7);
+        account.deposit(&Decimal::new(15, 1)).unwrap();
+        account.close().unwrap();
+
+        let view = AccountView::from(&account);
+        assert_eq!((*view.client(), *view.total(), view.closed(), view.dormant()), (7, Decimal::new(15, 1), true, None));
+
+        let restored = BasicAccount::from(view.clone());
+        assert_eq!(AccountView::from(restored), view);
+    }
+
+    #[test]
+    fn new_then_total() {
+        let view = AccountView::new(1, Decimal::from(10_u64), Decimal::from(5_u64), true, false).unwrap();
+        assert_eq!((*view.total(), view.locked()), (Decimal::from(15_u64), true));
+
+        assert!(AccountView::new(1, Decimal::ONE, Decimal::NEGATIVE_ONE, false, false).is_err());
+        assert!(AccountView::new(1, Decimal::MAX, Decimal::ONE, false, false).is_err());
+    }
+}
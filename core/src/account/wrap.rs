@@ -46,6 +46,10 @@ impl WrapAccount {
     }
 
     #[allow(dead_code)]
+    pub fn closed(&self) -> bool {
+        self.basic_account.closed()
+    }
+
     pub fn client(&self) -> &Client {
         self.basic_account.client()
     }
@@ -74,6 +78,14 @@ impl WrapAccount {
         self.basic_account.chargeback(amount)
     }
 
+    pub fn close(&mut self) -> Result<(), ProcessError> {
+        self.basic_account.close()
+    }
+
+    pub(crate) fn touch(&mut self, sequence: u64) {
+        self.basic_account.touch(sequence)
+    }
+
     pub fn tx_repository(&self) -> &TransactionRepository {
         &self.tx_repository
     }
@@ -90,7 +102,6 @@ impl WrapAccount {
         &mut self.dispute_tx_repository
     }
 
-    #[allow(dead_code)]
     pub fn account(&self) -> &BasicAccount {
         &self.basic_account
     }
@@ -0,0 +1,42 @@
+//! Interest or penalty on held funds (`BasicProcessor::set_held_accrual`), for platforms which compensate
+//! clients for balances held by disputes. Accrual is posted when dispute is resolved or charged back and
+//! tracked in separate `accrued` account field, available and total funds are not changed.
+
+use rust_decimal::Decimal;
+use crate::error::ProcessError;
+
+// accrued amounts are rounded to four places past the decimal
+const ACCRUAL_SCALE: u32 = 4;
+
+/// Rate accrued on held amount per `period` processed transactions (transactions carry no timestamps),
+/// positive rate is interest, negative rate is penalty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeldAccrual {
+    rate: Decimal,
+    period: u64,
+}
+
+impl HeldAccrual {
+    pub fn new(rate: Decimal, period: u64) -> Result<Self, ProcessError> {
+        if period == 0 {
+            return Err(ProcessError::InvalidAmount("accrual period must be positive".into()));
+        }
+        Ok(HeldAccrual { rate, period })
+    }
+
+    pub fn rate(&self) -> &Decimal {
+        &self.rate
+    }
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    /// Accrual on `amount` held for `duration` processed transactions
+    pub fn accrue(&self, amount: Decimal, duration: u64) -> Result<Decimal, ProcessError> {
+        amount.checked_mul(self.rate)
+            .and_then(|it| it.checked_mul(Decimal::from(duration)))
+            .and_then(|it| it.checked_div(Decimal::from(self.period)))
+            .map(|it| it.round_dp(ACCRUAL_SCALE))
+            .ok_or(ProcessError::DecimalAmountOverflow)
+    }
+}
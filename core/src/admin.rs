@@ -0,0 +1,126 @@
+//! Admin operations (unlock, release of review, adjustment, snapshot) gated by authorization hook. Submitters may only
+//! submit transactions, admin operations require admin role. Every operation, also denied one,
+//! is recorded in audit event log.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::prelude::*;
+use crate::account::view::AccountView;
+use crate::audit::{AdminAction, AuditLog};
+use crate::client::Client;
+use crate::error::ProcessError;
+use crate::BasicProcessor;
+
+/// Role of principal (user or service) calling processor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // may submit transactions
+    Submitter,
+
+    // may submit transactions and apply admin operations
+    Admin,
+}
+
+/// Authorization hook, role of principal
+pub trait Authorizer: Send {
+    fn role(&self, principal: &str) -> Role;
+}
+
+impl<F> Authorizer for F
+    where F: Fn(&str) -> Role + Send
+{
+    fn role(&self, principal: &str) -> Role {
+        self(principal)
+    }
+}
+
+/// Admin operations on `BasicProcessor`, authorized by `authorizer` and recorded in audit log
+pub struct Admin<A> {
+    authorizer: A,
+    audit_log: AuditLog,
+}
+
+impl<A: Authorizer> Admin<A> {
+    pub fn new(authorizer: A) -> Self {
+        Admin { authorizer, audit_log: AuditLog::new() }
+    }
+
+    /// Unlock account locked after chargeback
+    pub fn unlock(&mut self, principal: &str, processor: &mut BasicProcessor, client: Client) -> Result<(), ProcessError> {
+        let result = self.authorize(principal).and_then(|_| processor.unlock_account(client));
+        self.audit_log.record(principal, AdminAction::Unlock { client }, &result);
+        result
+    }
+
+    /// Release review of account put under review by risk rule
+    pub fn release_review(&mut self, principal: &str, processor: &mut BasicProcessor, client: Client) -> Result<(), ProcessError> {
+        let result = self.authorize(principal).and_then(|_| processor.release_review(client));
+        self.audit_log.record(principal, AdminAction::ReleaseReview { client }, &result);
+        result
+    }
+
+    /// Manual correction of available funds, negative `amount` decreases them
+    pub fn adjust(&mut self, principal: &str, processor: &mut BasicProcessor, client: Client, amount: Decimal) -> Result<(), ProcessError> {
+        let result = self.authorize(principal).and_then(|_| processor.adjust_balance(client, amount));
+        self.audit_log.record(principal, AdminAction::Adjust { client, amount }, &result);
+        result
+    }
+
+    /// Current state of all accounts
+    pub fn snapshot(&mut self, principal: &str, processor: &mut BasicProcessor) -> Result<Vec<AccountView>, ProcessError> {
+        let result = self.authorize(principal).map(|_| processor.into_iter().collect());
+        self.audit_log.record(principal, AdminAction::Snapshot, &result);
+        result
+    }
+
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit_log
+    }
+
+    pub fn into_audit_log(self) -> AuditLog {
+        self.audit_log
+    }
+
+    fn authorize(&self, principal: &str) -> Result<(), ProcessError> {
+        match self.authorizer.role(principal) {
+            Role::Admin => Ok(()),
+            Role::Submitter => Err(ProcessError::Unauthorized),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::{BasicProcessor, Transaction, TransactionProcessor};
+    use crate::admin::{Admin, Role};
+    use crate::audit::AdminAction;
+    use crate::error::ProcessError;
+    use crate::transaction_type::TransactionType::{Chargeback, Deposit, Dispute};
+
+    #[test]
+    fn submitter_denied_then_admin_unlocks_and_all_recorded() {
+        let mut processor = BasicProcessor::new();
+        for transaction in [Transaction::new(Deposit, 1, 1, Some(Decimal::TEN)), Transaction::new(Deposit, 1, 2, Some(Decimal::ONE)),
+                            Transaction::new(Dispute, 1, 2, None), Transaction::new(Chargeback, 1, 2, None)] {
+            assert!(processor.process(transaction).is_ok());
+        }
+
+        let mut admin = Admin::new(|principal: &str| if principal == "alice" { Role::Admin } else { Role::Submitter });
+        assert!(matches!(admin.unlock("bob", &mut processor, 1), Err(ProcessError::Unauthorized)));
+        assert!(processor.find_account(1).unwrap().locked());
+
+        assert!(admin.unlock("alice", &mut processor, 1).is_ok());
+        assert!(matches!(admin.adjust("alice", &mut processor, 1, Decimal::from(-11_i64)), Err(ProcessError::NotSufficientAvailableFunds)));
+        assert!(admin.adjust("alice", &mut processor, 1, Decimal::from(-4_i64)).is_ok());
+        assert!(matches!(admin.unlock("alice", &mut processor, 2), Err(ProcessError::AccountNotFound)));
+        let snapshot = admin.snapshot("alice", &mut processor).unwrap();
+        assert_eq!((snapshot[0].locked(), *snapshot[0].total()), (false, Decimal::from(6_u64)));
+
+        let log: Vec<_> = admin.audit_log().events().iter().map(|it| (it.id(), it.principal(), it.error())).collect();
+        assert_eq!(log, [(1, "bob", Some("E214")), (2, "alice", None), (3, "alice", Some("E201")), (4, "alice", None),
+                         (5, "alice", Some("E213")), (6, "alice", None)]);
+        assert_eq!(admin.audit_log().events()[3].action(), &AdminAction::Adjust { client: 1, amount: Decimal::from(-4_i64) });
+    }
+}
@@ -0,0 +1,119 @@
+//! Audit event log: every admin operation (see `admin::Admin`) is recorded with principal and outcome,
+//! also operations denied by authorization or failed. Changes of tunable policies by config reload are
+//! recorded as `ConfigChange` events.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::prelude::*;
+use crate::client::Client;
+use crate::error::ProcessError;
+
+/// Admin operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum AdminAction {
+    // unlock account locked after chargeback
+    Unlock { client: Client },
+
+    // release review of account put under review by risk rule
+    #[serde(rename = "release_review")]
+    ReleaseReview { client: Client },
+
+    // manual correction of available funds
+    Adjust {
+        client: Client,
+
+        #[serde(with = "rust_decimal::serde::str")]
+        amount: Decimal,
+    },
+
+    // current state of all accounts
+    Snapshot,
+
+    // tunable policy changed by config reload, None for setting not set
+    #[serde(rename = "config_change")]
+    ConfigChange {
+        setting: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+/// Record of admin operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    // position in log, starting from 1
+    id: u64,
+
+    principal: String,
+
+    action: AdminAction,
+
+    // error code of denied (E214) or failed operation, None when operation was applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    pub fn principal(&self) -> &str {
+        &self.principal
+    }
+    pub fn action(&self) -> &AdminAction {
+        &self.action
+    }
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Append-only log of audit events
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    events: Vec<AuditEvent>,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        AuditLog::default()
+    }
+
+    /// Append event of operation with its result
+    pub fn record<T>(&mut self, principal: &str, action: AdminAction, result: &Result<T, ProcessError>) -> &AuditEvent {
+        let event = AuditEvent {
+            id: self.events.len() as u64 + 1,
+            principal: principal.to_string(),
+            action,
+            error: result.as_ref().err().map(|it| it.code().to_string()),
+        };
+        self.events.push(event);
+        &self.events[self.events.len() - 1]
+    }
+
+    /// Events in order they were recorded
+    pub fn events(&self) -> &[AuditEvent] {
+        &self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::audit::{AdminAction, AuditLog};
+    use crate::error::ProcessError;
+
+    #[test]
+    fn record_then_numbered_events_with_error_code() {
+        let mut log = AuditLog::new();
+        log.record("alice", AdminAction::Adjust { client: 2, amount: Decimal::new(15, 1) }, &Ok::<_, ProcessError>(()));
+        log.record("mallory", AdminAction::Unlock { client: 1 }, &Err::<(), _>(ProcessError::Unauthorized));
+
+        let events = log.events();
+        assert_eq!(events.iter().map(|it| it.id()).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(events[0].error(), None);
+        assert_eq!(events[1].principal(), "mallory");
+        assert_eq!(events[1].error(), Some("E214"));
+    }
+}
@@ -1 +1,6 @@
-pub type Client = u16;
\ No newline at end of file
+use crate::prelude::*;
+
+pub type Client = u16;
+
+/// Namespace of clients, the same client id under different tenants has independent accounts
+pub type Tenant = String;
\ No newline at end of file
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use crate::{ProcessError, Transaction};
+use crate::pipeline::Sink;
+
+/// Transaction rejected by processor together with the reason, a dead letter queue record.
+/// Dead letters can be written to any `Sink` (see `pipeline::run_with_dlq`) and re-submitted later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    #[serde(flatten)]
+    transaction: Transaction,
+
+    // ingestion sequence number of rejected transaction
+    sequence: u64,
+
+    // stable error code, see `ProcessError::code`
+    code: String,
+
+    // human readable error message
+    error: String,
+}
+
+impl DeadLetter {
+    pub fn new(transaction: Transaction, error: &ProcessError) -> Self {
+        DeadLetter {
+            sequence: transaction.sequence(),
+            transaction,
+            code: error.code().to_string(),
+            error: error.to_string(),
+        }
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+
+    /// Transaction to re-submit, with its original sequence number
+    pub fn into_transaction(self) -> Transaction {
+        self.transaction.with_sequence(self.sequence)
+    }
+}
+
+/// Dead letter sink, boxed so sinks of different types can be routed together
+pub type DlqSink<'a, E> = Box<dyn Sink<DeadLetter, Error=E> + 'a>;
+
+/// Routes dead letters to separate sinks by error code, so different teams receive only
+/// the failures they own (e.g. duplicates `E205`, insufficient funds `E201`, malformed data `E1`).
+/// Route pattern is an error code or its prefix, first registered matching route wins.
+/// Dead letters without matching route go to default sink or are dropped.
+pub struct DlqRouter<'a, E> {
+    // code pattern with index of sink
+    routes: Vec<(String, usize)>,
+    sinks: Vec<DlqSink<'a, E>>,
+    default: Option<usize>,
+
+    // dead letters without matching route and default sink
+    dropped: u64,
+}
+
+impl<'a, E> Default for DlqRouter<'a, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, E> DlqRouter<'a, E> {
+    pub fn new() -> Self {
+        DlqRouter {
+            routes: Vec::new(),
+            sinks: Vec::new(),
+            default: None,
+            dropped: 0,
+        }
+    }
+
+    /// Route dead letters with code matching any of `patterns` to `sink`
+    pub fn route<S>(mut self, patterns: &[&str], sink: S) -> Self
+        where S: Sink<DeadLetter, Error=E> + 'a
+    {
+        let index = self.sinks.len();
+        self.sinks.push(Box::new(sink));
+        self.routes.extend(patterns.iter().map(|it| (it.to_string(), index)));
+        self
+    }
+
+    /// Sink for dead letters without matching route
+    pub fn default_route<S>(mut self, sink: S) -> Self
+        where S: Sink<DeadLetter, Error=E> + 'a
+    {
+        self.default = Some(self.sinks.len());
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn sink_index(&self, code: &str) -> Option<usize> {
+        self.routes.iter()
+            .find(|(pattern, _)| code.starts_with(pattern.as_str()))
+            .map(|(_, index)| *index)
+            .or(self.default)
+    }
+}
+
+impl<'a, E> Sink<DeadLetter> for DlqRouter<'a, E> {
+    type Error = E;
+
+    fn write(&mut self, record: DeadLetter) -> Result<(), Self::Error> {
+        match self.sink_index(record.code()) {
+            Some(index) => self.sinks[index].write(record),
+            None => {
+                self.dropped += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.sinks.iter_mut().try_for_each(|it| it.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use rust_decimal::Decimal;
+    use crate::dlq::{DeadLetter, DlqRouter};
+    use crate::pipeline::Sink;
+    use crate::ProcessError::{AccountLocked, AmountNotFound, NotSufficientAvailableFunds, TransactionExists};
+    use crate::Transaction;
+    use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+    #[test]
+    fn dead_letters_then_routed_by_code() {
+        let (mut duplicates, mut funds, mut malformed) = (Vec::new(), Vec::new(), Vec::new());
+        {
+            let mut router: DlqRouter<Infallible> = DlqRouter::new()
+                .route(&["E205"], &mut duplicates)
+                .route(&["E201", "E202"], &mut funds)
+                .route(&["E1"], &mut malformed);
+
+            assert!(router.write(DeadLetter::new(Transaction::new(Deposit, 1, 1, Some(Decimal::ONE)), &TransactionExists)).is_ok());
+            assert!(router.write(DeadLetter::new(Transaction::new(Withdrawal, 1, 2, Some(Decimal::TEN)), &NotSufficientAvailableFunds)).is_ok());
+            assert!(router.write(DeadLetter::new(Transaction::new(Deposit, 1, 3, None), &AmountNotFound)).is_ok());
+            assert!(router.write(DeadLetter::new(Transaction::new(Deposit, 1, 4, None), &AccountLocked)).is_ok());
+            assert_eq!(router.dropped(), 1);
+        }
+
+        assert_eq!(duplicates.iter().map(|it: &DeadLetter| it.transaction().tx_id()).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(funds.iter().map(|it: &DeadLetter| it.transaction().tx_id()).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(malformed.iter().map(|it: &DeadLetter| it.transaction().tx_id()).collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[cfg(feature = "metadata")]
+    #[test]
+    fn dead_letter_then_metadata_kept() {
+        let metadata = std::collections::HashMap::from([("branch".to_string(), "WAW".to_string())]);
+        let transaction = Transaction::new(Deposit, 1, 1, Some(Decimal::ONE)).with_metadata(metadata.clone());
+
+        assert_eq!(DeadLetter::new(transaction, &TransactionExists).into_transaction().metadata(), &metadata);
+    }
+}
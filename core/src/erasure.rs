@@ -0,0 +1,33 @@
+//! Client erasure (`BasicProcessor::erase_client`) for compliance-driven deletion requests: account and
+//! retained transactions of client are removed, only anonymized tombstone is recorded.
+
+use serde::{Deserialize, Serialize};
+
+/// Record of erased client, without client id, tx ids or amounts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    // number of transactions processed before erasure
+    sequence: u64,
+
+    // whether client had account (it may have only retained transactions, e.g. restored from history)
+    account: bool,
+
+    // retained (and disputed) transactions removed with account
+    transactions: u64,
+}
+
+impl Tombstone {
+    pub fn new(sequence: u64, account: bool, transactions: u64) -> Self {
+        Tombstone { sequence, account, transactions }
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+    pub fn account(&self) -> bool {
+        self.account
+    }
+    pub fn transactions(&self) -> u64 {
+        self.transactions
+    }
+}
@@ -1,11 +1,15 @@
-use std::error::Error;
-use std::fmt::{Display, Formatter};
+use core::error::Error;
+use core::fmt::{Display, Formatter};
+use crate::prelude::*;
 
 #[derive(Debug)]
 pub enum ProcessError {
     // Can't process tx: Transaction details not contains amount value
     AmountNotFound,
 
+    // Can't process tx: Amount value is not a valid decimal number
+    InvalidAmount(Box<dyn Error + Send + Sync>),
+
     // Can't process tx: Value overflow after transaction
     DecimalAmountOverflow,
 
@@ -21,6 +25,9 @@ pub enum ProcessError {
     // Can't process tx: Account locked after chargeback
     AccountLocked,
 
+    // Can't process tx: Account closed, deposit and withdrawal not allowed
+    AccountClosed,
+
     // Can't process tx: A transaction already exists in the repository
     TransactionExists,
 
@@ -41,6 +48,50 @@ pub enum ProcessError {
     // For example dispute transaction has different client id than original transaction
     MismatchClientId,
 
+    // Can't restore snapshot: Snapshot version unknown to this release (e.g. written by newer release)
+    UnsupportedSnapshotVersion(u32),
+
+    // Can't process tx: Rejected by custom business rule, with rule's reason
+    RuleViolation(String),
+
+    // Can't merge accounts: Source account not found or source same as target
+    InvalidMerge,
+
+    // Can't process tx: Transaction quota of tenant exhausted
+    QuotaExceeded,
+
+    // Can't apply admin operation: Account of client not exist
+    AccountNotFound,
+
+    // Can't apply admin operation: Principal not authorized for operation
+    Unauthorized,
+
+    // Can't change account status: Transition not allowed, e.g. closed account can't become active
+    InvalidStatusTransition,
+
+    // Can't process tx: Rejected by risk rule, which put account under review, with rule's reason
+    ReviewRequired(String),
+
+    // Can't process tx: Account under review, withdrawal not allowed until review is released
+    AccountUnderReview,
+
+    // Can't process tx: Amount of deposit or withdrawal is over limit set with `set_amount_limit`
+    AmountOverLimit,
+
+    // Can't process tx: Custom business rule failed to evaluate (e.g. script error)
+    RuleError(String),
+
+    // Can't process tx: Transaction repository operation failed (I/O error or injected fault)
+    // Storage (spill file, fault injection) requires std
+    #[cfg(feature = "std")]
+    StorageError(std::io::Error),
+
+    // Can't process tx: Account in store was changed by other instance since it was loaded (optimistic concurrency)
+    VersionConflict,
+
+    // Can't process tx: Transaction repository holds the maximum number of transactions (u32 handles)
+    RepositoryFull,
+
     // Can't process tx: Acquiring a Mutex lock or RwLock unsuccessful.
     MutexLockError,
 
@@ -48,10 +99,238 @@ pub enum ProcessError {
     UnknownOrUnexpectedError,
 }
 
+/// How serious is a processing error, from the operator point of view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    // Transaction rejected by business rule (funds, duplicates, dispute flow, account state)
+    Rejected,
+
+    // Transaction data is malformed (missing, invalid or negative amount)
+    Invalid,
+
+    // Processing failed for reason unrelated to transaction (storage, lock, overflow, unexpected)
+    Failure,
+}
+
+impl ProcessError {
+    pub fn severity(&self) -> Severity {
+        match self {
+            ProcessError::AmountNotFound
+            | ProcessError::InvalidAmount(_)
+            | ProcessError::NegativeAmount
+            | ProcessError::InvalidTransactionTypeOrAmount
+            | ProcessError::UnsupportedSnapshotVersion(_) => Severity::Invalid,
+            ProcessError::NotSufficientAvailableFunds
+            | ProcessError::NotSufficientHeldFunds
+            | ProcessError::AccountLocked
+            | ProcessError::AccountClosed
+            | ProcessError::TransactionExists
+            | ProcessError::OrgTransactionNotFound
+            | ProcessError::DisputedTransactionNotFound
+            | ProcessError::TransactionUnderDispute
+            | ProcessError::MismatchClientId
+            | ProcessError::RuleViolation(_)
+            | ProcessError::InvalidMerge
+            | ProcessError::QuotaExceeded
+            | ProcessError::AccountNotFound
+            | ProcessError::Unauthorized
+            | ProcessError::InvalidStatusTransition
+            | ProcessError::ReviewRequired(_)
+            | ProcessError::AccountUnderReview
+            | ProcessError::AmountOverLimit => Severity::Rejected,
+            #[cfg(feature = "std")]
+            ProcessError::StorageError(_) => Severity::Failure,
+            ProcessError::DecimalAmountOverflow
+            | ProcessError::MutexLockError
+            | ProcessError::RuleError(_)
+            | ProcessError::VersionConflict
+            | ProcessError::RepositoryFull
+            | ProcessError::UnknownOrUnexpectedError => Severity::Failure,
+        }
+    }
+
+    /// Stable machine-readable code, `E1xx` invalid data, `E2xx` business rejection, `E3xx` failure.
+    /// Codes are never reused or renumbered, new variants get new codes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProcessError::AmountNotFound => "E101",
+            ProcessError::InvalidAmount(_) => "E102",
+            ProcessError::NegativeAmount => "E103",
+            ProcessError::InvalidTransactionTypeOrAmount => "E104",
+            ProcessError::UnsupportedSnapshotVersion(_) => "E105",
+            ProcessError::NotSufficientAvailableFunds => "E201",
+            ProcessError::NotSufficientHeldFunds => "E202",
+            ProcessError::AccountLocked => "E203",
+            ProcessError::AccountClosed => "E204",
+            ProcessError::TransactionExists => "E205",
+            ProcessError::OrgTransactionNotFound => "E206",
+            ProcessError::DisputedTransactionNotFound => "E207",
+            ProcessError::TransactionUnderDispute => "E208",
+            ProcessError::MismatchClientId => "E209",
+            ProcessError::RuleViolation(_) => "E210",
+            ProcessError::InvalidMerge => "E211",
+            ProcessError::QuotaExceeded => "E212",
+            ProcessError::AccountNotFound => "E213",
+            ProcessError::Unauthorized => "E214",
+            ProcessError::InvalidStatusTransition => "E215",
+            ProcessError::ReviewRequired(_) => "E216",
+            ProcessError::AccountUnderReview => "E217",
+            ProcessError::AmountOverLimit => "E218",
+            ProcessError::DecimalAmountOverflow => "E301",
+            #[cfg(feature = "std")]
+            ProcessError::StorageError(_) => "E302",
+            ProcessError::MutexLockError => "E303",
+            ProcessError::RuleError(_) => "E304",
+            ProcessError::VersionConflict => "E305",
+            ProcessError::RepositoryFull => "E306",
+            ProcessError::UnknownOrUnexpectedError => "E399",
+        }
+    }
+
+    /// Error for stable code, e.g. returned by external processor. Only errors without details
+    /// can be created, codes of errors with details (`E102`, `E105`, `E210`, `E216`, `E302`, `E304`) return None.
+    pub fn from_code(code: &str) -> Option<ProcessError> {
+        match code {
+            "E101" => Some(ProcessError::AmountNotFound),
+            "E103" => Some(ProcessError::NegativeAmount),
+            "E104" => Some(ProcessError::InvalidTransactionTypeOrAmount),
+            "E201" => Some(ProcessError::NotSufficientAvailableFunds),
+            "E202" => Some(ProcessError::NotSufficientHeldFunds),
+            "E203" => Some(ProcessError::AccountLocked),
+            "E204" => Some(ProcessError::AccountClosed),
+            "E205" => Some(ProcessError::TransactionExists),
+            "E206" => Some(ProcessError::OrgTransactionNotFound),
+            "E207" => Some(ProcessError::DisputedTransactionNotFound),
+            "E208" => Some(ProcessError::TransactionUnderDispute),
+            "E209" => Some(ProcessError::MismatchClientId),
+            "E211" => Some(ProcessError::InvalidMerge),
+            "E212" => Some(ProcessError::QuotaExceeded),
+            "E213" => Some(ProcessError::AccountNotFound),
+            "E214" => Some(ProcessError::Unauthorized),
+            "E215" => Some(ProcessError::InvalidStatusTransition),
+            "E217" => Some(ProcessError::AccountUnderReview),
+            "E218" => Some(ProcessError::AmountOverLimit),
+            "E301" => Some(ProcessError::DecimalAmountOverflow),
+            "E303" => Some(ProcessError::MutexLockError),
+            "E305" => Some(ProcessError::VersionConflict),
+            "E306" => Some(ProcessError::RepositoryFull),
+            "E399" => Some(ProcessError::UnknownOrUnexpectedError),
+            _ => None,
+        }
+    }
+
+    /// Transient error, the same transaction may succeed when submitted again.
+    /// Business rejections and invalid data are permanent.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            #[cfg(feature = "std")]
+            ProcessError::StorageError(_) => true,
+            ProcessError::MutexLockError => true,
+            ProcessError::VersionConflict => true,
+            _ => false,
+        }
+    }
+}
+
+
 impl Display for ProcessError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let message = match self {
+            ProcessError::RuleViolation(reason) => return write!(f, "Can't process tx: rejected by rule: {}", reason),
+            ProcessError::ReviewRequired(reason) => return write!(f, "Can't process tx: account put under review by rule: {}", reason),
+            ProcessError::RuleError(reason) => return write!(f, "Can't process tx: rule evaluation failed: {}", reason),
+            ProcessError::UnsupportedSnapshotVersion(version) => return write!(f, "Can't restore snapshot: unsupported snapshot version {}", version),
+            ProcessError::AmountNotFound => "Can't process tx: transaction details not contains amount value",
+            ProcessError::InvalidAmount(_) => "Can't process tx: amount value is not a valid decimal number",
+            ProcessError::DecimalAmountOverflow => "Can't process tx: value overflow after transaction",
+            ProcessError::NegativeAmount => "Can't process tx: expected amount >= 0",
+            ProcessError::NotSufficientAvailableFunds => "Can't process tx: available money not sufficient for transaction",
+            ProcessError::NotSufficientHeldFunds => "Can't process tx: held money not sufficient for transaction",
+            ProcessError::AccountLocked => "Can't process tx: account locked after chargeback",
+            ProcessError::AccountClosed => "Can't process tx: account closed",
+            ProcessError::TransactionExists => "Can't process tx: transaction already exists",
+            ProcessError::OrgTransactionNotFound => "Can't process tx: original transaction not found",
+            ProcessError::DisputedTransactionNotFound => "Can't process tx: dispute process not started",
+            ProcessError::TransactionUnderDispute => "Can't process tx: transaction dispute process already started",
+            ProcessError::InvalidTransactionTypeOrAmount => "Can't process tx: invalid original transaction type or amount",
+            ProcessError::MismatchClientId => "Can't process tx: original client id differs from transaction client id",
+            ProcessError::InvalidMerge => "Can't merge accounts: source account not found or same as target",
+            ProcessError::QuotaExceeded => "Can't process tx: transaction quota of tenant exhausted",
+            ProcessError::AccountNotFound => "Can't apply admin operation: account not found",
+            ProcessError::Unauthorized => "Can't apply admin operation: principal not authorized",
+            ProcessError::InvalidStatusTransition => "Can't change account status: transition not allowed",
+            ProcessError::AccountUnderReview => "Can't process tx: account under review",
+            ProcessError::AmountOverLimit => "Can't process tx: amount over limit",
+            #[cfg(feature = "std")]
+            ProcessError::StorageError(_) => "Can't process tx: transaction repository operation failed",
+            ProcessError::MutexLockError => "Can't process tx: acquiring lock unsuccessful",
+            ProcessError::VersionConflict => "Can't process tx: account changed by other instance",
+            ProcessError::RepositoryFull => "Can't process tx: transaction repository full",
+            ProcessError::UnknownOrUnexpectedError => "Can't process tx: unexpected error",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl Error for ProcessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProcessError::InvalidAmount(e) => Some(e.as_ref()),
+            #[cfg(feature = "std")]
+            ProcessError::StorageError(e) => Some(e),
+            _ => None,
+        }
     }
 }
 
-impl Error for ProcessError {}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use crate::error::{ProcessError, Severity};
+
+    fn storage_error() -> ProcessError {
+        ProcessError::StorageError(std::io::Error::other("disk full"))
+    }
+
+    #[test]
+    fn severity_then_retryable() {
+        assert_eq!(ProcessError::NotSufficientAvailableFunds.severity(), Severity::Rejected);
+        assert_eq!(ProcessError::NegativeAmount.severity(), Severity::Invalid);
+        assert_eq!(storage_error().severity(), Severity::Failure);
+
+        assert!(storage_error().is_retryable());
+        assert!(!ProcessError::TransactionExists.is_retryable());
+        assert!(!ProcessError::DecimalAmountOverflow.is_retryable());
+    }
+
+    #[test]
+    fn code_then_severity_group() {
+        assert_eq!(ProcessError::AmountNotFound.code(), "E101");
+        assert_eq!(ProcessError::TransactionExists.code(), "E205");
+        assert_eq!(storage_error().code(), "E302");
+    }
+
+    #[test]
+    fn from_code_then_same_code() {
+        for code in ["E101", "E201", "E209", "E301", "E399"] {
+            assert_eq!(ProcessError::from_code(code).map(|it| it.code()), Some(code));
+        }
+        assert!(ProcessError::from_code("E302").is_none());
+        assert!(ProcessError::from_code("E999").is_none());
+    }
+
+    #[test]
+    fn rule_violation_then_reason_in_message() {
+        let error = ProcessError::RuleViolation("amount over limit".to_string());
+
+        assert_eq!(error.code(), "E210");
+        assert_eq!(error.severity(), Severity::Rejected);
+        assert_eq!(error.to_string(), "Can't process tx: rejected by rule: amount over limit");
+    }
+
+    #[test]
+    fn storage_error_then_source_chain() {
+        let error = storage_error();
+
+        assert_eq!(core::error::Error::source(&error).map(|it| it.to_string()), Some("disk full".to_string()));
+    }
+}
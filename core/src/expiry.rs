@@ -0,0 +1,69 @@
+//! Auto-expiry of disputes (`BasicProcessor::set_dispute_expiry`): disputes not resolved or charged back
+//! in time are resolved (held funds released) or flagged for manual review, so held funds don't linger forever.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::prelude::*;
+use crate::client::Client;
+use crate::transaction::TxId;
+
+/// When open dispute expires. Transactions carry no timestamps, time is measured in processed transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeExpiry {
+    // not resolved or charged back within N processed transactions
+    After(u64),
+
+    // still open when processor is finalized
+    EndOfRun,
+}
+
+/// What happens with expired dispute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpiryAction {
+    // dispute is resolved, held funds are available again
+    Resolve,
+
+    // dispute stays open (funds held), only event is emitted
+    Flag,
+}
+
+/// Event of expired dispute. Disputes of locked accounts can't be resolved and are flagged instead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpiredDispute {
+    tx: TxId,
+    client: Client,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    amount: Decimal,
+
+    // number of transactions processed before expiry
+    sequence: u64,
+
+    action: ExpiryAction,
+}
+
+impl ExpiredDispute {
+    pub fn new(tx: TxId, client: Client, amount: Decimal, sequence: u64, action: ExpiryAction) -> Self {
+        ExpiredDispute { tx, client, amount, sequence, action }
+    }
+
+    pub fn tx_id(&self) -> TxId {
+        self.tx
+    }
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn amount(&self) -> &Decimal {
+        &self.amount
+    }
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+    pub fn action(&self) -> ExpiryAction {
+        self.action
+    }
+}
+
+/// Receives events of expired disputes, e.g. to write them to event log
+pub type ExpiryListener = Box<dyn FnMut(&ExpiredDispute) + Send>;
@@ -0,0 +1,127 @@
+//! Balance explanation (`BasicProcessor::explain`): transactions which compose available and held funds
+//! of client account, so support staff can answer "why is my balance X".
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use crate::prelude::*;
+use crate::client::Client;
+use crate::transaction::TxId;
+use crate::transaction_type::TransactionType;
+use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+/// State of dispute of transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisputeOutcome {
+    Open,
+    Resolved,
+    ChargedBack,
+}
+
+/// Deposit or withdrawal with its contribution to available and held funds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExplainedTransaction {
+    tx: TxId,
+    r#type: TransactionType,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    amount: Decimal,
+
+    // None when transaction was never disputed
+    dispute: Option<DisputeOutcome>,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+}
+
+impl ExplainedTransaction {
+    pub fn new(tx: TxId, r#type: TransactionType, amount: Decimal, dispute: Option<DisputeOutcome>) -> Self {
+        // resolved withdrawal is reversed, charged back deposit is reversed
+        let (available, held) = match (r#type, dispute) {
+            (Deposit, None | Some(DisputeOutcome::Resolved)) => (amount, Decimal::ZERO),
+            (Deposit, Some(DisputeOutcome::Open)) => (Decimal::ZERO, amount),
+            (Withdrawal, None | Some(DisputeOutcome::ChargedBack)) => (-amount, Decimal::ZERO),
+            (Withdrawal, Some(DisputeOutcome::Open)) => (-amount, amount),
+            _ => (Decimal::ZERO, Decimal::ZERO),
+        };
+        ExplainedTransaction { tx, r#type, amount, dispute, available, held }
+    }
+
+    pub fn tx_id(&self) -> TxId {
+        self.tx
+    }
+    pub fn r#type(&self) -> &TransactionType {
+        &self.r#type
+    }
+    pub fn amount(&self) -> &Decimal {
+        &self.amount
+    }
+    pub fn dispute(&self) -> Option<DisputeOutcome> {
+        self.dispute
+    }
+    pub fn available(&self) -> &Decimal {
+        &self.available
+    }
+    pub fn held(&self) -> &Decimal {
+        &self.held
+    }
+}
+
+/// Current available and held funds of client with transactions composing them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceExplanation {
+    client: Client,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+
+    // retained transactions in processing order, followed by transactions of closed disputes
+    transactions: Vec<ExplainedTransaction>,
+
+    // part of funds not explained by transactions: restored snapshot, evicted transactions, merges, adjustments
+    #[serde(with = "rust_decimal::serde::str")]
+    unexplained_available: Decimal,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    unexplained_held: Decimal,
+}
+
+impl BalanceExplanation {
+    pub fn new(client: Client, available: Decimal, held: Decimal, transactions: Vec<ExplainedTransaction>) -> Self {
+        let (explained_available, explained_held) = transactions.iter()
+            .fold((Decimal::ZERO, Decimal::ZERO), |(available, held), it| (available.saturating_add(it.available), held.saturating_add(it.held)));
+        BalanceExplanation {
+            client,
+            available,
+            held,
+            transactions,
+            unexplained_available: available.saturating_sub(explained_available),
+            unexplained_held: held.saturating_sub(explained_held),
+        }
+    }
+
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn available(&self) -> &Decimal {
+        &self.available
+    }
+    pub fn held(&self) -> &Decimal {
+        &self.held
+    }
+    pub fn transactions(&self) -> &[ExplainedTransaction] {
+        &self.transactions
+    }
+    pub fn unexplained_available(&self) -> &Decimal {
+        &self.unexplained_available
+    }
+    pub fn unexplained_held(&self) -> &Decimal {
+        &self.unexplained_held
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+static CONFIG: RwLock<FaultConfig> = RwLock::new(FaultConfig::NONE);
+
+/// Failure injection settings for transaction repositories (`fault-injection` feature).
+/// Every insert/lookup rolls the dice: it may be delayed by `delay` and/or fail with `ProcessError::StorageError`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaultConfig {
+    // fraction (0.0 - 1.0) of inserts/lookups failing with storage error
+    pub fail_rate: f64,
+
+    // fraction (0.0 - 1.0) of inserts/lookups delayed by `delay`
+    pub delay_rate: f64,
+    pub delay: Duration,
+
+    // seed of pseudo random generator, same seed and input gives same faults
+    pub seed: u64,
+}
+
+impl FaultConfig {
+    /// No faults, repositories behave as without injection
+    pub const NONE: FaultConfig = FaultConfig {
+        fail_rate: 0.0,
+        delay_rate: 0.0,
+        delay: Duration::ZERO,
+        seed: 0,
+    };
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        FaultConfig::NONE
+    }
+}
+
+/// Set config used by repositories created after this call (for example by `BasicProcessor::new`)
+pub fn set_fault_config(config: FaultConfig) {
+    match CONFIG.write() {
+        Ok(mut it) => *it = config,
+        Err(e) => *e.into_inner() = config,
+    }
+}
+
+pub fn fault_config() -> FaultConfig {
+    match CONFIG.read() {
+        Ok(it) => *it,
+        Err(e) => *e.into_inner(),
+    }
+}
@@ -0,0 +1,201 @@
+use crate::prelude::*;
+use core::error::Error;
+use core::fmt::{Display, Formatter};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::account::view::AccountView;
+use crate::client::Client;
+
+/// ISO 4217 like currency code, for example "EUR"
+pub type Currency = String;
+
+// converted figures are rounded to four places past the decimal, same as input precision
+const CONVERTED_SCALE: u32 = 4;
+
+/// Single row of FX conversion table: 1 unit of `currency` = `rate` units of reporting currency
+#[derive(Debug, Clone, Deserialize)]
+pub struct FxRate {
+    currency: Currency,
+    #[serde(with = "rust_decimal::serde::str")]
+    rate: Decimal,
+}
+
+impl FxRate {
+    pub fn new(currency: Currency, rate: Decimal) -> Self {
+        FxRate {
+            currency,
+            rate,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FxError {
+    // Can't convert: no rate for currency in FX table
+    RateNotFound(Currency),
+
+    // Can't convert: Expected rate > 0
+    InvalidRate(Currency),
+
+    // Can't convert: Value overflow after conversion
+    DecimalAmountOverflow,
+}
+
+impl Display for FxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for FxError {}
+
+/// FX conversion table into single reporting currency.
+/// Balances are kept exact in native currency, conversion is used only for reporting.
+#[derive(Debug, Clone)]
+pub struct FxTable {
+    reporting: Currency,
+    rates: HashMap<Currency, Decimal>,
+}
+
+impl FxTable {
+    pub fn new(reporting: Currency) -> Self {
+        FxTable {
+            reporting,
+            rates: HashMap::new(),
+        }
+    }
+
+    pub fn from_rates<I>(reporting: Currency, rates: I) -> Result<Self, FxError>
+        where I: IntoIterator<Item=FxRate>
+    {
+        let mut table = FxTable::new(reporting);
+        for rate in rates {
+            table.insert(rate)?;
+        }
+        Ok(table)
+    }
+
+    pub fn insert(&mut self, rate: FxRate) -> Result<(), FxError> {
+        if rate.rate <= Decimal::ZERO {
+            return Err(FxError::InvalidRate(rate.currency));
+        }
+
+        self.rates.insert(rate.currency, rate.rate);
+        Ok(())
+    }
+
+    pub fn reporting(&self) -> &str {
+        &self.reporting
+    }
+
+    /// Rate into reporting currency, reporting currency itself is always 1
+    pub fn rate(&self, currency: &str) -> Option<Decimal> {
+        match currency == self.reporting {
+            true => Some(Decimal::ONE),
+            false => self.rates.get(currency).copied(),
+        }
+    }
+
+    pub fn convert(&self, currency: &str, amount: &Decimal) -> Result<Decimal, FxError> {
+        let rate = self.rate(currency).ok_or_else(|| FxError::RateNotFound(currency.to_string()))?;
+        amount.checked_mul(rate)
+            .map(|it| it.round_dp(CONVERTED_SCALE).normalize())
+            .ok_or(FxError::DecimalAmountOverflow)
+    }
+
+    /// Extended account row with native and converted figures
+    pub fn convert_account(&self, currency: &str, account: &AccountView) -> Result<ConvertedAccount, FxError> {
+        Ok(ConvertedAccount {
+            client: *account.client(),
+            currency: currency.to_string(),
+            available: *account.available(),
+            held: *account.held(),
+            total: *account.total(),
+            locked: account.locked(),
+            closed: account.closed(),
+            reporting_currency: self.reporting.clone(),
+            reporting_available: self.convert(currency, account.available())?,
+            reporting_held: self.convert(currency, account.held())?,
+            reporting_total: self.convert(currency, account.total())?,
+        })
+    }
+}
+
+/// Account state in native currency together with figures converted into reporting currency
+#[derive(Debug, Serialize)]
+pub struct ConvertedAccount {
+    client: Client,
+    currency: Currency,
+    #[serde(with = "rust_decimal::serde::str")]
+    available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    held: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    total: Decimal,
+    locked: bool,
+    closed: bool,
+    reporting_currency: Currency,
+    #[serde(with = "rust_decimal::serde::str")]
+    reporting_available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    reporting_held: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    reporting_total: Decimal,
+}
+
+impl ConvertedAccount {
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+    pub fn total(&self) -> &Decimal {
+        &self.total
+    }
+    pub fn reporting_total(&self) -> &Decimal {
+        &self.reporting_total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+    use rust_decimal::Decimal;
+    use crate::account::basic::BasicAccount;
+    use crate::fx::{FxError, FxRate, FxTable};
+
+    fn table() -> FxTable {
+        FxTable::from_rates("USD".to_string(), vec![
+            FxRate::new("EUR".to_string(), Decimal::from_str("1.1").unwrap()),
+            FxRate::new("JPY".to_string(), Decimal::from_str("0.0067").unwrap()),
+        ]).unwrap()
+    }
+
+    #[test]
+    fn convert() {
+        let table = table();
+        assert_eq!(table.convert("USD", &Decimal::from(10_u64)).unwrap(), Decimal::from(10_u64));
+        assert_eq!(table.convert("EUR", &Decimal::from(10_u64)).unwrap(), Decimal::from(11_u64));
+        assert_eq!(table.convert("JPY", &Decimal::from_str("1.2345").unwrap()).unwrap(), Decimal::from_str("0.0083").unwrap());
+        assert!(matches!(table.convert("GBP", &Decimal::ONE), Err(FxError::RateNotFound(_))));
+    }
+
+    #[test]
+    fn invalid_rate() {
+        let rates = vec![FxRate::new("EUR".to_string(), Decimal::ZERO)];
+        assert!(matches!(FxTable::from_rates("USD".to_string(), rates), Err(FxError::InvalidRate(_))));
+    }
+
+    #[test]
+    fn convert_account() {
+        let mut account = BasicAccount::new(1);
+        account.deposit(&Decimal::from(100_u64)).unwrap();
+
+        let converted = table().convert_account("EUR", &account.into()).unwrap();
+        assert_eq!(converted.client(), 1);
+        assert_eq!(converted.currency(), "EUR");
+        assert_eq!(converted.total(), &Decimal::from(100_u64));
+        assert_eq!(converted.reporting_total(), &Decimal::from(110_u64));
+    }
+}
@@ -0,0 +1,56 @@
+//! Transactions of previous runs. With accounts restored from snapshot, transactions of prior days are
+//! not held by processor, so disputes referencing them would fail with `OrgTransactionNotFound`.
+//! `BasicProcessor::with_history` consults `HistoricalTxLookup` in that case.
+
+use crate::prelude::*;
+use crate::error::ProcessError;
+use crate::transaction::{Transaction, TxId};
+use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+/// Source of archived deposits and withdrawals, e.g. previous days' input files or a database
+pub trait HistoricalTxLookup: Send {
+    /// Archived transaction with tx id, None when unknown
+    fn find(&self, tx: TxId) -> Result<Option<Transaction>, ProcessError>;
+}
+
+impl<F> HistoricalTxLookup for F
+    where F: Fn(TxId) -> Result<Option<Transaction>, ProcessError> + Send
+{
+    fn find(&self, tx: TxId) -> Result<Option<Transaction>, ProcessError> {
+        self(tx)
+    }
+}
+
+/// In-memory archive of deposits and withdrawals, other transactions can't be disputed and are skipped
+#[derive(Default)]
+pub struct TransactionArchive {
+    inner: IntMap<TxId, Transaction>,
+}
+
+impl TransactionArchive {
+    pub fn new() -> Self {
+        TransactionArchive::default()
+    }
+
+    /// Keep deposit or withdrawal with amount, later transaction with the same tx id replaces earlier one
+    pub fn insert(&mut self, transaction: Transaction) {
+        if matches!(transaction.r#type(), Deposit | Withdrawal) && transaction.amount().is_some() {
+            self.inner.insert(transaction.tx_id(), transaction);
+        }
+    }
+
+    /// Number of archived transactions
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+impl HistoricalTxLookup for TransactionArchive {
+    fn find(&self, tx: TxId) -> Result<Option<Transaction>, ProcessError> {
+        Ok(self.inner.get(&tx).cloned())
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+
+// sub-buckets per power of two, bucket width is at most 1/4 of its lower bound
+const SUB_BUCKET_BITS: u32 = 2;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+/// Log-linear histogram of durations (nanoseconds) with constant memory, percentiles are upper
+/// bounds of buckets (within 25% of recorded value). Serialized as count, p50, p99 and max in nanoseconds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    count: u64,
+    max: u64,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let index = bucket(nanos);
+        if self.counts.len() <= index {
+            self.counts.resize(index + 1, 0);
+        }
+
+        self.counts[index] += 1;
+        self.count += 1;
+        self.max = self.max.max(nanos);
+    }
+
+    /// Number of recorded durations
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    /// Duration below which `quantile` (0.0 - 1.0) of recorded durations are, None when empty
+    pub fn percentile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Duration::from_nanos(upper_bound(index).min(self.max)));
+            }
+        }
+
+        Some(self.max())
+    }
+}
+
+impl Serialize for LatencyHistogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let nanos = |quantile| self.percentile(quantile).map_or(0, |it| it.as_nanos() as u64);
+
+        let mut state = serializer.serialize_struct("LatencyHistogram", 4)?;
+        state.serialize_field("count", &self.count)?;
+        state.serialize_field("p50_ns", &nanos(0.5))?;
+        state.serialize_field("p99_ns", &nanos(0.99))?;
+        state.serialize_field("max_ns", &self.max)?;
+        state.end()
+    }
+}
+
+// values below SUB_BUCKETS have own buckets, then every power of two is split into SUB_BUCKETS
+fn bucket(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS {
+        return nanos as usize;
+    }
+
+    let exponent = 63 - nanos.leading_zeros();
+    let sub_bucket = (nanos >> (exponent - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+    ((exponent - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS + sub_bucket) as usize
+}
+
+// the highest value of bucket
+fn upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+
+    let shift = index / SUB_BUCKETS - 1;
+    let lower = (SUB_BUCKETS + index % SUB_BUCKETS) << shift;
+    lower.saturating_add((1 << shift) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::latency::{bucket, upper_bound, LatencyHistogram};
+
+    #[test]
+    fn bucket_then_upper_bound_covers_value() {
+        for nanos in [0, 3, 4, 7, 8, 9, 1_000, 123_456_789, u64::MAX] {
+            let index = bucket(nanos);
+            assert!(upper_bound(index) >= nanos);
+            assert!(index == 0 || upper_bound(index - 1) < nanos);
+        }
+    }
+
+    #[test]
+    fn record_then_percentiles() {
+        let mut histogram = LatencyHistogram::new();
+        assert_eq!(histogram.percentile(0.5), None);
+
+        for micros in 1..=100 {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        let p50 = histogram.percentile(0.5).unwrap();
+        assert!(p50 >= Duration::from_micros(50) && p50 <= Duration::from_micros(63));
+        let p99 = histogram.percentile(0.99).unwrap();
+        assert!(p99 >= Duration::from_micros(99) && p99 <= Duration::from_micros(100));
+        assert_eq!((histogram.count(), histogram.max()), (100, Duration::from_micros(100)));
+    }
+}
@@ -1,10 +1,25 @@
 //! Core library for calculating states and balance for client account.
 //! All calculation is based on the TransactionProcessor trait and on the implementation of this trait.
 //! This library contains two processors: BasicProcessor and WrapProcessor
+//! Without default `std` feature (and with `alloc`) account math and processors are `no_std + alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("core requires `std` or `alloc` feature");
 
 extern crate core;
+extern crate alloc;
 
 use crate::processor::wrap_processor::WrapTransactionProcessor;
+use crate::processor::multi_currency_processor::MultiCurrencyTransactionProcessor;
+use crate::processor::multi_tenant_processor::MultiTenantTransactionProcessor;
+#[cfg(feature = "std")]
+use crate::processor::mutex_processor::MutexTransactionProcessor;
+#[cfg(feature = "std")]
+use crate::processor::sharded_processor::ShardedTransactionProcessor;
+#[cfg(feature = "fastmath")]
+use crate::processor::fixed_processor::FixedTransactionProcessor;
 use crate::transaction::Transaction;
 use crate::error::ProcessError;
 #[allow(unused_imports)]
@@ -12,7 +27,13 @@ use crate::processor::basic_processor::BasicTransactionProcessor;
 #[allow(unused_imports)]
 use crate::repository::basic_account_repository::BasicAccountMemoryRepository;
 use crate::repository::wrap_account_repository::WrapAccountMemoryRepository;
+#[cfg(not(feature = "spill"))]
 use crate::repository::transaction_repository::TransactionMemoryRepository;
+#[cfg(feature = "spill")]
+use crate::repository::hybrid_transaction_repository::HybridTransactionRepository;
+#[cfg(feature = "fault-injection")]
+use crate::repository::fault_injector::FaultInjector;
+use crate::prelude::*;
 
 pub mod client;
 pub mod transaction;
@@ -20,18 +41,94 @@ pub mod transaction_type;
 pub(crate) mod repository;
 pub mod error;
 pub mod processor;
-mod account;
+pub mod account;
+pub(crate) mod prelude;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "std")]
+pub mod queue;
+pub mod fx;
+pub mod history;
+pub mod store;
+pub mod snapshot;
+pub mod expiry;
+pub mod accrual;
+pub mod erasure;
+pub mod explain;
+pub mod suspense;
+pub mod timeline;
+pub mod validation;
+pub mod audit;
+pub mod admin;
+#[cfg(feature = "std")]
+pub mod schedule;
+#[cfg(feature = "std")]
+pub mod dlq;
+pub mod shard;
+pub mod summary;
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod latency;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 pub type BasicProcessor = BasicTransactionProcessor;
 pub type WrapProcessor = WrapTransactionProcessor;
+pub type MultiCurrencyProcessor<P = BasicProcessor> = MultiCurrencyTransactionProcessor<P>;
+pub type MultiTenantProcessor<P = BasicProcessor> = MultiTenantTransactionProcessor<P>;
+#[cfg(feature = "std")]
+pub type MutexProcessor<P = BasicProcessor> = MutexTransactionProcessor<P>;
+#[cfg(feature = "std")]
+pub type ShardedProcessor<P = BasicProcessor> = ShardedTransactionProcessor<P>;
+#[cfg(feature = "fastmath")]
+pub type FixedProcessor = FixedTransactionProcessor;
 
 type WrapAccountRepository = WrapAccountMemoryRepository;
 type BasicAccountRepository = BasicAccountMemoryRepository;
-type TransactionRepository = TransactionMemoryRepository;
+#[cfg(not(feature = "spill"))]
+type StorageTransactionRepository = TransactionMemoryRepository;
+// keeps the most recent transactions in memory and spills older ones to disk
+#[cfg(feature = "spill")]
+type StorageTransactionRepository = HybridTransactionRepository;
+#[cfg(not(feature = "fault-injection"))]
+type TransactionRepository = StorageTransactionRepository;
+// fails or delays configured fraction of inserts/lookups, see `fault::set_fault_config`
+#[cfg(feature = "fault-injection")]
+type TransactionRepository = FaultInjector;
 
 
 /// Transaction processor trait is abstraction about process of transaction.
 /// We can easily build own transaction process if default processors are not enough.
-pub trait TransactionProcessor: IntoIterator {
+/// Ingest (`process`) is decoupled from result extraction (`finalize`), so processors
+/// can return richer outputs (accounts, report, event log) as `Output`.
+pub trait TransactionProcessor {
+    /// Result of processing returned by `finalize`
+    type Output;
+
     fn process(&mut self, transaction: Transaction) -> Result<(), ProcessError>;
+
+    /// Process transactions in order, result of every transaction in the same order.
+    /// By default transactions are processed one by one, processors may apply groups of
+    /// transactions at once (e.g. consecutive deposits of one client) with the same results.
+    fn process_batch(&mut self, transactions: Vec<Transaction>) -> Vec<Result<(), ProcessError>> {
+        transactions.into_iter().map(|it| self.process(it)).collect()
+    }
+
+    /// Consume processor and return result of processing
+    fn finalize(self) -> Self::Output where Self: Sized;
+}
+
+/// Thread-safe transaction processor, transactions can be processed from many threads through shared reference.
+/// Implementations must preserve per-client ordering semantics of `TransactionProcessor`
+/// (see `testing::concurrent_driver` behind `testing` feature).
+pub trait SharedTransactionProcessor: Sync {
+    /// Result of processing returned by `finalize`
+    type Output;
+
+    fn process(&self, transaction: Transaction) -> Result<(), ProcessError>;
+
+    /// Consume processor and return result of processing
+    fn finalize(self) -> Self::Output where Self: Sized;
 }
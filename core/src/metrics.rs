@@ -0,0 +1,122 @@
+//! Instrumentation of processors. Processors report metrics to `Recorder` set with `with_recorder`,
+//! so every frontend (cli, services) gets the same metric names and labels.
+//!
+//! Metrics:
+//! - `transactions_applied` counter, labels `type`
+//! - `transactions_rejected` counter, labels `type`, `code` (see `ProcessError::code`)
+//! - `transaction_amount` histogram of applied deposits and withdrawals, labels `type`
+//! - `accounts` gauge, number of accounts
+//! - `open_disputes` gauge, number of disputes not resolved or charged back (`BasicProcessor` only)
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use crate::error::ProcessError;
+use crate::transaction_type::TransactionType;
+
+pub const TRANSACTIONS_APPLIED: &str = "transactions_applied";
+pub const TRANSACTIONS_REJECTED: &str = "transactions_rejected";
+pub const TRANSACTION_AMOUNT: &str = "transaction_amount";
+pub const ACCOUNTS: &str = "accounts";
+pub const OPEN_DISPUTES: &str = "open_disputes";
+
+/// Metric labels, name and value
+pub type Labels<'a> = &'a [(&'static str, &'static str)];
+
+/// Metrics backend. All methods do nothing by default.
+pub trait Recorder: Send {
+    /// Increment counter by `value`
+    fn counter(&self, _name: &'static str, _value: u64, _labels: Labels) {}
+
+    /// Set gauge to `value`
+    fn gauge(&self, _name: &'static str, _value: f64, _labels: Labels) {}
+
+    /// Record `value` in histogram
+    fn histogram(&self, _name: &'static str, _value: f64, _labels: Labels) {}
+}
+
+/// Default recorder, metrics are dropped
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopRecorder;
+
+impl Recorder for NoopRecorder {}
+
+/// Adapter for `metrics` crate facade, metrics go to globally installed `metrics` recorder (exporter)
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MetricsRecorder;
+
+#[cfg(feature = "metrics")]
+impl Recorder for MetricsRecorder {
+    fn counter(&self, name: &'static str, value: u64, labels: Labels) {
+        metrics::counter!(name, labels).increment(value);
+    }
+
+    fn gauge(&self, name: &'static str, value: f64, labels: Labels) {
+        metrics::gauge!(name, labels).set(value);
+    }
+
+    fn histogram(&self, name: &'static str, value: f64, labels: Labels) {
+        metrics::histogram!(name, labels).record(value);
+    }
+}
+
+/// Outcome of processed transaction
+pub(crate) fn record_transaction(recorder: &dyn Recorder, r#type: TransactionType, amount: Option<Decimal>, result: &Result<(), ProcessError>) {
+    match result {
+        Ok(()) => {
+            recorder.counter(TRANSACTIONS_APPLIED, 1, &[("type", r#type.name())]);
+            if let Some(amount) = amount.and_then(|it| it.to_f64()) {
+                recorder.histogram(TRANSACTION_AMOUNT, amount, &[("type", r#type.name())]);
+            }
+        }
+        Err(e) => recorder.counter(TRANSACTIONS_REJECTED, 1, &[("type", r#type.name()), ("code", e.code())]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use crate::metrics::{Labels, Recorder, ACCOUNTS, TRANSACTIONS_APPLIED, TRANSACTIONS_REJECTED, TRANSACTION_AMOUNT};
+    use crate::{BasicProcessor, Transaction, TransactionProcessor};
+    use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+    // name, value and comma separated label values
+    type Metric = (&'static str, f64, String);
+
+    #[derive(Clone, Default)]
+    struct TestRecorder(Arc<Mutex<Vec<Metric>>>);
+
+    impl TestRecorder {
+        fn push(&self, name: &'static str, value: f64, labels: Labels) {
+            let labels = labels.iter().map(|(_, value)| *value).collect::<Vec<_>>().join(",");
+            self.0.lock().unwrap().push((name, value, labels));
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn counter(&self, name: &'static str, value: u64, labels: Labels) {
+            self.push(name, value as f64, labels);
+        }
+        fn gauge(&self, name: &'static str, value: f64, labels: Labels) {
+            self.push(name, value, labels);
+        }
+        fn histogram(&self, name: &'static str, value: f64, labels: Labels) {
+            self.push(name, value, labels);
+        }
+    }
+
+    #[test]
+    fn deposit_withdrawal_then_metrics() {
+        let recorder = TestRecorder::default();
+        let mut processor = BasicProcessor::new().with_recorder(recorder.clone());
+
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(10.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 2, Some(20.into()))).is_err());
+
+        let metrics = recorder.0.lock().unwrap();
+        assert!(metrics.contains(&(TRANSACTIONS_APPLIED, 1.0, "deposit".to_string())));
+        assert!(metrics.contains(&(TRANSACTION_AMOUNT, 10.0, "deposit".to_string())));
+        assert!(metrics.contains(&(TRANSACTIONS_REJECTED, 1.0, "withdrawal,E201".to_string())));
+        assert!(metrics.contains(&(ACCOUNTS, 1.0, String::new())));
+    }
+}
@@ -0,0 +1,549 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use serde::Serialize;
+use crate::{ProcessError, Transaction, TransactionProcessor};
+use crate::client::Client;
+use crate::dlq::DeadLetter;
+use crate::transaction::TxId;
+use crate::transaction_type::TransactionType;
+use crate::latency::LatencyHistogram;
+
+/// Sink is a destination for processing results (accounts, report rows, etc.).
+/// Records are written one by one, `finish` is called once after the last record.
+pub trait Sink<T> {
+    type Error;
+
+    fn write(&mut self, record: T) -> Result<(), Self::Error>;
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Collect records into memory
+impl<T> Sink<T> for Vec<T> {
+    type Error = Infallible;
+
+    fn write(&mut self, record: T) -> Result<(), Self::Error> {
+        self.push(record);
+        Ok(())
+    }
+}
+
+/// Borrowed sink, for example to keep access to sink registered in `dlq::DlqRouter`
+impl<T, S: Sink<T> + ?Sized> Sink<T> for &mut S {
+    type Error = S::Error;
+
+    fn write(&mut self, record: T) -> Result<(), Self::Error> {
+        (**self).write(record)
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        (**self).finish()
+    }
+}
+
+/// Cancellation token shared between pipeline and signal handler (or server shutdown hook).
+/// After cancel pipeline stops reading source, but processor output is still written to sink.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Underlying flag, for example to register it in signal handler
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+}
+
+/// Dump request shared between pipeline and signal handler (or admin endpoint).
+/// Pipeline checks it between transactions and passes current processor state to dump callback.
+#[derive(Debug, Clone, Default)]
+pub struct DumpTrigger {
+    requested: Arc<AtomicBool>,
+}
+
+impl DumpTrigger {
+    pub fn new() -> Self {
+        DumpTrigger::default()
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Return true (only once) if dump was requested
+    pub fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::Relaxed)
+    }
+
+    /// Underlying flag, for example to register it in signal handler
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.requested.clone()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    // abort run on first transaction rejected by processor
+    // by default rejected transactions are only counted
+    pub stop_on_process_error: bool,
+
+    // stop ingestion when cancelled
+    pub cancel: Option<CancellationToken>,
+
+    // call dump callback with current processor state when requested
+    pub dump: Option<DumpTrigger>,
+
+    // record processing latency per transaction type in report
+    pub measure_latency: bool,
+
+    // transactions passed to `TransactionProcessor::process_batch` at once (0 or 1: one by one),
+    // hook and cancellation are checked after every batch. Ignored with `stop_on_process_error`
+    // and `measure_latency`, which need results of single transactions.
+    pub batch_size: usize,
+}
+
+/// Summary of a single pipeline run.
+/// For cancelled run it is a checkpoint: `read` transactions from source were consumed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RunReport {
+    // transactions read from source
+    pub read: u64,
+
+    // transactions accepted by processor
+    pub processed: u64,
+
+    // transactions rejected by processor
+    pub rejected: u64,
+
+    // rejected transactions by error code (`ProcessError::code`)
+    pub rejected_by_code: BTreeMap<&'static str, u64>,
+
+    // records written to sink
+    pub written: u64,
+
+    // run stopped by cancellation token before the end of source
+    pub cancelled: bool,
+
+    // processing latency by transaction type (`TransactionType::name`), only with `measure_latency`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub latency: BTreeMap<&'static str, LatencyHistogram>,
+}
+
+/// Outcome of input transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Applied,
+    Rejected,
+}
+
+/// Per-transaction acknowledgement (see `run_with_results`), written in input order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransactionResult {
+    // ingestion sequence number of transaction
+    sequence: u64,
+    r#type: TransactionType,
+    client: Client,
+    tx: TxId,
+    outcome: Outcome,
+
+    // stable error code of rejected transaction, see `ProcessError::code`
+    code: Option<&'static str>,
+}
+
+impl TransactionResult {
+    pub fn new(transaction: &Transaction, result: &Result<(), ProcessError>) -> Self {
+        let (outcome, code) = match result {
+            Ok(_) => (Outcome::Applied, None),
+            Err(e) => (Outcome::Rejected, Some(e.code())),
+        };
+        TransactionResult {
+            sequence: transaction.sequence(),
+            r#type: *transaction.r#type(),
+            client: transaction.client(),
+            tx: transaction.tx_id(),
+            outcome,
+            code,
+        }
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+    pub fn r#type(&self) -> &TransactionType {
+        &self.r#type
+    }
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn tx_id(&self) -> TxId {
+        self.tx
+    }
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+    pub fn code(&self) -> Option<&'static str> {
+        self.code
+    }
+}
+
+#[derive(Debug)]
+pub enum PipelineError<S, W, D = Infallible, R = Infallible> {
+    // Can't read transaction from source
+    Source(S),
+
+    // Transaction rejected by processor (only with `stop_on_process_error`)
+    Process(ProcessError),
+
+    // Can't write record to sink
+    Sink(W),
+
+    // Can't write rejected transaction to dead letter queue
+    Dlq(D),
+
+    // Can't write transaction result
+    Results(R),
+}
+
+impl<S: Display, W: Display, D: Display, R: Display> Display for PipelineError<S, W, D, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineError::Source(e) => write!(f, "Source error: {}", e),
+            PipelineError::Process(e) => write!(f, "Process error: {}", e),
+            PipelineError::Sink(e) => write!(f, "Sink error: {}", e),
+            PipelineError::Dlq(e) => write!(f, "DLQ error: {}", e),
+            PipelineError::Results(e) => write!(f, "Results error: {}", e),
+        }
+    }
+}
+
+impl<S: Debug + Display, W: Debug + Display, D: Debug + Display, R: Debug + Display> Error for PipelineError<S, W, D, R> {}
+
+/// Report of finished run or error of source, processor, sink, dead letter queue or results sink
+pub type RunResult<S, W, D = Infallible, R = Infallible> = Result<RunReport, PipelineError<S, W, D, R>>;
+
+/// Read all transactions from source, process them and write processor output to sink.
+/// Source error aborts the run, processor errors are counted (or abort the run with `stop_on_process_error`).
+/// Cancelled run stops reading source and writes partial output.
+pub fn run<I, E, P, K>(source: I, processor: P, sink: &mut K, options: &RunOptions) -> Result<RunReport, PipelineError<E, K::Error>>
+    where I: IntoIterator<Item=Result<Transaction, E>>,
+          P: TransactionProcessor,
+          P::Output: IntoIterator,
+          K: Sink<<P::Output as IntoIterator>::Item> + ?Sized
+{
+    run_with_dump(source, processor, sink, options, |_| {})
+}
+
+/// Same as `run`, but `dump` is called with processor (current state) every time `options.dump` is triggered.
+/// Dump errors should be handled by callback, service keeps running.
+pub fn run_with_dump<I, E, P, K, D>(source: I, processor: P, sink: &mut K, options: &RunOptions, dump: D) -> Result<RunReport, PipelineError<E, K::Error>>
+    where I: IntoIterator<Item=Result<Transaction, E>>,
+          P: TransactionProcessor,
+          P::Output: IntoIterator,
+          K: Sink<<P::Output as IntoIterator>::Item> + ?Sized,
+          D: FnMut(&mut P)
+{
+    let mut dump = dump;
+    run_with_hook(source, processor, sink, options, |processor| {
+        if options.dump.as_ref().is_some_and(|it| it.take()) {
+            dump(processor);
+        }
+    })
+}
+
+/// Same as `run`, but `hook` is called with processor after every transaction,
+/// for example to dump state or apply scheduled postings.
+pub fn run_with_hook<I, E, P, K, H>(source: I, processor: P, sink: &mut K, options: &RunOptions, hook: H) -> Result<RunReport, PipelineError<E, K::Error>>
+    where I: IntoIterator<Item=Result<Transaction, E>>,
+          P: TransactionProcessor,
+          P::Output: IntoIterator,
+          K: Sink<<P::Output as IntoIterator>::Item> + ?Sized,
+          H: FnMut(&mut P)
+{
+    run_with_dlq(source, processor, sink, None::<&mut Vec<DeadLetter>>, options, hook)
+}
+
+/// Same as `run_with_hook`, but transactions rejected by processor are written to `dlq`
+/// (unless run is stopped by `stop_on_process_error`).
+pub fn run_with_dlq<I, E, P, K, D, H>(source: I, processor: P, sink: &mut K, dlq: Option<&mut D>, options: &RunOptions, hook: H) -> Result<RunReport, PipelineError<E, K::Error, D::Error>>
+    where I: IntoIterator<Item=Result<Transaction, E>>,
+          P: TransactionProcessor,
+          P::Output: IntoIterator,
+          K: Sink<<P::Output as IntoIterator>::Item> + ?Sized,
+          D: Sink<DeadLetter> + ?Sized,
+          H: FnMut(&mut P)
+{
+    run_with_results(source, processor, sink, dlq, None::<&mut Vec<TransactionResult>>, options, hook)
+}
+
+/// Same as `run_with_dlq`, but result of every transaction (applied or rejected with error code)
+/// is written to `results` in input order, so upstream systems get per-row acknowledgements.
+pub fn run_with_results<I, E, P, K, D, R, H>(source: I, processor: P, sink: &mut K, dlq: Option<&mut D>, results: Option<&mut R>, options: &RunOptions, hook: H)
+    -> RunResult<E, K::Error, D::Error, R::Error>
+    where I: IntoIterator<Item=Result<Transaction, E>>,
+          P: TransactionProcessor,
+          P::Output: IntoIterator,
+          K: Sink<<P::Output as IntoIterator>::Item> + ?Sized,
+          D: Sink<DeadLetter> + ?Sized,
+          R: Sink<TransactionResult> + ?Sized,
+          H: FnMut(&mut P)
+{
+    let mut hook = hook;
+    let mut dlq = dlq;
+    let mut results = results;
+    let mut processor = processor;
+    let mut report = RunReport::default();
+
+    let batch_size = match options.stop_on_process_error || options.measure_latency {
+        true => 1,
+        false => options.batch_size.max(1),
+    };
+    let mut source = source.into_iter();
+    let mut batch = Vec::new();
+
+    loop {
+        for record in source.by_ref().take(batch_size) {
+            let transaction = record.map_err(PipelineError::Source)?;
+            report.read += 1;
+
+            // source order, stored with retained transactions
+            batch.push(transaction.with_sequence(report.read));
+        }
+
+        if batch.is_empty() {
+            break;
+        }
+
+        if batch_size == 1 {
+            for transaction in batch.drain(..) {
+                // copy is kept only if it may be needed for dead letter or result
+                let retained = (dlq.is_some() || results.is_some()).then(|| transaction.clone());
+
+                let r#type = transaction.r#type().name();
+                let started = options.measure_latency.then(Instant::now);
+                let result = processor.process(transaction);
+                if let Some(started) = started {
+                    report.latency.entry(r#type).or_default().record(started.elapsed());
+                }
+                record_result(&mut report, dlq.as_deref_mut(), results.as_deref_mut(), retained, result, options)?;
+            }
+        } else {
+            let mut retained = (dlq.is_some() || results.is_some()).then(|| batch.clone().into_iter());
+            for result in processor.process_batch(std::mem::take(&mut batch)) {
+                let transaction = retained.as_mut().and_then(Iterator::next);
+                record_result(&mut report, dlq.as_deref_mut(), results.as_deref_mut(), transaction, result, options)?;
+            }
+        }
+
+        hook(&mut processor);
+
+        // checked after processing, so every consumed transaction is counted in checkpoint
+        if options.cancel.as_ref().is_some_and(|it| it.is_cancelled()) {
+            report.cancelled = true;
+            break;
+        }
+    }
+
+    for record in processor.finalize() {
+        sink.write(record).map_err(PipelineError::Sink)?;
+        report.written += 1;
+    }
+
+    sink.finish().map_err(PipelineError::Sink)?;
+    if let Some(dlq) = dlq {
+        dlq.finish().map_err(PipelineError::Dlq)?;
+    }
+    if let Some(results) = results {
+        results.finish().map_err(PipelineError::Results)?;
+    }
+
+    Ok(report)
+}
+
+// count processed or rejected transaction, rejected transaction is written to dlq, result of every transaction
+// is written to results (also of transaction stopping the run)
+fn record_result<S, W, D, R>(report: &mut RunReport, dlq: Option<&mut D>, results: Option<&mut R>, transaction: Option<Transaction>,
+                             result: Result<(), ProcessError>, options: &RunOptions) -> Result<(), PipelineError<S, W, D::Error, R::Error>>
+    where D: Sink<DeadLetter> + ?Sized,
+          R: Sink<TransactionResult> + ?Sized
+{
+    if let (Some(results), Some(transaction)) = (results, &transaction) {
+        results.write(TransactionResult::new(transaction, &result)).map_err(PipelineError::Results)?;
+    }
+
+    match result {
+        Ok(_) => report.processed += 1,
+        Err(e) if options.stop_on_process_error => return Err(PipelineError::Process(e)),
+        Err(e) => {
+            report.rejected += 1;
+            *report.rejected_by_code.entry(e.code()).or_default() += 1;
+            if let (Some(dlq), Some(transaction)) = (dlq, transaction) {
+                dlq.write(DeadLetter::new(transaction, &e)).map_err(PipelineError::Dlq)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::convert::Infallible;
+    use rust_decimal::Decimal;
+    use crate::{BasicProcessor, ProcessError, Transaction};
+    use crate::account::view::AccountView;
+    use crate::dlq::DeadLetter;
+    use crate::pipeline::{run, run_with_dlq, run_with_dump, run_with_hook, run_with_results, CancellationToken, DumpTrigger, Outcome, PipelineError, RunOptions, RunReport, TransactionResult};
+    use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+    fn transactions() -> Vec<Result<Transaction, Infallible>> {
+        vec![
+            Ok(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))),
+            Ok(Transaction::new(Withdrawal, 1, 2, Some(200_u64.into()))),
+            Ok(Transaction::new(Deposit, 2, 3, Some(50_u64.into()))),
+        ]
+    }
+
+    #[test]
+    fn run_with_latency_then_histogram_per_type() {
+        let mut sink: Vec<AccountView> = Vec::new();
+        let options = RunOptions { measure_latency: true, ..Default::default() };
+        let report = run(transactions(), BasicProcessor::new(), &mut sink, &options).unwrap();
+
+        let counts: Vec<_> = report.latency.iter().map(|(r#type, histogram)| (*r#type, histogram.count())).collect();
+        assert_eq!(counts, vec![("deposit", 2), ("withdrawal", 1)]);
+        assert!(report.latency["deposit"].percentile(0.99).is_some());
+    }
+
+    #[test]
+    fn run_then_report() {
+        let mut sink: Vec<AccountView> = Vec::new();
+        let report = run(transactions(), BasicProcessor::new(), &mut sink, &RunOptions::default());
+
+        assert_eq!(report.unwrap(), RunReport { read: 3, processed: 2, rejected: 1, rejected_by_code: BTreeMap::from([("E201", 1)]), written: 2, cancelled: false, latency: BTreeMap::new() });
+
+        sink.sort_by_key(|it| *it.client());
+        assert_eq!(sink[0].total(), &Decimal::from(100_u64));
+        assert_eq!(sink[1].total(), &Decimal::from(50_u64));
+    }
+
+    #[test]
+    fn run_then_stop_on_process_error() {
+        let mut sink: Vec<AccountView> = Vec::new();
+        let options = RunOptions { stop_on_process_error: true, ..Default::default() };
+        let report = run(transactions(), BasicProcessor::new(), &mut sink, &options);
+
+        assert!(matches!(report, Err(PipelineError::Process(ProcessError::NotSufficientAvailableFunds))));
+        assert!(sink.is_empty());
+    }
+
+    #[test]
+    fn run_then_source_error() {
+        let source = vec![Ok(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))), Err("broken row")];
+        let mut sink: Vec<AccountView> = Vec::new();
+        let report = run(source, BasicProcessor::new(), &mut sink, &RunOptions::default());
+
+        assert!(matches!(report, Err(PipelineError::Source("broken row"))));
+    }
+
+    #[test]
+    fn run_then_cancel() {
+        let cancel = CancellationToken::new();
+        let options = RunOptions { cancel: Some(cancel.clone()), ..Default::default() };
+
+        // cancel after first transaction
+        let source = transactions().into_iter().inspect(|_| cancel.cancel());
+        let mut sink: Vec<AccountView> = Vec::new();
+        let report = run(source, BasicProcessor::new(), &mut sink, &options);
+
+        assert_eq!(report.unwrap(), RunReport { read: 1, processed: 1, rejected: 0, rejected_by_code: BTreeMap::new(), written: 1, cancelled: true, latency: BTreeMap::new() });
+        assert_eq!(sink[0].total(), &Decimal::from(100_u64));
+    }
+
+    #[test]
+    fn run_then_dump() {
+        let trigger = DumpTrigger::new();
+        let options = RunOptions { dump: Some(trigger.clone()), ..Default::default() };
+
+        // request dump after every transaction
+        let source = transactions().into_iter().inspect(|_| trigger.request());
+        let mut dumps = Vec::new();
+        let mut sink: Vec<AccountView> = Vec::new();
+        let report = run_with_dump(source, BasicProcessor::new(), &mut sink, &options, |processor| {
+            dumps.push(processor.into_iter().count());
+        });
+
+        assert!(report.is_ok());
+        assert_eq!(dumps, vec![1, 1, 2]);
+        assert_eq!(sink.len(), 2);
+    }
+
+    #[test]
+    fn run_then_sequence_in_history() {
+        let mut sequences = Vec::new();
+        let mut sink: Vec<AccountView> = Vec::new();
+        let report = run_with_hook(transactions(), BasicProcessor::new(), &mut sink, &RunOptions::default(), |processor| {
+            sequences.push(processor.find_transaction(3).map(|it| it.sequence()));
+        });
+
+        assert!(report.is_ok());
+        // deposit tx 3 is the third transaction of source
+        assert_eq!(sequences, vec![None, None, Some(3)]);
+    }
+
+    #[test]
+    fn run_then_rejected_in_dlq() {
+        let mut dlq: Vec<DeadLetter> = Vec::new();
+        let mut sink: Vec<AccountView> = Vec::new();
+        let report = run_with_dlq(transactions(), BasicProcessor::new(), &mut sink, Some(&mut dlq), &RunOptions::default(), |_| {});
+
+        assert_eq!(report.unwrap().rejected, 1);
+        assert_eq!(dlq.len(), 1);
+        assert_eq!(dlq[0].transaction().tx_id(), 2);
+        assert_eq!(dlq[0].sequence(), 2);
+        assert_eq!(dlq[0].code(), "E201");
+    }
+
+    #[test]
+    fn run_batched_then_same_report_and_dlq() {
+        let options = RunOptions { batch_size: 2, ..Default::default() };
+        let mut dlq: Vec<DeadLetter> = Vec::new();
+        let mut sink: Vec<AccountView> = Vec::new();
+        let report = run_with_dlq(transactions(), BasicProcessor::new(), &mut sink, Some(&mut dlq), &options, |_| {}).unwrap();
+
+        assert_eq!((report.read, report.processed, report.rejected, report.written), (3, 2, 1, 2));
+        assert_eq!((dlq.len(), dlq[0].sequence()), (1, 2));
+    }
+
+    #[test]
+    fn run_batched_then_result_per_transaction_in_order() {
+        let options = RunOptions { batch_size: 2, ..Default::default() };
+        let mut results: Vec<TransactionResult> = Vec::new();
+        let mut sink: Vec<AccountView> = Vec::new();
+        let report = run_with_results(transactions(), BasicProcessor::new(), &mut sink, None::<&mut Vec<DeadLetter>>, Some(&mut results), &options, |_| {});
+
+        assert!(report.is_ok());
+        let results: Vec<_> = results.iter().map(|it| (it.sequence(), it.tx_id(), it.outcome(), it.code())).collect();
+        assert_eq!(results, vec![(1, 1, Outcome::Applied, None), (2, 2, Outcome::Rejected, Some("E201")), (3, 3, Outcome::Applied, None)]);
+    }
+}
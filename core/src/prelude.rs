@@ -0,0 +1,16 @@
+//! Allocation types and maps used across the crate, the same with `std` and with `no_std + alloc`
+
+pub(crate) use alloc::borrow::Cow;
+pub(crate) use alloc::boxed::Box;
+pub(crate) use alloc::string::{String, ToString};
+pub(crate) use alloc::vec::Vec;
+pub(crate) use alloc::{format, vec};
+use nohash_hasher::BuildNoHashHasher;
+
+#[cfg(feature = "std")]
+pub use std::collections::{hash_map, HashMap};
+#[cfg(not(feature = "std"))]
+pub use hashbrown::{hash_map, HashMap};
+
+/// HashMap with integer keys used as hashes (client, tx id), `nohash_hasher::IntMap` is std only
+pub type IntMap<K, V> = HashMap<K, V, BuildNoHashHasher<K>>;
@@ -0,0 +1,108 @@
+//! Lock-free reads of account state for queries (`ShardedProcessor::account`), published by ingestion
+//! after every transaction. Readers never take processor lock, old states are freed with epoch based
+//! reclamation when no reader can see them anymore.
+
+use std::sync::atomic::Ordering;
+use crossbeam_epoch::{self as epoch, Atomic, Owned, Shared};
+use crate::account::view::AccountView;
+use crate::client::Client;
+use crate::BasicProcessor;
+
+/// Current state of client account, for processors which can be read through `ShardedProcessor::account`
+pub trait AccountLookup {
+    fn find_account(&self, client: Client) -> Option<AccountView>;
+}
+
+impl AccountLookup for BasicProcessor {
+    fn find_account(&self, client: Client) -> Option<AccountView> {
+        BasicProcessor::find_account(self, client)
+    }
+}
+
+#[cfg(feature = "fastmath")]
+impl AccountLookup for crate::FixedProcessor {
+    fn find_account(&self, client: Client) -> Option<AccountView> {
+        crate::FixedProcessor::find_account(self, client)
+    }
+}
+
+/// Latest published state of every client account of processors `P`, slot per client id
+pub(crate) struct AccountReads<P> {
+    slots: Box<[Atomic<AccountView>]>,
+
+    // state of client account in processor
+    lookup: fn(&P, Client) -> Option<AccountView>,
+}
+
+impl<P> AccountReads<P> {
+    pub(crate) fn new(lookup: fn(&P, Client) -> Option<AccountView>) -> Self {
+        AccountReads { slots: (0..=Client::MAX).map(|_| Atomic::null()).collect(), lookup }
+    }
+
+    pub(crate) fn get(&self, client: Client) -> Option<AccountView> {
+        let guard = epoch::pin();
+        let account = self.slots[client as usize].load(Ordering::Acquire, &guard);
+        // state is freed only after all guards pinned before it was replaced are dropped
+        unsafe { account.as_ref() }.cloned()
+    }
+
+    /// Publish current state of client account in `processor`, writers of one client must be serialized (shard lock)
+    pub(crate) fn publish(&self, processor: &P, client: Client) {
+        let guard = epoch::pin();
+        let account = match (self.lookup)(processor, client) {
+            Some(account) => Owned::new(account).into_shared(&guard),
+            None => Shared::null(),
+        };
+        let previous = self.slots[client as usize].swap(account, Ordering::AcqRel, &guard);
+        if !previous.is_null() {
+            // previous state is no longer reachable from slot, readers may still hold it until unpinned
+            unsafe { guard.defer_destroy(previous) };
+        }
+    }
+}
+
+impl<P> Drop for AccountReads<P> {
+    fn drop(&mut self) {
+        for slot in self.slots.iter() {
+            // no readers are left, reads borrow processor
+            unsafe { drop(slot.load(Ordering::Relaxed, epoch::unprotected()).try_into_owned()) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use rust_decimal::Decimal;
+    use crate::{BasicProcessor, ShardedProcessor, SharedTransactionProcessor, Transaction};
+    use crate::shard::ShardRouter;
+    use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+    #[test]
+    fn concurrent_reads_then_latest_state() {
+        let processor = ShardedProcessor::new(ShardRouter::new(4).unwrap(), BasicProcessor::new).with_reads();
+        assert!(processor.account(1).is_none());
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for tx in 1..=1000 {
+                    assert!(processor.process(Transaction::new(Deposit, 1, tx, Some(Decimal::TWO))).is_ok());
+                }
+            });
+            scope.spawn(|| {
+                // available never decreases while only deposits are applied
+                let mut last = Decimal::ZERO;
+                for _ in 0..1000 {
+                    if let Some(account) = processor.account(1) {
+                        assert!(*account.available() >= last);
+                        last = *account.available();
+                    }
+                }
+            });
+        });
+
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 1001, Some(Decimal::ONE))).is_ok());
+        assert_eq!(processor.account(1).map(|it| *it.available()), Some(Decimal::from(1999_u64)));
+        assert!(processor.account(2).is_none());
+    }
+}
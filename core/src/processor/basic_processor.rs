@@ -1,11 +1,41 @@
+use crate::prelude::*;
+use alloc::collections::VecDeque;
+use rust_decimal::Decimal;
 use crate::account::basic;
+use crate::account::status::{AccountStatus, StatusChange, StatusListener};
+use crate::account::merge::AccountMerge;
+use crate::account::hierarchy::AccountHierarchy;
+use crate::metrics::{record_transaction, NoopRecorder, Recorder, ACCOUNTS, OPEN_DISPUTES};
+use crate::account::view::{AccountView, AccountsPage};
+use crate::account::iter::{Accounts, IntoAccounts};
+use crate::account::factory::AccountFactory;
+use crate::history::HistoricalTxLookup;
+use crate::store::{AccountStore, CacheStats};
+use crate::snapshot::Snapshot;
+use crate::expiry::{DisputeExpiry, ExpiredDispute, ExpiryAction, ExpiryListener};
+use crate::accrual::HeldAccrual;
+use crate::erasure::Tombstone;
+use crate::explain::{BalanceExplanation, DisputeOutcome, ExplainedTransaction};
+use crate::suspense::{SuspenseEntry, SuspenseListener, SuspenseReason};
+use crate::timeline::AccountTimeline;
+use crate::validation::{StandardValidator, TransactionValidator, ValidationContext};
 use crate::client::Client;
+use crate::transaction::TxId;
 use crate::{Transaction, TransactionProcessor};
 use crate::ProcessError::*;
 use crate::{BasicAccountRepository, TransactionRepository};
 use crate::error::ProcessError;
-use crate::error::ProcessError::{AccountLocked, AmountNotFound, MismatchClientId, OrgTransactionNotFound, TransactionExists, TransactionUnderDispute};
-use crate::transaction_type::TransactionType::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+use crate::error::ProcessError::{AccountLocked, AmountNotFound, MismatchClientId, OrgTransactionNotFound, TransactionUnderDispute};
+use crate::transaction_type::TransactionType;
+use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
+
+// retained transactions of client by tx id, with order they were retained
+pub(crate) type RetainedTxs = IntMap<TxId, u64>;
+
+/// Custom business rule evaluated before transaction is applied, with current state of client account
+/// (None for client seen the first time). `Err` rejects transaction, e.g. with `ProcessError::RuleViolation`.
+/// `ProcessError::ReviewRequired` also puts account under review (risk rule), see `BasicProcessor::release_review`.
+pub type Rule = Box<dyn FnMut(&Transaction, Option<&AccountView>) -> Result<(), ProcessError> + Send>;
 
 /// BasicTransactionProcessor contains separate repositories for account, transaction and dispute
 /// BasicTransactionProcessor is using BasicAccountRepository and BasicAccount.
@@ -20,6 +50,91 @@ pub struct BasicTransactionProcessor {
     // as alternative solution we can store this in HashSet<TxId> if transaction details not needed
     dispute_tx_repository: TransactionRepository,
 
+    // retained transactions of client, transaction can be disputed only by its owner
+    client_txs: IntMap<Client, RetainedTxs>,
+
+    // order of the last retained transaction
+    retained: u64,
+
+    // number of processed transactions, used as activity clock for accounts
+    sequence: u64,
+
+    // retained transactions can be disputed only within last N transactions, older ones are evicted
+    dispute_window: Option<u64>,
+
+    // (sequence, tx id) of retained transactions in processing order, only with dispute window
+    retention: VecDeque<(u64, TxId)>,
+
+    // open disputes are resolved or flagged when expired
+    dispute_expiry: Option<(DisputeExpiry, ExpiryAction)>,
+
+    // (sequence, tx id) of opened disputes in processing order, only with dispute expiry
+    open_disputes: VecDeque<(u64, TxId)>,
+
+    // receives events of expired disputes
+    expiry_listener: Option<ExpiryListener>,
+
+    // receives disputes, resolves and chargebacks rejected for missing or other client transaction
+    suspense_listener: Option<SuspenseListener>,
+
+    // receives status changes of accounts
+    status_listener: Option<StatusListener>,
+
+    // interest or penalty on funds held by dispute, posted on resolve or chargeback
+    held_accrual: Option<HeldAccrual>,
+
+    // sequence when dispute was opened, only with held accrual
+    dispute_opened: IntMap<TxId, u64>,
+
+    // mark accounts without activity in last N transactions as dormant in output
+    dormant_threshold: Option<u64>,
+
+    // validation run after standard validation, before transaction is applied
+    validators: Vec<Box<dyn TransactionValidator>>,
+
+    // bespoke validation, for example user supplied script
+    rule: Option<Rule>,
+
+    // deposits and withdrawals over limit are rejected
+    amount_limit: Option<Decimal>,
+
+    // historical transactions are processed: no account freezes and no listener notifications
+    backfill: bool,
+
+    // (sequence when closed, transaction) of resolved and charged back disputes by client in closing order,
+    // they are no longer retained
+    closed_disputes: IntMap<Client, VecDeque<(u64, ExplainedTransaction)>>,
+
+    // (sequence, client) of closed disputes in closing order, only with dispute window
+    closed_retention: VecDeque<(u64, Client)>,
+
+    // clients merged into other client account, transactions of merged client are redirected
+    merged: IntMap<Client, Client>,
+
+    // log of account merges
+    merges: Vec<AccountMerge>,
+
+    // anonymized log of erased clients
+    tombstones: Vec<Tombstone>,
+
+    // states of accounts after every change, only with timeline enabled
+    timeline: Option<AccountTimeline>,
+
+    // parent-child client relationships, used only for roll-up reporting
+    hierarchy: AccountHierarchy,
+
+    // metrics backend, metrics are dropped by default
+    recorder: Box<dyn Recorder>,
+
+    // archived transactions of previous runs, consulted when disputed transaction is not retained
+    history: Option<Box<dyn HistoricalTxLookup>>,
+
+    // persistent backend of accounts, accounts in memory are its cache
+    store: Option<Box<dyn AccountStore>>,
+
+    // hits and misses of accounts in memory, only with account store
+    cache_stats: CacheStats,
+
     // for future use if we want to store transaction with all kind errors
     // dead letter queue
     // _dlq_repository: DlqRepository,
@@ -41,9 +156,654 @@ impl BasicTransactionProcessor {
             client_repository,
             tx_repository,
             dispute_tx_repository,
+            client_txs: IntMap::default(),
+            retained: 0,
+            sequence: 0,
+            dispute_window: None,
+            retention: VecDeque::new(),
+            dispute_expiry: None,
+            open_disputes: VecDeque::new(),
+            expiry_listener: None,
+            suspense_listener: None,
+            status_listener: None,
+            held_accrual: None,
+            dispute_opened: IntMap::default(),
+            dormant_threshold: None,
+            validators: Vec::new(),
+            rule: None,
+            amount_limit: None,
+            backfill: false,
+            closed_disputes: IntMap::default(),
+            closed_retention: VecDeque::new(),
+            merged: IntMap::default(),
+            merges: Vec::new(),
+            tombstones: Vec::new(),
+            timeline: None,
+            hierarchy: AccountHierarchy::default(),
+            recorder: Box::new(NoopRecorder),
+            history: None,
+            store: None,
+            cache_stats: CacheStats::default(),
+        }
+    }
+
+    /// Accounts of clients seen the first time are created by `factory` instead of empty `BasicAccount::new`
+    pub fn with_account_factory<F: AccountFactory + 'static>(mut self, factory: F) -> Self {
+        self.client_repository.set_factory(Box::new(factory));
+        self
+    }
+
+    /// Processing metrics are reported to `recorder` (see `core::metrics`)
+    pub fn with_recorder<R: Recorder + 'static>(mut self, recorder: R) -> Self {
+        self.recorder = Box::new(recorder);
+        self
+    }
+
+    /// Disputes of transactions not retained by processor (e.g. of previous days, with accounts restored
+    /// from snapshot) are honored when `history` knows the transaction
+    pub fn with_history<H: HistoricalTxLookup + 'static>(mut self, history: H) -> Self {
+        self.history = Some(Box::new(history));
+        self
+    }
+
+    /// Accounts are loaded from `store` when missing in memory and every change of account is saved to it,
+    /// see `core::store`. Output contains only accounts held in memory (changed or warmed up in this run).
+    pub fn with_account_store<S: AccountStore + 'static>(mut self, store: S) -> Self {
+        self.store = Some(Box::new(store));
+        self
+    }
+
+    /// Load up to `limit` accounts with the most recent activity from account store before processing,
+    /// accounts already held in memory are kept. Returns number of loaded accounts, 0 without account store.
+    pub fn warm_up(&mut self, limit: usize) -> Result<usize, ProcessError> {
+        let Some(store) = self.store.as_mut() else {
+            return Ok(0);
+        };
+
+        let mut warmed = 0;
+        for account in store.load_recent(limit)? {
+            if self.client_repository.get_by_client(*account.client()).is_none() {
+                self.client_repository.insert(account.into());
+                warmed += 1;
+            }
+        }
+        self.cache_stats.warm(warmed as u64);
+        Ok(warmed)
+    }
+
+    /// Hits and misses of accounts held in memory, only counted with account store
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache_stats
+    }
+
+    /// When set, output accounts contain `dormant` flag
+    pub fn set_dormant_threshold(&mut self, threshold: Option<u64>) {
+        self.dormant_threshold = threshold;
+    }
+
+    /// Transactions can be disputed only within `window` processed transactions, older transactions are
+    /// evicted automatically (unless under dispute), so memory is proportional to the window rather than
+    /// to total history. Window applies to transactions retained after it is set.
+    pub fn set_dispute_window(&mut self, window: Option<u64>) {
+        self.dispute_window = window;
+        if window.is_none() {
+            self.retention.clear();
+        }
+    }
+
+    /// Deposits and withdrawals with amount over `limit` are rejected (`AmountOverLimit`), limit can be changed
+    /// between transactions, e.g. on config reload
+    pub fn set_amount_limit(&mut self, limit: Option<Decimal>) {
+        self.amount_limit = limit;
+    }
+
+    /// Backfill mode for reloading historical transactions into running processor. Chargeback doesn't lock account,
+    /// risk rule doesn't put account under review (transaction is still rejected) and status, expiry and suspense
+    /// listeners are not notified, so past freezes and alerts are not triggered again. Balances, retained
+    /// transactions, timeline and metrics are updated as for live transactions. Can be switched between transactions.
+    pub fn set_backfill(&mut self, backfill: bool) {
+        self.backfill = backfill;
+    }
+
+    pub fn backfill(&self) -> bool {
+        self.backfill
+    }
+
+    /// Disputes not resolved or charged back in time are resolved or flagged with `action`.
+    /// Expiry applies to disputes opened after it is set.
+    pub fn set_dispute_expiry(&mut self, expiry: Option<DisputeExpiry>, action: ExpiryAction) {
+        self.dispute_expiry = expiry.map(|it| (it, action));
+        if expiry.is_none() {
+            self.open_disputes.clear();
+        }
+    }
+
+    /// Events of expired disputes are passed to `listener`
+    pub fn set_expiry_listener(&mut self, listener: Option<ExpiryListener>) {
+        self.expiry_listener = listener;
+    }
+
+    /// Disputes, resolves and chargebacks referencing missing transactions or transactions of other client
+    /// are still rejected, but passed to `listener` with referenced amount (see `suspense::SuspenseLedger`)
+    pub fn set_suspense_listener(&mut self, listener: Option<SuspenseListener>) {
+        self.suspense_listener = listener;
+    }
+
+    /// Status changes of accounts (lock by chargeback, close, review, unlock) are passed to `listener`
+    pub fn set_status_listener(&mut self, listener: Option<StatusListener>) {
+        self.status_listener = listener;
+    }
+
+    /// Record state of account after every change, so it can be looked up with `account_at`.
+    /// Memory grows with number of processed transactions, changes before it is enabled aren't recorded.
+    pub fn set_timeline(&mut self, enabled: bool) {
+        self.timeline = enabled.then(|| self.timeline.take().unwrap_or_default());
+    }
+
+    /// Interest or penalty accrues on funds held by disputes opened after it is set. Accrual is posted
+    /// on resolve or chargeback to `accrued` account field, which is written for every account.
+    pub fn set_held_accrual(&mut self, accrual: Option<HeldAccrual>) {
+        self.held_accrual = accrual;
+        if accrual.is_none() {
+            self.dispute_opened.clear();
+        }
+    }
+
+    /// Output and dormant accounts are written in ascending client order, bit-for-bit reproducible
+    /// across runs and platforms (accounts are sorted when iterated)
+    pub fn set_ordered(&mut self, ordered: bool) {
+        self.client_repository.set_ordered(ordered);
+    }
+
+    /// Validator run for every transaction after `StandardValidator` and previously added validators
+    pub fn add_validator<V: TransactionValidator + 'static>(&mut self, validator: V) {
+        self.validators.push(Box::new(validator));
+    }
+
+    /// Rule evaluated for every transaction before it is applied
+    pub fn set_rule(&mut self, rule: Option<Rule>) {
+        self.rule = rule;
+    }
+
+    /// Sub-accounts relationships used by `rollup`
+    pub fn set_hierarchy(&mut self, hierarchy: AccountHierarchy) {
+        self.hierarchy = hierarchy;
+    }
+
+    /// Restore account state, for example from accounts snapshot (output of previous run).
+    /// Transaction history is not part of snapshot, so earlier transactions can't be disputed.
+    pub fn restore_account(&mut self, account: AccountView) {
+        let client = *account.client();
+        self.client_repository.insert(account.into());
+        self.record_state(client);
+    }
+
+    /// Restore accounts of versioned snapshot, accounts of older snapshot versions are migrated first.
+    /// Snapshot of unsupported version is rejected before any account is restored. Restored accounts
+    /// replace accounts of the same clients, so restoring the same snapshot again gives the same state.
+    /// Returns number of restored accounts.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<usize, ProcessError> {
+        let accounts = snapshot.into_accounts()?;
+        let restored = accounts.len();
+        accounts.into_iter().for_each(|it| self.restore_account(it));
+        Ok(restored)
+    }
+
+    /// Unlock account of client locked after chargeback. Not authorized nor audited, see `admin::Admin`.
+    pub fn unlock_account(&mut self, client: Client) -> Result<(), ProcessError> {
+        let client = self.merged_client(client);
+        self.cached(client)?;
+        let account = self.client_repository.get_mut_by_client(client).ok_or(AccountNotFound)?;
+        let status = account.status();
+        account.unlock();
+        self.record_state(client);
+        self.notify_status(client, status);
+        self.persist(client)
+    }
+
+    /// Release review of client account put under review by risk rule, withdrawals are allowed again.
+    /// Not authorized nor audited, see `admin::Admin`.
+    pub fn release_review(&mut self, client: Client) -> Result<(), ProcessError> {
+        let client = self.merged_client(client);
+        self.cached(client)?;
+        self.client_repository.get_mut_by_client(client).ok_or(AccountNotFound)?.release_review()?;
+        self.record_state(client);
+        self.notify_status(client, AccountStatus::UnderReview);
+        self.persist(client)
+    }
+
+    /// Manual correction of available funds of client account. Not authorized nor audited, see `admin::Admin`.
+    pub fn adjust_balance(&mut self, client: Client, amount: Decimal) -> Result<(), ProcessError> {
+        let client = self.merged_client(client);
+        self.cached(client)?;
+        self.client_repository.get_mut_by_client(client).ok_or(AccountNotFound)?.adjust(&amount)?;
+        self.record_state(client);
+        self.persist(client)
+    }
+
+    /// Retained (deposit/withdrawal) transaction with its ingestion sequence number
+    pub fn find_transaction(&self, tx: TxId) -> Option<Cow<'_, Transaction>> {
+        self.tx_repository.find_by_tx_id(&tx).ok().flatten()
+    }
+
+    /// Retained (deposit/withdrawal, not resolved or charged back) transactions of client in processing order,
+    /// transactions of merged clients follow own transactions of client
+    pub fn client_transactions(&self, client: Client) -> impl Iterator<Item=Cow<'_, Transaction>> + '_ {
+        self.retained_txs(self.merged_client(client))
+            .into_iter()
+            .filter_map(|it| self.tx_repository.find_by_tx_id(&it).ok().flatten())
+    }
+
+    /// Number of retained (deposit/withdrawal) transactions
+    pub fn retained_transactions(&self) -> usize {
+        self.tx_repository.len()
+    }
+
+    /// Evict old settled transactions, bounding memory of long-running processing: of every client
+    /// only the last `keep_last_n_per_client` retained transactions and transactions under dispute are kept.
+    /// Evicted transactions can't be disputed anymore (unless found by `with_history`) and their tx ids
+    /// are no longer rejected as duplicates. Closed disputes kept for `explain` are dropped too.
+    /// Returns number of evicted transactions.
+    pub fn compact(&mut self, keep_last_n_per_client: usize) -> Result<usize, ProcessError> {
+        self.compact_with(keep_last_n_per_client, |_| {})
+    }
+
+    /// Same as `compact`, evicted transactions are passed to `archive`, e.g. written to archive file
+    pub fn compact_with<F>(&mut self, keep_last_n_per_client: usize, mut archive: F) -> Result<usize, ProcessError>
+        where F: FnMut(Transaction)
+    {
+        let mut evicted = 0;
+        let clients: Vec<_> = self.client_txs.keys().copied().collect();
+        for client in clients {
+            let txs = self.retained_txs(client);
+            let old = txs.len().saturating_sub(keep_last_n_per_client);
+            for tx_id in &txs[..old] {
+                if self.dispute_tx_repository.exist_by_tx_id(tx_id)? {
+                    continue;
+                }
+
+                if let Some(transaction) = self.tx_repository.find_by_tx_id(tx_id)? {
+                    archive(transaction.into_owned());
+                }
+                self.tx_repository.delete_by_id(tx_id);
+                self.release(client, *tx_id);
+                evicted += 1;
+            }
+        }
+        self.closed_disputes.clear();
+        self.closed_retention.clear();
+
+        Ok(evicted)
+    }
+
+    /// Accounts without activity in last `threshold` transactions
+    pub fn dormant_accounts(&self, threshold: u64) -> impl Iterator<Item=AccountView> + '_ {
+        let sequence = self.sequence;
+        self.client_repository.get_all_account_iter()
+            .filter(move |it| it.is_dormant(sequence, threshold))
+            .map(AccountView::from)
+    }
+
+    /// Current state of client account, for example to publish update after each transaction
+    pub fn find_account(&self, client: Client) -> Option<AccountView> {
+        self.client_repository.get_by_client(client).map(AccountView::from)
+    }
+
+    /// State of client account after `sequence` processed transactions (see `set_timeline`), None when
+    /// account didn't exist at that point or timeline is not enabled. Merged clients aren't followed.
+    pub fn account_at(&self, client: Client, sequence: u64) -> Option<AccountView> {
+        self.timeline.as_ref()?.at(client, sequence).cloned()
+    }
+
+    /// Recorded changes of client account (see `set_timeline`) in processing order
+    pub fn account_changes(&self, client: Client) -> impl Iterator<Item=(u64, Option<&AccountView>)> + '_ {
+        self.timeline.iter().flat_map(move |it| it.changes(client))
+    }
+
+    /// Retained transactions (with open disputes) and transactions of closed disputes which compose current
+    /// available and held funds of client, with dispute window only disputes closed within the window. Funds of
+    /// restored snapshot, evicted (also compacted) transactions, merges and adjustments are reported as unexplained.
+    pub fn explain(&self, client: Client) -> Result<BalanceExplanation, ProcessError> {
+        let client = self.merged_client(client);
+        let account = self.client_repository.get_by_client(client).ok_or(AccountNotFound)?;
+
+        let mut transactions = Vec::new();
+        for tx_id in self.retained_txs(client) {
+            let Some(transaction) = self.tx_repository.find_by_tx_id(&tx_id)? else {
+                continue;
+            };
+            if let Some(amount) = transaction.amount() {
+                let dispute = self.dispute_tx_repository.exist_by_tx_id(&tx_id)?.then_some(DisputeOutcome::Open);
+                transactions.push(ExplainedTransaction::new(tx_id, *transaction.r#type(), amount, dispute));
+            }
+        }
+        transactions.extend(self.closed_disputes.get(&client).into_iter().flatten().map(|(_, it)| it.clone()));
+
+        Ok(BalanceExplanation::new(client, *account.available(), *account.held(), transactions))
+    }
+
+    /// Current state of up to `limit` accounts in ascending client order, starting after `cursor`
+    /// (`next_cursor` of previous page, None for the first page)
+    pub fn accounts_page(&self, cursor: Option<Client>, limit: usize) -> AccountsPage {
+        let accounts = self.client_repository.page(cursor, limit).into_iter().map(AccountView::from).collect();
+        AccountsPage::new(accounts, limit)
+    }
+
+    /// Consolidated account of `parent` with all its sub-accounts (see `set_hierarchy`)
+    pub fn rollup(&self, parent: Client) -> Result<AccountView, ProcessError> {
+        self.hierarchy.rollup(parent, |client| self.find_account(client))
+    }
+
+    /// Merge account of `from` client into account of `to` client, e.g. after identity deduplication.
+    /// Available and held funds are moved and `from` account is removed. Later transactions of `from`
+    /// (also resolve/chargeback of disputes opened before merge) are applied to `to` account.
+    /// Merge is recorded in `merges` log.
+    pub fn merge_accounts(&mut self, from: Client, to: Client) -> Result<(), ProcessError> {
+        let (from, to) = (self.merged_client(from), self.merged_client(to));
+        if from == to {
+            return Err(InvalidMerge);
         }
+        self.cached(from)?;
+        self.cached(to)?;
+        // stored state of target account is written back when merge can't be stored
+        let before = self.store.as_ref().and(self.client_repository.get_by_client(to)).map(AccountView::from);
+
+        // source account is removed first (and restored if rejected), target account can't be borrowed with it
+        let source = self.client_repository.remove(from).ok_or(InvalidMerge)?;
+        let result = match source.locked() {
+            true => Err(AccountLocked),
+            false => self.account(to).and_then(|it| it.merge(&source)),
+        };
+        if let Err(e) = result {
+            self.client_repository.insert(source);
+            return Err(e);
+        }
+        self.client_repository.find_by_client(to).touch(self.sequence);
+        // both accounts are stored at once, target account which can't be stored is dropped and loaded again
+        // (together with source account) by the next transaction
+        if let Some(store) = self.store.as_mut() {
+            let merged = self.client_repository.get_by_client(to).map(AccountView::from).ok_or(InvalidMerge)?;
+            if let Err(e) = store.save_merged(&merged, before.as_ref(), from) {
+                self.client_repository.remove(to);
+                return Err(e);
+            }
+        }
+        self.record_state(from);
+        self.record_state(to);
+
+        // disputes of `from` transactions are opened (and resolved) by `to` client, its transactions follow
+        // transactions of `to` client
+        for tx_id in self.retained_txs(from) {
+            self.retain_order(to, tx_id);
+        }
+        self.client_txs.remove(&from);
+        if let Some(txs) = self.closed_disputes.remove(&from) {
+            let closed = self.closed_disputes.entry(to).or_default();
+            closed.extend(txs);
+            closed.make_contiguous().sort_by_key(|(sequence, _)| *sequence);
+        }
+
+        // clients merged into `from` earlier follow it
+        for client in self.merged.values_mut().filter(|it| **it == from) {
+            *client = to;
+        }
+        self.merged.insert(from, to);
+        self.merges.push(AccountMerge::new(from, to, self.sequence, *source.available(), *source.held()));
+
+        Ok(())
+    }
+
+    /// Account merges in order they were done
+    pub fn merges(&self) -> &[AccountMerge] {
+        &self.merges
+    }
+
+    /// Erase client for compliance-driven deletion request: account (also with held funds) and retained
+    /// transactions of client are removed, merges of client are forgotten, clients merged into it are
+    /// unlinked. Only anonymized tombstone is recorded in `tombstones` log. Later transactions of client
+    /// open a new account.
+    pub fn erase_client(&mut self, client: Client) -> Result<&Tombstone, ProcessError> {
+        // stored account is deleted first, so failed delete leaves client untouched
+        self.cached(client)?;
+        if let Some(store) = self.store.as_mut() {
+            store.delete(client)?;
+        }
+
+        let account = self.client_repository.remove(client);
+        let txs = self.client_txs.remove(&client).unwrap_or_default();
+        self.closed_disputes.remove(&client);
+        if account.is_none() && txs.is_empty() {
+            return Err(AccountNotFound);
+        }
+
+        for tx_id in txs.keys() {
+            self.tx_repository.delete_by_id(tx_id);
+            self.dispute_tx_repository.delete_by_id(tx_id);
+            self.dispute_opened.remove(tx_id);
+        }
+        // queued retention and dispute expiry entries of removed transactions are skipped when due
+
+        self.merged.retain(|from, to| *from != client && *to != client);
+        self.merges.retain(|it| it.from() != client && it.to() != client);
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.forget(client);
+        }
+
+        self.tombstones.push(Tombstone::new(self.sequence, account.is_some(), txs.len() as u64));
+        Ok(&self.tombstones[self.tombstones.len() - 1])
+    }
+
+    /// Tombstones of erased clients in order they were erased
+    pub fn tombstones(&self) -> &[Tombstone] {
+        &self.tombstones
+    }
+
+    // client which account holds funds of given client
+    fn merged_client(&self, client: Client) -> Client {
+        self.merged.get(&client).copied().unwrap_or(client)
+    }
+
+    // current state of client account is recorded in timeline
+    fn record_state(&mut self, client: Client) {
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.record(client, self.sequence, self.client_repository.get_by_client(client).map(AccountView::from));
+        }
+    }
+
+    // account missing in memory is loaded from account store (read-through)
+    fn cached(&mut self, client: Client) -> Result<(), ProcessError> {
+        let Some(store) = self.store.as_mut() else {
+            return Ok(());
+        };
+
+        if self.client_repository.get_by_client(client).is_some() {
+            self.cache_stats.hit();
+            return Ok(());
+        }
+        self.cache_stats.miss();
+        if let Some(account) = store.load(client)? {
+            self.client_repository.insert(account.into());
+        }
+        Ok(())
+    }
+
+    // current state of client account is saved to account store (write-through), removed account is deleted.
+    // Account which can't be saved (e.g. changed by other instance) is dropped, the next transaction loads it again.
+    fn persist(&mut self, client: Client) -> Result<(), ProcessError> {
+        let Some(store) = self.store.as_mut() else {
+            return Ok(());
+        };
+
+        let result = match self.client_repository.get_by_client(client) {
+            Some(account) => store.save(&AccountView::from(account)),
+            None => store.delete(client),
+        };
+        if result.is_err() {
+            self.client_repository.remove(client);
+        }
+        result
+    }
+
+    // change of account status since `before` is passed to status listener
+    fn notify_status(&mut self, client: Client, before: AccountStatus) {
+        let Some(listener) = self.status_listener.as_mut().filter(|_| !self.backfill) else {
+            return;
+        };
+
+        match self.client_repository.get_by_client(client).map(basic::BasicAccount::status) {
+            Some(status) if status != before => listener(&StatusChange::new(client, before, status, self.sequence)),
+            _ => {}
+        }
+    }
+
+    // archived transaction is retained again, so it can be disputed, resolved and charged back
+    fn restore_historical(&mut self, tx_id: TxId) -> Result<(), ProcessError> {
+        let transaction = match &self.history {
+            Some(history) if !self.tx_repository.exist_by_tx_id(&tx_id)? => history.find(tx_id)?,
+            _ => None,
+        };
+
+        if let Some(transaction) = transaction.filter(|it| it.tx_id() == tx_id) {
+            let client = self.merged_client(transaction.client());
+            self.tx_repository.insert(tx_id, transaction)?;
+            self.retain(client, tx_id);
+        }
+        Ok(())
+    }
+
+    // transaction is retained, so it can be disputed by its owner
+    fn retain(&mut self, client: Client, tx_id: TxId) {
+        self.retain_order(client, tx_id);
+        if self.dispute_window.is_some() {
+            self.retention.push_back((self.sequence, tx_id));
+        }
+    }
+
+    // transaction of client is retained after all retained so far
+    fn retain_order(&mut self, client: Client, tx_id: TxId) {
+        self.retained += 1;
+        self.client_txs.entry(client).or_default().insert(tx_id, self.retained);
+    }
+
+    // retained transactions of client in order they were retained
+    fn retained_txs(&self, client: Client) -> Vec<TxId> {
+        let mut txs: Vec<_> = self.client_txs.get(&client).into_iter().flatten().map(|(tx_id, order)| (*order, *tx_id)).collect();
+        txs.sort_unstable();
+        txs.into_iter().map(|(_, tx_id)| tx_id).collect()
+    }
+
+    // transactions retained before dispute window are evicted, transactions under dispute are kept
+    // until resolved or charged back. Closed disputes kept for `explain` are evicted after the window too.
+    fn expire(&mut self) -> Result<(), ProcessError> {
+        let Some(window) = self.dispute_window else {
+            return Ok(());
+        };
+
+        while let Some((_, tx_id)) = self.retention.front().copied().filter(|(it, _)| it.saturating_add(window) < self.sequence) {
+            if !self.dispute_tx_repository.exist_by_tx_id(&tx_id)? {
+                if let Some(client) = self.tx_repository.find_by_tx_id(&tx_id)?.map(|it| it.client()) {
+                    self.tx_repository.delete_by_id(&tx_id);
+                    self.release(self.merged_client(client), tx_id);
+                }
+            }
+            self.retention.pop_front();
+        }
+
+        let sequence = self.sequence;
+        while let Some((_, client)) = self.closed_retention.front().copied().filter(|(it, _)| it.saturating_add(window) < sequence) {
+            let client = self.merged_client(client);
+            if let Some(closed) = self.closed_disputes.get_mut(&client) {
+                while closed.front().is_some_and(|(it, _)| it.saturating_add(window) < sequence) {
+                    closed.pop_front();
+                }
+                if closed.is_empty() {
+                    self.closed_disputes.remove(&client);
+                }
+            }
+            self.closed_retention.pop_front();
+        }
+        Ok(())
+    }
+
+    // opened dispute can expire and accrues on held funds
+    fn open_dispute(&mut self, tx_id: TxId) {
+        if self.dispute_expiry.is_some() {
+            self.open_disputes.push_back((self.sequence, tx_id));
+        }
+        if self.held_accrual.is_some() {
+            self.dispute_opened.insert(tx_id, self.sequence);
+        }
+    }
+
+    // accrual on amount held by dispute since it was opened
+    fn accrued(&self, tx_id: TxId, amount: Decimal) -> Result<Option<Decimal>, ProcessError> {
+        match (&self.held_accrual, self.dispute_opened.get(&tx_id)) {
+            (Some(accrual), Some(opened)) => accrual.accrue(amount, self.sequence.saturating_sub(*opened)).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    // disputes opened before `before` sequence (all with None) expire, already closed disputes are skipped
+    fn expire_disputes(&mut self, before: Option<u64>) -> Result<(), ProcessError> {
+        let Some((_, action)) = self.dispute_expiry else {
+            return Ok(());
+        };
+
+        while let Some((_, tx_id)) = self.open_disputes.front().copied().filter(|(it, _)| before.is_none_or(|before| *it < before)) {
+            if let Some(dispute_tx) = self.dispute_tx_repository.find_by_tx_id(&tx_id)?.map(Cow::into_owned) {
+                let client = self.merged_client(dispute_tx.client());
+                let amount = self.tx_repository.find_by_tx_id(&tx_id)?.and_then(|it| it.amount()).unwrap_or_default();
+
+                // dispute of locked account can't be resolved
+                let action = match action {
+                    ExpiryAction::Resolve if self.resolve(Transaction::new(Resolve, client, tx_id, None)).is_ok() => ExpiryAction::Resolve,
+                    _ => ExpiryAction::Flag,
+                };
+                if action == ExpiryAction::Resolve {
+                    self.record_state(client);
+                }
+                if let Some(listener) = self.expiry_listener.as_mut().filter(|_| !self.backfill) {
+                    listener(&ExpiredDispute::new(tx_id, client, amount, self.sequence, action));
+                }
+            }
+            self.open_disputes.pop_front();
+        }
+        Ok(())
+    }
+
+    // rejected transaction referencing missing or other client transaction goes to suspense
+    fn suspense(&mut self, r#type: TransactionType, client: Client, tx_id: TxId, amount: Option<Decimal>, error: &ProcessError) {
+        let Some(listener) = self.suspense_listener.as_mut().filter(|_| !self.backfill) else {
+            return;
+        };
+
+        let reason = match (r#type, error) {
+            (Dispute | Resolve | Chargeback, OrgTransactionNotFound) => SuspenseReason::MissingTransaction,
+            (Resolve | Chargeback, DisputedTransactionNotFound) => SuspenseReason::MissingDispute,
+            (Dispute | Resolve | Chargeback, MismatchClientId) => SuspenseReason::ClientMismatch,
+            _ => return,
+        };
+        let referenced = self.tx_repository.find_by_tx_id(&tx_id).ok().flatten().and_then(|it| it.amount());
+        listener(&SuspenseEntry::new(r#type, client, tx_id, reason, referenced.or(amount), self.sequence));
     }
 
+    // transaction of closed dispute is kept for `explain`, with dispute window only within the window
+    fn close_dispute(&mut self, client: Client, transaction: ExplainedTransaction) {
+        self.closed_disputes.entry(client).or_default().push_back((self.sequence, transaction));
+        if self.dispute_window.is_some() {
+            self.closed_retention.push_back((self.sequence, client));
+        }
+    }
+
+    // transaction is no longer retained (resolved, charged back or evicted)
+    fn release(&mut self, client: Client, tx_id: TxId) {
+        if let Some(txs) = self.client_txs.get_mut(&client) {
+            txs.remove(&tx_id);
+            if txs.is_empty() {
+                self.client_txs.remove(&client);
+            }
+        }
+    }
 
     fn account(&mut self, client: Client) -> Result<&mut basic::BasicAccount, ProcessError> {
         let account = self.client_repository.find_by_client(client);
@@ -61,14 +821,16 @@ impl BasicTransactionProcessor {
     fn withdrawal(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
         let amount = &transaction.amount().ok_or(AmountNotFound)?;
 
-        if self.tx_repository.exist_by_tx_id(&transaction.tx_id()) {
-            return Err(TransactionExists);
-        }
-
-        let account = self.account(transaction.client())?;
-        let _ = account.withdrawal(amount)?;
+        let tx_id = transaction.tx_id();
+        let client = transaction.client();
         // The document is a bit unclear about what kind of transactions can be disputed, so we must save withdrawal transactions
-        self.tx_repository.insert(transaction.tx_id(), transaction);
+        // transaction is stored first (and removed if rejected), so storage error leaves account untouched
+        self.tx_repository.insert(tx_id, transaction)?;
+        if let Err(e) = self.account(client).and_then(|it| it.withdrawal(amount)).and_then(|_| self.persist(client)) {
+            self.tx_repository.delete_by_id(&tx_id);
+            return Err(e);
+        }
+        self.retain(client, tx_id);
 
         Ok(())
     }
@@ -78,15 +840,16 @@ impl BasicTransactionProcessor {
     fn deposit(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
         let amount = &transaction.amount().ok_or(AmountNotFound)?;
 
-        if self.tx_repository.exist_by_tx_id(&transaction.tx_id()) {
-            return Err(TransactionExists);
-        }
-
-        let account = self.account(transaction.client())?;
-
-        let _ = account.deposit(amount)?;
+        let tx_id = transaction.tx_id();
+        let client = transaction.client();
         // The document is a bit unclear about what kind of transactions can be disputed, so we must save deposit transactions
-        self.tx_repository.insert(transaction.tx_id(), transaction);
+        // transaction is stored first (and removed if rejected), so storage error leaves account untouched
+        self.tx_repository.insert(tx_id, transaction)?;
+        if let Err(e) = self.account(client).and_then(|it| it.deposit(amount)).and_then(|_| self.persist(client)) {
+            self.tx_repository.delete_by_id(&tx_id);
+            return Err(e);
+        }
+        self.retain(client, tx_id);
         Ok(())
     }
 
@@ -95,32 +858,36 @@ impl BasicTransactionProcessor {
     /// that the clients available funds should decrease by the amount disputed, their held funds should
     /// increase by the amount disputed, while their total funds should remain the same.
     fn dispute(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
-        if self.dispute_tx_repository.exist_by_tx_id(&transaction.tx_id()) {
+        if self.dispute_tx_repository.exist_by_tx_id(&transaction.tx_id())? {
             return Err(TransactionUnderDispute);
         }
 
-        let org_tx = self.tx_repository.find_by_tx_id(&transaction.tx_id()).ok_or(OrgTransactionNotFound)?;
-
-        if org_tx.client() != transaction.client() {
-            return Err(MismatchClientId);
-        }
+        let org_tx = self.tx_repository.find_by_tx_id(&transaction.tx_id())?.ok_or(OrgTransactionNotFound)?;
 
         // The document is a bit unclear about what kind of transactions can be disputed
         match (org_tx.r#type(), org_tx.amount()) {
             (Deposit, Some(amount)) => {
-                let account = self.account(transaction.client())?;
+                let (tx_id, client) = (transaction.tx_id(), transaction.client());
+                self.dispute_tx_repository.insert(tx_id, transaction)?;
 
                 // 1. In multi thread env we need start transaction or use some *Lock
-                let _ = account.dispute_deposit(&amount)?;
-                self.dispute_tx_repository.insert(transaction.tx_id(), transaction);
+                if let Err(e) = self.account(client).and_then(|it| it.dispute_deposit(&amount)).and_then(|_| self.persist(client)) {
+                    self.dispute_tx_repository.delete_by_id(&tx_id);
+                    return Err(e);
+                }
+                self.open_dispute(tx_id);
                 Ok(())
             }
             (Withdrawal, Some(amount)) => {
-                let account = self.account(transaction.client())?;
+                let (tx_id, client) = (transaction.tx_id(), transaction.client());
+                self.dispute_tx_repository.insert(tx_id, transaction)?;
 
                 // 1. In multi thread env we need start transaction or use some *Lock
-                let _ = account.dispute_withdrawal(&amount)?;
-                self.dispute_tx_repository.insert(transaction.tx_id(), transaction);
+                if let Err(e) = self.account(client).and_then(|it| it.dispute_withdrawal(&amount)).and_then(|_| self.persist(client)) {
+                    self.dispute_tx_repository.delete_by_id(&tx_id);
+                    return Err(e);
+                }
+                self.open_dispute(tx_id);
                 Ok(())
             }
             _ => Err(InvalidTransactionTypeOrAmount)
@@ -132,23 +899,27 @@ impl BasicTransactionProcessor {
     /// decrease by the amount no longer disputed, their available funds should increase by the
     /// amount no longer disputed, and their total funds should remain the same.
     fn resolve(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
-        let dispute_tx = self.dispute_tx_repository.find_by_tx_id(&transaction.tx_id()).ok_or(DisputedTransactionNotFound)?;
-        let org_tx = self.tx_repository.find_by_tx_id(&dispute_tx.tx_id()).ok_or(OrgTransactionNotFound)?;
-
-        if org_tx.client() != transaction.client() {
-            return Err(MismatchClientId);
-        }
+        let dispute_tx = self.dispute_tx_repository.find_by_tx_id(&transaction.tx_id())?.ok_or(DisputedTransactionNotFound)?;
+        let org_tx = self.tx_repository.find_by_tx_id(&dispute_tx.tx_id())?.ok_or(OrgTransactionNotFound)?;
 
         // can we use resolve only for withdrawal?
-        match (org_tx.r#type(), org_tx.amount()) {
-            (Withdrawal | Deposit, Some(amount)) => {
+        match (*org_tx.r#type(), org_tx.amount()) {
+            (r#type @ (Withdrawal | Deposit), Some(amount)) => {
+                let accrued = self.accrued(transaction.tx_id(), amount)?;
                 let account = self.account(transaction.client())?;
 
                 // 1. In multi thread env we need start transaction or use some *Lock
-                let _ = account.resolve(&amount)?;
+                account.resolve(&amount)?;
+                if let Some(accrued) = accrued {
+                    account.accrue(accrued);
+                }
+                self.persist(transaction.client())?;
                 self.dispute_tx_repository.delete_by_id(&transaction.tx_id());
                 // no re-dispute allowed
                 self.tx_repository.delete_by_id(&transaction.tx_id());
+                self.release(transaction.client(), transaction.tx_id());
+                self.dispute_opened.remove(&transaction.tx_id());
+                self.close_dispute(transaction.client(), ExplainedTransaction::new(transaction.tx_id(), r#type, amount, Some(DisputeOutcome::Resolved)));
 
                 Ok(())
             }
@@ -161,60 +932,238 @@ impl BasicTransactionProcessor {
     /// total funds should decrease by the amount previously disputed. If a chargeback occurs the
     /// client's account should be immediately frozen.
     fn charge_back(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
-        let dispute_tx = self.dispute_tx_repository.find_by_tx_id(&transaction.tx_id()).ok_or(DisputedTransactionNotFound)?;
-        let org_tx = self.tx_repository.find_by_tx_id(&dispute_tx.tx_id()).ok_or(OrgTransactionNotFound)?;
-
-        if org_tx.client() != transaction.client() {
-            return Err(MismatchClientId);
-        }
+        let dispute_tx = self.dispute_tx_repository.find_by_tx_id(&transaction.tx_id())?.ok_or(DisputedTransactionNotFound)?;
+        let org_tx = self.tx_repository.find_by_tx_id(&dispute_tx.tx_id())?.ok_or(OrgTransactionNotFound)?;
 
         // can we use chargeback only for withdrawal?
-        match (org_tx.r#type(), org_tx.amount()) {
-            (Withdrawal | Deposit, Some(amount)) => {
+        match (*org_tx.r#type(), org_tx.amount()) {
+            (r#type @ (Withdrawal | Deposit), Some(amount)) => {
+                let accrued = self.accrued(transaction.tx_id(), amount)?;
+                let backfill = self.backfill;
                 let account = self.account(transaction.client())?;
 
                 // 1. In multi thread env we need start transaction or use some *Lock
-                let _ = account.chargeback(&amount)?;
+                match backfill {
+                    true => account.chargeback_unlocked(&amount)?,
+                    false => account.chargeback(&amount)?,
+                }
+                if let Some(accrued) = accrued {
+                    account.accrue(accrued);
+                }
+                self.persist(transaction.client())?;
                 self.dispute_tx_repository.delete_by_id(&transaction.tx_id());
                 // no re-dispute allowed
                 self.tx_repository.delete_by_id(&transaction.tx_id());
+                self.release(transaction.client(), transaction.tx_id());
+                self.dispute_opened.remove(&transaction.tx_id());
+                self.close_dispute(transaction.client(), ExplainedTransaction::new(transaction.tx_id(), r#type, amount, Some(DisputeOutcome::ChargedBack)));
 
                 Ok(())
             }
             _ => Err(InvalidTransactionTypeOrAmount)
         }
     }
+
+    /// A close marks the client's account as closed. Further deposits and withdrawals are rejected,
+    /// open disputes can still be resolved or charged back.
+    fn close(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        self.account(transaction.client())?.close()?;
+        self.persist(transaction.client())
+    }
+
+    // deposit which can be applied in group, rules see (and account store saves) account state after every transaction,
+    // dispute window and expiry evict by sequence of every transaction and timeline records state after every transaction
+    fn batchable(&self, transaction: &Transaction) -> bool {
+        *transaction.r#type() == Deposit
+            && transaction.amount().is_some()
+            && self.rule.is_none()
+            && self.validators.is_empty()
+            && self.amount_limit.is_none()
+            && self.store.is_none()
+            && self.dispute_window.is_none()
+            && self.dispute_expiry.is_none()
+            && self.timeline.is_none()
+            && !self.merged.contains_key(&transaction.client())
+    }
+
+    /// Consecutive deposits of one client applied with one balance update and bulk insert of transactions.
+    /// When any deposit would be rejected (duplicate, locked or closed account, negative amount, overflow)
+    /// or storage fails, deposits are processed one by one. Only deposits which don't depend on sequence of
+    /// every transaction are grouped (see `batchable`), so results are the same as of one by one processing.
+    fn deposit_group(&mut self, group: Vec<Transaction>) -> Vec<Result<(), ProcessError>> {
+        if group.len() < 2 {
+            return group.into_iter().map(|it| self.process(it)).collect();
+        }
+
+        let client = group[0].client();
+        let tx_ids: Vec<TxId> = group.iter().map(|it| it.tx_id()).collect();
+        let amounts: Vec<Decimal> = group.iter().filter_map(|it| it.amount()).collect();
+
+        let new = {
+            let mut sorted = tx_ids.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            sorted.len() == tx_ids.len() && tx_ids.iter().all(|it| matches!(self.tx_repository.exist_by_tx_id(it), Ok(false)))
+        };
+        if amounts.len() != group.len() || !new {
+            return group.into_iter().map(|it| self.process(it)).collect();
+        }
+
+        // transactions are stored first (and removed if rejected), same as single deposit
+        if self.tx_repository.insert_all(&group).is_err() {
+            return group.into_iter().map(|it| self.process(it)).collect();
+        }
+        if self.account(client).and_then(|it| it.deposit_batch(&amounts)).is_err() {
+            tx_ids.iter().for_each(|it| self.tx_repository.delete_by_id(it));
+            return group.into_iter().map(|it| self.process(it)).collect();
+        }
+
+        self.sequence += group.len() as u64;
+        self.client_repository.find_by_client(client).touch(self.sequence);
+        self.record_state(client);
+        for tx_id in tx_ids {
+            self.retain(client, tx_id);
+        }
+        for amount in amounts {
+            record_transaction(self.recorder.as_ref(), Deposit, Some(amount), &Ok(()));
+        }
+        self.recorder.gauge(ACCOUNTS, self.client_repository.len() as f64, &[]);
+        self.recorder.gauge(OPEN_DISPUTES, self.dispute_tx_repository.len() as f64, &[]);
+
+        group.iter().map(|_| Ok(())).collect()
+    }
 }
 
 impl TransactionProcessor for BasicTransactionProcessor {
+    // all accounts state, same as IntoIterator
+    type Output = IntoAccounts;
+
     fn process(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
         // we can here match result and write transaction with errors to dlq repository
         // but by default we do nothing
-        match &transaction.r#type() {
+        self.sequence += 1;
+        let transaction = match self.merged.get(&transaction.client()) {
+            Some(client) => transaction.with_client(*client),
+            None => transaction,
+        };
+        let (client, r#type, tx_id, amount) = (transaction.client(), *transaction.r#type(), transaction.tx_id(), transaction.amount());
+        // status of client seen the first time is active
+        let status = match self.status_listener {
+            Some(_) => self.client_repository.get_by_client(client).map(basic::BasicAccount::status).unwrap_or_default(),
+            None => AccountStatus::Active,
+        };
+
+        let expire_before = match self.dispute_expiry {
+            Some((DisputeExpiry::After(after), _)) => self.sequence.checked_sub(after),
+            _ => None,
+        };
+        let result = self.cached(client)
+            .and_then(|_| self.expire())
+            .and_then(|_| expire_before.map_or(Ok(()), |it| self.expire_disputes(Some(it))))
+            // archived transaction is retained again before validation, so its owner is known
+            .and_then(|_| match r#type {
+                Dispute => self.restore_historical(tx_id),
+                _ => Ok(()),
+            })
+            .and_then(|_| {
+                let context = ValidationContext::new(&self.client_repository, &self.tx_repository, &self.client_txs);
+                StandardValidator.validate(&transaction, &context)
+                    .and_then(|_| match (self.amount_limit, amount) {
+                        (Some(limit), Some(amount)) if matches!(r#type, Deposit | Withdrawal) && amount > limit => Err(ProcessError::AmountOverLimit),
+                        _ => Ok(()),
+                    })
+                    .and_then(|_| self.validators.validate(&transaction, &context))
+            })
+            .and_then(|_| match self.rule.as_mut() {
+            Some(rule) => rule(&transaction, self.client_repository.get_by_client(client).map(AccountView::from).as_ref()),
+            None => Ok(()),
+        });
+
+        let result = result.and_then(|_| match r#type {
             Withdrawal => self.withdrawal(transaction),
             Deposit => self.deposit(transaction),
             Dispute => self.dispute(transaction),
             Resolve => self.resolve(transaction),
             Chargeback => self.charge_back(transaction),
+            Close => self.close(transaction),
+        });
+
+        let result = match result {
+            // account is saved to account store by transaction handler
+            Ok(()) => {
+                self.client_repository.find_by_client(client).touch(self.sequence);
+                self.record_state(client);
+                self.notify_status(client, status);
+                Ok(())
+            }
+            // rejected by risk rule, account is put on hold (only active one, e.g. not again when already under review)
+            Err(e @ ProcessError::ReviewRequired(_)) if !self.backfill => match self.client_repository.find_by_client(client).review() {
+                Ok(()) => {
+                    self.record_state(client);
+                    self.notify_status(client, AccountStatus::Active);
+                    self.persist(client).and(Err(e))
+                }
+                Err(_) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &result {
+            self.suspense(r#type, client, tx_id, amount, e);
         }
+
+        record_transaction(self.recorder.as_ref(), r#type, amount, &result);
+        self.recorder.gauge(ACCOUNTS, self.client_repository.len() as f64, &[]);
+        self.recorder.gauge(OPEN_DISPUTES, self.dispute_tx_repository.len() as f64, &[]);
+
+        result
+    }
+
+    /// Consecutive deposits of the same client (without rule and merge) are applied as a group
+    fn process_batch(&mut self, transactions: Vec<Transaction>) -> Vec<Result<(), ProcessError>> {
+        let mut results = Vec::with_capacity(transactions.len());
+        let mut transactions = transactions.into_iter().peekable();
+        while let Some(transaction) = transactions.next() {
+            if !self.batchable(&transaction) {
+                results.push(self.process(transaction));
+                continue;
+            }
+
+            let client = transaction.client();
+            let mut group = vec![transaction];
+            while let Some(next) = transactions.next_if(|it| it.client() == client && self.batchable(it)) {
+                group.push(next);
+            }
+            results.extend(self.deposit_group(group));
+        }
+        results
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.into_iter()
     }
 }
 
 impl<'a> IntoIterator for &'a mut BasicTransactionProcessor {
-    type Item = &'a basic::BasicAccount;
-    type IntoIter = Box<dyn Iterator<Item=Self::Item> + 'a>; //impl Iterator is unstable :(
+    type Item = AccountView;
+    type IntoIter = Accounts<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        Box::new(self.client_repository.get_all_account_iter())
+        Accounts::new(self.client_repository.get_all_account_iter()).with_accrual(self.held_accrual.is_some())
     }
 }
 
 impl IntoIterator for BasicTransactionProcessor {
-    type Item = basic::BasicAccount;
-    type IntoIter = Box<dyn Iterator<Item=Self::Item>>; //impl Iterator in traits is unstable :(
+    type Item = AccountView;
+    type IntoIter = IntoAccounts;
 
-    fn into_iter(self) -> Self::IntoIter {
-        Box::new(self.client_repository.get_all_account_into_iter())
+    fn into_iter(mut self) -> Self::IntoIter {
+        // disputes still open at the end of run expire, storage error leaves them open
+        if matches!(self.dispute_expiry, Some((DisputeExpiry::EndOfRun, _))) {
+            let _ = self.expire_disputes(None);
+        }
+
+        let dormant = self.dormant_threshold.map(|threshold| (self.sequence, threshold));
+        IntoAccounts::basic(self.client_repository.get_all_account_into_iter(), dormant).with_accrual(self.held_accrual.is_some())
     }
 }
 
@@ -224,7 +1173,16 @@ mod tests {
     use rust_decimal::Decimal;
     use rust_decimal::prelude::FromPrimitive;
     use crate::{BasicAccountRepository, BasicTransactionProcessor, Transaction, TransactionProcessor, TransactionRepository};
-    use crate::transaction_type::TransactionType::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+    use crate::account::basic::BasicAccount;
+    use crate::account::view::AccountView;
+    use crate::account::hierarchy::AccountHierarchy;
+    use crate::error::ProcessError;
+    use crate::explain::DisputeOutcome;
+    use crate::expiry::{DisputeExpiry, ExpiryAction};
+    use crate::history::TransactionArchive;
+    use crate::transaction::TransactionRef;
+    use crate::validation::{MaxScale, TransactionValidator, ValidationContext};
+    use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
 
     #[test]
     fn deposit() {
@@ -241,6 +1199,60 @@ mod tests {
         assert!(!account.locked());
     }
 
+    #[test]
+    fn deposit_ref_dispute_ref_then_invalid_amount() {
+        let mut processor = BasicTransactionProcessor::new();
+        let transaction = TransactionRef::new(Deposit, 1, 1, Some(b"100.5")).to_transaction().unwrap();
+        assert!(processor.process(transaction).is_ok());
+
+        let transaction = TransactionRef::new(Dispute, 1, 1, None).to_transaction().unwrap();
+        assert!(processor.process(transaction).is_ok());
+
+        let transaction = TransactionRef::new(Deposit, 1, 2, Some(b"1.x"));
+        assert!(matches!(transaction.to_transaction(), Err(ProcessError::InvalidAmount(_))));
+
+        let account = processor.client_repository.find_by_client(1);
+        assert_eq!(account.total(), &Decimal::from_f32(100.5).unwrap());
+        assert_eq!(account.available(), &Decimal::from(0_u64));
+        assert_eq!(account.held(), &Decimal::from_f32(100.5).unwrap());
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn deposit_then_finalize() {
+        let mut processor = BasicTransactionProcessor::new();
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(50_u64.into()))).is_ok());
+
+        let mut accounts: Vec<_> = processor.finalize().collect();
+        accounts.sort_by_key(|it| *it.client());
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].total(), &Decimal::from(100_u64));
+        assert_eq!(accounts[1].total(), &Decimal::from(50_u64));
+    }
+
+
+    #[test]
+    fn deposits_then_dormant_accounts() {
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_dormant_threshold(Some(2));
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(50_u64.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 3, Some(50_u64.into()))).is_ok());
+        // rejected transaction is not an activity
+        assert!(processor.process(Transaction::new(Withdrawal, 3, 4, Some(50_u64.into()))).is_err());
+
+        // account 3 was created by rejected withdrawal, but never active
+        let mut dormant: Vec<_> = processor.dormant_accounts(2).map(|it| *it.client()).collect();
+        dormant.sort();
+        assert_eq!(dormant, vec![1, 3]);
+
+        let mut accounts: Vec<_> = processor.finalize().map(|it| (*it.client(), it.dormant())).collect();
+        accounts.sort();
+        assert_eq!(accounts, vec![(1, Some(true)), (2, Some(false)), (3, Some(true))]);
+    }
+
     #[test]
     fn deposit_then_withdrawal() {
         let mut processor = BasicTransactionProcessor::new();
@@ -409,6 +1421,41 @@ mod tests {
         assert!(!account.locked());
     }
 
+
+    #[test]
+    fn deposit_dispute_close_then_resolve() {
+        let mut processor = BasicTransactionProcessor::new();
+
+        let transaction = Transaction::new(Deposit, 1, 1, Some(100_u64.into()));
+        assert!(processor.process(transaction).is_ok());
+
+        let transaction = Transaction::new(Dispute, 1, 1, None);
+        assert!(processor.process(transaction).is_ok());
+
+        let transaction = Transaction::new(Close, 1, 2, None);
+        assert!(processor.process(transaction).is_ok());
+
+        // closed account rejects deposits and withdrawals
+        let transaction = Transaction::new(Deposit, 1, 3, Some(100_u64.into()));
+        assert!(matches!(processor.process(transaction), Err(ProcessError::AccountClosed)));
+
+        let transaction = Transaction::new(Withdrawal, 1, 4, Some(1_u64.into()));
+        assert!(matches!(processor.process(transaction), Err(ProcessError::AccountClosed)));
+
+        // dispute resolution is still allowed
+        let transaction = Transaction::new(Resolve, 1, 1, None);
+        assert!(processor.process(transaction).is_ok());
+
+        let account = processor.client_repository.find_by_client(1);
+
+        assert_eq!(account.total(), &Decimal::from(100_u64));
+        assert_eq!(account.available(), &Decimal::from(100_u64));
+        assert_eq!(account.held(), &Decimal::from(0_u64));
+        assert!(account.closed());
+        //account not locked
+        assert!(!account.locked());
+    }
+
     #[test]
     fn deposit_dispute_then_invalid_dispute() {
         let mut processor = BasicTransactionProcessor::new();
@@ -569,6 +1616,796 @@ mod tests {
         assert!(!account.locked());
     }
 
+    #[test]
+    fn deposit_with_reference_then_find_transaction() {
+        let mut processor = BasicTransactionProcessor::new();
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100.into())).with_reference("INV-1")).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(100.into()))).is_ok());
+
+        assert_eq!(processor.find_transaction(1).and_then(|it| it.reference().map(str::to_string)), Some("INV-1".to_string()));
+        assert_eq!(processor.find_transaction(2).and_then(|it| it.reference().map(str::to_string)), None);
+    }
+
+    #[test]
+    fn deposit_then_find_account() {
+        let mut processor = BasicTransactionProcessor::new();
+        assert!(processor.find_account(1).is_none());
+
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100.into()))).is_ok());
+        let account = processor.find_account(1);
+        assert_eq!(account.map(|it| *it.available()), Some(Decimal::from(100_u64)));
+        assert!(processor.find_account(2).is_none());
+    }
+
+    #[test]
+    fn account_factory_then_starting_balance() {
+        let mut processor = BasicTransactionProcessor::new().with_account_factory(|client| {
+            let mut account = BasicAccount::new(client);
+            account.deposit(&Decimal::from(50_u64)).unwrap();
+            account
+        });
+
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 1, Some(30.into()))).is_ok());
+        assert_eq!(processor.find_account(1).map(|it| *it.available()), Some(Decimal::from(20_u64)));
+    }
+
+    #[test]
+    fn rule_then_reject_withdrawal_over_limit() {
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_rule(Some(Box::new(|transaction, account| {
+            match (transaction.r#type(), account) {
+                (Withdrawal, Some(account)) if transaction.amount() > Some(account.total() / Decimal::TWO) =>
+                    Err(ProcessError::RuleViolation("withdrawal over half of funds".to_string())),
+                _ => Ok(()),
+            }
+        })));
+
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100.into()))).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Withdrawal, 1, 2, Some(60.into()))), Err(ProcessError::RuleViolation(_))));
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(40.into()))).is_ok());
+        assert_eq!(processor.find_account(1).map(|it| *it.total()), Some(Decimal::from(60_u64)));
+    }
+
+    #[test]
+    fn validators_then_rejected_before_applied() {
+        struct NoWithdrawals;
+        impl TransactionValidator for NoWithdrawals {
+            fn validate(&mut self, transaction: &Transaction, context: &ValidationContext<'_>) -> Result<(), ProcessError> {
+                match (transaction.r#type(), context.account(transaction.client())) {
+                    (Withdrawal, Some(_)) => Err(ProcessError::RuleViolation("withdrawals disabled".to_string())),
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        let mut processor = BasicTransactionProcessor::new();
+        processor.add_validator(MaxScale(2));
+        processor.add_validator(NoWithdrawals);
+
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::new(1005, 1)))).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Deposit, 1, 2, Some(Decimal::new(1005, 3)))), Err(ProcessError::InvalidAmount(_))));
+        assert!(matches!(processor.process(Transaction::new(Deposit, 1, 3, Some((-1).into()))), Err(ProcessError::NegativeAmount)));
+        assert!(matches!(processor.process(Transaction::new(Deposit, 1, 1, Some(1.into()))), Err(ProcessError::TransactionExists)));
+        assert!(matches!(processor.process(Transaction::new(Withdrawal, 1, 4, Some(1.into()))), Err(ProcessError::RuleViolation(_))));
+        assert!(matches!(processor.process(Transaction::new(Dispute, 2, 1, None)), Err(ProcessError::MismatchClientId)));
+
+        // rejected transactions left no trace, tx id 2 is still free
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(1.into()))).is_ok());
+        assert_eq!(processor.find_account(1).map(|it| *it.total()), Some(Decimal::new(1015, 1)));
+        assert_eq!(processor.retained_transactions(), 2);
+    }
+
+    #[test]
+    fn deposit_dispute_merge_then_chargeback() {
+        let mut processor = BasicTransactionProcessor::new();
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(30.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 3, Some(10.into()))).is_ok());
+
+        assert!(matches!(processor.merge_accounts(1, 1), Err(ProcessError::InvalidMerge)));
+        assert!(matches!(processor.merge_accounts(3, 2), Err(ProcessError::InvalidMerge)));
+        assert!(processor.merge_accounts(1, 2).is_ok());
+        assert!(processor.find_account(1).is_none());
+
+        let account = processor.find_account(2).unwrap();
+        assert_eq!((*account.available(), *account.held()), (Decimal::from(110_u64), Decimal::from(30_u64)));
+
+        // dispute opened before merge, and new transactions of merged client, apply to target account
+        assert!(processor.process(Transaction::new(Chargeback, 2, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 1, 4, Some(5.into()))).is_err());
+
+        let account = processor.find_account(2).unwrap();
+        assert_eq!((*account.total(), account.locked()), (Decimal::from(110_u64), true));
+        assert!(processor.find_account(1).is_none());
+
+        let merge = &processor.merges()[0];
+        assert_eq!((merge.from(), merge.to(), merge.sequence()), (1, 2, 4));
+        assert_eq!((*merge.available(), *merge.held()), (Decimal::from(100_u64), Decimal::from(30_u64)));
+    }
+
+    #[test]
+    fn deposit_dispute_erase_then_only_tombstone_left() {
+        let mut processor = BasicTransactionProcessor::new();
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(30.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 3, Some(10.into()))).is_ok());
+        assert!(processor.merge_accounts(2, 1).is_ok());
+
+        let tombstone = processor.erase_client(1).unwrap();
+        assert_eq!((tombstone.sequence(), tombstone.account(), tombstone.transactions()), (4, true, 3));
+        assert!(matches!(processor.erase_client(1), Err(ProcessError::AccountNotFound)));
+        assert!(processor.find_account(1).is_none());
+        assert!(processor.find_transaction(2).is_none());
+        assert!(processor.merges().is_empty());
+        assert_eq!(processor.retained_transactions(), 0);
+
+        // resolve of erased dispute is rejected, later transactions of erased client open new account
+        assert!(processor.process(Transaction::new(Resolve, 1, 2, None)).is_err());
+        assert!(processor.process(Transaction::new(Deposit, 2, 4, Some(5.into()))).is_ok());
+        assert_eq!(*processor.find_account(2).unwrap().total(), Decimal::from(5_u64));
+        assert_eq!(processor.tombstones().len(), 1);
+    }
+
+    #[test]
+    fn deposits_disputes_then_explain_balance() {
+        let mut processor = BasicTransactionProcessor::new();
+        processor.restore_account(AccountView::new(1, 5.into(), Decimal::ZERO, false, false).unwrap());
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(30.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(20.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 3, None)).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 1, 3, None)).is_ok());
+
+        let explanation = processor.explain(1).unwrap();
+        assert_eq!((*explanation.available(), *explanation.held()), (Decimal::from(105_u64), Decimal::from(30_u64)));
+        let transactions: Vec<_> = explanation.transactions().iter().map(|it| (it.tx_id(), it.dispute())).collect();
+        assert_eq!(transactions, vec![(1, None), (2, Some(DisputeOutcome::Open)), (3, Some(DisputeOutcome::Resolved))]);
+
+        // restored snapshot balance isn't explained by transactions
+        assert_eq!(*explanation.unexplained_available(), Decimal::from(5_u64));
+        assert_eq!(*explanation.unexplained_held(), Decimal::ZERO);
+        assert!(matches!(processor.explain(2), Err(ProcessError::AccountNotFound)));
+    }
+
+    #[test]
+    fn timeline_then_account_at_sequence() {
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_timeline(true);
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(10.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(5.into()))).is_err());
+        assert!(processor.process(Transaction::new(Chargeback, 1, 1, None)).is_ok());
+
+        assert!(processor.account_at(1, 0).is_none());
+        assert_eq!(*processor.account_at(1, 2).unwrap().available(), Decimal::from(100_u64));
+        assert_eq!(*processor.account_at(1, 4).unwrap().held(), Decimal::from(100_u64));
+        assert!(!processor.account_at(1, 4).unwrap().locked());
+        assert!(processor.account_at(1, 5).unwrap().locked());
+        assert_eq!(processor.account_changes(1).map(|(it, _)| it).collect::<Vec<_>>(), vec![1, 3, 5]);
+
+        // merged account is removed from that point, erased client is forgotten
+        assert!(processor.process(Transaction::new(Deposit, 3, 4, Some(7.into()))).is_ok());
+        assert!(processor.merge_accounts(3, 2).is_ok());
+        assert!(processor.erase_client(1).is_ok());
+        assert!(processor.account_at(1, 5).is_none());
+        assert!(processor.account_at(3, 6).is_none());
+        assert_eq!(*processor.account_at(2, 6).unwrap().total(), Decimal::from(17_u64));
+    }
+
+    #[test]
+    fn deposits_withdrawal_then_client_transactions() {
+        let mut processor = BasicTransactionProcessor::new();
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(50.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(20.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 4, Some(500.into()))).is_err());
+
+        let txs = |processor: &BasicTransactionProcessor, client| processor.client_transactions(client).map(|it| it.tx_id()).collect::<Vec<_>>();
+        assert_eq!(txs(&processor, 1), vec![1, 3]);
+        assert_eq!(txs(&processor, 2), vec![2]);
+
+        // transaction of other client can't be disputed
+        assert!(matches!(processor.process(Transaction::new(Dispute, 2, 1, None)), Err(ProcessError::MismatchClientId)));
+        assert!(processor.process(Transaction::new(Dispute, 2, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 2, 2, None)).is_ok());
+        assert!(txs(&processor, 2).is_empty());
+    }
+
+    #[test]
+    fn released_and_merged_transactions_then_client_transactions_in_order() {
+        let mut processor = BasicTransactionProcessor::new();
+        for (client, tx) in [(1, 1), (2, 2), (1, 3), (1, 4), (2, 5)] {
+            assert!(processor.process(Transaction::new(Deposit, client, tx, Some(10.into()))).is_ok());
+        }
+        assert!(processor.process(Transaction::new(Dispute, 1, 3, None)).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 1, 3, None)).is_ok());
+
+        let txs = |processor: &BasicTransactionProcessor, client| processor.client_transactions(client).map(|it| it.tx_id()).collect::<Vec<_>>();
+        assert_eq!(txs(&processor, 1), vec![1, 4]);
+        assert!(processor.process(Transaction::new(Dispute, 1, 3, None)).is_err());
+
+        // transactions of merged client follow own transactions and are disputed by target client
+        assert!(processor.merge_accounts(1, 2).is_ok());
+        assert_eq!(txs(&processor, 2), vec![2, 5, 1, 4]);
+        assert_eq!(txs(&processor, 1), vec![2, 5, 1, 4]);
+        assert!(processor.process(Transaction::new(Dispute, 2, 4, None)).is_ok());
+    }
+
+    #[test]
+    fn process_batch_then_same_results_as_one_by_one() {
+        let transactions = vec![
+            Transaction::new(Deposit, 1, 1, Some(10.into())),
+            Transaction::new(Deposit, 1, 2, Some(20.into())),
+            Transaction::new(Deposit, 1, 3, Some(30.into())),
+            Transaction::new(Deposit, 2, 4, Some(5.into())),
+            Transaction::new(Deposit, 2, 4, Some(5.into())),
+            Transaction::new(Deposit, 2, 5, Some(Decimal::NEGATIVE_ONE)),
+            Transaction::new(Dispute, 1, 2, None),
+            Transaction::new(Chargeback, 1, 2, None),
+            Transaction::new(Deposit, 1, 6, Some(1.into())),
+            Transaction::new(Deposit, 1, 7, Some(1.into())),
+            Transaction::new(Deposit, 3, 8, Some(Decimal::MAX)),
+            Transaction::new(Deposit, 3, 9, Some(Decimal::ONE)),
+        ];
+
+        let mut batched = BasicTransactionProcessor::new();
+        let mut one_by_one = BasicTransactionProcessor::new();
+        let results: Vec<_> = batched.process_batch(transactions.clone()).into_iter().map(|it| it.map_err(|e| e.code())).collect();
+        let expected: Vec<_> = transactions.into_iter().map(|it| one_by_one.process(it).map_err(|e| e.code())).collect();
+        assert_eq!(results, expected);
+        assert_eq!(results[..4], [Ok(()), Ok(()), Ok(()), Ok(())]);
+
+        for client in 1..=3 {
+            assert_eq!(batched.find_account(client), one_by_one.find_account(client));
+            let txs = |processor: &BasicTransactionProcessor| processor.client_transactions(client).map(|it| it.tx_id()).collect::<Vec<_>>();
+            assert_eq!(txs(&batched), txs(&one_by_one));
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn process_batch_with_window_expiry_and_timeline_then_same_results_as_one_by_one(
+            transactions in proptest::collection::vec((0..6_u8, 1..4_u16, 1..24_u32, 0..50_u64), 1..60),
+            window in 1..8_u64,
+            after in 1..8_u64,
+        ) {
+            let transactions: Vec<_> = transactions.into_iter()
+                .map(|(r#type, client, tx, amount)| match r#type {
+                    0..=2 => Transaction::new(Deposit, client, tx, Some(amount.into())),
+                    3 => Transaction::new(Withdrawal, client, tx, Some(amount.into())),
+                    4 => Transaction::new(Dispute, client, tx, None),
+                    _ => Transaction::new(Resolve, client, tx, None),
+                })
+                .collect();
+            let processor = || {
+                let mut processor = BasicTransactionProcessor::new();
+                processor.set_dispute_window(Some(window));
+                processor.set_dispute_expiry(Some(DisputeExpiry::After(after)), ExpiryAction::Resolve);
+                processor.set_timeline(true);
+                processor
+            };
+
+            let (mut batched, mut one_by_one) = (processor(), processor());
+            let results: Vec<_> = batched.process_batch(transactions.clone()).into_iter().map(|it| it.map_err(|e| e.code())).collect();
+            let expected: Vec<_> = transactions.into_iter().map(|it| one_by_one.process(it).map_err(|e| e.code())).collect();
+            proptest::prop_assert_eq!(results, expected);
+
+            for client in 1..4 {
+                proptest::prop_assert_eq!(batched.find_account(client), one_by_one.find_account(client));
+                let txs = |processor: &BasicTransactionProcessor| processor.client_transactions(client).map(|it| it.tx_id()).collect::<Vec<_>>();
+                proptest::prop_assert_eq!(txs(&batched), txs(&one_by_one));
+                let changes = |processor: &BasicTransactionProcessor| processor.account_changes(client).map(|(sequence, it)| (sequence, it.cloned())).collect::<Vec<_>>();
+                proptest::prop_assert_eq!(changes(&batched), changes(&one_by_one));
+            }
+        }
+    }
+
+    #[test]
+    fn deposits_then_rollup() {
+        let mut hierarchy = AccountHierarchy::new();
+        hierarchy.insert(2, 1);
+        hierarchy.insert(3, 1);
+
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_hierarchy(hierarchy);
+        assert!(processor.process(Transaction::new(Deposit, 2, 1, Some(20.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 3, 2, Some(30.into()))).is_ok());
+
+        let rollup = processor.rollup(1).unwrap();
+        assert_eq!((*rollup.client(), *rollup.total()), (1, Decimal::from(50_u64)));
+        assert_eq!(processor.rollup(2).map(|it| *it.total()).unwrap(), Decimal::from(20_u64));
+    }
+
+    #[test]
+    fn held_accrual_then_posted_on_resolve_and_chargeback() {
+        use crate::accrual::HeldAccrual;
+
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_held_accrual(Some(HeldAccrual::new(Decimal::new(1, 2), 2).unwrap()));
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::from(100_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(Decimal::from(100_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 3, 3, Some(Decimal::ONE))).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 1, 1, None)).is_ok());
+        let account = processor.find_account(1).unwrap();
+        assert_eq!((account.accrued(), *account.total()), (Some(&Decimal::from(1_u64)), Decimal::from(100_u64)));
+
+        processor.set_held_accrual(Some(HeldAccrual::new(Decimal::new(-1, 1), 1).unwrap()));
+        assert!(processor.process(Transaction::new(Dispute, 2, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Chargeback, 2, 2, None)).is_ok());
+        assert_eq!(processor.find_account(2).unwrap().accrued(), Some(&Decimal::from(-10_i64)));
+
+        let mut accrued: Vec<_> = processor.finalize().map(|it| (*it.client(), it.accrued().copied())).collect();
+        accrued.sort_unstable();
+        assert_eq!(accrued, [(1, Some(Decimal::ONE)), (2, Some(Decimal::from(-10_i64))), (3, Some(Decimal::ZERO))]);
+    }
+
+    #[test]
+    fn dispute_expiry_then_resolved_or_flagged_with_events() {
+        use std::sync::{Arc, Mutex};
+        use crate::expiry::{DisputeExpiry, ExpiryAction};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener = {
+            let events = events.clone();
+            move |it: &crate::expiry::ExpiredDispute| events.lock().unwrap().push((it.tx_id(), it.action()))
+        };
+
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_dispute_expiry(Some(DisputeExpiry::After(1)), ExpiryAction::Resolve);
+        processor.set_expiry_listener(Some(Box::new(listener.clone())));
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(Decimal::ONE))).is_ok());
+        assert_eq!(*processor.find_account(1).unwrap().held(), Decimal::from(10_u64));
+        assert!(processor.process(Transaction::new(Deposit, 2, 3, Some(Decimal::ONE))).is_ok());
+        assert_eq!(*processor.find_account(1).unwrap().available(), Decimal::from(10_u64));
+        assert!(matches!(processor.process(Transaction::new(Chargeback, 1, 1, None)), Err(ProcessError::DisputedTransactionNotFound)));
+
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_dispute_expiry(Some(DisputeExpiry::EndOfRun), ExpiryAction::Flag);
+        processor.set_expiry_listener(Some(Box::new(listener)));
+        assert!(processor.process(Transaction::new(Deposit, 1, 4, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 4, None)).is_ok());
+        let account = processor.finalize().next().unwrap();
+        assert_eq!(*account.held(), Decimal::from(10_u64));
+        assert_eq!(*events.lock().unwrap(), [(1, ExpiryAction::Resolve), (4, ExpiryAction::Flag)]);
+    }
+
+    #[test]
+    fn unmatched_disputes_then_suspense_ledger() {
+        use std::sync::{Arc, Mutex};
+        use crate::suspense::{SuspenseLedger, SuspenseReason};
+
+        let ledger = Arc::new(Mutex::new(SuspenseLedger::new()));
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_suspense_listener(Some(Box::new({
+            let ledger = ledger.clone();
+            move |it| ledger.lock().unwrap().record(it.clone())
+        })));
+
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(Decimal::from(5_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 2, 1, None)).is_err());
+        assert!(processor.process(Transaction::new(Dispute, 1, 9, None)).is_err());
+        assert!(processor.process(Transaction::new(Chargeback, 1, 2, None)).is_err());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(Decimal::from(50_u64)))).is_err());
+
+        let ledger = ledger.lock().unwrap();
+        assert_eq!(ledger.entries().iter().map(|it| (it.tx_id(), it.reason(), it.sequence())).collect::<Vec<_>>(),
+                   [(1, SuspenseReason::ClientMismatch, 3), (9, SuspenseReason::MissingTransaction, 4), (2, SuspenseReason::MissingDispute, 5)]);
+        assert_eq!(ledger.total(SuspenseReason::ClientMismatch).map(|it| *it.amount()), Some(Decimal::from(10_u64)));
+        assert_eq!(ledger.total(SuspenseReason::MissingTransaction).map(|it| it.unknown_amounts()), Some(1));
+    }
+
+    #[test]
+    fn close_chargeback_unlock_then_status_changes() {
+        use std::sync::{Arc, Mutex};
+        use crate::account::status::AccountStatus::{Active, Closed, Locked};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_status_listener(Some(Box::new({
+            let events = events.clone();
+            move |it| events.lock().unwrap().push((it.client(), it.from(), it.to(), it.sequence()))
+        })));
+
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(Decimal::from(5_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Close, 1, 0, None)).is_ok());
+        assert!(processor.process(Transaction::new(Chargeback, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 2, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Chargeback, 2, 2, None)).is_ok());
+        assert_eq!(processor.find_account(1).map(|it| (it.status(), it.locked(), it.closed())), Some((Locked, true, true)));
+
+        assert!(processor.unlock_account(1).is_ok());
+        assert!(processor.unlock_account(2).is_ok());
+        assert_eq!(processor.find_account(1).map(|it| it.status()), Some(Closed));
+        assert_eq!(processor.find_account(2).map(|it| it.status()), Some(Active));
+
+        assert_eq!(*events.lock().unwrap(), [(1, Active, Closed, 4), (1, Closed, Locked, 5), (2, Active, Locked, 7), (1, Locked, Closed, 7), (2, Locked, Active, 7)]);
+    }
+
+    #[test]
+    fn risk_rule_then_under_review_until_released() {
+        use crate::account::status::AccountStatus::{Active, UnderReview};
+
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_rule(Some(Box::new(|transaction, _| match (transaction.r#type(), transaction.amount()) {
+            (Withdrawal, Some(amount)) if amount > Decimal::from(50_u64) => Err(ProcessError::ReviewRequired("withdrawal over limit".to_string())),
+            _ => Ok(()),
+        })));
+
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::from(100_u64)))).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Withdrawal, 1, 2, Some(Decimal::from(60_u64)))), Err(ProcessError::ReviewRequired(_))));
+        assert_eq!(processor.find_account(1).map(|it| (it.status(), it.locked())), Some((UnderReview, false)));
+
+        // deposits and disputes allowed, withdrawals blocked
+        assert!(processor.process(Transaction::new(Deposit, 1, 3, Some(Decimal::TEN))).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Withdrawal, 1, 4, Some(Decimal::ONE))), Err(ProcessError::AccountUnderReview)));
+        assert!(processor.process(Transaction::new(Dispute, 1, 3, None)).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 1, 3, None)).is_ok());
+
+        assert!(processor.release_review(1).is_ok());
+        assert!(matches!(processor.release_review(1), Err(ProcessError::InvalidStatusTransition)));
+        assert!(matches!(processor.release_review(2), Err(ProcessError::AccountNotFound)));
+        assert_eq!(processor.find_account(1).map(|it| it.status()), Some(Active));
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 5, Some(Decimal::ONE))).is_ok());
+        assert_eq!(*processor.find_account(1).unwrap().available(), Decimal::from(109_u64));
+    }
+
+    #[test]
+    fn account_store_then_warmed_read_and_written_through() {
+        use std::collections::BTreeMap;
+        use std::sync::{Arc, Mutex};
+        use crate::client::Client;
+        use crate::store::AccountStore;
+
+        // accounts with save clock, shared with test
+        #[derive(Clone, Default)]
+        struct Store(Arc<Mutex<BTreeMap<Client, (u64, AccountView)>>>);
+
+        impl AccountStore for Store {
+            fn load(&mut self, client: Client) -> Result<Option<AccountView>, ProcessError> {
+                Ok(self.0.lock().unwrap().get(&client).map(|(_, it)| it.clone()))
+            }
+            fn load_recent(&mut self, limit: usize) -> Result<Vec<AccountView>, ProcessError> {
+                let mut accounts: Vec<_> = self.0.lock().unwrap().values().cloned().collect();
+                accounts.sort_by_key(|(clock, _)| std::cmp::Reverse(*clock));
+                Ok(accounts.into_iter().take(limit).map(|(_, it)| it).collect())
+            }
+            fn save(&mut self, account: &AccountView) -> Result<(), ProcessError> {
+                let mut accounts = self.0.lock().unwrap();
+                let clock = accounts.values().map(|(clock, _)| *clock).max().unwrap_or_default() + 1;
+                accounts.insert(*account.client(), (clock, account.clone()));
+                Ok(())
+            }
+            fn delete(&mut self, client: Client) -> Result<(), ProcessError> {
+                self.0.lock().unwrap().remove(&client);
+                Ok(())
+            }
+        }
+
+        let store = Store::default();
+        for client in 1..=3 {
+            assert!(store.clone().save(&AccountView::new(client, Decimal::from(client), Decimal::ZERO, false, false).unwrap()).is_ok());
+        }
+
+        let mut processor = BasicTransactionProcessor::new().with_account_store(store.clone());
+        assert_eq!(processor.warm_up(2).ok(), Some(2));
+        assert!(processor.find_account(1).is_none());
+
+        // 3 is warmed, 1 is loaded on first transaction, 4 is new
+        assert!(processor.process(Transaction::new(Deposit, 3, 1, Some(Decimal::ONE))).is_ok());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 2, Some(Decimal::ONE))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 4, 3, Some(Decimal::TEN))).is_ok());
+        assert!(processor.merge_accounts(2, 4).is_ok());
+
+        let stats = processor.cache_stats();
+        assert_eq!((stats.warmed(), stats.hits(), stats.misses()), (2, 3, 2));
+
+        let stored = store.0.lock().unwrap();
+        let balances: Vec<_> = stored.values().map(|(_, it)| (*it.client(), *it.available())).collect();
+        assert_eq!(balances, [(1, Decimal::ZERO), (3, Decimal::from(4_u64)), (4, Decimal::from(12_u64))]);
+    }
+
+    #[test]
+    fn merge_with_conflicting_delete_then_target_restored_in_store() {
+        use std::collections::BTreeMap;
+        use std::sync::{Arc, Mutex};
+        use crate::client::Client;
+        use crate::store::AccountStore;
+
+        // accounts shared with test, delete fails while `conflicts` is not zero
+        #[derive(Clone, Default)]
+        struct Store {
+            accounts: Arc<Mutex<BTreeMap<Client, AccountView>>>,
+            conflicts: Arc<Mutex<u32>>,
+        }
+
+        impl AccountStore for Store {
+            fn load(&mut self, client: Client) -> Result<Option<AccountView>, ProcessError> {
+                Ok(self.accounts.lock().unwrap().get(&client).cloned())
+            }
+            fn load_recent(&mut self, _: usize) -> Result<Vec<AccountView>, ProcessError> {
+                Ok(Vec::new())
+            }
+            fn save(&mut self, account: &AccountView) -> Result<(), ProcessError> {
+                self.accounts.lock().unwrap().insert(*account.client(), account.clone());
+                Ok(())
+            }
+            fn delete(&mut self, client: Client) -> Result<(), ProcessError> {
+                let mut conflicts = self.conflicts.lock().unwrap();
+                if *conflicts > 0 {
+                    *conflicts -= 1;
+                    return Err(ProcessError::VersionConflict);
+                }
+                self.accounts.lock().unwrap().remove(&client);
+                Ok(())
+            }
+        }
+
+        let store = Store::default();
+        let stored = |store: &Store| store.accounts.lock().unwrap().values().map(|it| (*it.client(), *it.available())).collect::<Vec<_>>();
+        let mut processor = BasicTransactionProcessor::new().with_account_store(store.clone());
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::TEN))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(Decimal::ONE))).is_ok());
+
+        // second write of merge conflicts, funds of source account are not stored twice
+        *store.conflicts.lock().unwrap() = 1;
+        assert!(matches!(processor.merge_accounts(1, 2), Err(ProcessError::VersionConflict)));
+        assert_eq!(stored(&store), [(1, Decimal::TEN), (2, Decimal::ONE)]);
+        assert!(processor.merges().is_empty());
+        assert!(processor.find_account(1).is_none());
+        assert!(processor.find_account(2).is_none());
+
+        // retry loads both accounts again
+        assert!(processor.merge_accounts(1, 2).is_ok());
+        assert_eq!(stored(&store), [(2, Decimal::from(11_u64))]);
+        assert!(processor.process(Transaction::new(Deposit, 1, 3, Some(Decimal::ONE))).is_ok());
+        assert_eq!(stored(&store), [(2, Decimal::from(12_u64))]);
+    }
+
+    #[test]
+    fn backfill_then_no_freeze_and_no_notifications_until_live() {
+        use std::sync::{Arc, Mutex};
+        use crate::account::status::AccountStatus::Active;
+
+        let mut processor = BasicTransactionProcessor::new();
+        let changes = Arc::new(Mutex::new(0));
+        processor.set_status_listener(Some(Box::new({
+            let changes = changes.clone();
+            move |_: &crate::account::status::StatusChange| *changes.lock().unwrap() += 1
+        })));
+        processor.set_rule(Some(Box::new(|transaction, _| match transaction.r#type() {
+            Withdrawal => Err(ProcessError::ReviewRequired("withdrawal".to_string())),
+            _ => Ok(()),
+        })));
+
+        processor.set_backfill(true);
+        for transaction in [Transaction::new(Deposit, 1, 1, Some(Decimal::TEN)), Transaction::new(Deposit, 1, 2, Some(Decimal::ONE)),
+                            Transaction::new(Dispute, 1, 2, None), Transaction::new(Chargeback, 1, 2, None)] {
+            assert!(processor.process(transaction).is_ok());
+        }
+        assert!(matches!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(Decimal::ONE))), Err(ProcessError::ReviewRequired(_))));
+        assert_eq!(processor.find_account(1).map(|it| (it.status(), *it.total())), Some((Active, Decimal::TEN)));
+        assert_eq!(*changes.lock().unwrap(), 0);
+
+        processor.set_backfill(false);
+        assert!(processor.process(Transaction::new(Dispute, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Chargeback, 1, 1, None)).is_ok());
+        assert!(processor.find_account(1).unwrap().locked());
+        assert_eq!(*changes.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn amount_limit_changed_between_transactions_then_applied_to_next() {
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_amount_limit(Some(Decimal::TEN));
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::TEN))).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Deposit, 1, 2, Some(Decimal::from(11_u64)))), Err(ProcessError::AmountOverLimit)));
+        assert!(processor.process(Transaction::new(Dispute, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 1, 1, None)).is_ok());
+
+        // rejected deposit was not retained, tx id can be used again
+        processor.set_amount_limit(Some(Decimal::from(20_u64)));
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(Decimal::from(11_u64)))).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(Decimal::from(21_u64)))), Err(ProcessError::AmountOverLimit)));
+
+        processor.set_amount_limit(None);
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(Decimal::from(21_u64)))).is_ok());
+        assert_eq!(*processor.find_account(1).unwrap().available(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn shared_store_changed_by_other_instance_then_version_conflict_and_retry() {
+        use std::collections::BTreeMap;
+        use std::sync::{Arc, Mutex};
+        use crate::client::Client;
+        use crate::store::AccountStore;
+
+        // accounts with versions shared by instances, every instance remembers versions it has seen
+        #[derive(Default)]
+        struct Store {
+            accounts: Arc<Mutex<BTreeMap<Client, (u64, AccountView)>>>,
+            versions: BTreeMap<Client, u64>,
+        }
+
+        impl AccountStore for Store {
+            fn load(&mut self, client: Client) -> Result<Option<AccountView>, ProcessError> {
+                let accounts = self.accounts.lock().unwrap();
+                let (version, account) = accounts.get(&client).cloned().unzip();
+                self.versions.insert(client, version.unwrap_or_default());
+                Ok(account)
+            }
+            fn load_recent(&mut self, _: usize) -> Result<Vec<AccountView>, ProcessError> {
+                Ok(Vec::new())
+            }
+            fn save(&mut self, account: &AccountView) -> Result<(), ProcessError> {
+                let mut accounts = self.accounts.lock().unwrap();
+                let current = accounts.get(account.client()).map(|(version, _)| *version).unwrap_or_default();
+                if self.versions.get(account.client()) != Some(&current) {
+                    return Err(ProcessError::VersionConflict);
+                }
+                accounts.insert(*account.client(), (current + 1, account.clone()));
+                self.versions.insert(*account.client(), current + 1);
+                Ok(())
+            }
+            fn delete(&mut self, client: Client) -> Result<(), ProcessError> {
+                self.accounts.lock().unwrap().remove(&client);
+                Ok(())
+            }
+        }
+
+        let accounts = Arc::new(Mutex::new(BTreeMap::new()));
+        let mut first = BasicTransactionProcessor::new().with_account_store(Store { accounts: accounts.clone(), ..Store::default() });
+        let mut second = BasicTransactionProcessor::new().with_account_store(Store { accounts: accounts.clone(), ..Store::default() });
+
+        assert!(first.process(Transaction::new(Deposit, 1, 1, Some(Decimal::TEN))).is_ok());
+        assert!(second.process(Transaction::new(Deposit, 1, 2, Some(Decimal::from(5_u64)))).is_ok());
+
+        // first instance holds stale account, transaction is rejected and rolled back
+        let result = first.process(Transaction::new(Deposit, 1, 3, Some(Decimal::ONE)));
+        assert!(matches!(result, Err(ProcessError::VersionConflict)));
+        assert!(result.is_err_and(|e| e.is_retryable()));
+        assert!(first.find_account(1).is_none());
+
+        // retry loads current account
+        assert!(first.process(Transaction::new(Deposit, 1, 3, Some(Decimal::ONE))).is_ok());
+        assert_eq!(*first.find_account(1).unwrap().available(), Decimal::from(16_u64));
+        assert_eq!(accounts.lock().unwrap().get(&1).map(|(version, it)| (*version, *it.available())), Some((3, Decimal::from(16_u64))));
+    }
+
+    #[test]
+    fn dispute_window_then_older_transactions_evicted() {
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_dispute_window(Some(2));
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 3, Some(Decimal::ONE))).is_ok());
+
+        // deposit 1 is out of window, disputed deposit 2 is kept until resolved
+        assert_eq!(processor.retained_transactions(), 2);
+        assert!(matches!(processor.process(Transaction::new(Dispute, 1, 1, None)), Err(ProcessError::OrgTransactionNotFound)));
+        assert!(processor.process(Transaction::new(Deposit, 2, 4, Some(Decimal::ONE))).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 1, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 3, None)).is_err());
+        assert_eq!(processor.client_transactions(1).count(), 0);
+        assert_eq!(*processor.find_account(1).unwrap().available(), Decimal::from(19_u64));
+    }
+
+    #[test]
+    fn dispute_window_then_closed_disputes_evicted_from_explanation() {
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_dispute_window(Some(3));
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 1, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 2, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, 2, 2, None)).is_ok());
+        assert!(processor.merge_accounts(2, 1).is_ok());
+
+        let closed = |processor: &BasicTransactionProcessor| processor.explain(1).unwrap().transactions().iter().map(|it| it.tx_id()).collect::<Vec<_>>();
+        assert_eq!(closed(&processor), vec![1, 2]);
+
+        // dispute closed first is out of window first, then the one of merged client
+        assert!(processor.process(Transaction::new(Deposit, 3, 3, Some(Decimal::ONE))).is_ok());
+        assert_eq!(closed(&processor), vec![2]);
+        for tx in 4..=6 {
+            assert!(processor.process(Transaction::new(Deposit, 3, tx, Some(Decimal::ONE))).is_ok());
+        }
+        assert!(closed(&processor).is_empty());
+        assert_eq!(*processor.find_account(1).unwrap().available(), Decimal::from(20_u64));
+    }
+
+    #[test]
+    fn compact_then_old_settled_transactions_evicted() {
+        let mut processor = BasicTransactionProcessor::new();
+        for tx in 1..=5 {
+            assert!(processor.process(Transaction::new(Deposit, 1, tx, Some(Decimal::from(10_u64)))).is_ok());
+        }
+        assert!(processor.process(Transaction::new(Deposit, 2, 6, Some(Decimal::from(10_u64)))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 1, None)).is_ok());
+
+        let mut archived = Vec::new();
+        assert_eq!(processor.compact_with(2, |it| archived.push(it.tx_id())).unwrap(), 2);
+        assert_eq!(archived, [2, 3]);
+        assert_eq!(processor.retained_transactions(), 4);
+        let retained: Vec<_> = processor.client_transactions(1).map(|it| it.tx_id()).collect();
+        assert_eq!(retained, [1, 4, 5]);
+
+        // disputed transaction is kept, evicted one can't be disputed
+        assert!(processor.process(Transaction::new(Resolve, 1, 1, None)).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Dispute, 1, 2, None)), Err(ProcessError::OrgTransactionNotFound)));
+        assert!(processor.process(Transaction::new(Dispute, 1, 5, None)).is_ok());
+        assert_eq!(*processor.find_account(1).unwrap().total(), Decimal::from(50_u64));
+    }
+
+    #[test]
+    fn restored_account_then_dispute_of_archived_deposit() {
+        let mut archive = TransactionArchive::new();
+        archive.insert(Transaction::new(Deposit, 1, 7, Some(Decimal::from(40_u64))));
+        archive.insert(Transaction::new(Dispute, 1, 8, None));
+        assert_eq!(archive.len(), 1);
+
+        let snapshot = AccountView::new(1, Decimal::from(100_u64), Decimal::ZERO, false, false).unwrap();
+        let mut without_history = BasicTransactionProcessor::new();
+        without_history.restore_account(snapshot.clone());
+        assert!(matches!(without_history.process(Transaction::new(Dispute, 1, 7, None)), Err(ProcessError::OrgTransactionNotFound)));
+
+        let mut processor = BasicTransactionProcessor::new().with_history(archive);
+        processor.restore_account(snapshot);
+        assert!(matches!(processor.process(Transaction::new(Dispute, 2, 7, None)), Err(ProcessError::MismatchClientId)));
+        assert!(processor.process(Transaction::new(Dispute, 1, 7, None)).is_ok());
+        assert_eq!(*processor.find_account(1).unwrap().held(), Decimal::from(40_u64));
+
+        assert!(processor.process(Transaction::new(Chargeback, 1, 7, None)).is_ok());
+        let account = processor.find_account(1).unwrap();
+        assert_eq!((*account.total(), account.locked()), (Decimal::from(60_u64), true));
+        assert!(matches!(processor.process(Transaction::new(Dispute, 1, 8, None)), Err(ProcessError::OrgTransactionNotFound)));
+    }
+
+    #[test]
+    fn accounts_page_then_next_page_from_cursor() {
+        let mut processor = BasicTransactionProcessor::new();
+        for (tx_id, client) in [900_u16, 3, 65_000, 1, 42].into_iter().enumerate() {
+            assert!(processor.process(Transaction::new(Deposit, client, tx_id as u32, Some(Decimal::ONE))).is_ok());
+        }
+
+        let mut cursor = None;
+        let mut pages = Vec::new();
+        loop {
+            let page = processor.accounts_page(cursor, 2);
+            pages.push(page.accounts().iter().map(|it| *it.client()).collect::<Vec<_>>());
+            cursor = page.next_cursor();
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(pages, [vec![1, 3], vec![42, 900], vec![65_000]]);
+    }
+
+    #[test]
+    fn ordered_then_accounts_in_client_order() {
+        let mut processor = BasicTransactionProcessor::new();
+        processor.set_ordered(true);
+        for (tx_id, client) in [900_u16, 3, 65_000, 1, 42].into_iter().enumerate() {
+            assert!(processor.process(Transaction::new(Deposit, client, tx_id as u32, Some(Decimal::ONE))).is_ok());
+        }
+
+        let clients = (&mut processor).into_iter().map(|it| *it.client()).collect::<Vec<_>>();
+        assert_eq!(clients, [1, 3, 42, 900, 65_000]);
+        let clients = processor.finalize().map(|it| *it.client()).collect::<Vec<_>>();
+        assert_eq!(clients, [1, 3, 42, 900, 65_000]);
+    }
+
     #[test]
     fn max_client_deposit_then_withdrawal() {
         let client_repository = BasicAccountRepository::with_capacity(u16::MAX as usize);
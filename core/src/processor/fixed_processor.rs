@@ -0,0 +1,226 @@
+use crate::prelude::*;
+use crate::account::fixed::{Fixed, FixedAccount};
+use crate::account::iter::IntoAccounts;
+use crate::account::view::AccountView;
+use crate::client::Client;
+use crate::metrics::{record_transaction, NoopRecorder, Recorder, ACCOUNTS};
+use crate::transaction::TxId;
+use crate::ProcessError::{AccountLocked, AmountNotFound, DisputedTransactionNotFound, InvalidAmount, InvalidTransactionTypeOrAmount, MismatchClientId, OrgTransactionNotFound, TransactionExists, TransactionUnderDispute};
+use crate::{ProcessError, Transaction, TransactionProcessor};
+use crate::transaction_type::TransactionType;
+use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
+
+/// Retained deposit/withdrawal, only fields needed for disputes
+#[derive(Debug, Clone, Copy)]
+struct FixedTransaction {
+    client: Client,
+    r#type: TransactionType,
+    amount: Fixed,
+    disputed: bool,
+}
+
+/// FixedTransactionProcessor applies the same rules as BasicTransactionProcessor, but balances are
+/// fixed-point i64 values (4 decimal places, see `Fixed`) and retained transactions keep only client,
+/// type and amount. Amounts with more decimal places or out of range are rejected as invalid.
+/// Faster and smaller than `Decimal` accounts, at the cost of range and precision.
+/// Dormant accounts, rules, merges and roll-ups are not supported.
+pub struct FixedTransactionProcessor {
+    accounts: IntMap<Client, FixedAccount>,
+
+    // deposits and withdrawals which can be disputed
+    transactions: IntMap<TxId, FixedTransaction>,
+
+    // metrics backend, metrics are dropped by default
+    recorder: Box<dyn Recorder>,
+}
+
+impl Default for FixedTransactionProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FixedTransactionProcessor {
+    pub fn new() -> Self {
+        FixedTransactionProcessor {
+            accounts: IntMap::default(),
+            transactions: IntMap::default(),
+            recorder: Box::new(NoopRecorder),
+        }
+    }
+
+    /// Processing metrics are reported to `recorder` (see `core::metrics`)
+    pub fn with_recorder<R: Recorder + 'static>(mut self, recorder: R) -> Self {
+        self.recorder = Box::new(recorder);
+        self
+    }
+
+    /// Current state of client account
+    pub fn find_account(&self, client: Client) -> Option<AccountView> {
+        self.accounts.get(&client).map(AccountView::from)
+    }
+
+    fn account(&mut self, client: Client) -> Result<&mut FixedAccount, ProcessError> {
+        let account = self.accounts.entry(client).or_insert_with(|| FixedAccount::new(client));
+
+        // Whether the account is locked. An account is locked if a charge back occurs
+        if account.locked() {
+            return Err(AccountLocked);
+        }
+
+        Ok(account)
+    }
+
+    /// Deposit or withdrawal, transaction is retained only when applied
+    fn transfer(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        let amount = transaction.amount().ok_or(AmountNotFound)?;
+        let amount = Fixed::try_from(amount).map_err(|e| InvalidAmount(Box::new(e)))?;
+
+        let tx_id = transaction.tx_id();
+        if self.transactions.contains_key(&tx_id) {
+            return Err(TransactionExists);
+        }
+
+        let (client, r#type) = (transaction.client(), *transaction.r#type());
+        let account = self.account(client)?;
+        match r#type {
+            Deposit => account.deposit(amount)?,
+            _ => account.withdrawal(amount)?,
+        }
+
+        self.transactions.insert(tx_id, FixedTransaction { client, r#type, amount, disputed: false });
+        Ok(())
+    }
+
+    /// Funds of disputed deposit are held, disputed withdrawal increases held funds
+    fn dispute(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        let org_tx = self.transactions.get(&transaction.tx_id()).copied().ok_or(OrgTransactionNotFound)?;
+        if org_tx.disputed {
+            return Err(TransactionUnderDispute);
+        }
+        if org_tx.client != transaction.client() {
+            return Err(MismatchClientId);
+        }
+
+        let account = self.account(org_tx.client)?;
+        match org_tx.r#type {
+            Deposit => account.dispute_deposit(org_tx.amount)?,
+            Withdrawal => account.dispute_withdrawal(org_tx.amount)?,
+            _ => return Err(InvalidTransactionTypeOrAmount),
+        }
+
+        if let Some(it) = self.transactions.get_mut(&transaction.tx_id()) {
+            it.disputed = true;
+        }
+        Ok(())
+    }
+
+    /// Resolve releases held funds, chargeback withdraws them and locks account.
+    /// Disputed transaction is removed, so it can't be disputed again.
+    fn settle(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        let org_tx = self.transactions.get(&transaction.tx_id()).copied()
+            .filter(|it| it.disputed)
+            .ok_or(DisputedTransactionNotFound)?;
+        if org_tx.client != transaction.client() {
+            return Err(MismatchClientId);
+        }
+
+        let account = self.account(org_tx.client)?;
+        match transaction.r#type() {
+            Resolve => account.resolve(org_tx.amount)?,
+            _ => account.chargeback(org_tx.amount)?,
+        }
+
+        self.transactions.remove(&transaction.tx_id());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl crate::processor::sharded_processor::TransactionLookup for FixedTransactionProcessor {
+    fn retains(&self, tx_id: TxId) -> bool {
+        self.transactions.contains_key(&tx_id)
+    }
+}
+
+impl TransactionProcessor for FixedTransactionProcessor {
+    // all accounts state, same as IntoIterator
+    type Output = IntoAccounts;
+
+    fn process(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        let (r#type, amount) = (*transaction.r#type(), transaction.amount());
+
+        let result = match r#type {
+            Deposit | Withdrawal => self.transfer(transaction),
+            Dispute => self.dispute(transaction),
+            Resolve | Chargeback => self.settle(transaction),
+            Close => self.account(transaction.client()).and_then(|it| it.close()),
+        };
+
+        record_transaction(self.recorder.as_ref(), r#type, amount, &result);
+        self.recorder.gauge(ACCOUNTS, self.accounts.len() as f64, &[]);
+
+        result
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.into_iter()
+    }
+}
+
+impl IntoIterator for FixedTransactionProcessor {
+    type Item = AccountView;
+    type IntoIter = IntoAccounts;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoAccounts::fixed(self.accounts.into_values())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::{BasicProcessor, FixedProcessor, ProcessError, Transaction, TransactionProcessor};
+    use crate::account::iter::IntoAccounts;
+    use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
+
+    #[test]
+    fn same_transactions_then_same_accounts_as_basic() {
+        let transactions = [
+            Transaction::new(Deposit, 1, 1, Some(Decimal::new(105, 1))),
+            Transaction::new(Deposit, 2, 2, Some(Decimal::new(20_0001, 4))),
+            Transaction::new(Withdrawal, 1, 3, Some(Decimal::new(25, 1))),
+            Transaction::new(Withdrawal, 2, 4, Some(Decimal::from(100_u64))),
+            Transaction::new(Dispute, 2, 1, None),
+            Transaction::new(Dispute, 1, 3, None),
+            Transaction::new(Resolve, 1, 3, None),
+            Transaction::new(Dispute, 2, 2, None),
+            Transaction::new(Dispute, 2, 2, None),
+            Transaction::new(Chargeback, 2, 2, None),
+            Transaction::new(Deposit, 2, 5, Some(Decimal::ONE)),
+            Transaction::new(Close, 3, 0, None),
+        ];
+
+        let mut basic = BasicProcessor::new();
+        let mut fixed = FixedProcessor::new();
+        for transaction in transactions {
+            let expected = basic.process(transaction.clone()).map_err(|e| e.code());
+            assert_eq!(fixed.process(transaction).map_err(|e| e.code()), expected);
+        }
+
+        let sorted = |accounts: IntoAccounts| {
+            let mut accounts: Vec<_> = accounts.collect();
+            accounts.sort_unstable_by_key(|it| *it.client());
+            accounts
+        };
+        assert_eq!(sorted(fixed.finalize()), sorted(basic.finalize()));
+    }
+
+    #[test]
+    fn deposit_with_five_decimal_places_then_invalid_amount() {
+        let mut processor = FixedProcessor::new();
+        let result = processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::new(1_00001, 5))));
+        assert!(matches!(result, Err(ProcessError::InvalidAmount(_))));
+        assert!(processor.find_account(1).is_none());
+    }
+}
@@ -1,2 +1,23 @@
 pub mod basic_processor;
-pub mod wrap_processor;
\ No newline at end of file
+pub mod wrap_processor;
+pub mod multi_currency_processor;
+pub mod multi_tenant_processor;
+#[cfg(feature = "std")]
+pub mod mutex_processor;
+#[cfg(feature = "std")]
+pub mod sharded_processor;
+#[cfg(feature = "lock-free-reads")]
+pub mod account_reads;
+#[cfg(feature = "fastmath")]
+pub mod fixed_processor;
+
+use crate::prelude::*;
+
+// processor (or stats) of key (currency, tenant), key is allocated only for first transaction under this key
+pub(crate) fn keyed_processor<'a, P: Default>(processors: &'a mut HashMap<String, P>, key: &str) -> &'a mut P {
+    if !processors.contains_key(key) {
+        processors.insert(key.to_string(), P::default());
+    }
+
+    processors.get_mut(key).unwrap()
+}
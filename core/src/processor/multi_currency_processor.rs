@@ -0,0 +1,77 @@
+use crate::prelude::*;
+use crate::fx::Currency;
+use crate::{ProcessError, Transaction, TransactionProcessor};
+use crate::processor::keyed_processor;
+
+/// MultiCurrencyTransactionProcessor keeps separate processor (and so separate balances) for each currency.
+/// Balances are never mixed between currencies, conversion into reporting currency is done on output (see `FxTable`).
+/// Transactions without currency are processed in `default_currency`.
+pub struct MultiCurrencyTransactionProcessor<P> {
+    default_currency: Currency,
+
+    // processor per currency, created when currency is first seen
+    processors: HashMap<Currency, P>,
+}
+
+impl<P> MultiCurrencyTransactionProcessor<P>
+    where P: TransactionProcessor + Default
+{
+    pub fn new(default_currency: Currency) -> Self {
+        MultiCurrencyTransactionProcessor {
+            default_currency,
+            processors: HashMap::new(),
+        }
+    }
+
+    pub fn default_currency(&self) -> &str {
+        &self.default_currency
+    }
+
+    /// Process transaction in given currency
+    pub fn process_in(&mut self, currency: &str, transaction: Transaction) -> Result<(), ProcessError> {
+        keyed_processor(&mut self.processors, currency).process(transaction)
+    }
+}
+
+impl<P> TransactionProcessor for MultiCurrencyTransactionProcessor<P>
+    where P: TransactionProcessor + Default
+{
+    // processor output for each currency
+    type Output = Vec<(Currency, P::Output)>;
+
+    fn process(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        keyed_processor(&mut self.processors, &self.default_currency).process(transaction)
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.processors.into_iter()
+            .map(|(currency, processor)| (currency, processor.finalize()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::{BasicProcessor, Transaction, TransactionProcessor};
+    use crate::processor::multi_currency_processor::MultiCurrencyTransactionProcessor;
+    use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+    #[test]
+    fn deposit_in_two_currencies_then_withdrawal() {
+        let mut processor: MultiCurrencyTransactionProcessor<BasicProcessor> = MultiCurrencyTransactionProcessor::new("USD".to_string());
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))).is_ok());
+        assert!(processor.process_in("EUR", Transaction::new(Deposit, 1, 2, Some(50_u64.into()))).is_ok());
+
+        // balances are not mixed between currencies
+        assert!(processor.process_in("EUR", Transaction::new(Withdrawal, 1, 3, Some(60_u64.into()))).is_err());
+        assert!(processor.process_in("USD", Transaction::new(Withdrawal, 1, 4, Some(60_u64.into()))).is_ok());
+
+        let mut output: Vec<_> = processor.finalize().into_iter()
+            .flat_map(|(currency, accounts)| accounts.map(move |it| (currency.clone(), *it.total())))
+            .collect();
+        output.sort();
+
+        assert_eq!(output, vec![("EUR".to_string(), Decimal::from(50_u64)), ("USD".to_string(), Decimal::from(40_u64))]);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::client::Tenant;
+use crate::processor::keyed_processor;
+use crate::{ProcessError, Transaction, TransactionProcessor};
+
+/// Transactions of tenant submitted to `MultiTenantProcessor`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantStats {
+    processed: u64,
+
+    // rejected by processor, quota rejections are not included
+    rejected: u64,
+
+    // rejected because quota of tenant was exhausted
+    throttled: u64,
+}
+
+impl TenantStats {
+    pub fn processed(&self) -> u64 {
+        self.processed
+    }
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+    pub fn throttled(&self) -> u64 {
+        self.throttled
+    }
+
+    // transactions counted against quota, rejected transactions are processed too
+    fn submitted(&self) -> u64 {
+        self.processed + self.rejected
+    }
+}
+
+/// MultiTenantTransactionProcessor keeps separate processor (and so separate account and transaction
+/// repositories) for each tenant, accounts are keyed by (tenant, client). The same client id or tx id
+/// under different tenants is independent. Transactions without tenant are processed in `default_tenant`.
+/// With quotas one noisy tenant can't consume shared deployment: transactions over quota of tenant are
+/// rejected with `ProcessError::QuotaExceeded`.
+pub struct MultiTenantTransactionProcessor<P> {
+    default_tenant: Tenant,
+
+    // processor per tenant, created when tenant is first seen
+    processors: HashMap<Tenant, P>,
+
+    // max transactions of tenant without own quota, None for unlimited
+    default_quota: Option<u64>,
+
+    // quotas of selected tenants
+    quotas: HashMap<Tenant, u64>,
+
+    stats: HashMap<Tenant, TenantStats>,
+}
+
+impl<P> MultiTenantTransactionProcessor<P>
+    where P: TransactionProcessor + Default
+{
+    pub fn new(default_tenant: Tenant) -> Self {
+        MultiTenantTransactionProcessor {
+            default_tenant,
+            processors: HashMap::new(),
+            default_quota: None,
+            quotas: HashMap::new(),
+            stats: HashMap::new(),
+        }
+    }
+
+    /// Every tenant (without own quota) may submit at most `quota` transactions
+    pub fn with_quota(mut self, quota: u64) -> Self {
+        self.default_quota = Some(quota);
+        self
+    }
+
+    /// Quota of tenant, overrides default quota
+    pub fn set_tenant_quota(&mut self, tenant: &str, quota: Option<u64>) {
+        match quota {
+            Some(quota) => self.quotas.insert(tenant.to_string(), quota),
+            None => self.quotas.remove(tenant),
+        };
+    }
+
+    pub fn default_tenant(&self) -> &str {
+        &self.default_tenant
+    }
+
+    /// Process transaction of given tenant
+    pub fn process_in(&mut self, tenant: &str, transaction: Transaction) -> Result<(), ProcessError> {
+        let quota = self.quotas.get(tenant).copied().or(self.default_quota);
+        let stats = keyed_processor(&mut self.stats, tenant);
+        if quota.is_some_and(|it| stats.submitted() >= it) {
+            stats.throttled += 1;
+            return Err(ProcessError::QuotaExceeded);
+        }
+
+        let result = keyed_processor(&mut self.processors, tenant).process(transaction);
+        match result {
+            Ok(_) => stats.processed += 1,
+            Err(_) => stats.rejected += 1,
+        }
+        result
+    }
+
+    /// Processor of tenant, None for tenant without transactions
+    pub fn tenant(&self, tenant: &str) -> Option<&P> {
+        self.processors.get(tenant)
+    }
+
+    /// Stats of tenant, None for tenant without transactions
+    pub fn stats(&self, tenant: &str) -> Option<&TenantStats> {
+        self.stats.get(tenant)
+    }
+
+    /// Stats of all tenants
+    pub fn all_stats(&self) -> impl Iterator<Item=(&str, &TenantStats)> + '_ {
+        self.stats.iter().map(|(tenant, stats)| (tenant.as_str(), stats))
+    }
+}
+
+impl<P> TransactionProcessor for MultiTenantTransactionProcessor<P>
+    where P: TransactionProcessor + Default
+{
+    // processor output for each tenant
+    type Output = Vec<(Tenant, P::Output)>;
+
+    fn process(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        // default tenant is moved out while processor is borrowed mutably
+        let default_tenant = core::mem::take(&mut self.default_tenant);
+        let result = self.process_in(&default_tenant, transaction);
+        self.default_tenant = default_tenant;
+        result
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.processors.into_iter()
+            .map(|(tenant, processor)| (tenant, processor.finalize()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::{BasicProcessor, Transaction, TransactionProcessor};
+    use crate::processor::multi_tenant_processor::MultiTenantTransactionProcessor;
+    use crate::error::ProcessError;
+    use crate::transaction_type::TransactionType::{Deposit, Dispute, Withdrawal};
+
+    #[test]
+    fn same_client_and_tx_under_two_tenants_then_independent_accounts() {
+        let mut processor: MultiTenantTransactionProcessor<BasicProcessor> = MultiTenantTransactionProcessor::new("acme".to_string());
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))).is_ok());
+        assert!(processor.process_in("globex", Transaction::new(Deposit, 1, 1, Some(30_u64.into()))).is_ok());
+        assert!(processor.process_in("globex", Transaction::new(Dispute, 1, 1, None)).is_ok());
+
+        assert_eq!(*processor.tenant("acme").and_then(|it| it.find_account(1)).unwrap().held(), Decimal::ZERO);
+        assert!(processor.tenant("initech").is_none());
+
+        let mut output: Vec<_> = processor.finalize().into_iter()
+            .flat_map(|(tenant, accounts)| accounts.map(move |it| (tenant.clone(), *it.client(), *it.held())))
+            .collect();
+        output.sort();
+
+        assert_eq!(output, vec![("acme".to_string(), 1, Decimal::ZERO), ("globex".to_string(), 1, Decimal::from(30_u64))]);
+    }
+
+    #[test]
+    fn quota_exhausted_then_other_tenants_still_processed() {
+        let mut processor: MultiTenantTransactionProcessor<BasicProcessor> = MultiTenantTransactionProcessor::new("acme".to_string()).with_quota(2);
+        processor.set_tenant_quota("globex", Some(3));
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Withdrawal, 1, 2, Some(200_u64.into()))).is_err());
+        assert!(matches!(processor.process(Transaction::new(Deposit, 1, 3, Some(1_u64.into()))), Err(ProcessError::QuotaExceeded)));
+        for tx in 1..=3 {
+            assert!(processor.process_in("globex", Transaction::new(Deposit, 1, tx, Some(1_u64.into()))).is_ok());
+        }
+
+        let acme = processor.stats("acme").unwrap();
+        assert_eq!((acme.processed(), acme.rejected(), acme.throttled()), (1, 1, 1));
+        assert_eq!(processor.stats("globex").map(|it| it.processed()), Some(3));
+        assert_eq!(processor.all_stats().count(), 2);
+    }
+}
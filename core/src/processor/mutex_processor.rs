@@ -0,0 +1,35 @@
+use std::sync::Mutex;
+use crate::{ProcessError, SharedTransactionProcessor, Transaction, TransactionProcessor};
+
+/// MutexTransactionProcessor makes any processor thread-safe with single global lock.
+/// Simple reference implementation of `SharedTransactionProcessor`, all transactions are serialized.
+pub struct MutexTransactionProcessor<P> {
+    inner: Mutex<P>,
+}
+
+impl<P> MutexTransactionProcessor<P> {
+    pub fn new(processor: P) -> Self {
+        MutexTransactionProcessor {
+            inner: Mutex::new(processor),
+        }
+    }
+}
+
+impl<P> SharedTransactionProcessor for MutexTransactionProcessor<P>
+    where P: TransactionProcessor + Send
+{
+    type Output = P::Output;
+
+    fn process(&self, transaction: Transaction) -> Result<(), ProcessError> {
+        self.inner.lock()
+            .map_err(|_| ProcessError::MutexLockError)?
+            .process(transaction)
+    }
+
+    fn finalize(self) -> Self::Output {
+        // poisoned lock still contains valid state of all finished transactions
+        self.inner.into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .finalize()
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::iter::Flatten;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+#[cfg(feature = "lock-free-reads")]
+use crate::account::view::AccountView;
+#[cfg(feature = "lock-free-reads")]
+use crate::client::Client;
+#[cfg(feature = "lock-free-reads")]
+use crate::processor::account_reads::{AccountLookup, AccountReads};
+use crate::shard::ShardRouter;
+use crate::transaction::TxId;
+use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
+use crate::ProcessError::{AmountNotFound, MismatchClientId, NegativeAmount, TransactionExists};
+use crate::{BasicProcessor, ProcessError, SharedTransactionProcessor, Transaction, TransactionProcessor};
+
+// stripes of tx id index, transactions with tx ids in different stripes don't wait for each other
+const TX_INDEX_STRIPES: usize = 64;
+
+/// ShardedTransactionProcessor makes any processor thread-safe with lock per shard of clients (`ShardRouter`),
+/// every shard has own processor. Transactions of clients in different shards don't wait for each other,
+/// transactions of one client are serialized. Shards share index of retained tx ids (tx id to shard, striped
+/// by tx id), so tx id retained by other shard is rejected as duplicate and dispute of other client's
+/// transaction is rejected as client mismatch, the same as by single processor. Transactions retained by
+/// shard processors before processing (e.g. restored) are not indexed.
+pub struct ShardedTransactionProcessor<P> {
+    router: ShardRouter,
+    shards: Vec<Shard<P>>,
+
+    // shard which retains tx id, every stripe is locked before shard lock
+    tx_index: Vec<Mutex<HashMap<TxId, usize>>>,
+
+    // published account states, see `with_reads`
+    #[cfg(feature = "lock-free-reads")]
+    reads: Option<AccountReads<P>>,
+}
+
+struct Shard<P> {
+    processor: Mutex<P>,
+    acquisitions: AtomicU64,
+    contended: AtomicU64,
+    wait_nanos: AtomicU64,
+}
+
+/// Retained (deposit/withdrawal) transactions of processor, tx ids are checked across shards of
+/// `ShardedProcessor`
+pub trait TransactionLookup {
+    /// Whether transaction is retained, its tx id can't be reused and it can be disputed by its client only
+    fn retains(&self, tx_id: TxId) -> bool;
+}
+
+impl TransactionLookup for BasicProcessor {
+    fn retains(&self, tx_id: TxId) -> bool {
+        self.find_transaction(tx_id).is_some()
+    }
+}
+
+/// Lock contention of shard since processor was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockContention {
+    // number of times lock was acquired
+    acquisitions: u64,
+
+    // number of acquisitions which had to wait for other thread
+    contended: u64,
+
+    // total time spent waiting for lock
+    wait: Duration,
+}
+
+impl LockContention {
+    pub fn acquisitions(&self) -> u64 {
+        self.acquisitions
+    }
+    pub fn contended(&self) -> u64 {
+        self.contended
+    }
+    pub fn wait(&self) -> Duration {
+        self.wait
+    }
+}
+
+impl<P> ShardedTransactionProcessor<P> {
+    /// Processor with `router.shards()` shards, processor of every shard is created with `processor`
+    pub fn new<F>(router: ShardRouter, mut processor: F) -> Self
+        where F: FnMut() -> P
+    {
+        let shards = (0..router.shards())
+            .map(|_| Shard {
+                processor: Mutex::new(processor()),
+                acquisitions: AtomicU64::new(0),
+                contended: AtomicU64::new(0),
+                wait_nanos: AtomicU64::new(0),
+            })
+            .collect();
+        ShardedTransactionProcessor {
+            router,
+            shards,
+            tx_index: (0..TX_INDEX_STRIPES).map(|_| Mutex::new(HashMap::new())).collect(),
+            #[cfg(feature = "lock-free-reads")]
+            reads: None,
+        }
+    }
+
+    /// Lock contention of every shard in shard order, e.g. to find hot shards
+    pub fn contention(&self) -> Vec<LockContention> {
+        self.shards.iter()
+            .map(|it| LockContention {
+                acquisitions: it.acquisitions.load(Ordering::Relaxed),
+                contended: it.contended.load(Ordering::Relaxed),
+                wait: Duration::from_nanos(it.wait_nanos.load(Ordering::Relaxed)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "lock-free-reads")]
+impl<P: AccountLookup> ShardedTransactionProcessor<P> {
+    /// State of client account is published after every transaction of client, so it can be read with
+    /// `account` without waiting for ingestion. Should be enabled before processing.
+    pub fn with_reads(mut self) -> Self {
+        self.reads = Some(AccountReads::new(P::find_account));
+        self
+    }
+}
+
+#[cfg(feature = "lock-free-reads")]
+impl<P> ShardedTransactionProcessor<P> {
+    /// Lock-free read of client account state after the last transaction of client (e.g. for
+    /// `GET /accounts/{client}`), None for unknown client or when reads are not enabled (`with_reads`)
+    pub fn account(&self, client: Client) -> Option<AccountView> {
+        self.reads.as_ref().and_then(|it| it.get(client))
+    }
+}
+
+impl<P> Shard<P> {
+    fn lock(&self) -> Result<MutexGuard<'_, P>, ProcessError> {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        match self.processor.try_lock() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::WouldBlock) => {
+                self.contended.fetch_add(1, Ordering::Relaxed);
+                let start = Instant::now();
+                let guard = self.processor.lock().map_err(|_| ProcessError::MutexLockError);
+                self.wait_nanos.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                guard
+            }
+            Err(TryLockError::Poisoned(_)) => Err(ProcessError::MutexLockError),
+        }
+    }
+}
+
+// rejection of transaction with tx id retained by other shard, errors checked before tx id by single
+// processor come first
+fn foreign_tx_error(transaction: &Transaction) -> Option<ProcessError> {
+    match transaction.r#type() {
+        Deposit | Withdrawal => match transaction.amount() {
+            None => Some(AmountNotFound),
+            Some(amount) if amount.is_sign_negative() => Some(NegativeAmount),
+            Some(_) => Some(TransactionExists),
+        },
+        Dispute | Resolve | Chargeback => Some(MismatchClientId),
+        Close => None,
+    }
+}
+
+impl<P> SharedTransactionProcessor for ShardedTransactionProcessor<P>
+    where P: TransactionProcessor + TransactionLookup + Send,
+          P::Output: IntoIterator
+{
+    // outputs of shards in shard order, e.g. accounts of all clients
+    type Output = Flatten<std::vec::IntoIter<P::Output>>;
+
+    fn process(&self, transaction: Transaction) -> Result<(), ProcessError> {
+        let (client, tx_id) = (transaction.client(), transaction.tx_id());
+        let shard = self.router.shard(client);
+
+        // stripe is locked until index is updated, so tx id can't be retained by two shards
+        let mut tx_index = self.tx_index[tx_id as usize % TX_INDEX_STRIPES].lock().map_err(|_| ProcessError::MutexLockError)?;
+        if let Some(owner) = tx_index.get(&tx_id).copied().filter(|it| *it != shard) {
+            // transaction evicted by other shard (e.g. resolved) is not retained anymore
+            match self.shards[owner].lock()?.retains(tx_id) {
+                true => if let Some(e) = foreign_tx_error(&transaction) {
+                    return Err(e);
+                },
+                false => {
+                    tx_index.remove(&tx_id);
+                }
+            }
+        }
+
+        let mut processor = self.shards[shard].lock()?;
+        let result = processor.process(transaction);
+        match processor.retains(tx_id) {
+            true => {
+                tx_index.insert(tx_id, shard);
+            }
+            false if tx_index.get(&tx_id) == Some(&shard) => {
+                tx_index.remove(&tx_id);
+            }
+            false => {}
+        }
+        drop(tx_index);
+
+        // published under shard lock, so states of client are published in processing order
+        #[cfg(feature = "lock-free-reads")]
+        if let Some(reads) = &self.reads {
+            reads.publish(&processor, client);
+        }
+        result
+    }
+
+    fn finalize(self) -> Self::Output {
+        // poisoned lock still contains valid state of all finished transactions
+        self.shards.into_iter()
+            .map(|it| it.processor.into_inner().unwrap_or_else(|e| e.into_inner()).finalize())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::shard::ShardRouter;
+    use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
+    use crate::{BasicProcessor, ProcessError, ShardedProcessor, SharedTransactionProcessor, Transaction, TransactionProcessor};
+
+    #[test]
+    fn dispute_of_other_shard_transaction_then_client_mismatch() {
+        let router = ShardRouter::new(4).unwrap();
+        let (first, second) = (1, (2..).find(|it| router.shard(*it) != router.shard(1)).unwrap());
+        let processor = ShardedProcessor::new(router, BasicProcessor::new);
+
+        assert!(processor.process(Transaction::new(Deposit, first, 1, Some(Decimal::TEN))).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Deposit, second, 1, Some(Decimal::ONE))), Err(ProcessError::TransactionExists)));
+        assert!(matches!(processor.process(Transaction::new(Dispute, second, 1, None)), Err(ProcessError::MismatchClientId)));
+
+        // resolved transaction is not retained, its tx id can be used by other client
+        assert!(processor.process(Transaction::new(Dispute, first, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Resolve, first, 1, None)).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, second, 1, Some(Decimal::ONE))).is_ok());
+        assert!(matches!(processor.process(Transaction::new(Withdrawal, first, 1, Some(Decimal::ONE))), Err(ProcessError::TransactionExists)));
+    }
+
+    #[test]
+    fn shared_tx_ids_then_same_results_as_basic_processor() {
+        // tx ids are shared by clients of different shards, disputes reference transactions of other clients
+        let mut seed = 0x2545_f491_u64;
+        let mut next = |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+        let transactions: Vec<_> = (0..5_000)
+            .map(|_| {
+                let (client, tx) = (next(8) as u16 + 1, next(40) as u32);
+                match next(10) {
+                    0..=3 => Transaction::new(Deposit, client, tx, Some(Decimal::from(next(100)))),
+                    4 | 5 => Transaction::new(Withdrawal, client, tx, Some(Decimal::from(next(50)))),
+                    6 | 7 => Transaction::new(Dispute, client, tx, None),
+                    8 => Transaction::new(Resolve, client, tx, None),
+                    _ => match next(20) {
+                        0 => Transaction::new(Close, client, tx, None),
+                        _ => Transaction::new(Chargeback, client, tx, None),
+                    },
+                }
+            })
+            .collect();
+
+        let sharded = ShardedProcessor::new(ShardRouter::new(4).unwrap(), BasicProcessor::new);
+        let mut basic = BasicProcessor::new();
+        for transaction in transactions {
+            let expected = basic.process(transaction.clone()).map_err(|e| e.code());
+            assert_eq!(sharded.process(transaction.clone()).map_err(|e| e.code()), expected, "{:?}", transaction);
+        }
+
+        let mut accounts: Vec<_> = sharded.finalize().collect();
+        accounts.sort_by_key(|it| *it.client());
+        let mut expected: Vec<_> = basic.finalize().collect();
+        expected.sort_by_key(|it| *it.client());
+        assert_eq!(accounts, expected);
+    }
+}
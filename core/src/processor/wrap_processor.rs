@@ -1,9 +1,13 @@
+use crate::prelude::*;
 use crate::client::Client;
+use crate::transaction::TxId;
 use crate::ProcessError::{AccountLocked, AmountNotFound, DisputedTransactionNotFound, InvalidTransactionTypeOrAmount, MismatchClientId, OrgTransactionNotFound, TransactionExists, TransactionUnderDispute};
 use crate::{WrapAccountRepository, ProcessError, Transaction, TransactionProcessor};
 use crate::account::wrap::WrapAccount;
-use crate::account::basic::BasicAccount;
-use crate::transaction_type::TransactionType::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+use crate::account::iter::{AccountStates, IntoAccounts};
+use crate::account::view::{AccountView, AccountsPage};
+use crate::metrics::{record_transaction, NoopRecorder, Recorder, ACCOUNTS};
+use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
 
 /// WrapTransactionProcessor contains only wrap-account repository.
 /// Wrap account structure contains rest of data.
@@ -11,6 +15,15 @@ use crate::transaction_type::TransactionType::{Chargeback, Deposit, Dispute, Res
 pub struct WrapTransactionProcessor {
     client_repository: WrapAccountRepository,
 
+    // number of processed transactions, used as activity clock for accounts
+    sequence: u64,
+
+    // mark accounts without activity in last N transactions as dormant in output
+    dormant_threshold: Option<u64>,
+
+    // metrics backend, metrics are dropped by default
+    recorder: Box<dyn Recorder>,
+
     //for future use if we want to store transaction with all kind errors
     //dead letter queue
     //_dlq_repository: DlqRepository,
@@ -26,10 +39,57 @@ impl WrapTransactionProcessor {
     pub fn new() -> Self {
         WrapTransactionProcessor {
             client_repository: WrapAccountRepository::new(),
+            sequence: 0,
+            dormant_threshold: None,
+            recorder: Box::new(NoopRecorder),
             //_dlq_repository: None,
         }
     }
 
+    /// Iterate over per-client state: (client, account, open disputes, stored transactions)
+    /// without consuming the processor.
+    pub fn accounts_state(&self) -> AccountStates<'_> {
+        self.client_repository.into_iter()
+    }
+
+    /// Processing metrics are reported to `recorder` (see `core::metrics`)
+    pub fn with_recorder<R: Recorder + 'static>(mut self, recorder: R) -> Self {
+        self.recorder = Box::new(recorder);
+        self
+    }
+
+    /// When set, output accounts contain `dormant` flag
+    pub fn set_dormant_threshold(&mut self, threshold: Option<u64>) {
+        self.dormant_threshold = threshold;
+    }
+
+    /// Output and dormant accounts are written in ascending client order, bit-for-bit reproducible
+    /// across runs and platforms (accounts are sorted when iterated)
+    pub fn set_ordered(&mut self, ordered: bool) {
+        self.client_repository.set_ordered(ordered);
+    }
+
+    /// Retained (deposit/withdrawal) transaction of client with its ingestion sequence number
+    pub fn find_transaction(&self, client: Client, tx: TxId) -> Option<Cow<'_, Transaction>> {
+        self.client_repository.find(client)?.tx_repository().find_by_tx_id(&tx).ok().flatten()
+    }
+
+    /// Accounts without activity in last `threshold` transactions
+    pub fn dormant_accounts(&self, threshold: u64) -> impl Iterator<Item=AccountView> + '_ {
+        let sequence = self.sequence;
+        self.client_repository.get_all_account_iter()
+            .map(|it| it.account())
+            .filter(move |it| it.is_dormant(sequence, threshold))
+            .map(AccountView::from)
+    }
+
+    /// Current state of up to `limit` accounts in ascending client order, starting after `cursor`
+    /// (`next_cursor` of previous page, None for the first page)
+    pub fn accounts_page(&self, cursor: Option<Client>, limit: usize) -> AccountsPage {
+        let accounts = self.client_repository.page(cursor, limit).into_iter().map(|it| AccountView::from(it.account())).collect();
+        AccountsPage::new(accounts, limit)
+    }
+
     fn account(&mut self, client: Client) -> Result<&mut WrapAccount, ProcessError> {
         let account = self.client_repository.find_by_client(client);
 
@@ -48,12 +108,17 @@ impl WrapTransactionProcessor {
 
         let account = self.account(transaction.client())?;
 
-        match account.tx_repository().exist_by_tx_id(&transaction.tx_id()) {
+        match account.tx_repository().exist_by_tx_id(&transaction.tx_id())? {
             true => Err(TransactionExists),
             false => {
-                let _ = account.withdrawal(amount)?;
+                let tx_id = transaction.tx_id();
                 // The document is a bit unclear about what kind of transactions can be disputed, so we must save withdrawal transactions
-                account.mut_tx_repository().insert(transaction.tx_id(), transaction);
+                // transaction is stored first (and removed if rejected), so storage error leaves account untouched
+                account.mut_tx_repository().insert(tx_id, transaction)?;
+                if let Err(e) = account.withdrawal(amount) {
+                    account.mut_tx_repository().delete_by_id(&tx_id);
+                    return Err(e);
+                }
                 Ok(())
             }
         }
@@ -66,12 +131,17 @@ impl WrapTransactionProcessor {
 
         let account = self.account(transaction.client())?;
 
-        match account.tx_repository().exist_by_tx_id(&transaction.tx_id()) {
+        match account.tx_repository().exist_by_tx_id(&transaction.tx_id())? {
             true => Err(TransactionExists),
             false => {
-                let _ = account.deposit(amount)?;
+                let tx_id = transaction.tx_id();
                 // The document is a bit unclear about what kind of transactions can be disputed, so we must save deposit transactions
-                account.mut_tx_repository().insert(transaction.tx_id(), transaction);
+                // transaction is stored first (and removed if rejected), so storage error leaves account untouched
+                account.mut_tx_repository().insert(tx_id, transaction)?;
+                if let Err(e) = account.deposit(amount) {
+                    account.mut_tx_repository().delete_by_id(&tx_id);
+                    return Err(e);
+                }
                 Ok(())
             }
         }
@@ -84,11 +154,11 @@ impl WrapTransactionProcessor {
     fn dispute(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
         let account = self.account(transaction.client())?;
 
-        if account.dispute_tx_repository().exist_by_tx_id(&transaction.tx_id()) {
+        if account.dispute_tx_repository().exist_by_tx_id(&transaction.tx_id())? {
             return Err(TransactionUnderDispute);
         }
 
-        let org_tx = account.tx_repository().find_by_tx_id(&transaction.tx_id()).ok_or(OrgTransactionNotFound)?;
+        let org_tx = account.tx_repository().find_by_tx_id(&transaction.tx_id())?.ok_or(OrgTransactionNotFound)?;
 
         if org_tx.client() != transaction.client() {
             return Err(MismatchClientId);
@@ -97,13 +167,21 @@ impl WrapTransactionProcessor {
         // The document is a bit unclear about what kind of transactions can be disputed
         match (org_tx.r#type(), org_tx.amount()) {
             (Deposit, Some(amount)) => {
-                let _ = account.dispute_deposit(&amount)?;
-                account.mut_dispute_tx_repository().insert(transaction.tx_id(), transaction);
+                let tx_id = transaction.tx_id();
+                account.mut_dispute_tx_repository().insert(tx_id, transaction)?;
+                if let Err(e) = account.dispute_deposit(&amount) {
+                    account.mut_dispute_tx_repository().delete_by_id(&tx_id);
+                    return Err(e);
+                }
                 Ok(())
             }
             (Withdrawal, Some(amount)) => {
-                let _ = account.dispute_withdrawal(&amount)?;
-                account.mut_dispute_tx_repository().insert(transaction.tx_id(), transaction);
+                let tx_id = transaction.tx_id();
+                account.mut_dispute_tx_repository().insert(tx_id, transaction)?;
+                if let Err(e) = account.dispute_withdrawal(&amount) {
+                    account.mut_dispute_tx_repository().delete_by_id(&tx_id);
+                    return Err(e);
+                }
                 Ok(())
             }
             _ => Err(InvalidTransactionTypeOrAmount)
@@ -117,8 +195,8 @@ impl WrapTransactionProcessor {
     fn resolve(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
         let account = self.account(transaction.client())?;
 
-        let dispute_tx = account.dispute_tx_repository().find_by_tx_id(&transaction.tx_id()).ok_or(DisputedTransactionNotFound)?;
-        let org_tx = account.tx_repository().find_by_tx_id(&dispute_tx.tx_id()).ok_or(OrgTransactionNotFound)?;
+        let dispute_tx = account.dispute_tx_repository().find_by_tx_id(&transaction.tx_id())?.ok_or(DisputedTransactionNotFound)?;
+        let org_tx = account.tx_repository().find_by_tx_id(&dispute_tx.tx_id())?.ok_or(OrgTransactionNotFound)?;
 
         if org_tx.client() != transaction.client() {
             return Err(MismatchClientId);
@@ -127,7 +205,7 @@ impl WrapTransactionProcessor {
         // can we use resolve only for withdrawal?
         match (org_tx.r#type(), org_tx.amount()) {
             (Withdrawal | Deposit, Some(amount)) => {
-                let _ = account.resolve(&amount)?;
+                account.resolve(&amount)?;
                 account.mut_dispute_tx_repository().delete_by_id(&transaction.tx_id());
                 // no re-dispute allowed
                 account.mut_tx_repository().delete_by_id(&transaction.tx_id());
@@ -144,8 +222,8 @@ impl WrapTransactionProcessor {
     fn charge_back(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
         let account = self.account(transaction.client())?;
 
-        let dispute_tx = account.dispute_tx_repository().find_by_tx_id(&transaction.tx_id()).ok_or(DisputedTransactionNotFound)?;
-        let org_tx = account.tx_repository().find_by_tx_id(&dispute_tx.tx_id()).ok_or(OrgTransactionNotFound)?;
+        let dispute_tx = account.dispute_tx_repository().find_by_tx_id(&transaction.tx_id())?.ok_or(DisputedTransactionNotFound)?;
+        let org_tx = account.tx_repository().find_by_tx_id(&dispute_tx.tx_id())?.ok_or(OrgTransactionNotFound)?;
 
         if org_tx.client() != transaction.client() {
             return Err(MismatchClientId);
@@ -154,7 +232,7 @@ impl WrapTransactionProcessor {
         // can we use chargeback only for withdrawal?
         match (org_tx.r#type(), org_tx.amount()) {
             (Withdrawal | Deposit, Some(amount)) => {
-                let _ = account.chargeback(&amount)?;
+                account.chargeback(&amount)?;
                 account.mut_dispute_tx_repository().delete_by_id(&transaction.tx_id());
                 // no re-dispute allowed
                 account.mut_tx_repository().delete_by_id(&transaction.tx_id());
@@ -163,28 +241,55 @@ impl WrapTransactionProcessor {
             _ => Err(InvalidTransactionTypeOrAmount)
         }
     }
+
+    /// A close marks the client's account as closed. Further deposits and withdrawals are rejected,
+    /// open disputes can still be resolved or charged back.
+    fn close(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
+        self.account(transaction.client())?.close()
+    }
 }
 
 impl TransactionProcessor for WrapTransactionProcessor {
+    // all accounts state, same as IntoIterator
+    type Output = IntoAccounts;
+
     fn process(&mut self, transaction: Transaction) -> Result<(), ProcessError> {
         // we can here match result and write transaction with errors to dlq repository
         // but by default we do nothing
-        match &transaction.r#type() {
+        self.sequence += 1;
+        let (client, r#type, amount) = (transaction.client(), *transaction.r#type(), transaction.amount());
+
+        let result = match r#type {
             Withdrawal => self.withdrawal(transaction),
             Deposit => self.deposit(transaction),
             Dispute => self.dispute(transaction),
             Resolve => self.resolve(transaction),
             Chargeback => self.charge_back(transaction),
+            Close => self.close(transaction),
+        };
+
+        if result.is_ok() {
+            self.client_repository.find_by_client(client).touch(self.sequence);
         }
+
+        record_transaction(self.recorder.as_ref(), r#type, amount, &result);
+        self.recorder.gauge(ACCOUNTS, self.client_repository.len() as f64, &[]);
+
+        result
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.into_iter()
     }
 }
 
 impl IntoIterator for WrapTransactionProcessor {
-    type Item = BasicAccount;
-    type IntoIter = Box<dyn Iterator<Item=Self::Item>>; //impl Iterator in traits is unstable :(
+    type Item = AccountView;
+    type IntoIter = IntoAccounts;
 
     fn into_iter(self) -> Self::IntoIter {
-        Box::new(self.client_repository.get_all_account_into_iter())
+        let dormant = self.dormant_threshold.map(|threshold| (self.sequence, threshold));
+        IntoAccounts::wrap(self.client_repository.get_all_account_into_iter(), dormant)
     }
 }
 
@@ -192,8 +297,8 @@ impl IntoIterator for WrapTransactionProcessor {
 mod tests {
     use rust_decimal::Decimal;
     use rust_decimal::prelude::FromPrimitive;
-    use crate::{WrapTransactionProcessor, Transaction, TransactionProcessor};
-    use crate::transaction_type::TransactionType::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+    use crate::{WrapTransactionProcessor, ProcessError, Transaction, TransactionProcessor};
+    use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
 
     #[test]
     fn deposit_into_iter() {
@@ -210,6 +315,46 @@ mod tests {
         assert!(!account.locked());
     }
 
+    #[test]
+    fn deposit_dispute_then_accounts_state() {
+        let mut processor = WrapTransactionProcessor::new();
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 1, 2, Some(50_u64.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 1, 2, None)).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 3, Some(10_u64.into()))).is_ok());
+
+        let mut state: Vec<_> = processor.accounts_state().collect();
+        state.sort_by_key(|it| it.0);
+
+        assert_eq!(state.len(), 2);
+        let (client, account, open_disputes, stored_tx_count) = state[0];
+        assert_eq!(client, 1);
+        assert_eq!(account.held(), &Decimal::from(50_u64));
+        assert_eq!(open_disputes, 1);
+        assert_eq!(stored_tx_count, 2);
+
+        let (client, _, open_disputes, stored_tx_count) = state[1];
+        assert_eq!(client, 2);
+        assert_eq!(open_disputes, 0);
+        assert_eq!(stored_tx_count, 1);
+    }
+
+
+    #[test]
+    fn deposits_then_dormant_accounts() {
+        let mut processor = WrapTransactionProcessor::new();
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Deposit, 2, 2, Some(50_u64.into()))).is_ok());
+        assert!(processor.process(Transaction::new(Dispute, 2, 2, None)).is_ok());
+
+        let dormant: Vec<_> = processor.dormant_accounts(2).map(|it| *it.client()).collect();
+        assert_eq!(dormant, vec![1]);
+        assert_eq!(processor.dormant_accounts(4).count(), 0);
+
+        // dormant flag is not set without threshold
+        assert!(processor.finalize().all(|it| it.dormant().is_none()));
+    }
+
     #[test]
     fn deposit_find_by_client() {
         let mut processor = WrapTransactionProcessor::new();
@@ -412,6 +557,35 @@ mod tests {
         assert!(!account.locked());
     }
 
+
+    #[test]
+    fn deposit_dispute_close_then_chargeback() {
+        let mut processor = WrapTransactionProcessor::new();
+
+        let transaction = Transaction::new(Deposit, 1, 1, Some(100_u64.into()));
+        assert!(processor.process(transaction).is_ok());
+
+        let transaction = Transaction::new(Dispute, 1, 1, None);
+        assert!(processor.process(transaction).is_ok());
+
+        let transaction = Transaction::new(Close, 1, 2, None);
+        assert!(processor.process(transaction).is_ok());
+
+        let transaction = Transaction::new(Deposit, 1, 3, Some(100_u64.into()));
+        assert!(matches!(processor.process(transaction), Err(ProcessError::AccountClosed)));
+
+        let transaction = Transaction::new(Chargeback, 1, 1, None);
+        assert!(processor.process(transaction).is_ok());
+
+        let account = processor.into_iter().next();
+        assert!(account.is_some());
+        let account = account.unwrap();
+
+        assert_eq!(account.total(), &Decimal::from(0_u64));
+        assert!(account.closed());
+        assert!(account.locked());
+    }
+
     #[test]
     fn deposit_dispute_then_invalid_dispute() {
         let mut processor = WrapTransactionProcessor::new();
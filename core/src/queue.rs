@@ -0,0 +1,424 @@
+//! Bounded transaction queue with spill to disk (`SpillQueue`) and source read ahead on separate thread
+//! (`prefetch`), so bursty sources (stdin, tcp) are drained while processor catches up and memory stays bounded.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use rust_decimal::Decimal;
+use crate::Transaction;
+use crate::transaction_type::TransactionType;
+
+static SPILL_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// record kinds in spill file
+const TRANSACTION: u8 = 0;
+const ERROR: u8 = 1;
+
+/// FIFO of source records. Up to `capacity` records are kept in memory, the rest is appended to spill file
+/// and read back (in batches of `capacity`) when memory part is consumed. Source errors are not spilled,
+/// only their position is. Spill file is created lazily, truncated when drained and removed on drop.
+pub struct SpillQueue<E> {
+    memory: VecDeque<Result<Transaction, E>>,
+    capacity: usize,
+
+    // errors of spilled part in order, their positions are marked in spill file
+    errors: VecDeque<E>,
+
+    spill_path: PathBuf,
+    spill_file: Option<File>,
+    write_offset: u64,
+    read_offset: u64,
+
+    // records in spill file not read back yet
+    spilled: usize,
+}
+
+impl<E> SpillQueue<E> {
+    pub fn new(capacity: usize) -> Self {
+        let id = SPILL_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        Self::with_spill_path(capacity, std::env::temp_dir().join(format!("queue-spill-{}-{}.bin", std::process::id(), id)))
+    }
+
+    pub fn with_spill_path(capacity: usize, spill_path: PathBuf) -> Self {
+        SpillQueue {
+            memory: VecDeque::new(),
+            capacity: capacity.max(1),
+            errors: VecDeque::new(),
+            spill_path,
+            spill_file: None,
+            write_offset: 0,
+            read_offset: 0,
+            spilled: 0,
+        }
+    }
+
+    /// Number of queued records
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.spilled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of queued records in spill file
+    pub fn spilled(&self) -> usize {
+        self.spilled
+    }
+
+    /// Remove all queued records and spill file
+    pub fn clear(&mut self) {
+        self.memory.clear();
+        self.errors.clear();
+        self.spilled = 0;
+        self.read_offset = 0;
+        self.write_offset = 0;
+        if self.spill_file.take().is_some() {
+            let _ = std::fs::remove_file(&self.spill_path);
+        }
+    }
+
+    /// Record is dropped when it can't be spilled
+    pub fn push(&mut self, record: Result<Transaction, E>) -> io::Result<()> {
+        if self.spilled == 0 && self.memory.len() < self.capacity {
+            self.memory.push_back(record);
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        match record {
+            Ok(transaction) => {
+                buf.push(TRANSACTION);
+                encode(&transaction, &mut buf);
+            }
+            Err(e) => {
+                buf.push(ERROR);
+                self.errors.push_back(e);
+            }
+        }
+        self.write_record(&buf)?;
+        self.spilled += 1;
+        Ok(())
+    }
+
+    /// Oldest record, spilled records are read back when memory part is empty
+    pub fn pop(&mut self) -> Option<io::Result<Result<Transaction, E>>> {
+        if self.memory.is_empty() && self.spilled > 0 {
+            if let Err(e) = self.read_back() {
+                return Some(Err(e));
+            }
+        }
+        self.memory.pop_front().map(Ok)
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        if self.spill_file.is_none() {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&self.spill_path)?;
+            self.spill_file = Some(file);
+        }
+
+        if let Some(file) = self.spill_file.as_mut() {
+            // cursor is shared with reads, so always seek to the end of written data
+            file.seek(SeekFrom::Start(self.write_offset))?;
+            file.write_all(record)?;
+        }
+        self.write_offset += record.len() as u64;
+        Ok(())
+    }
+
+    // move up to capacity spilled records into memory, drained file is truncated
+    fn read_back(&mut self) -> io::Result<()> {
+        let file = self.spill_file.as_mut()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "spill file not open"))?;
+        file.seek(SeekFrom::Start(self.read_offset))?;
+
+        let unread = self.write_offset - self.read_offset;
+        let mut reader = BufReader::new((&mut *file).take(unread));
+        let count = self.spilled.min(self.capacity);
+        for _ in 0..count {
+            let mut kind = [0; 1];
+            reader.read_exact(&mut kind)?;
+            let record = match kind[0] {
+                TRANSACTION => Ok(decode(&mut reader)?),
+                ERROR => Err(self.errors.pop_front().ok_or_else(corrupted)?),
+                _ => return Err(corrupted()),
+            };
+            self.memory.push_back(record);
+        }
+        // bytes read from file less bytes still buffered
+        self.read_offset += unread - reader.get_ref().limit() - reader.buffer().len() as u64;
+        self.spilled -= count;
+
+        if self.spilled == 0 {
+            file.set_len(0)?;
+            self.read_offset = 0;
+            self.write_offset = 0;
+        }
+        Ok(())
+    }
+}
+
+impl<E> Drop for SpillQueue<E> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+// shared state of prefetch reader thread and consumer
+struct Shared<E> {
+    queue: SpillQueue<E>,
+
+    // reader thread finished, with error when record couldn't be spilled
+    done: bool,
+    failure: Option<E>,
+
+    // consumer dropped, reader thread stops after next record
+    stopped: bool,
+}
+
+type SharedState<E> = Arc<(Mutex<Shared<E>>, Condvar)>;
+
+fn lock<E>(state: &SharedState<E>) -> MutexGuard<'_, Shared<E>> {
+    state.0.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Records of source read ahead on separate thread, see `prefetch`
+pub struct Prefetch<E> {
+    state: SharedState<E>,
+}
+
+/// Read source opened by `open` on separate thread into `queue` as fast as source delivers, records are
+/// consumed from returned iterator in source order. Spill error (e.g. disk full) stops reading, it is the last
+/// record of iterator. Reader thread ends with source or after iterator is dropped.
+pub fn prefetch<F, I, E>(open: F, queue: SpillQueue<E>) -> Prefetch<E>
+    where F: FnOnce() -> I + Send + 'static,
+          I: IntoIterator<Item=Result<Transaction, E>>,
+          E: From<io::Error> + Send + 'static
+{
+    let state: SharedState<E> = Arc::new((Mutex::new(Shared { queue, done: false, failure: None, stopped: false }), Condvar::new()));
+    let reader = state.clone();
+
+    thread::spawn(move || {
+        // consumer is woken up also when source panics
+        let _done = Done(reader.clone());
+        for record in open() {
+            let mut shared = lock(&reader);
+            if shared.stopped {
+                return;
+            }
+            if let Err(e) = shared.queue.push(record) {
+                shared.failure = Some(e.into());
+                return;
+            }
+            reader.1.notify_one();
+        }
+    });
+
+    Prefetch { state }
+}
+
+// marks reader thread as finished when dropped
+struct Done<E>(SharedState<E>);
+
+impl<E> Drop for Done<E> {
+    fn drop(&mut self) {
+        lock(&self.0).done = true;
+        self.0.1.notify_one();
+    }
+}
+
+impl<E: From<io::Error>> Iterator for Prefetch<E> {
+    type Item = Result<Transaction, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut shared = lock(&self.state);
+        loop {
+            match shared.queue.pop() {
+                Some(Ok(record)) => return Some(record),
+                Some(Err(e)) => return Some(Err(e.into())),
+                None if shared.done => return shared.failure.take().map(Err),
+                None => shared = self.state.1.wait(shared).unwrap_or_else(PoisonError::into_inner),
+            }
+        }
+    }
+}
+
+impl<E> Drop for Prefetch<E> {
+    // reader thread may outlive iterator (blocked on source), spill file is removed now
+    fn drop(&mut self) {
+        let mut shared = lock(&self.state);
+        shared.stopped = true;
+        shared.queue.clear();
+    }
+}
+
+fn corrupted() -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, "corrupted queue spill record")
+}
+
+// type, client, tx, amount, sequence, reference and metadata
+fn encode(transaction: &Transaction, buf: &mut Vec<u8>) {
+    buf.push(match transaction.r#type() {
+        TransactionType::Chargeback => 0,
+        TransactionType::Deposit => 1,
+        TransactionType::Dispute => 2,
+        TransactionType::Resolve => 3,
+        TransactionType::Withdrawal => 4,
+        TransactionType::Close => 5,
+    });
+    buf.extend_from_slice(&transaction.client().to_le_bytes());
+    buf.extend_from_slice(&transaction.tx_id().to_le_bytes());
+    match transaction.amount() {
+        Some(amount) => {
+            buf.push(1);
+            buf.extend_from_slice(&amount.serialize());
+        }
+        None => buf.push(0),
+    }
+    buf.extend_from_slice(&transaction.sequence().to_le_bytes());
+    encode_str(transaction.reference().unwrap_or_default(), buf);
+
+    #[cfg(feature = "metadata")]
+    {
+        buf.extend_from_slice(&(transaction.metadata().len() as u32).to_le_bytes());
+        for (name, value) in transaction.metadata() {
+            encode_str(name, buf);
+            encode_str(value, buf);
+        }
+    }
+    #[cfg(not(feature = "metadata"))]
+    buf.extend_from_slice(&0_u32.to_le_bytes());
+}
+
+fn decode<R: Read>(reader: &mut R) -> io::Result<Transaction> {
+    let mut fixed = [0; 8];
+    reader.read_exact(&mut fixed)?;
+    let r#type = match fixed[0] {
+        0 => TransactionType::Chargeback,
+        1 => TransactionType::Deposit,
+        2 => TransactionType::Dispute,
+        3 => TransactionType::Resolve,
+        4 => TransactionType::Withdrawal,
+        5 => TransactionType::Close,
+        _ => return Err(corrupted()),
+    };
+    let client = u16::from_le_bytes([fixed[1], fixed[2]]);
+    let tx = u32::from_le_bytes([fixed[3], fixed[4], fixed[5], fixed[6]]);
+    let amount = match fixed[7] {
+        1 => {
+            let mut bytes = [0; 16];
+            reader.read_exact(&mut bytes)?;
+            Some(Decimal::deserialize(bytes))
+        }
+        _ => None,
+    };
+
+    let mut sequence = [0; 8];
+    reader.read_exact(&mut sequence)?;
+    let mut transaction = Transaction::new(r#type, client, tx, amount).with_sequence(u64::from_le_bytes(sequence));
+
+    let reference = decode_str(reader)?;
+    if !reference.is_empty() {
+        transaction = transaction.with_reference(reference);
+    }
+
+    // always empty without metadata feature
+    let metadata = (0..decode_len(reader)?)
+        .map(|_| Ok((decode_str(reader)?, decode_str(reader)?)))
+        .collect::<io::Result<HashMap<_, _>>>()?;
+    #[cfg(feature = "metadata")]
+    let transaction = transaction.with_metadata(metadata);
+    #[cfg(not(feature = "metadata"))]
+    let _ = metadata;
+
+    Ok(transaction)
+}
+
+fn encode_str(value: &str, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+fn decode_len<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    Ok(u32::from_le_bytes(len))
+}
+
+fn decode_str<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut value = vec![0; decode_len(reader)? as usize];
+    reader.read_exact(&mut value)?;
+    String::from_utf8(value).map_err(|_| corrupted())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use rust_decimal::Decimal;
+    use crate::queue::{prefetch, SpillQueue};
+    use crate::Transaction;
+    use crate::transaction_type::TransactionType::{Deposit, Dispute};
+
+    #[derive(Debug, PartialEq)]
+    enum TestError {
+        Row(u64),
+        Io,
+    }
+
+    impl From<io::Error> for TestError {
+        fn from(_: io::Error) -> Self {
+            TestError::Io
+        }
+    }
+
+    fn records(len: u32) -> impl Iterator<Item=Result<Transaction, TestError>> {
+        (1..=len).map(|tx| match tx % 5 {
+            0 => Err(TestError::Row(tx as u64)),
+            _ => Ok(Transaction::new(Deposit, 1, tx, Some(Decimal::new(tx as i64, 2))).with_sequence(tx as u64).with_reference(format!("ref-{}", tx))),
+        })
+    }
+
+    fn key(record: Result<Transaction, TestError>) -> Result<(u32, Option<Decimal>, u64, Option<String>), TestError> {
+        record.map(|it| (it.tx_id(), it.amount(), it.sequence(), it.reference().map(str::to_string)))
+    }
+
+    #[test]
+    fn push_over_capacity_then_spilled_in_order() {
+        let path = std::env::temp_dir().join("queue-spill-test-in-order.bin");
+        let mut queue = SpillQueue::with_spill_path(3, path.clone());
+        for record in records(10) {
+            queue.push(record).unwrap();
+        }
+        assert_eq!((queue.len(), queue.spilled()), (10, 7));
+        assert!(path.exists());
+
+        // interleaved push while spilled part is read back
+        let mut popped = vec![queue.pop().unwrap().unwrap()];
+        queue.push(Ok(Transaction::new(Dispute, 1, 1, None))).unwrap();
+        while let Some(record) = queue.pop() {
+            popped.push(record.unwrap());
+        }
+
+        let expected: Vec<_> = records(10).chain([Ok(Transaction::new(Dispute, 1, 1, None))]).map(key).collect();
+        assert_eq!(popped.into_iter().map(key).collect::<Vec<_>>(), expected);
+        assert!(queue.is_empty());
+
+        drop(queue);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn prefetch_then_same_records_in_source_order() {
+        let prefetched: Vec<_> = prefetch(|| records(100), SpillQueue::new(4)).map(key).collect();
+        assert_eq!(prefetched, records(100).map(key).collect::<Vec<_>>());
+    }
+}
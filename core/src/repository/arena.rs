@@ -0,0 +1,102 @@
+use crate::{ProcessError, Transaction};
+use crate::prelude::*;
+
+/// Index handle of transaction stored in arena
+pub type Handle = u32;
+
+/// Bump arena for stored transactions.
+/// Transactions are kept in one contiguous Vec (cache friendly, one growing allocation
+/// instead of per-entry allocations), repositories keep only u32 handles.
+/// Released slots are vacant (transaction is dropped) until reused by next allocation.
+pub struct TransactionArena {
+    slots: Vec<Option<Transaction>>,
+    free: Vec<Handle>,
+}
+
+impl Default for TransactionArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransactionArena {
+    pub fn new() -> Self {
+        TransactionArena {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        TransactionArena {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+        }
+    }
+
+    /// More than `Handle::MAX + 1` transactions can't be stored, then `RepositoryFull` is returned
+    pub fn alloc(&mut self, transaction: Transaction) -> Result<Handle, ProcessError> {
+        match self.free.pop() {
+            Some(handle) => {
+                self.slots[handle as usize] = Some(transaction);
+                Ok(handle)
+            }
+            None => {
+                let handle = Handle::try_from(self.slots.len()).map_err(|_| ProcessError::RepositoryFull)?;
+                self.slots.push(Some(transaction));
+                Ok(handle)
+            }
+        }
+    }
+
+    /// Make room for `additional` transactions (free slots are reused first)
+    pub fn reserve(&mut self, additional: usize) {
+        self.slots.reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    /// None for released handle
+    pub fn get(&self, handle: Handle) -> Option<&Transaction> {
+        self.slots.get(handle as usize).and_then(Option::as_ref)
+    }
+
+    pub fn replace(&mut self, handle: Handle, transaction: Transaction) {
+        self.slots[handle as usize] = Some(transaction);
+    }
+
+    /// Transaction is dropped and slot is marked for reuse. Releasing vacant slot again does nothing.
+    pub fn release(&mut self, handle: Handle) {
+        if self.slots.get_mut(handle as usize).and_then(Option::take).is_some() {
+            self.free.push(handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::arena::TransactionArena;
+    use crate::Transaction;
+    use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+    #[test]
+    fn alloc_release_then_reuse() {
+        let mut arena = TransactionArena::new();
+
+        let first = arena.alloc(Transaction::new(Deposit, 1, 1, Some(1.into()))).unwrap();
+        let second = arena.alloc(Transaction::new(Deposit, 1, 2, Some(2.into()))).unwrap();
+        assert_eq!(arena.get(second).map(|it| it.tx_id()), Some(2));
+
+        arena.release(first);
+        assert!(arena.get(first).is_none());
+        // double release doesn't hand out the slot twice
+        arena.release(first);
+
+        let third = arena.alloc(Transaction::new(Withdrawal, 1, 3, Some(3.into()))).unwrap();
+        assert_eq!(third, first);
+        assert_eq!(arena.get(third).map(|it| it.tx_id()), Some(3));
+        assert_eq!(arena.get(third).map(|it| *it.r#type()), Some(Withdrawal));
+        assert_eq!(arena.get(second).map(|it| it.tx_id()), Some(2));
+
+        let fourth = arena.alloc(Transaction::new(Deposit, 1, 4, Some(4.into()))).unwrap();
+        assert_ne!(fourth, third);
+    }
+}
@@ -1,5 +1,8 @@
-use nohash_hasher::{BuildNoHashHasher, IntMap};
+use nohash_hasher::BuildNoHashHasher;
+use crate::prelude::*;
 use crate::account::basic::BasicAccount;
+use crate::account::factory::AccountFactory;
+use crate::account::iter::{IntoValues, Values};
 use crate::client::Client;
 
 /// Repository to store client account state
@@ -7,6 +10,12 @@ use crate::client::Client;
 /// Client is a valid u16 client ID
 pub struct BasicAccountMemoryRepository {
     inner: IntMap<Client, BasicAccount>,
+
+    // creates accounts of new clients, empty accounts by default
+    factory: Option<Box<dyn AccountFactory>>,
+
+    // accounts are iterated in ascending client order
+    ordered: bool,
 }
 
 impl Default for BasicAccountMemoryRepository {
@@ -18,25 +27,94 @@ impl Default for BasicAccountMemoryRepository {
 impl BasicAccountMemoryRepository {
     pub fn new() -> Self {
         BasicAccountMemoryRepository {
-            inner: IntMap::default()
+            inner: IntMap::default(),
+            factory: None,
+            ordered: false,
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         BasicAccountMemoryRepository {
-            inner: IntMap::with_capacity_and_hasher(capacity, BuildNoHashHasher::default())
+            inner: IntMap::with_capacity_and_hasher(capacity, BuildNoHashHasher::default()),
+            factory: None,
+            ordered: false,
         }
     }
 
+    pub fn set_factory(&mut self, factory: Box<dyn AccountFactory>) {
+        self.factory = Some(factory);
+    }
+
+    /// Iterate accounts in ascending client order (sorted on every iteration), so output is
+    /// reproducible across runs and platforms
+    pub fn set_ordered(&mut self, ordered: bool) {
+        self.ordered = ordered;
+    }
+
     pub fn find_by_client(&mut self, client: Client) -> &mut BasicAccount {
-        self.inner.entry(client).or_insert_with(|| BasicAccount::new(client))
+        let factory = &self.factory;
+        self.inner.entry(client).or_insert_with(|| match factory {
+            Some(factory) => factory.create(client),
+            None => BasicAccount::new(client),
+        })
+    }
+
+    /// Account without creating it when missing
+    pub fn get_by_client(&self, client: Client) -> Option<&BasicAccount> {
+        self.inner.get(&client)
+    }
+
+    /// Mutable account without creating it when missing
+    pub fn get_mut_by_client(&mut self, client: Client) -> Option<&mut BasicAccount> {
+        self.inner.get_mut(&client)
+    }
+
+    /// Insert (or replace) account, for example restored from snapshot
+    pub fn insert(&mut self, account: BasicAccount) {
+        self.inner.insert(*account.client(), account);
     }
 
-    pub fn get_all_account_iter(&self) -> impl Iterator<Item=&BasicAccount> {
-        self.inner.iter().map(|it| it.1)
+    /// Number of accounts
+    pub fn len(&self) -> usize {
+        self.inner.len()
     }
 
-    pub fn get_all_account_into_iter(self) -> impl Iterator<Item=BasicAccount> {
-        self.inner.into_iter().map(|it| it.1)
+    /// Remove account, for example merged into other client account
+    pub fn remove(&mut self, client: Client) -> Option<BasicAccount> {
+        self.inner.remove(&client)
+    }
+
+    pub(crate) fn get_all_account_iter(&self) -> Values<'_, BasicAccount> {
+        if !self.ordered {
+            return Values::Unordered(self.inner.values());
+        }
+
+        let mut accounts: Vec<_> = self.inner.values().collect();
+        accounts.sort_unstable_by_key(|it| *it.client());
+        Values::Ordered(accounts.into_iter())
+    }
+
+    /// `limit` accounts with client greater than `after`, in ascending client order
+    pub(crate) fn page(&self, after: Option<Client>, limit: usize) -> Vec<&BasicAccount> {
+        let mut accounts: Vec<_> = self.inner.iter()
+            .filter(|(client, _)| after.is_none_or(|after| **client > after))
+            .map(|(_, account)| account)
+            .collect();
+        if accounts.len() > limit {
+            accounts.select_nth_unstable_by_key(limit, |it| *it.client());
+            accounts.truncate(limit);
+        }
+        accounts.sort_unstable_by_key(|it| *it.client());
+        accounts
+    }
+
+    pub(crate) fn get_all_account_into_iter(self) -> IntoValues<BasicAccount> {
+        if !self.ordered {
+            return IntoValues::Unordered(self.inner.into_values());
+        }
+
+        let mut accounts: Vec<_> = self.inner.into_values().collect();
+        accounts.sort_unstable_by_key(|it| *it.client());
+        IntoValues::Ordered(accounts.into_iter())
     }
 }
\ No newline at end of file
@@ -0,0 +1,118 @@
+use crate::prelude::*;
+use crate::transaction::TxId;
+
+const MIN_BITS: usize = 1 << 10;
+const BITS_PER_ITEM: usize = 10;
+const HASHES: u64 = 3;
+
+/// Simple Bloom filter for tx ids.
+/// `may_contain` returns false only if tx id was never inserted, so the common "not seen before"
+/// case can skip a HashMap probe and reading of spill segments. False positives are possible, callers must fall back to the lookup.
+/// Items can't be removed, deleted tx ids just stay as (harmless) false positives.
+/// Filter starts small, owner should rebuild it with bigger capacity when `is_full`.
+pub struct TxBloomFilter {
+    bits: Vec<u64>,
+    mask: u64,
+    capacity: usize,
+    len: usize,
+}
+
+impl Default for TxBloomFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TxBloomFilter {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Size filter for expected number of tx ids (~1% false positive rate)
+    pub fn with_capacity(capacity: usize) -> Self {
+        let bits = capacity.saturating_mul(BITS_PER_ITEM).max(MIN_BITS).next_power_of_two();
+        TxBloomFilter {
+            bits: vec![0; bits / 64],
+            mask: bits as u64 - 1,
+            capacity: bits / BITS_PER_ITEM,
+            len: 0,
+        }
+    }
+
+    /// More items than filter was sized for, false positive rate grows
+    pub fn is_full(&self) -> bool {
+        self.len > self.capacity
+    }
+
+    pub fn insert(&mut self, tx_id: TxId) {
+        self.len += 1;
+        let (h1, h2) = hash(tx_id);
+        for i in 0..HASHES {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) & self.mask;
+            self.bits[(bit >> 6) as usize] |= 1 << (bit & 63);
+        }
+    }
+
+    pub fn may_contain(&self, tx_id: TxId) -> bool {
+        let (h1, h2) = hash(tx_id);
+        (0..HASHES).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) & self.mask;
+            self.bits[(bit >> 6) as usize] & (1 << (bit & 63)) != 0
+        })
+    }
+}
+
+// splitmix64 finalizer, two halves are used for double hashing
+fn hash(tx_id: TxId) -> (u64, u64) {
+    let mut z = (tx_id as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z & 0xFFFF_FFFF, (z >> 32) | 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::bloom_filter::TxBloomFilter;
+
+    #[test]
+    fn no_false_negatives() {
+        let mut filter = TxBloomFilter::with_capacity(100_000);
+        for tx_id in (0..200_000_u32).step_by(2) {
+            filter.insert(tx_id);
+        }
+
+        for tx_id in (0..200_000_u32).step_by(2) {
+            assert!(filter.may_contain(tx_id));
+        }
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let mut filter = TxBloomFilter::with_capacity(100_000);
+        for tx_id in 0..100_000_u32 {
+            filter.insert(tx_id);
+        }
+
+        let false_positives = (100_000..200_000_u32).filter(|it| filter.may_contain(*it)).count();
+        // expected ~1%, allow some slack
+        assert!(false_positives < 5_000);
+    }
+
+    #[test]
+    fn rebuild_when_full() {
+        let mut filter = TxBloomFilter::new();
+        let mut inserted = Vec::new();
+        for tx_id in 0..10_000_u32 {
+            if filter.is_full() {
+                filter = TxBloomFilter::with_capacity(inserted.len() * 2);
+                inserted.iter().for_each(|it| filter.insert(*it));
+            }
+            filter.insert(tx_id);
+            inserted.push(tx_id);
+        }
+
+        assert!(!filter.is_full());
+        assert!(inserted.iter().all(|it| filter.may_contain(*it)));
+    }
+}
@@ -2,6 +2,8 @@
 use crate::ProcessError;
 #[cfg(feature = "dlq")]
 use crate::Transaction;
+#[cfg(feature = "dlq")]
+use crate::prelude::*;
 
 #[cfg(feature = "dlq")]
 pub struct NaiveDlqMemoryRepository {
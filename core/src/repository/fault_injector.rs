@@ -0,0 +1,144 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::{ProcessError, StorageTransactionRepository, Transaction};
+use crate::fault::{fault_config, FaultConfig};
+use crate::transaction::TxId;
+
+// every injector gets its own random stream derived from configured seed
+static INJECTOR_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Wrapper repository which fails or delays configured fraction of inserts and lookups
+/// (`find_by_tx_id`, `exist_by_tx_id`). Deletes are never failed, processors use them to roll back.
+/// Faults are pseudo random (splitmix64), so a run can be repeated with the same seed.
+pub struct FaultInjector {
+    inner: StorageTransactionRepository,
+    config: FaultConfig,
+    state: Cell<u64>,
+}
+
+impl Default for FaultInjector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::with_config(StorageTransactionRepository::new(), fault_config())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_config(StorageTransactionRepository::with_capacity(capacity), fault_config())
+    }
+
+    pub fn with_config(inner: StorageTransactionRepository, config: FaultConfig) -> Self {
+        let stream = INJECTOR_COUNTER.fetch_add(1, Ordering::Relaxed);
+        FaultInjector {
+            inner,
+            config,
+            state: Cell::new(config.seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+        }
+    }
+
+    pub fn find_by_tx_id(&self, tx_id: &TxId) -> Result<Option<Cow<'_, Transaction>>, ProcessError> {
+        self.inject()?;
+        self.inner.find_by_tx_id(tx_id)
+    }
+
+    pub fn exist_by_tx_id(&self, tx_id: &TxId) -> Result<bool, ProcessError> {
+        self.inject()?;
+        self.inner.exist_by_tx_id(tx_id)
+    }
+
+    /// Number of stored transactions
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn insert(&mut self, tx_id: TxId, transaction: Transaction) -> Result<(), ProcessError> {
+        self.inject()?;
+        self.inner.insert(tx_id, transaction)
+    }
+
+    /// Bulk insert is one storage operation, it fails (or is delayed) as a whole
+    pub fn insert_all(&mut self, transactions: &[Transaction]) -> Result<(), ProcessError> {
+        self.inject()?;
+        self.inner.insert_all(transactions)
+    }
+
+    pub fn delete_by_id(&mut self, tx_id: &TxId) {
+        self.inner.delete_by_id(tx_id)
+    }
+
+    fn inject(&self) -> Result<(), ProcessError> {
+        if self.config.delay_rate > 0.0 && self.roll() < self.config.delay_rate {
+            std::thread::sleep(self.config.delay);
+        }
+
+        match self.config.fail_rate > 0.0 && self.roll() < self.config.fail_rate {
+            true => Err(ProcessError::StorageError(std::io::Error::other("injected fault"))),
+            false => Ok(()),
+        }
+    }
+
+    /// Uniform value in [0, 1)
+    fn roll(&self) -> f64 {
+        let state = self.state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(state);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        (z >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::{BasicAccountRepository, BasicTransactionProcessor, ProcessError, StorageTransactionRepository, Transaction, TransactionProcessor, TransactionRepository};
+    use crate::fault::FaultConfig;
+    use crate::repository::fault_injector::FaultInjector;
+    use crate::transaction_type::TransactionType::Deposit;
+
+    fn injector(fail_rate: f64) -> FaultInjector {
+        FaultInjector::with_config(StorageTransactionRepository::new(), FaultConfig { fail_rate, seed: 7, ..FaultConfig::NONE })
+    }
+
+    #[test]
+    fn insert_then_find_without_faults() {
+        let mut repository = injector(0.0);
+
+        assert!(repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::ONE))).is_ok());
+        assert!(repository.exist_by_tx_id(&1).unwrap());
+        assert_eq!(repository.find_by_tx_id(&1).unwrap().unwrap().tx_id(), 1);
+    }
+
+    #[test]
+    fn insert_then_storage_error() {
+        let mut repository = injector(1.0);
+
+        assert!(matches!(repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::ONE))), Err(ProcessError::StorageError(_))));
+        assert!(matches!(repository.exist_by_tx_id(&1), Err(ProcessError::StorageError(_))));
+        assert_eq!(repository.len(), 0);
+    }
+
+    #[test]
+    fn fail_rate_then_fraction_of_faults() {
+        let repository = injector(0.25);
+
+        let failed = (0..10_000).filter(|it| repository.exist_by_tx_id(it).is_err()).count();
+        assert!((2_000..3_000).contains(&failed));
+    }
+
+    #[test]
+    fn deposit_then_storage_error_leaves_account_untouched() {
+        let mut processor = BasicTransactionProcessor::with_repository(BasicAccountRepository::new(), injector(1.0), TransactionRepository::new());
+
+        assert!(matches!(processor.process(Transaction::new(Deposit, 1, 1, Some(Decimal::ONE))), Err(ProcessError::StorageError(_))));
+        assert!(processor.into_iter().all(|it| it.total() == &Decimal::ZERO));
+    }
+}
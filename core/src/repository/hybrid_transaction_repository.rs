@@ -0,0 +1,765 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use nohash_hasher::{BuildNoHashHasher, IntMap, IntSet};
+use rust_decimal::Decimal;
+use crate::{ProcessError, Transaction};
+use crate::transaction::TxId;
+use crate::transaction_type::TransactionType;
+#[cfg(feature = "bloom")]
+use crate::repository::bloom_filter::TxBloomFilter;
+
+// by default keep the most recent 1 million transactions in memory
+const DEFAULT_HOT_CAPACITY: usize = 1_000_000;
+
+// one sparse index entry (first tx id and offset) per block of spilled records
+const BLOCK_RECORDS: usize = 128;
+
+// inserts without spilling after spill failed, then spill is tried again
+const SPILL_RETRY: usize = 1024;
+
+// tx id (4) + body length (4) + deleted flag (1)
+const HEADER_SIZE: usize = 9;
+const DELETED_OFFSET: u64 = 8;
+
+static SPILL_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Repository to store transaction with bounded memory usage.
+/// The most recent transactions are kept in memory (hot tier). When hot tier is full, the oldest quarter
+/// is spilled to disk as a segment file: complete records (also reference and metadata) sorted by tx id.
+/// Only a sparse index is kept in memory for each segment (first tx id and offset of every 128th record),
+/// so lookup on dispute is a binary search plus one block read per segment whose tx id range matches.
+/// Segments of similar size are merged (deleted records are dropped), so there are O(log n) of them.
+/// Deleted spilled transactions are marked in place. Spill files are created lazily in spill directory
+/// and removed when the repository is dropped.
+/// With `bloom` feature a Bloom filter of stored tx ids is checked first, so lookups of new tx ids
+/// (duplicate check of every deposit) don't probe hot tier nor read segments.
+pub struct HybridTransactionRepository {
+    // transactions with insertion stamp
+    hot: IntMap<TxId, (u64, Transaction)>,
+
+    // insertion order of hot transactions, oldest first
+    // entries with stale stamp (deleted or inserted again) are skipped on spill and compacted away
+    order: VecDeque<(TxId, u64)>,
+    stamp: u64,
+    hot_capacity: usize,
+
+    // after failed spill transactions are kept in memory for SPILL_RETRY inserts
+    spill_backoff: usize,
+    spill_dir: PathBuf,
+    next_segment: usize,
+
+    // oldest first
+    segments: Vec<Segment>,
+
+    // spilled tx ids deleted while their records couldn't be marked on disk
+    deleted: IntSet<TxId>,
+
+    #[cfg(feature = "bloom")]
+    filter: TxBloomFilter,
+}
+
+impl Default for HybridTransactionRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HybridTransactionRepository {
+    pub fn new() -> Self {
+        Self::with_hot_capacity(DEFAULT_HOT_CAPACITY, Self::temp_spill_dir())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut repository = Self::new();
+        repository.hot.reserve(capacity.min(repository.hot_capacity));
+        repository
+    }
+
+    /// Spill directory is created on the first spill
+    pub fn with_hot_capacity(hot_capacity: usize, spill_dir: PathBuf) -> Self {
+        HybridTransactionRepository {
+            hot: IntMap::with_capacity_and_hasher(0, BuildNoHashHasher::default()),
+            order: VecDeque::new(),
+            stamp: 0,
+            hot_capacity,
+            spill_backoff: 0,
+            spill_dir,
+            next_segment: 0,
+            segments: Vec::new(),
+            deleted: IntSet::default(),
+            #[cfg(feature = "bloom")]
+            filter: TxBloomFilter::new(),
+        }
+    }
+
+    fn temp_spill_dir() -> PathBuf {
+        let id = SPILL_DIR_COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("tx-spill-{}-{}", std::process::id(), id))
+    }
+
+    /// Transactions read from disk are returned as owned values.
+    /// If spill file can't be read, `StorageError` is returned.
+    pub fn find_by_tx_id(&self, tx_id: &TxId) -> Result<Option<Cow<'_, Transaction>>, ProcessError> {
+        if let Some((_, transaction)) = self.hot.get(tx_id) {
+            return Ok(Some(Cow::Borrowed(transaction)));
+        }
+
+        match self.find_spilled(*tx_id)? {
+            Some((_, _, record)) => decode(&record)
+                .map(|it| Some(Cow::Owned(it)))
+                .ok_or_else(|| ProcessError::StorageError(io::Error::new(ErrorKind::InvalidData, "corrupted spill record"))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn exist_by_tx_id(&self, tx_id: &TxId) -> Result<bool, ProcessError> {
+        #[cfg(feature = "bloom")]
+        if !self.filter.may_contain(*tx_id) {
+            return Ok(false);
+        }
+
+        Ok(self.hot.contains_key(tx_id) || self.find_spilled(*tx_id)?.is_some())
+    }
+
+    /// Number of stored transactions
+    pub fn len(&self) -> usize {
+        (self.hot.len() + self.segments.iter().map(|it| it.live).sum::<usize>()).saturating_sub(self.deleted.len())
+    }
+
+    /// If transaction can't be spilled, it is kept in memory. Insert fails only when spilled
+    /// transaction with the same tx id can't be replaced.
+    pub fn insert(&mut self, tx_id: TxId, transaction: Transaction) -> Result<(), ProcessError> {
+        if !self.hot.contains_key(&tx_id) {
+            self.forget_spilled(tx_id).map_err(ProcessError::StorageError)?;
+        }
+
+        #[cfg(feature = "bloom")]
+        {
+            if self.filter.is_full() {
+                self.rebuild_filter();
+            }
+            self.filter.insert(tx_id);
+        }
+
+        self.stamp += 1;
+        self.hot.insert(tx_id, (self.stamp, transaction));
+        self.order.push_back((tx_id, self.stamp));
+
+        if self.hot.len() > self.hot_capacity {
+            self.spill();
+        }
+        if self.order.len() > 2 * self.hot.len() + BLOCK_RECORDS {
+            let hot = &self.hot;
+            self.order.retain(|(tx_id, stamp)| hot.get(tx_id).is_some_and(|(it, _)| it == stamp));
+        }
+        Ok(())
+    }
+
+    /// Store transactions under their tx ids (see `insert`)
+    pub fn insert_all(&mut self, transactions: &[Transaction]) -> Result<(), ProcessError> {
+        for transaction in transactions {
+            self.insert(transaction.tx_id(), transaction.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn delete_by_id(&mut self, tx_id: &TxId) {
+        if self.hot.remove(tx_id).is_none() && self.forget_spilled(*tx_id).is_err() {
+            // record stays on disk, it is hidden until tx id is inserted again
+            self.deleted.insert(*tx_id);
+        }
+    }
+
+    fn find_spilled(&self, tx_id: TxId) -> Result<Option<(usize, u64, Vec<u8>)>, ProcessError> {
+        match self.deleted.contains(&tx_id) {
+            true => Ok(None),
+            false => self.spilled(tx_id).map_err(ProcessError::StorageError),
+        }
+    }
+
+    // the newest spilled record of tx id: segment, offset and record
+    fn spilled(&self, tx_id: TxId) -> io::Result<Option<(usize, u64, Vec<u8>)>> {
+        for (index, segment) in self.segments.iter().enumerate().rev() {
+            if let Some((offset, record)) = segment.find(tx_id)? {
+                return Ok(Some((index, offset, record)));
+            }
+        }
+        Ok(None)
+    }
+
+    // mark spilled record of tx id deleted, segments without live records are removed
+    fn forget_spilled(&mut self, tx_id: TxId) -> io::Result<()> {
+        if let Some((index, offset, _)) = self.spilled(tx_id)? {
+            self.segments[index].mark_deleted(offset)?;
+            if self.segments[index].live == 0 {
+                self.segments.remove(index);
+            }
+        }
+        self.deleted.remove(&tx_id);
+        Ok(())
+    }
+
+    fn spill(&mut self) {
+        if self.spill_backoff > 0 {
+            self.spill_backoff -= 1;
+            return;
+        }
+
+        let batch = (self.hot_capacity / 4).max(1);
+        let mut spilled = Vec::with_capacity(batch);
+        while spilled.len() < batch {
+            match self.order.pop_front() {
+                Some((tx_id, stamp)) if self.hot.get(&tx_id).is_some_and(|(it, _)| *it == stamp) => spilled.push((tx_id, stamp)),
+                Some(_) => continue,
+                None => break,
+            }
+        }
+
+        if spilled.is_empty() {
+            return;
+        }
+        let mut tx_ids: Vec<TxId> = spilled.iter().map(|(tx_id, _)| *tx_id).collect();
+        tx_ids.sort_unstable();
+        match self.write_segment(&tx_ids) {
+            Ok(segment) => {
+                tx_ids.iter().for_each(|it| {
+                    self.hot.remove(it);
+                });
+                self.segments.push(segment);
+                self.merge_segments();
+            }
+            Err(_) => {
+                // can't spill, keep transactions in memory rather than lose them and try again later
+                spilled.into_iter().rev().for_each(|it| self.order.push_front(it));
+                self.spill_backoff = SPILL_RETRY;
+            }
+        }
+    }
+
+    fn segment_path(&mut self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.spill_dir)?;
+        self.next_segment += 1;
+        Ok(self.spill_dir.join(format!("segment-{}.bin", self.next_segment)))
+    }
+
+    fn write_segment(&mut self, tx_ids: &[TxId]) -> io::Result<Segment> {
+        let path = self.segment_path()?;
+        let hot = &self.hot;
+        SegmentWriter::write(path, |writer| {
+            let mut record = Vec::new();
+            for tx_id in tx_ids {
+                if let Some((_, transaction)) = hot.get(tx_id) {
+                    record.clear();
+                    encode(transaction, &mut record);
+                    writer.push(*tx_id, &record)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // merge the newest segment with previous one while they are of similar size, on error segments are kept as they are
+    fn merge_segments(&mut self) {
+        while let [.., older, newer] = self.segments.as_slice() {
+            if newer.live * 2 < older.live {
+                return;
+            }
+            let Ok(path) = self.segment_path() else {
+                return;
+            };
+            let [.., older, newer] = self.segments.as_slice() else {
+                return;
+            };
+            let Ok(merged) = Segment::merge(older, newer, path) else {
+                return;
+            };
+            self.segments.truncate(self.segments.len() - 2);
+            if merged.live > 0 {
+                self.segments.push(merged);
+            }
+        }
+    }
+
+    // rebuild failure keeps current filter, it only has higher false positive rate
+    #[cfg(feature = "bloom")]
+    fn rebuild_filter(&mut self) {
+        let mut filter = TxBloomFilter::with_capacity(self.len().saturating_mul(2));
+        self.hot.keys().for_each(|it| filter.insert(*it));
+        let spilled = self.segments.iter().try_for_each(|segment| {
+            let mut reader = segment.reader()?;
+            while let Some((tx_id, _)) = reader.next()? {
+                filter.insert(tx_id);
+            }
+            Ok::<_, io::Error>(())
+        });
+        if spilled.is_ok() {
+            self.filter = filter;
+        }
+    }
+}
+
+impl Drop for HybridTransactionRepository {
+    fn drop(&mut self) {
+        // segment files are removed by segments
+        self.segments.clear();
+        let _ = fs::remove_dir(&self.spill_dir);
+    }
+}
+
+/// Spilled records sorted by tx id with sparse index, file is removed when dropped
+struct Segment {
+    path: PathBuf,
+    file: File,
+    first: TxId,
+    last: TxId,
+
+    // first tx id and offset of every BLOCK_RECORDS-th record
+    blocks: Vec<(TxId, u64)>,
+    size: u64,
+
+    // records not marked deleted
+    live: usize,
+}
+
+impl Segment {
+    // offset and record of tx id when it is not deleted
+    fn find(&self, tx_id: TxId) -> io::Result<Option<(u64, Vec<u8>)>> {
+        if tx_id < self.first || tx_id > self.last {
+            return Ok(None);
+        }
+        let block = match self.blocks.partition_point(|(first, _)| *first <= tx_id) {
+            0 => return Ok(None),
+            it => it - 1,
+        };
+        let start = self.blocks[block].1;
+        let end = self.blocks.get(block + 1).map(|(_, it)| *it).unwrap_or(self.size);
+
+        let mut buf = vec![0_u8; (end - start) as usize];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut buf)?;
+
+        let mut position = 0;
+        while position < buf.len() {
+            let (id, end) = header(&buf[position..])
+                .map(|(id, len)| (id, position + HEADER_SIZE + len))
+                .filter(|(_, end)| *end <= buf.len())
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "corrupted spill record"))?;
+            if id == tx_id {
+                return Ok((buf[position + 8] == 0).then(|| (start + position as u64, buf[position..end].to_vec())));
+            }
+            if id > tx_id {
+                break;
+            }
+            position = end;
+        }
+        Ok(None)
+    }
+
+    fn mark_deleted(&mut self, offset: u64) -> io::Result<()> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset + DELETED_OFFSET))?;
+        file.write_all(&[1])?;
+        self.live -= 1;
+        Ok(())
+    }
+
+    fn reader(&self) -> io::Result<SegmentReader> {
+        Ok(SegmentReader { reader: BufReader::new(File::open(&self.path)?) })
+    }
+
+    // live records of both segments, newer record wins for the same tx id
+    fn merge(older: &Segment, newer: &Segment, path: PathBuf) -> io::Result<Segment> {
+        let (mut older, mut newer) = (older.reader()?, newer.reader()?);
+        SegmentWriter::write(path, |writer| {
+            let (mut left, mut right) = (older.next()?, newer.next()?);
+            loop {
+                match (&left, &right) {
+                    (Some((l, record)), Some((r, _))) if l < r => {
+                        writer.push(*l, record)?;
+                        left = older.next()?;
+                    }
+                    (Some((l, _)), Some((r, record))) => {
+                        if l == r {
+                            left = older.next()?;
+                        }
+                        writer.push(*r, record)?;
+                        right = newer.next()?;
+                    }
+                    (Some((l, record)), None) => {
+                        writer.push(*l, record)?;
+                        left = older.next()?;
+                    }
+                    (None, Some((r, record))) => {
+                        writer.push(*r, record)?;
+                        right = newer.next()?;
+                    }
+                    (None, None) => return Ok(()),
+                }
+            }
+        })
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+struct SegmentReader {
+    reader: BufReader<File>,
+}
+
+impl SegmentReader {
+    // the next live record
+    fn next(&mut self) -> io::Result<Option<(TxId, Vec<u8>)>> {
+        loop {
+            let mut head = [0_u8; HEADER_SIZE];
+            match self.reader.read_exact(&mut head) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+            let (tx_id, len) = header(&head).ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "corrupted spill record"))?;
+            let mut record = vec![0_u8; HEADER_SIZE + len];
+            record[..HEADER_SIZE].copy_from_slice(&head);
+            self.reader.read_exact(&mut record[HEADER_SIZE..])?;
+            if head[8] == 0 {
+                return Ok(Some((tx_id, record)));
+            }
+        }
+    }
+}
+
+struct SegmentWriter {
+    writer: BufWriter<File>,
+    blocks: Vec<(TxId, u64)>,
+    size: u64,
+    records: usize,
+    last: TxId,
+}
+
+impl SegmentWriter {
+    // segment of records pushed by `write`, file is removed when writing fails
+    fn write<F>(path: PathBuf, write: F) -> io::Result<Segment>
+        where F: FnOnce(&mut SegmentWriter) -> io::Result<()>
+    {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        let mut writer = SegmentWriter { writer: BufWriter::new(file), blocks: Vec::new(), size: 0, records: 0, last: 0 };
+        let segment = write(&mut writer).and_then(|_| writer.finish(&path));
+        if segment.is_err() {
+            let _ = fs::remove_file(&path);
+        }
+        segment
+    }
+
+    // records are pushed in ascending tx id order
+    fn push(&mut self, tx_id: TxId, record: &[u8]) -> io::Result<()> {
+        if self.records.is_multiple_of(BLOCK_RECORDS) {
+            self.blocks.push((tx_id, self.size));
+        }
+        self.writer.write_all(record)?;
+        self.size += record.len() as u64;
+        self.records += 1;
+        self.last = tx_id;
+        Ok(())
+    }
+
+    fn finish(self, path: &Path) -> io::Result<Segment> {
+        let file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(Segment {
+            path: path.to_path_buf(),
+            file,
+            first: self.blocks.first().map(|(it, _)| *it).unwrap_or_default(),
+            last: self.last,
+            blocks: self.blocks,
+            size: self.size,
+            live: self.records,
+        })
+    }
+}
+
+// tx id and body length of record
+fn header(buf: &[u8]) -> Option<(TxId, usize)> {
+    let tx_id = u32::from_le_bytes(buf.get(0..4)?.try_into().ok()?);
+    let len = u32::from_le_bytes(buf.get(4..8)?.try_into().ok()?);
+    buf.get(8)?;
+    Some((tx_id, len as usize))
+}
+
+// header (tx id, body length, deleted flag) and body: type, client, amount, sequence, reference and metadata
+fn encode(transaction: &Transaction, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&transaction.tx_id().to_le_bytes());
+    buf.extend_from_slice(&[0; 4]);
+    buf.push(0);
+
+    buf.push(match transaction.r#type() {
+        TransactionType::Chargeback => 0,
+        TransactionType::Deposit => 1,
+        TransactionType::Dispute => 2,
+        TransactionType::Resolve => 3,
+        TransactionType::Withdrawal => 4,
+        TransactionType::Close => 5,
+    });
+    buf.extend_from_slice(&transaction.client().to_le_bytes());
+    match transaction.amount() {
+        Some(amount) => {
+            buf.push(1);
+            buf.extend_from_slice(&amount.serialize());
+        }
+        None => buf.push(0),
+    }
+    buf.extend_from_slice(&transaction.sequence().to_le_bytes());
+    encode_str(transaction.reference(), buf);
+    #[cfg(feature = "metadata")]
+    {
+        buf.extend_from_slice(&(transaction.metadata().len() as u32).to_le_bytes());
+        for (key, value) in transaction.metadata() {
+            encode_str(Some(key), buf);
+            encode_str(Some(value), buf);
+        }
+    }
+
+    let len = (buf.len() - HEADER_SIZE) as u32;
+    buf[4..8].copy_from_slice(&len.to_le_bytes());
+}
+
+// length (u32::MAX for None) and bytes
+fn encode_str(value: Option<&str>, buf: &mut Vec<u8>) {
+    match value {
+        Some(value) => {
+            buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            buf.extend_from_slice(value.as_bytes());
+        }
+        None => buf.extend_from_slice(&u32::MAX.to_le_bytes()),
+    }
+}
+
+fn decode(record: &[u8]) -> Option<Transaction> {
+    let (tx, _) = header(record)?;
+    let mut body = Body(record.get(HEADER_SIZE..)?);
+
+    let r#type = match body.take::<1>()?[0] {
+        0 => TransactionType::Chargeback,
+        1 => TransactionType::Deposit,
+        2 => TransactionType::Dispute,
+        3 => TransactionType::Resolve,
+        4 => TransactionType::Withdrawal,
+        5 => TransactionType::Close,
+        _ => return None,
+    };
+    let client = u16::from_le_bytes(body.take()?);
+    let amount = match body.take::<1>()?[0] {
+        1 => Some(Decimal::deserialize(body.take()?)),
+        _ => None,
+    };
+    let sequence = u64::from_le_bytes(body.take()?);
+    let mut transaction = Transaction::new(r#type, client, tx, amount).with_sequence(sequence);
+    if let Some(reference) = body.str()? {
+        transaction = transaction.with_reference(reference);
+    }
+    #[cfg(feature = "metadata")]
+    {
+        let len = u32::from_le_bytes(body.take()?);
+        let metadata = (0..len)
+            .map(|_| Some((body.str()??.to_string(), body.str()??.to_string())))
+            .collect::<Option<_>>()?;
+        transaction = transaction.with_metadata(metadata);
+    }
+    Some(transaction)
+}
+
+struct Body<'a>(&'a [u8]);
+
+impl<'a> Body<'a> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.bytes(N)?;
+        bytes.try_into().ok()
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (bytes, rest) = (self.0.get(..len)?, self.0.get(len..)?);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    // None inside for missing value, outer None for corrupted record
+    fn str(&mut self) -> Option<Option<&'a str>> {
+        match u32::from_le_bytes(self.take()?) {
+            u32::MAX => Some(None),
+            len => std::str::from_utf8(self.bytes(len as usize)?).ok().map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use rust_decimal::prelude::FromPrimitive;
+    use crate::repository::hybrid_transaction_repository::{HybridTransactionRepository, BLOCK_RECORDS};
+    use crate::Transaction;
+    use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+
+    #[test]
+    fn insert_then_find_spilled() {
+        let path = std::env::temp_dir().join("tx-spill-test-insert-then-find");
+        let mut repository = HybridTransactionRepository::with_hot_capacity(2, path.clone());
+
+        repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::from_f32(1.5).unwrap())).with_sequence(11)).unwrap();
+        repository.insert(2, Transaction::new(Withdrawal, 2, 2, Some(Decimal::from(2_u64)))).unwrap();
+        repository.insert(3, Transaction::new(Deposit, 3, 3, None)).unwrap();
+        repository.insert(4, Transaction::new(Deposit, 4, 4, Some(Decimal::from(4_u64)))).unwrap();
+
+        assert!(path.exists());
+        assert!(repository.exist_by_tx_id(&1).unwrap());
+        assert!(repository.exist_by_tx_id(&4).unwrap());
+        assert!(!repository.exist_by_tx_id(&5).unwrap());
+        assert_eq!(repository.len(), 4);
+
+        let transaction = repository.find_by_tx_id(&1).unwrap().unwrap();
+        assert_eq!(transaction.r#type(), &Deposit);
+        assert_eq!(transaction.client(), 1);
+        assert_eq!(transaction.tx_id(), 1);
+        assert_eq!(transaction.amount(), Some(Decimal::from_f32(1.5).unwrap()));
+        assert_eq!(transaction.sequence(), 11);
+
+        let transaction = repository.find_by_tx_id(&2).unwrap().unwrap();
+        assert_eq!(transaction.r#type(), &Withdrawal);
+        assert_eq!(transaction.amount(), Some(Decimal::from(2_u64)));
+
+        let transaction = repository.find_by_tx_id(&3).unwrap().unwrap();
+        assert_eq!(transaction.amount(), None);
+
+        drop(repository);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn delete_spilled() {
+        let path = std::env::temp_dir().join("tx-spill-test-delete-spilled");
+        let mut repository = HybridTransactionRepository::with_hot_capacity(1, path);
+
+        repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::from(1_u64)))).unwrap();
+        repository.insert(2, Transaction::new(Deposit, 1, 2, Some(Decimal::from(2_u64)))).unwrap();
+
+        repository.delete_by_id(&1);
+        repository.delete_by_id(&2);
+
+        assert!(!repository.exist_by_tx_id(&1).unwrap());
+        assert!(repository.find_by_tx_id(&1).unwrap().is_none());
+        assert!(!repository.exist_by_tx_id(&2).unwrap());
+        assert_eq!(repository.len(), 0);
+    }
+
+    #[test]
+    fn spilled_reference_then_kept() {
+        let path = std::env::temp_dir().join("tx-spill-test-reference");
+        let mut repository = HybridTransactionRepository::with_hot_capacity(1, path);
+
+        repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::ONE)).with_reference("memo ąę 1")).unwrap();
+        repository.insert(2, Transaction::new(Deposit, 1, 2, Some(Decimal::ONE)).with_reference("")).unwrap();
+        repository.insert(3, Transaction::new(Deposit, 1, 3, Some(Decimal::ONE))).unwrap();
+        repository.insert(4, Transaction::new(Deposit, 1, 4, Some(Decimal::ONE))).unwrap();
+
+        assert!(repository.hot.len() <= 1);
+        assert_eq!(repository.find_by_tx_id(&1).unwrap().unwrap().reference(), Some("memo ąę 1"));
+        assert_eq!(repository.find_by_tx_id(&2).unwrap().unwrap().reference(), Some(""));
+        assert_eq!(repository.find_by_tx_id(&3).unwrap().unwrap().reference(), None);
+    }
+
+    #[cfg(feature = "metadata")]
+    #[test]
+    fn spilled_metadata_then_kept() {
+        let path = std::env::temp_dir().join("tx-spill-test-metadata");
+        let mut repository = HybridTransactionRepository::with_hot_capacity(1, path);
+        let metadata: crate::prelude::HashMap<String, String> = [("channel".to_string(), "web".to_string())].into_iter().collect();
+
+        repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::ONE)).with_metadata(metadata.clone())).unwrap();
+        repository.insert(2, Transaction::new(Deposit, 1, 2, Some(Decimal::ONE))).unwrap();
+
+        assert_eq!(repository.find_by_tx_id(&1).unwrap().unwrap().metadata(), &metadata);
+    }
+
+    #[test]
+    fn deleted_then_inserted_again_then_single_copy() {
+        let path = std::env::temp_dir().join("tx-spill-test-reinsert");
+        let mut repository = HybridTransactionRepository::with_hot_capacity(2, path);
+
+        // deleted in hot tier and inserted again, the old order entry is stale
+        repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::ONE))).unwrap();
+        repository.delete_by_id(&1);
+        repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::TEN))).unwrap();
+        repository.insert(2, Transaction::new(Deposit, 1, 2, Some(Decimal::ONE))).unwrap();
+        repository.insert(3, Transaction::new(Deposit, 1, 3, Some(Decimal::ONE))).unwrap();
+        assert_eq!(repository.find_by_tx_id(&1).unwrap().unwrap().amount(), Some(Decimal::TEN));
+        assert!(!repository.hot.contains_key(&1));
+        assert_eq!(repository.len(), 3);
+
+        // spilled, deleted and inserted again
+        repository.delete_by_id(&1);
+        assert!(!repository.exist_by_tx_id(&1).unwrap());
+        repository.insert(1, Transaction::new(Withdrawal, 1, 1, Some(Decimal::TWO))).unwrap();
+        (4..10).for_each(|it| repository.insert(it, Transaction::new(Deposit, 1, it, Some(Decimal::ONE))).unwrap());
+        assert_eq!(repository.find_by_tx_id(&1).unwrap().unwrap().r#type(), &Withdrawal);
+        assert_eq!(repository.len(), 9);
+
+        // replaced while spilled
+        repository.insert(1, Transaction::new(Deposit, 1, 1, Some(Decimal::ONE))).unwrap();
+        repository.delete_by_id(&1);
+        assert!(repository.find_by_tx_id(&1).unwrap().is_none());
+        assert_eq!(repository.len(), 8);
+    }
+
+    #[test]
+    fn many_spilled_then_memory_bounded_and_all_found() {
+        let path = std::env::temp_dir().join("tx-spill-test-many");
+        let mut repository = HybridTransactionRepository::with_hot_capacity(100, path);
+
+        // out of order tx ids, segments overlap
+        let tx_id = |it: u32| it.wrapping_mul(2_654_435_761);
+        let tx_ids: Vec<u32> = (0..20_000_u32).map(tx_id).collect();
+        for (sequence, tx_id) in tx_ids.iter().enumerate() {
+            repository.insert(*tx_id, Transaction::new(Deposit, 1, *tx_id, Some(Decimal::ONE)).with_sequence(sequence as u64)).unwrap();
+        }
+
+        assert!(repository.hot.len() <= 100);
+        assert!(repository.order.len() <= 2 * 100 + BLOCK_RECORDS);
+        assert!(repository.segments.len() <= 16);
+        assert!(repository.segments.iter().map(|it| it.blocks.len()).sum::<usize>() <= 20_000 / BLOCK_RECORDS + 16);
+        assert_eq!(repository.len(), 20_000);
+        for (sequence, tx_id) in tx_ids.iter().enumerate() {
+            assert_eq!(repository.find_by_tx_id(tx_id).unwrap().unwrap().sequence(), sequence as u64);
+        }
+        assert!(!repository.exist_by_tx_id(&tx_id(20_000)).unwrap());
+    }
+
+    #[test]
+    fn spill_failed_then_capacity_kept_and_retried() {
+        let file = std::env::temp_dir().join("tx-spill-test-not-a-dir");
+        std::fs::write(&file, b"").unwrap();
+        let mut repository = HybridTransactionRepository::with_hot_capacity(4, file.join("spill"));
+
+        (0..6).for_each(|it| repository.insert(it, Transaction::new(Deposit, 1, it, Some(Decimal::ONE))).unwrap());
+        assert_eq!(repository.hot.len(), 6);
+        assert_eq!(repository.hot_capacity, 4);
+
+        // storage available again
+        repository.spill_dir = std::env::temp_dir().join("tx-spill-test-retried");
+        repository.spill_backoff = 0;
+        repository.insert(6, Transaction::new(Deposit, 1, 6, Some(Decimal::ONE))).unwrap();
+        assert_eq!(repository.hot.len(), 6);
+        assert!(repository.find_by_tx_id(&0).unwrap().is_some());
+        std::fs::remove_file(file).unwrap();
+    }
+}
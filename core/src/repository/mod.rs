@@ -2,11 +2,20 @@
 use crate::repository::dlq_repository::NaiveDlqMemoryRepository;
 
 
+#[cfg_attr(feature = "spill", allow(dead_code))]
+pub(crate) mod arena;
 pub(crate) mod basic_account_repository;
 pub(crate) mod wrap_account_repository;
 #[cfg(feature = "dlq")]
 pub mod dlq_repository;
+#[cfg_attr(feature = "spill", allow(dead_code))]
 pub(crate) mod transaction_repository;
+#[cfg(feature = "bloom")]
+pub(crate) mod bloom_filter;
+#[cfg(feature = "spill")]
+pub(crate) mod hybrid_transaction_repository;
+#[cfg(feature = "fault-injection")]
+pub(crate) mod fault_injector;
 
 #[cfg(feature = "dlq")]
 pub type DlqRepository = NaiveDlqMemoryRepository;
@@ -1,12 +1,16 @@
-use nohash_hasher::{BuildNoHashHasher, IntMap};
-use crate::Transaction;
+use crate::prelude::*;
+use nohash_hasher::BuildNoHashHasher;
+use crate::{ProcessError, Transaction};
 use crate::transaction::TxId;
+use crate::repository::arena::{Handle, TransactionArena};
 
 /// Repository to store transaction (withdraw, dispute, or other transaction types if needed)
 /// This repository is using HashMap/BuildNoHashHasher as hash implementation
 /// TxId is a valid u32 transaction ID
+/// Transactions are stored in arena, HashMap keeps only u32 handles
 pub struct TransactionMemoryRepository {
-    inner: IntMap<TxId, Transaction>,
+    inner: IntMap<TxId, Handle>,
+    arena: TransactionArena,
 }
 
 impl Default for TransactionMemoryRepository {
@@ -18,29 +22,55 @@ impl Default for TransactionMemoryRepository {
 impl TransactionMemoryRepository {
     pub fn new() -> Self {
         TransactionMemoryRepository {
-            inner: IntMap::default()
+            inner: IntMap::default(),
+            arena: TransactionArena::new(),
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         TransactionMemoryRepository {
-            inner: IntMap::with_capacity_and_hasher(capacity,  BuildNoHashHasher::default())
+            inner: IntMap::with_capacity_and_hasher(capacity,  BuildNoHashHasher::default()),
+            arena: TransactionArena::with_capacity(capacity),
         }
     }
 
-    pub fn find_by_tx_id(&self, tx_id: &TxId) -> Option<&Transaction> {
-        self.inner.get(tx_id)
+    pub fn find_by_tx_id(&self, tx_id: &TxId) -> Result<Option<Cow<'_, Transaction>>, ProcessError> {
+        Ok(self.inner.get(tx_id).and_then(|it| self.arena.get(*it)).map(Cow::Borrowed))
     }
 
-    pub fn exist_by_tx_id(&self, tx_id: &TxId) -> bool {
-        self.inner.contains_key(tx_id)
+    pub fn exist_by_tx_id(&self, tx_id: &TxId) -> Result<bool, ProcessError> {
+        Ok(self.inner.contains_key(tx_id))
     }
 
-    pub fn insert(&mut self, tx_id: TxId, transaction: Transaction) {
-        self.inner.insert(tx_id, transaction);
+    /// Number of stored transactions
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn insert(&mut self, tx_id: TxId, transaction: Transaction) -> Result<(), ProcessError> {
+        match self.inner.get(&tx_id) {
+            Some(handle) => self.arena.replace(*handle, transaction),
+            None => {
+                let handle = self.arena.alloc(transaction)?;
+                self.inner.insert(tx_id, handle);
+            }
+        }
+        Ok(())
+    }
+
+    /// Store transactions under their tx ids, space for all of them is reserved up front
+    pub fn insert_all(&mut self, transactions: &[Transaction]) -> Result<(), ProcessError> {
+        self.inner.reserve(transactions.len());
+        self.arena.reserve(transactions.len());
+        for transaction in transactions {
+            self.insert(transaction.tx_id(), transaction.clone())?;
+        }
+        Ok(())
     }
 
     pub fn delete_by_id(&mut self, tx_id: &TxId) {
-        self.inner.remove(tx_id);
+        if let Some(handle) = self.inner.remove(tx_id) {
+            self.arena.release(handle);
+        }
     }
 }
@@ -1,4 +1,5 @@
-use nohash_hasher::IntMap;
+use crate::prelude::*;
+use crate::account::iter::{AccountStates, IntoValues, Values};
 use crate::account::wrap::WrapAccount;
 use crate::account::basic::BasicAccount;
 use crate::client::Client;
@@ -8,22 +9,82 @@ use crate::client::Client;
 /// Client is a valid u16 client ID
 pub struct WrapAccountMemoryRepository {
     inner: IntMap<Client, WrapAccount>,
+
+    // accounts are iterated in ascending client order
+    ordered: bool,
 }
 
 impl WrapAccountMemoryRepository {
     pub fn new() -> Self {
         WrapAccountMemoryRepository {
-            inner: IntMap::default()
+            inner: IntMap::default(),
+            ordered: false,
         }
     }
 
+    /// Iterate accounts in ascending client order (sorted on every iteration), so output is
+    /// reproducible across runs and platforms
+    pub fn set_ordered(&mut self, ordered: bool) {
+        self.ordered = ordered;
+    }
+
+    /// Number of accounts
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
     pub fn find_by_client(&mut self, client: Client) -> &mut WrapAccount {
         self.inner.entry(client).or_insert_with(|| WrapAccount::new(client))
     }
 
-    pub fn get_all_account_into_iter(self) -> impl Iterator<Item=BasicAccount> {
-        self.inner.into_iter().map(|it| {
-            it.1.into_account()
-        })
+    pub fn find(&self, client: Client) -> Option<&WrapAccount> {
+        self.inner.get(&client)
+    }
+
+    pub(crate) fn get_all_account_iter(&self) -> Values<'_, WrapAccount> {
+        if !self.ordered {
+            return Values::Unordered(self.inner.values());
+        }
+
+        let mut accounts: Vec<_> = self.inner.values().collect();
+        accounts.sort_unstable_by_key(|it| *it.client());
+        Values::Ordered(accounts.into_iter())
+    }
+
+    /// `limit` accounts with client greater than `after`, in ascending client order
+    pub(crate) fn page(&self, after: Option<Client>, limit: usize) -> Vec<&WrapAccount> {
+        let mut accounts: Vec<_> = self.inner.iter()
+            .filter(|(client, _)| after.is_none_or(|after| **client > after))
+            .map(|(_, account)| account)
+            .collect();
+        if accounts.len() > limit {
+            accounts.select_nth_unstable_by_key(limit, |it| *it.client());
+            accounts.truncate(limit);
+        }
+        accounts.sort_unstable_by_key(|it| *it.client());
+        accounts
+    }
+
+    pub(crate) fn get_all_account_into_iter(self) -> IntoValues<WrapAccount> {
+        if !self.ordered {
+            return IntoValues::Unordered(self.inner.into_values());
+        }
+
+        let mut accounts: Vec<_> = self.inner.into_values().collect();
+        accounts.sort_unstable_by_key(|it| *it.client());
+        IntoValues::Ordered(accounts.into_iter())
     }
-}
\ No newline at end of file
+}
+
+/// Per-client state: client, account, number of open disputes, number of stored transactions.
+/// Useful for operational tooling to gauge per-client memory/state without consuming the processor.
+pub type AccountState<'a> = (Client, &'a BasicAccount, usize, usize);
+
+impl<'a> IntoIterator for &'a WrapAccountMemoryRepository {
+    type Item = AccountState<'a>;
+    type IntoIter = AccountStates<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        AccountStates::new(self.get_all_account_iter())
+    }
+}
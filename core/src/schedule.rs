@@ -0,0 +1,273 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rust_decimal::Decimal;
+use crate::account::view::AccountView;
+use crate::transaction::TxId;
+use crate::transaction_type::TransactionType::{Deposit, Withdrawal};
+use crate::{Transaction, TransactionProcessor};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// interest is rounded to four places past the decimal, same as input precision
+const INTEREST_SCALE: u32 = 4;
+
+/// Wall-clock (UTC) period of posting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    // posted at every midnight
+    Daily,
+
+    // posted at midnight of the first day of month
+    Monthly,
+}
+
+impl Period {
+    /// Sequential number of period containing `time`, it changes exactly at period boundary
+    fn key(&self, time: SystemTime) -> i64 {
+        let days = (time.duration_since(UNIX_EPOCH).map_or(0, |it| it.as_secs()) / SECONDS_PER_DAY) as i64;
+        match self {
+            Period::Daily => days,
+            Period::Monthly => {
+                let (year, month) = civil_from_days(days);
+                year * 12 + month as i64
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostingKind {
+    // fixed fee withdrawn from available funds
+    Fee(Decimal),
+
+    // interest rate per period, deposited as available * rate
+    Interest(Decimal),
+}
+
+/// Periodic posting, for example `daily:fee:0.5` or `monthly:interest:0.01`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Posting {
+    period: Period,
+    kind: PostingKind,
+}
+
+impl Posting {
+    pub fn new(period: Period, kind: PostingKind) -> Self {
+        Posting {
+            period,
+            kind,
+        }
+    }
+
+    pub fn period(&self) -> Period {
+        self.period
+    }
+
+    pub fn kind(&self) -> PostingKind {
+        self.kind
+    }
+
+    /// Synthetic transaction for account, None if account is not eligible
+    fn transaction(&self, account: &AccountView, tx: TxId) -> Option<Transaction> {
+        if account.locked() || account.closed() {
+            return None;
+        }
+
+        match self.kind {
+            PostingKind::Fee(fee) if account.available() >= &fee => {
+                Some(Transaction::new(Withdrawal, *account.client(), tx, Some(fee)))
+            }
+            PostingKind::Interest(rate) => {
+                let interest = account.available().checked_mul(rate)?.round_dp(INTEREST_SCALE);
+                match interest > Decimal::ZERO {
+                    true => Some(Transaction::new(Deposit, *account.client(), tx, Some(interest))),
+                    false => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PostingParseError(String);
+
+impl Display for PostingParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid posting '{}', expected <daily|monthly>:<fee|interest>:<amount>", self.0)
+    }
+}
+
+impl Error for PostingParseError {}
+
+impl FromStr for Posting {
+    type Err = PostingParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || PostingParseError(s.to_string());
+        let mut parts = s.split(':');
+
+        let period = match parts.next() {
+            Some("daily") => Period::Daily,
+            Some("monthly") => Period::Monthly,
+            _ => return Err(error()),
+        };
+
+        let kind = parts.next().ok_or_else(error)?;
+        let amount = parts.next()
+            .and_then(|it| Decimal::from_str(it).ok())
+            .filter(|it| *it > Decimal::ZERO)
+            .ok_or_else(error)?;
+
+        let kind = match kind {
+            "fee" => PostingKind::Fee(amount),
+            "interest" => PostingKind::Interest(amount),
+            _ => return Err(error()),
+        };
+
+        match parts.next() {
+            None => Ok(Posting::new(period, kind)),
+            Some(_) => Err(error()),
+        }
+    }
+}
+
+/// Scheduler applies configured postings to eligible accounts when wall-clock crosses period boundary.
+/// Postings are processed as synthetic deposit/withdrawal transactions, so they are stored in history
+/// like any other transaction. Synthetic tx ids are allocated downwards from `TxId::MAX`.
+pub struct PostingScheduler {
+    // posting with key of period when it was last checked
+    postings: Vec<(Posting, i64)>,
+
+    // next synthetic tx id
+    next_tx: TxId,
+
+    // synthetic transactions accepted by processor
+    posted: u64,
+}
+
+impl PostingScheduler {
+    /// Postings are due first time at the next boundary after `now`
+    pub fn new(postings: Vec<Posting>, now: SystemTime) -> Self {
+        PostingScheduler {
+            postings: postings.into_iter().map(|it| (it, it.period.key(now))).collect(),
+            next_tx: TxId::MAX,
+            posted: 0,
+        }
+    }
+
+    pub fn posted(&self) -> u64 {
+        self.posted
+    }
+
+    /// Postings with period boundary crossed since last call
+    fn due(&mut self, now: SystemTime) -> Vec<Posting> {
+        self.postings.iter_mut()
+            .filter_map(|(posting, key)| {
+                let now_key = posting.period.key(now);
+                match now_key != *key {
+                    true => {
+                        *key = now_key;
+                        Some(*posting)
+                    }
+                    false => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Apply due postings to all accounts of processor, return number of posted transactions
+    pub fn run<P>(&mut self, now: SystemTime, processor: &mut P) -> u64
+        where P: TransactionProcessor,
+              for<'a> &'a mut P: IntoIterator<Item=AccountView>
+    {
+        let mut posted = 0;
+
+        for posting in self.due(now) {
+            let mut transactions = Vec::new();
+            for account in &mut *processor {
+                if let Some(transaction) = posting.transaction(&account, self.next_tx) {
+                    transactions.push(transaction);
+                    self.next_tx -= 1;
+                }
+            }
+
+            for transaction in transactions {
+                if processor.process(transaction).is_ok() {
+                    posted += 1;
+                }
+            }
+        }
+
+        self.posted += posted;
+        posted
+    }
+}
+
+/// Year and month (1-12) from days since 1970-01-01 (H. Hinnant civil_from_days)
+fn civil_from_days(days: i64) -> (i64, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::{Duration, UNIX_EPOCH};
+    use rust_decimal::Decimal;
+    use crate::{BasicProcessor, Transaction, TransactionProcessor};
+    use crate::schedule::{civil_from_days, Period, Posting, PostingKind, PostingScheduler};
+    use crate::transaction_type::TransactionType::Deposit;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    #[test]
+    fn parse_posting() {
+        assert_eq!(Posting::from_str("daily:fee:0.5").unwrap(), Posting::new(Period::Daily, PostingKind::Fee(Decimal::from_str("0.5").unwrap())));
+        assert_eq!(Posting::from_str("monthly:interest:0.01").unwrap(), Posting::new(Period::Monthly, PostingKind::Interest(Decimal::from_str("0.01").unwrap())));
+        assert!(Posting::from_str("weekly:fee:1").is_err());
+        assert!(Posting::from_str("daily:fee:-1").is_err());
+        assert!(Posting::from_str("daily:fee:1:2").is_err());
+    }
+
+    #[test]
+    fn civil_from_days_then_month() {
+        assert_eq!(civil_from_days(0), (1970, 1));
+        // 2000-02-29
+        assert_eq!(civil_from_days(11016), (2000, 2));
+        // 2024-03-01
+        assert_eq!(civil_from_days(19783), (2024, 3));
+    }
+
+    #[test]
+    fn deposit_then_daily_fee_and_monthly_interest() {
+        // 2024-01-30 12:00 UTC
+        let start = UNIX_EPOCH + Duration::from_secs(19752 * DAY + DAY / 2);
+        let postings = vec![Posting::from_str("daily:fee:1").unwrap(), Posting::from_str("monthly:interest:0.1").unwrap()];
+        let mut scheduler = PostingScheduler::new(postings, start);
+
+        let mut processor = BasicProcessor::new();
+        assert!(processor.process(Transaction::new(Deposit, 1, 1, Some(100_u64.into()))).is_ok());
+
+        // same day, nothing due
+        assert_eq!(scheduler.run(start + Duration::from_secs(60), &mut processor), 0);
+
+        // next day: fee
+        assert_eq!(scheduler.run(start + Duration::from_secs(DAY), &mut processor), 1);
+        assert_eq!((&mut processor).into_iter().next().unwrap().available(), &Decimal::from(99_u64));
+
+        // next month: fee and interest from balance after fee
+        assert_eq!(scheduler.run(start + Duration::from_secs(2 * DAY), &mut processor), 2);
+        assert_eq!((&mut processor).into_iter().next().unwrap().available(), &Decimal::from_str("107.8").unwrap());
+        assert_eq!(scheduler.posted(), 3);
+    }
+}
@@ -0,0 +1,74 @@
+//! Partitioning of clients into shards (`ShardRouter`), shared by sharded outputs and distributed runs,
+//! so client lands in the same shard everywhere.
+
+use crate::client::Client;
+
+/// Client to shard index with jump consistent hash (Lamport, Veach). Assignment depends only on client
+/// and shard count, so it is stable across runs and machines. When shard count grows from N to N + 1
+/// only about 1/(N + 1) of clients move, all of them into the new shard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardRouter {
+    shards: usize,
+}
+
+impl ShardRouter {
+    /// Router over `shards` shards, None when `shards` is zero
+    pub fn new(shards: usize) -> Option<Self> {
+        match shards {
+            0 => None,
+            shards => Some(ShardRouter { shards }),
+        }
+    }
+
+    pub fn shards(&self) -> usize {
+        self.shards
+    }
+
+    /// Shard index of client, in `0..shards`
+    pub fn shard(&self, client: Client) -> usize {
+        // client ids are small consecutive integers, they are mixed before jump (splitmix64 finalizer)
+        let mut key = client as u64;
+        key = (key ^ (key >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        key = (key ^ (key >> 27)).wrapping_mul(0x94d049bb133111eb);
+        key ^= key >> 31;
+
+        let mut shard = 0;
+        let mut next = 0;
+        while next < self.shards as u64 {
+            shard = next;
+            key = key.wrapping_mul(2862933555777941757).wrapping_add(1);
+            next = ((shard + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as u64;
+        }
+        shard as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::Client;
+    use crate::shard::ShardRouter;
+
+    #[test]
+    fn routes_clients_consistently() {
+        assert_eq!(ShardRouter::new(0), None);
+
+        let one = ShardRouter::new(1).unwrap();
+        assert!((0..=Client::MAX).all(|it| one.shard(it) == 0));
+
+        let four = ShardRouter::new(4).unwrap();
+        let five = ShardRouter::new(5).unwrap();
+        let mut counts = [0usize; 5];
+        for client in 0..=Client::MAX {
+            let shard = five.shard(client);
+            counts[shard] += 1;
+            // growing shard count only moves clients into the new shard
+            assert!(shard == four.shard(client) || shard == 4);
+        }
+
+        // roughly balanced, 13107 clients per shard
+        assert!(counts.iter().all(|it| (12_500..13_700).contains(it)), "{:?}", counts);
+
+        // stable across runs
+        assert_eq!([1, 2, 3, 1000].map(|it| four.shard(it)), [0, 3, 0, 1]);
+    }
+}
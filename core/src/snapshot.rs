@@ -0,0 +1,111 @@
+//! Versioned accounts snapshot (account rows of previous run or dump). Every change of snapshot columns
+//! gets new version, `BasicProcessor::restore` validates version and migrates accounts of older versions,
+//! so state saved by older release can be restored after upgrade. Versions:
+//! - 1: client, available, held, total, locked
+//! - 2: closed added
+//! - 3: status added (current)
+
+use crate::prelude::*;
+use crate::account::view::AccountView;
+use crate::error::ProcessError;
+
+/// Version of snapshots written by this release
+pub const SNAPSHOT_VERSION: u32 = 3;
+
+/// Accounts with version of snapshot they were read from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    version: u32,
+    accounts: Vec<AccountView>,
+}
+
+impl Snapshot {
+    /// Snapshot of current version, e.g. written by this release
+    pub fn new(accounts: Vec<AccountView>) -> Self {
+        Snapshot { version: SNAPSHOT_VERSION, accounts }
+    }
+
+    /// Snapshot of `version`, validated when accounts are taken (`into_accounts`)
+    pub fn with_version(version: u32, accounts: Vec<AccountView>) -> Self {
+        Snapshot { version, accounts }
+    }
+
+    /// Version of snapshot without version marker (e.g. csv output of a run) by its columns
+    pub fn detect_version<'a, I>(columns: I) -> u32
+        where I: IntoIterator<Item=&'a str>
+    {
+        let columns: Vec<&str> = columns.into_iter().collect();
+        match (columns.contains(&"closed"), columns.contains(&"status")) {
+            (_, true) => 3,
+            (true, false) => 2,
+            (false, false) => 1,
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Accounts migrated to current version, snapshot of unknown (e.g. newer) version is rejected
+    pub fn into_accounts(self) -> Result<Vec<AccountView>, ProcessError> {
+        let version = self.version;
+        match version {
+            1..=SNAPSHOT_VERSION => Ok(self.accounts.into_iter().map(|it| migrate(version, it)).collect()),
+            _ => Err(ProcessError::UnsupportedSnapshotVersion(version)),
+        }
+    }
+}
+
+// shims of older versions, applied in order
+fn migrate(version: u32, account: AccountView) -> AccountView {
+    let account = match version {
+        // there were no closed accounts
+        1 => account.reopened(),
+        _ => account,
+    };
+    match version {
+        // status was known only by locked and closed flags
+        1 | 2 => account.with_derived_status(),
+        _ => account,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::account::status::AccountStatus::{Closed, Locked};
+    use crate::account::view::AccountView;
+    use crate::error::ProcessError;
+    use crate::snapshot::{Snapshot, SNAPSHOT_VERSION};
+    use crate::{BasicProcessor, Transaction, TransactionProcessor};
+    use crate::transaction_type::TransactionType::Deposit;
+
+    #[test]
+    fn older_snapshot_then_migrated_and_restored_idempotently() {
+        assert_eq!(Snapshot::detect_version(["client", "available", "held", "total", "locked"]), 1);
+        assert_eq!(Snapshot::detect_version(["client", "available", "held", "total", "locked", "closed"]), 2);
+        assert_eq!(Snapshot::detect_version(["client", "available", "held", "total", "locked", "closed", "status"]), SNAPSHOT_VERSION);
+
+        let accounts = vec![
+            AccountView::new(1, Decimal::TEN, Decimal::ZERO, true, false).unwrap(),
+            AccountView::new(2, Decimal::ONE, Decimal::ZERO, false, true).unwrap(),
+        ];
+        let mut processor = BasicProcessor::new();
+        assert!(matches!(processor.restore(Snapshot::with_version(SNAPSHOT_VERSION + 1, accounts.clone())), Err(ProcessError::UnsupportedSnapshotVersion(4))));
+        assert!(matches!(processor.restore(Snapshot::with_version(0, accounts.clone())), Err(ProcessError::UnsupportedSnapshotVersion(0))));
+        assert!(processor.find_account(1).is_none());
+
+        // version 2 keeps closed account, version 1 had none
+        assert_eq!(processor.restore(Snapshot::with_version(2, accounts.clone())).ok(), Some(2));
+        assert_eq!(processor.find_account(2).map(|it| it.status()), Some(Closed));
+        assert_eq!(processor.restore(Snapshot::with_version(1, accounts.clone())).ok(), Some(2));
+        assert_eq!(processor.find_account(2).map(|it| it.closed()), Some(false));
+
+        // restoring the same snapshot again gives the same state
+        assert!(processor.process(Transaction::new(Deposit, 2, 1, Some(Decimal::ONE))).is_ok());
+        assert_eq!(processor.restore(Snapshot::with_version(1, accounts.clone())).ok(), Some(2));
+        assert_eq!(processor.restore(Snapshot::with_version(1, accounts)).ok(), Some(2));
+        assert_eq!(processor.find_account(1).map(|it| it.status()), Some(Locked));
+        assert_eq!(processor.find_account(2).map(|it| *it.total()), Some(Decimal::ONE));
+    }
+}
@@ -0,0 +1,85 @@
+//! Persistent backend of accounts (`AccountStore`, e.g. database table). With `BasicProcessor::with_account_store`
+//! accounts held by processor are a cache in front of the store: account missing in memory is loaded from store
+//! (read-through) and every change of account is saved to store (write-through). Hot accounts can be loaded
+//! before processing with `BasicProcessor::warm_up`, so the first transactions of active clients don't pay
+//! store lookups and per-transaction latency stays flat.
+
+use crate::prelude::*;
+use crate::account::view::AccountView;
+use crate::client::Client;
+use crate::error::ProcessError;
+
+/// Persistent backend of accounts
+pub trait AccountStore: Send {
+    /// Stored account of client, None for client not stored yet
+    fn load(&mut self, client: Client) -> Result<Option<AccountView>, ProcessError>;
+
+    /// At most `limit` accounts with the most recent activity, the most recently saved first
+    fn load_recent(&mut self, limit: usize) -> Result<Vec<AccountView>, ProcessError>;
+
+    /// Store current state of account. Store shared by many processor instances compares version of account
+    /// loaded by this instance with stored one and fails with retryable `VersionConflict` when it was changed
+    /// in the meantime (optimistic concurrency). Failed save rejects transaction, account is dropped from
+    /// memory and loaded again from store by the next transaction of client.
+    fn save(&mut self, account: &AccountView) -> Result<(), ProcessError>;
+
+    /// Store states of accounts at once, e.g. in one database transaction. By default accounts are saved one by one.
+    fn save_all(&mut self, accounts: &[AccountView]) -> Result<(), ProcessError> {
+        accounts.iter().try_for_each(|it| self.save(it))
+    }
+
+    /// Remove account of client, e.g. merged into other account or erased. Like `save`, store shared by many
+    /// processor instances fails with `VersionConflict` when account was changed since this instance loaded it.
+    fn delete(&mut self, client: Client) -> Result<(), ProcessError>;
+
+    /// Store account `to` with funds merged from account of `from` client and remove `from` account at once,
+    /// e.g. in one database transaction. By default `to` is saved first and when `from` can't be removed,
+    /// `before` (state of `to` before merge, None when it wasn't stored) is written back, so merged funds are
+    /// never stored twice.
+    fn save_merged(&mut self, to: &AccountView, before: Option<&AccountView>, from: Client) -> Result<(), ProcessError> {
+        self.save(to)?;
+        if let Err(e) = self.delete(from) {
+            let _ = match before {
+                Some(account) => self.save(account),
+                None => self.delete(*to.client()),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// Account cache statistics of processor with account store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    // accounts loaded by warm-up
+    warmed: u64,
+
+    // account was in memory
+    hits: u64,
+
+    // account was looked up in store
+    misses: u64,
+}
+
+impl CacheStats {
+    pub fn warmed(&self) -> u64 {
+        self.warmed
+    }
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub(crate) fn warm(&mut self, accounts: u64) {
+        self.warmed += accounts;
+    }
+    pub(crate) fn hit(&mut self) {
+        self.hits += 1;
+    }
+    pub(crate) fn miss(&mut self) {
+        self.misses += 1;
+    }
+}
@@ -0,0 +1,199 @@
+use core::cmp::{Ordering, Reverse};
+use alloc::collections::BinaryHeap;
+#[cfg(feature = "std")]
+use core::convert::Infallible;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use crate::account::view::AccountView;
+#[cfg(feature = "std")]
+use crate::pipeline::Sink;
+use crate::prelude::*;
+
+/// Default histogram bounds: negative, 0, 1, 10, ... 1 000 000
+pub const DEFAULT_BOUNDS: [Decimal; 8] = [
+    Decimal::ZERO,
+    Decimal::ONE,
+    Decimal::TEN,
+    Decimal::ONE_HUNDRED,
+    Decimal::ONE_THOUSAND,
+    Decimal::from_parts(10_000, 0, 0, false, 0),
+    Decimal::from_parts(100_000, 0, 0, false, 0),
+    Decimal::from_parts(1_000_000, 0, 0, false, 0),
+];
+
+/// Aggregates of accounts state: funds in system, top accounts by total balance
+/// and distribution of total balances.
+#[derive(Debug, Serialize)]
+pub struct AccountsSummary {
+    pub accounts: u64,
+    pub locked: u64,
+    pub closed: u64,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    pub total_available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub total_held: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub total_funds: Decimal,
+
+    // accounts with the highest total, descending
+    pub top: Vec<AccountView>,
+    pub histogram: Vec<HistogramBucket>,
+}
+
+/// Number of accounts with total in `[from, to)`, open bound is None
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HistogramBucket {
+    pub from: Option<Decimal>,
+    pub to: Option<Decimal>,
+    pub count: u64,
+}
+
+/// Sink which computes `AccountsSummary` without keeping all accounts in memory
+/// (only top N accounts are kept).
+pub struct SummaryCollector {
+    top_n: usize,
+
+    // ascending histogram bounds, bucket i counts totals in [bounds[i - 1], bounds[i])
+    bounds: Vec<Decimal>,
+    counts: Vec<u64>,
+
+    // min heap, the lowest of top accounts is replaced first
+    top: BinaryHeap<Reverse<ByTotal>>,
+    summary: AccountsSummary,
+}
+
+impl SummaryCollector {
+    pub fn new(top_n: usize) -> Self {
+        Self::with_bounds(top_n, DEFAULT_BOUNDS.to_vec())
+    }
+
+    /// Histogram with custom bounds, bounds are sorted and deduplicated
+    pub fn with_bounds(top_n: usize, mut bounds: Vec<Decimal>) -> Self {
+        bounds.sort();
+        bounds.dedup();
+
+        SummaryCollector {
+            top_n,
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            top: BinaryHeap::with_capacity(top_n + 1),
+            summary: AccountsSummary {
+                accounts: 0,
+                locked: 0,
+                closed: 0,
+                total_available: Decimal::ZERO,
+                total_held: Decimal::ZERO,
+                total_funds: Decimal::ZERO,
+                top: Vec::new(),
+                histogram: Vec::new(),
+            },
+        }
+    }
+
+    pub fn add(&mut self, account: AccountView) {
+        let summary = &mut self.summary;
+        summary.accounts += 1;
+        summary.locked += account.locked() as u64;
+        summary.closed += account.closed() as u64;
+
+        // saturating, summary of overflowing funds is still useful
+        summary.total_available = summary.total_available.saturating_add(*account.available());
+        summary.total_held = summary.total_held.saturating_add(*account.held());
+        summary.total_funds = summary.total_funds.saturating_add(*account.total());
+
+        let bucket = self.bounds.partition_point(|it| it <= account.total());
+        self.counts[bucket] += 1;
+
+        if self.top_n > 0 {
+            self.top.push(Reverse(ByTotal(account)));
+            if self.top.len() > self.top_n {
+                self.top.pop();
+            }
+        }
+    }
+
+    pub fn summary(mut self) -> AccountsSummary {
+        self.summary.top = self.top.into_sorted_vec().into_iter().map(|it| it.0.0).collect();
+
+        let lower = core::iter::once(None).chain(self.bounds.iter().copied().map(Some));
+        let upper = self.bounds.iter().copied().map(Some).chain(core::iter::once(None));
+        self.summary.histogram = lower.zip(upper).zip(self.counts)
+            .map(|((from, to), count)| HistogramBucket { from, to, count })
+            .collect();
+
+        self.summary
+    }
+}
+
+#[cfg(feature = "std")]
+impl Sink<AccountView> for SummaryCollector {
+    type Error = Infallible;
+
+    fn write(&mut self, record: AccountView) -> Result<(), Self::Error> {
+        self.add(record);
+        Ok(())
+    }
+}
+
+/// Account ordered by total, on equal totals lower client id is greater (comes first in top)
+struct ByTotal(AccountView);
+
+impl Ord for ByTotal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total().cmp(other.0.total())
+            .then_with(|| other.0.client().cmp(self.0.client()))
+    }
+}
+
+impl PartialOrd for ByTotal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ByTotal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByTotal {}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use crate::account::basic::BasicAccount;
+    use crate::account::view::AccountView;
+    use crate::summary::{HistogramBucket, SummaryCollector};
+
+    fn account(client: u16, total: i64) -> BasicAccount {
+        let mut account = BasicAccount::new(client);
+        account.deposit(&Decimal::from(total)).unwrap();
+        account
+    }
+
+    #[test]
+    fn add_accounts_then_totals_top_and_histogram() {
+        let mut collector = SummaryCollector::with_bounds(2, vec![Decimal::TEN, Decimal::ZERO]);
+        for (client, total) in [(1, 5), (2, 50), (3, 0), (4, 50), (5, 20)] {
+            collector.add(account(client, total).into());
+        }
+        let mut locked = account(6, 1);
+        locked.dispute_deposit(&Decimal::ONE).unwrap();
+        locked.chargeback(&Decimal::ONE).unwrap();
+        collector.add(AccountView::from(locked));
+
+        let summary = collector.summary();
+        assert_eq!(summary.accounts, 6);
+        assert_eq!(summary.locked, 1);
+        assert_eq!(summary.total_funds, Decimal::from(125));
+        assert_eq!(summary.total_held, Decimal::ZERO);
+        assert_eq!(summary.top.iter().map(|it| *it.client()).collect::<Vec<_>>(), vec![2, 4]);
+        assert_eq!(summary.histogram, vec![
+            HistogramBucket { from: None, to: Some(Decimal::ZERO), count: 0 },
+            HistogramBucket { from: Some(Decimal::ZERO), to: Some(Decimal::TEN), count: 3 },
+            HistogramBucket { from: Some(Decimal::TEN), to: None, count: 3 },
+        ]);
+    }
+}
@@ -0,0 +1,124 @@
+//! Suspense tracking (`BasicProcessor::set_suspense_listener`): disputes, resolves and chargebacks referencing
+//! missing transactions or transactions of other client are still rejected, but referenced amounts are
+//! reported, so reconciliation teams can see how much money is in question.
+
+use alloc::collections::BTreeMap;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use crate::prelude::*;
+use crate::client::Client;
+use crate::transaction::TxId;
+use crate::transaction_type::TransactionType;
+
+/// Why transaction went to suspense
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuspenseReason {
+    // dispute of transaction which is not retained (unknown or evicted)
+    MissingTransaction,
+
+    // resolve or chargeback of transaction which is not under dispute
+    MissingDispute,
+
+    // referenced transaction belongs to other client
+    ClientMismatch,
+}
+
+/// Rejected transaction with amount in question
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SuspenseEntry {
+    r#type: TransactionType,
+    client: Client,
+    tx: TxId,
+    reason: SuspenseReason,
+
+    // amount of referenced transaction (or of rejected transaction itself), None when unknown
+    #[serde(with = "rust_decimal::serde::str_option")]
+    amount: Option<Decimal>,
+
+    // number of transactions processed before rejection
+    sequence: u64,
+}
+
+impl SuspenseEntry {
+    pub fn new(r#type: TransactionType, client: Client, tx: TxId, reason: SuspenseReason, amount: Option<Decimal>, sequence: u64) -> Self {
+        SuspenseEntry { r#type, client, tx, reason, amount, sequence }
+    }
+
+    pub fn r#type(&self) -> &TransactionType {
+        &self.r#type
+    }
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn tx_id(&self) -> TxId {
+        self.tx
+    }
+    pub fn reason(&self) -> SuspenseReason {
+        self.reason
+    }
+    pub fn amount(&self) -> Option<&Decimal> {
+        self.amount.as_ref()
+    }
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+/// Receives suspense entries, e.g. to collect them in `SuspenseLedger`
+pub type SuspenseListener = Box<dyn FnMut(&SuspenseEntry) + Send>;
+
+/// Entries of one reason
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SuspenseTotal {
+    entries: u64,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    amount: Decimal,
+
+    // entries without known amount, not included in amount
+    unknown_amounts: u64,
+}
+
+impl SuspenseTotal {
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+    pub fn amount(&self) -> &Decimal {
+        &self.amount
+    }
+    pub fn unknown_amounts(&self) -> u64 {
+        self.unknown_amounts
+    }
+}
+
+/// Suspense ledger of run: entries with totals by reason
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SuspenseLedger {
+    totals: BTreeMap<SuspenseReason, SuspenseTotal>,
+    entries: Vec<SuspenseEntry>,
+}
+
+impl SuspenseLedger {
+    pub fn new() -> Self {
+        SuspenseLedger::default()
+    }
+
+    pub fn record(&mut self, entry: SuspenseEntry) {
+        let total = self.totals.entry(entry.reason).or_default();
+        total.entries += 1;
+        match entry.amount {
+            Some(amount) => total.amount = total.amount.saturating_add(amount),
+            None => total.unknown_amounts += 1,
+        }
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[SuspenseEntry] {
+        &self.entries
+    }
+
+    pub fn total(&self, reason: SuspenseReason) -> Option<&SuspenseTotal> {
+        self.totals.get(&reason)
+    }
+}
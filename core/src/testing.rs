@@ -0,0 +1,124 @@
+//! Test utilities for thread-safe processor implementations.
+
+use std::thread;
+use rust_decimal::Decimal;
+use crate::account::view::AccountView;
+use crate::client::Client;
+use crate::shard::ShardRouter;
+use crate::{SharedTransactionProcessor, Transaction, TransactionProcessor};
+
+// comparable account state, without processing order dependent fields (last activity)
+type AccountSummary = (Client, Decimal, Decimal, Decimal, bool, bool);
+
+fn summary(account: &AccountView) -> AccountSummary {
+    (*account.client(), *account.available(), *account.held(), *account.total(), account.locked(), account.closed())
+}
+
+/// Replay `transactions` through `shared` processor from `threads` threads and assert that result
+/// is the same as single-threaded run of `reference` processor.
+/// Transactions are partitioned by client, so per-client ordering is preserved, but transactions
+/// of different clients are interleaved. Tx ids must be unique across clients, interleaving changes which
+/// client uses shared tx id first.
+/// Returns accounts of concurrent run sorted by client.
+pub fn concurrent_driver<S, P>(transactions: &[Transaction], threads: usize, shared: S, reference: P) -> Vec<AccountView>
+    where S: SharedTransactionProcessor,
+          S::Output: IntoIterator<Item=AccountView>,
+          P: TransactionProcessor,
+          P::Output: IntoIterator<Item=AccountView>
+{
+    let Some(router) = ShardRouter::new(threads) else { panic!("threads must be > 0") };
+
+    // sequence numbers as assigned by pipeline, so processor can verify source order
+    let transactions: Vec<_> = transactions.iter()
+        .enumerate()
+        .map(|(i, it)| it.clone().with_sequence(i as u64 + 1))
+        .collect();
+
+    let mut partitions: Vec<Vec<&Transaction>> = (0..threads).map(|_| Vec::new()).collect();
+    for transaction in transactions.iter() {
+        partitions[router.shard(transaction.client())].push(transaction);
+    }
+
+    thread::scope(|scope| {
+        for partition in partitions.iter() {
+            let shared = &shared;
+            scope.spawn(move || {
+                for transaction in partition {
+                    let _ = shared.process((*transaction).clone());
+                }
+            });
+        }
+    });
+
+    let mut reference = reference;
+    for transaction in transactions {
+        let _ = reference.process(transaction);
+    }
+
+    let mut accounts: Vec<_> = shared.finalize().into_iter().collect();
+    accounts.sort_by_key(|it| *it.client());
+
+    let mut expected: Vec<_> = reference.finalize().into_iter().map(|it| summary(&it)).collect();
+    expected.sort_by_key(|it| it.0);
+
+    let actual: Vec<_> = accounts.iter().map(summary).collect();
+    assert_eq!(actual, expected, "concurrent run differs from single-threaded run");
+
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BasicProcessor, MutexProcessor, ShardedProcessor, SharedTransactionProcessor, Transaction, WrapProcessor};
+    use crate::shard::ShardRouter;
+    use crate::testing::concurrent_driver;
+    use crate::transaction_type::TransactionType::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+
+    fn transactions() -> Vec<Transaction> {
+        let mut transactions = Vec::new();
+        for client in 1..=32_u16 {
+            let tx = client as u32 * 10;
+            transactions.push(Transaction::new(Deposit, client, tx, Some(100_u64.into())));
+            transactions.push(Transaction::new(Withdrawal, client, tx + 1, Some(30_u64.into())));
+            transactions.push(Transaction::new(Deposit, client, tx + 2, Some(50_u64.into())));
+            transactions.push(Transaction::new(Dispute, client, tx + 2, None));
+            match client % 2 {
+                0 => transactions.push(Transaction::new(Resolve, client, tx + 2, None)),
+                _ => transactions.push(Transaction::new(Chargeback, client, tx + 2, None)),
+            }
+            // rejected after chargeback for odd clients
+            transactions.push(Transaction::new(Deposit, client, tx + 3, Some(5_u64.into())));
+        }
+        transactions
+    }
+
+    #[test]
+    fn mutex_basic_processor_then_same_result() {
+        let accounts = concurrent_driver(&transactions(), 4, MutexProcessor::new(BasicProcessor::new()), BasicProcessor::new());
+        assert_eq!(accounts.len(), 32);
+        assert!(accounts[0].locked());
+        assert!(!accounts[1].locked());
+    }
+
+    #[test]
+    fn mutex_wrap_processor_then_same_result() {
+        let accounts = concurrent_driver(&transactions(), 3, MutexProcessor::new(WrapProcessor::new()), BasicProcessor::new());
+        assert_eq!(accounts.len(), 32);
+    }
+
+    #[test]
+    fn sharded_basic_processor_then_same_result() {
+        let processor = ShardedProcessor::new(ShardRouter::new(8).unwrap(), BasicProcessor::new);
+        for transaction in transactions() {
+            let _ = processor.process(transaction);
+        }
+        let contention = processor.contention();
+        assert_eq!(contention.len(), 8);
+        assert_eq!(contention.iter().map(|it| it.acquisitions()).sum::<u64>(), 32 * 6);
+        assert!(contention.iter().all(|it| it.contended() == 0));
+
+        let accounts = concurrent_driver(&transactions(), 4, ShardedProcessor::new(ShardRouter::new(8).unwrap(), BasicProcessor::new), BasicProcessor::new());
+        assert_eq!(accounts.len(), 32);
+        assert!(accounts[0].locked());
+    }
+}
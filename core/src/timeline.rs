@@ -0,0 +1,47 @@
+//! Account timeline (`BasicProcessor::set_timeline`): state of client account is recorded after every
+//! change, so balance as of any processed transaction can be looked up, e.g. to find exactly when account
+//! went negative or got locked. Transactions have no timestamps, point in time is processing sequence.
+
+use crate::prelude::*;
+use crate::account::view::AccountView;
+use crate::client::Client;
+
+/// States of client accounts by processing sequence
+#[derive(Debug, Clone, Default)]
+pub struct AccountTimeline {
+    // (sequence, state after change) in ascending sequence order, None when account was removed (merged)
+    states: IntMap<Client, Vec<(u64, Option<AccountView>)>>,
+}
+
+impl AccountTimeline {
+    pub fn new() -> Self {
+        AccountTimeline::default()
+    }
+
+    /// Record state of client account after transaction `sequence`, state recorded earlier for the same
+    /// sequence is replaced
+    pub fn record(&mut self, client: Client, sequence: u64, account: Option<AccountView>) {
+        let states = self.states.entry(client).or_default();
+        match states.last_mut() {
+            Some((last, state)) if *last == sequence => *state = account,
+            _ => states.push((sequence, account)),
+        }
+    }
+
+    /// State of client account after transaction `sequence`, None when account didn't exist at that point
+    pub fn at(&self, client: Client, sequence: u64) -> Option<&AccountView> {
+        let states = self.states.get(&client)?;
+        let index = states.partition_point(|(it, _)| *it <= sequence);
+        states.get(index.checked_sub(1)?)?.1.as_ref()
+    }
+
+    /// Recorded changes of client account in ascending sequence order
+    pub fn changes(&self, client: Client) -> impl Iterator<Item=(u64, Option<&AccountView>)> + '_ {
+        self.states.get(&client).into_iter().flatten().map(|(sequence, state)| (*sequence, state.as_ref()))
+    }
+
+    /// Forget all states of client, e.g. when client is erased
+    pub fn forget(&mut self, client: Client) {
+        self.states.remove(&client);
+    }
+}
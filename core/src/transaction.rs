@@ -1,16 +1,32 @@
+use crate::prelude::*;
+use core::str::FromStr;
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use crate::client::Client;
+use crate::error::ProcessError;
 use crate::transaction_type::TransactionType;
 
 pub type TxId = u32;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     r#type: TransactionType,
     client: Client,
     tx: TxId,
     amount: Option<Decimal>,
+
+    // monotonically increasing number assigned at ingestion (source order), 0 if not assigned
+    #[serde(skip)]
+    sequence: u64,
+
+    // upstream memo/reference, passed through to outputs (e.g. DLQ) so rows can be matched back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reference: Option<Box<str>>,
+
+    // extra input columns passed through to outputs (e.g. DLQ), not used in processing
+    #[cfg(feature = "metadata")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    metadata: HashMap<String, String>,
 }
 
 impl Transaction {
@@ -20,9 +36,31 @@ impl Transaction {
             client,
             tx,
             amount,
+            sequence: 0,
+            reference: None,
+            #[cfg(feature = "metadata")]
+            metadata: HashMap::new(),
         }
     }
 
+    /// Assign ingestion sequence number
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Attach upstream memo/reference
+    pub fn with_reference<S: Into<Box<str>>>(mut self, reference: S) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    /// Redirect transaction to other client, e.g. client merged into other account
+    pub(crate) fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     pub fn r#type(&self) -> &TransactionType {
         &self.r#type
     }
@@ -35,5 +73,71 @@ impl Transaction {
     pub fn amount(&self) -> Option<Decimal> {
         self.amount
     }
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+    pub fn reference(&self) -> Option<&str> {
+        self.reference.as_deref()
+    }
+
+    /// Attach extra input columns
+    #[cfg(feature = "metadata")]
+    pub fn with_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    #[cfg(feature = "metadata")]
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
 }
 
+/// Non-owning view of a transaction row, borrowing raw amount from the reader buffer.
+/// Amount is parsed only when needed, so dispute/resolve/chargeback rows never touch Decimal parsing.
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionRef<'a> {
+    r#type: TransactionType,
+    client: Client,
+    tx: TxId,
+    amount: Option<&'a [u8]>,
+}
+
+impl<'a> TransactionRef<'a> {
+    pub fn new(r#type: TransactionType, client: Client, tx: TxId, amount: Option<&'a [u8]>) -> Self {
+        TransactionRef {
+            r#type,
+            client,
+            tx,
+            amount,
+        }
+    }
+
+    pub fn r#type(&self) -> &TransactionType {
+        &self.r#type
+    }
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn tx_id(&self) -> TxId {
+        self.tx
+    }
+    pub fn raw_amount(&self) -> Option<&'a [u8]> {
+        self.amount
+    }
+
+    pub fn amount(&self) -> Result<Option<Decimal>, ProcessError> {
+        match self.amount {
+            None | Some(b"") => Ok(None),
+            Some(amount) => {
+                let amount = core::str::from_utf8(amount).map_err(|e| ProcessError::InvalidAmount(e.into()))?;
+                // rust_decimal::Error implements Error only with std, message is kept
+                Decimal::from_str(amount.trim()).map(Some).map_err(|e| ProcessError::InvalidAmount(e.to_string().into()))
+            }
+        }
+    }
+
+    pub fn to_transaction(&self) -> Result<Transaction, ProcessError> {
+        Ok(Transaction::new(self.r#type, self.client, self.tx, self.amount()?))
+    }
+}
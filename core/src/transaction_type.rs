@@ -1,6 +1,10 @@
-use serde::Deserialize;
+use crate::prelude::*;
+use core::fmt::Formatter;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{Error, Visitor};
 
-#[derive(Debug, Deserialize)]
+/// Serialized as lowercase name, deserialized case-insensitively with aliases (see `TransactionType::from_name`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionType {
     // A chargeback is the final state of a dispute and represents the client reversing a transaction.
@@ -9,6 +13,10 @@ pub enum TransactionType {
     // client's account should be immediately frozen.
     Chargeback,
 
+    // A close marks the client's account as closed. Further deposits and withdrawals are rejected,
+    // but open disputes can still be resolved or charged back.
+    Close,
+
     // A deposit is a credit to the client's asset account, meaning it should increase the available and
     // total funds of the client account
     Deposit,
@@ -28,4 +36,139 @@ pub enum TransactionType {
     // A withdraw is a debit to the client's asset account, meaning it should decrease the available and
     // total funds of the client account
     Withdrawal,
-}
\ No newline at end of file
+}
+
+impl TransactionType {
+    /// Lowercase type name, the same as serialized
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Close => "close",
+            TransactionType::Deposit => "deposit",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Withdrawal => "withdrawal",
+        }
+    }
+
+    /// Parse lowercase type name from raw bytes (csv field) without allocation
+    pub fn from_bytes(bytes: &[u8]) -> Option<TransactionType> {
+        match bytes {
+            b"chargeback" => Some(TransactionType::Chargeback),
+            b"close" => Some(TransactionType::Close),
+            b"deposit" => Some(TransactionType::Deposit),
+            b"dispute" => Some(TransactionType::Dispute),
+            b"resolve" => Some(TransactionType::Resolve),
+            b"withdrawal" => Some(TransactionType::Withdrawal),
+            _ => None,
+        }
+    }
+
+    /// Parse type name case-insensitively, ignoring '_', '-' and spaces, with built-in aliases,
+    /// so "WITHDRAW", "withdraw", "charge_back" and "Charge-Back" are accepted
+    pub fn from_name(name: &[u8]) -> Option<TransactionType> {
+        Self::from_bytes(name).or_else(|| match normalize(name).as_str() {
+            "withdraw" => Some(TransactionType::Withdrawal),
+            name => Self::from_bytes(name.as_bytes()),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for TransactionType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        struct NameVisitor;
+
+        impl<'de> Visitor<'de> for NameVisitor {
+            type Value = TransactionType;
+
+            fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
+                formatter.write_str("transaction type name")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                TransactionType::from_name(v.as_bytes()).ok_or_else(|| E::custom(format!("unknown transaction type '{}'", v)))
+            }
+        }
+
+        deserializer.deserialize_str(NameVisitor)
+    }
+}
+
+/// Configurable alias table for vendor specific type names (e.g. CREDIT for deposit),
+/// on top of canonical names and built-in aliases of `TransactionType::from_name`.
+/// Aliases are matched the same way, case-insensitively and ignoring '_', '-' and spaces.
+#[derive(Debug, Clone, Default)]
+pub struct TypeAliases {
+    // normalized alias name
+    aliases: HashMap<String, TransactionType>,
+}
+
+impl TypeAliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: &str, r#type: TransactionType) {
+        self.aliases.insert(normalize(name.as_bytes()), r#type);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Canonical lowercase names are matched without allocation, then aliases, then `from_name`
+    pub fn resolve(&self, name: &[u8]) -> Option<TransactionType> {
+        TransactionType::from_bytes(name)
+            .or_else(|| match self.aliases.is_empty() {
+                true => None,
+                false => self.aliases.get(&normalize(name)).copied(),
+            })
+            .or_else(|| TransactionType::from_name(name))
+    }
+}
+
+// lowercase, without '_', '-' and whitespace
+fn normalize(name: &[u8]) -> String {
+    name.iter()
+        .filter(|it| !matches!(it, b'_' | b'-') && !it.is_ascii_whitespace())
+        .map(|it| it.to_ascii_lowercase() as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde::de::IntoDeserializer;
+    use serde::de::value::Error;
+    use crate::transaction_type::{TransactionType, TypeAliases};
+
+    #[test]
+    fn from_name_then_case_and_separators_ignored() {
+        assert_eq!(TransactionType::from_name(b"WITHDRAW"), Some(TransactionType::Withdrawal));
+        assert_eq!(TransactionType::from_name(b"withdraw"), Some(TransactionType::Withdrawal));
+        assert_eq!(TransactionType::from_name(b"charge_back"), Some(TransactionType::Chargeback));
+        assert_eq!(TransactionType::from_name(b"Charge-Back"), Some(TransactionType::Chargeback));
+        assert_eq!(TransactionType::from_name(b"bonus"), None);
+    }
+
+    #[test]
+    fn aliases_then_resolved() {
+        let mut aliases = TypeAliases::new();
+        aliases.insert("CREDIT", TransactionType::Deposit);
+
+        assert_eq!(aliases.resolve(b"credit"), Some(TransactionType::Deposit));
+        assert_eq!(aliases.resolve(b"deposit"), Some(TransactionType::Deposit));
+        assert_eq!(aliases.resolve(b"Withdraw"), Some(TransactionType::Withdrawal));
+        assert_eq!(aliases.resolve(b"debit"), None);
+    }
+
+    #[test]
+    fn deserialize_then_alias_accepted() {
+        let deserialize = |name: &str| TransactionType::deserialize(name.into_deserializer()).map_err(|e: Error| e.to_string());
+
+        assert_eq!(deserialize("charge-back"), Ok(TransactionType::Chargeback));
+        assert_eq!(deserialize("bonus"), Err("unknown transaction type 'bonus'".to_string()));
+    }
+}
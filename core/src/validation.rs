@@ -0,0 +1,97 @@
+//! Business validation of transactions (`TransactionValidator`), run by `BasicProcessor` before any state
+//! is changed, so applying transaction only handles arithmetic. `StandardValidator` is always run first,
+//! custom validators (`BasicProcessor::add_validator`) are run after it in order they were added.
+
+use crate::prelude::*;
+use crate::account::view::AccountView;
+use crate::client::Client;
+use crate::error::ProcessError;
+use crate::error::ProcessError::{AmountNotFound, InvalidAmount, MismatchClientId, NegativeAmount, TransactionExists};
+use crate::transaction::{Transaction, TxId};
+use crate::transaction_type::TransactionType::{Chargeback, Close, Deposit, Dispute, Resolve, Withdrawal};
+use crate::processor::basic_processor::RetainedTxs;
+use crate::{BasicAccountRepository, TransactionRepository};
+
+/// Processor state visible to validators
+pub struct ValidationContext<'a> {
+    accounts: &'a BasicAccountRepository,
+    transactions: &'a TransactionRepository,
+    client_txs: &'a IntMap<Client, RetainedTxs>,
+}
+
+impl<'a> ValidationContext<'a> {
+    pub(crate) fn new(accounts: &'a BasicAccountRepository, transactions: &'a TransactionRepository, client_txs: &'a IntMap<Client, RetainedTxs>) -> Self {
+        ValidationContext { accounts, transactions, client_txs }
+    }
+
+    /// Current state of client account, None for client seen the first time
+    pub fn account(&self, client: Client) -> Option<AccountView> {
+        self.accounts.get_by_client(client).map(AccountView::from)
+    }
+
+    /// Whether deposit or withdrawal with tx id is retained (can be disputed)
+    pub fn transaction_exists(&self, tx_id: TxId) -> Result<bool, ProcessError> {
+        self.transactions.exist_by_tx_id(&tx_id)
+    }
+
+    /// Whether retained transaction belongs to client
+    pub fn owns(&self, client: Client, tx_id: TxId) -> bool {
+        self.client_txs.get(&client).is_some_and(|it| it.contains_key(&tx_id))
+    }
+}
+
+/// Validation of transaction before it is applied. `Err` rejects transaction, processor state is not changed.
+pub trait TransactionValidator: Send {
+    fn validate(&mut self, transaction: &Transaction, context: &ValidationContext<'_>) -> Result<(), ProcessError>;
+}
+
+/// Validators run in order, the first error rejects transaction
+impl TransactionValidator for Vec<Box<dyn TransactionValidator>> {
+    fn validate(&mut self, transaction: &Transaction, context: &ValidationContext<'_>) -> Result<(), ProcessError> {
+        self.iter_mut().try_for_each(|it| it.validate(transaction, context))
+    }
+}
+
+/// Checks which keep processor state consistent:
+/// - deposit and withdrawal have amount (`AmountNotFound`), which is not negative (`NegativeAmount`),
+///   and new tx id (`TransactionExists`),
+/// - dispute, resolve and chargeback of retained transaction are sent by its owner (`MismatchClientId`),
+///   missing transaction is reported when transaction is applied.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StandardValidator;
+
+impl TransactionValidator for StandardValidator {
+    fn validate(&mut self, transaction: &Transaction, context: &ValidationContext<'_>) -> Result<(), ProcessError> {
+        let (client, tx_id) = (transaction.client(), transaction.tx_id());
+        match transaction.r#type() {
+            Deposit | Withdrawal => {
+                if transaction.amount().ok_or(AmountNotFound)?.is_sign_negative() {
+                    return Err(NegativeAmount);
+                }
+                if context.transaction_exists(tx_id)? {
+                    return Err(TransactionExists);
+                }
+                Ok(())
+            }
+            Dispute | Resolve | Chargeback => match context.transaction_exists(tx_id)? && !context.owns(client, tx_id) {
+                true => Err(MismatchClientId),
+                false => Ok(()),
+            },
+            Close => Ok(()),
+        }
+    }
+}
+
+/// Amount of deposit and withdrawal has at most `scale` decimal places, e.g. 4 for csv output precision
+#[derive(Debug, Clone, Copy)]
+pub struct MaxScale(pub u32);
+
+impl TransactionValidator for MaxScale {
+    fn validate(&mut self, transaction: &Transaction, _: &ValidationContext<'_>) -> Result<(), ProcessError> {
+        match transaction.amount().map(|it| it.normalize()) {
+            Some(amount) if amount.scale() > self.0 => Err(InvalidAmount(format!("amount has more than {} decimal places", self.0).into())),
+            _ => Ok(()),
+        }
+    }
+}
+